@@ -1,4 +1,6 @@
 #CGI_NO_HEADER
+#CGI_CSRF
+#CGI_SECURITY_HEADERS
 <?basil
   // ----- minimal helpers (same as index, plus DB and cookie setter) -----
   LET SITE_TITLE$ = "Basil Website Skeleton"
@@ -13,7 +15,8 @@
     RETURN 0;
   END
   FUNC set_cookie(name$, value$) BEGIN
-    PRINT "Set-Cookie: " + name$ + "=" + value$ + "; Path=/; HttpOnly\r\n";
+    // COOKIE$ adds HttpOnly, Secure and SameSite=Lax
+    PRINT COOKIE$(name$, value$) + "\r\n";
     RETURN 0;
   END
   FUNC send_header_redirect(loc$) BEGIN
@@ -110,7 +113,7 @@
   LET __d% = send_header_ok_html();
   LET __d% = layout_start("Log in");
 ?>
-<?basil PRINT READFILE$("views/login.html"); ?>
+<?basil PRINT CSRF_FORMS$(READFILE$("views/login.html")); ?>
 <?basil
   IF LEN(err$) > 0 THEN BEGIN
     PRINT "<p class=\"error\">" + HTML$(err$) + "</p>\n";
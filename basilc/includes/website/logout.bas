@@ -2,6 +2,6 @@
 <?basil
   // Clear cookie by expiring it
   PRINT "Status: 302 Found\r\n";
-  PRINT "Set-Cookie: user=; Path=/; Expires=Thu, 01 Jan 1970 00:00:00 GMT\r\n";
+  PRINT COOKIE$("user", "", "Max-Age=0; Expires=Thu, 01 Jan 1970 00:00:00 GMT") + "\r\n";
   PRINT "Location: index.basil\r\n\r\n";
 ?>
\ No newline at end of file
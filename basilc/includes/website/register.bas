@@ -1,4 +1,6 @@
 #CGI_NO_HEADER
+#CGI_CSRF
+#CGI_SECURITY_HEADERS
 <?basil
 
     LET SITE_TITLE$ = "Basil Website Skeleton"
@@ -11,7 +13,8 @@
     END
 
     FUNC set_cookie(name$, value$) BEGIN
-        PRINT "Set-Cookie: " + name$ + "=" + value$ + "; Path=/; HttpOnly\r\n";
+        // COOKIE$ adds HttpOnly, Secure and SameSite=Lax
+        PRINT COOKIE$(name$, value$) + "\r\n";
         RETURN 0;
     END
 
@@ -143,7 +146,7 @@
     LET __d% = send_header_ok_html()
     LET __d% = layout_start("Register")
 ?>
-<?basil PRINT READFILE$("views/register.html"); ?>
+<?basil PRINT CSRF_FORMS$(READFILE$("views/register.html")); ?>
 <?basil
     //LET err$ = "Hello";
 
@@ -57,15 +57,34 @@ use std::collections::HashMap;
 
 mod template;
 mod repl;
-use template::{precompile_template, parse_directives_and_bom, Directives};
+use template::{precompile_template_file, parse_directives_and_bom};
 mod embedded;
+mod router;
+mod security;
+mod server;
 
 fn cmd_analyze(path: String, json: bool) {
     let src = match std::fs::read_to_string(&path) {
         Ok(s) => s,
         Err(e) => { eprintln!("read {}: {}", path, e); std::process::exit(1); }
     };
-    let diags: CompilerDiagnostics = analyze_source(&src, &path);
+    // Templates are analyzed as their generated Basil, with positions mapped back to the template
+    let pre = if src.contains("<?") {
+        match precompile_template_file(&src, Path::new(&path)) {
+            Ok(r) => r,
+            Err(e) => { println!("Errors:"); println!("- Error at {}", e); return; }
+        }
+    } else {
+        template::PrecompileResult::plain(src.clone())
+    };
+    let mut diags: CompilerDiagnostics = analyze_source(&pre.basil_source, &path);
+    for d in diags.errors.iter_mut() {
+        if let Some((_, loc)) = pre.source_map.locate_message(&d.message) {
+            d.line = loc.line as usize;
+            d.column = loc.col as usize;
+            d.message = pre.source_map.remap_message(&d.message);
+        }
+    }
     if json {
         eprintln!("--json output not available in lean build; showing plain text instead.\n");
     }
@@ -92,7 +111,7 @@ fn cmd_debug(path: Option<String>) {
     };
     let abs_path: PathBuf = match fs::canonicalize(&input_path) { Ok(p)=>p, Err(_)=>PathBuf::from(&input_path) };
     let src = match std::fs::read_to_string(&abs_path) { Ok(s)=>s, Err(e)=>{ eprintln!("{}", e); std::process::exit(1);} };
-    let pre = template::PrecompileResult { basil_source: src.clone(), directives: Directives::default() };
+    let pre = template::PrecompileResult::plain(src.clone());
     let ast = match parse(&pre.basil_source) { Ok(a)=>a, Err(e)=>{ eprintln!("parse error: {}", e); std::process::exit(1);} };
     let program = match compile(&ast) { Ok(p)=>p, Err(e)=>{ eprintln!("compile error: {}", e); std::process::exit(1);} };
     let dbg = Debugger::new();
@@ -146,6 +165,7 @@ fn print_help() {
     println!("  run        Parse → compile → run a .bas file");
     println!("  test       Run program in test mode with auto-mocked input");
     println!("  lex        Dump tokens from a .bas file (debug)");
+    println!("  serve      Serve a directory over HTTP (routes.bas, .bas scripts, static files)");
     println!("  make       Export an embedded file or directory (use --list to see available)");
     println!("");
     println!("Usage:");
@@ -155,6 +175,7 @@ fn print_help() {
     println!("  basic make examples/hello.bas");
     println!("  basic run examples/hello.bas");
     println!("  basic lex examples/hello.bas");
+    println!("  basic serve examples/router --port 8080");
     println!("  basic make upgrade");
     println!("  basic make --list");
     println!("");
@@ -248,46 +269,17 @@ fn cmd_lex(path: Option<String>) {
     }
 }
 
-fn cmd_run(path: Option<String>) {
-    // Require a path
-    let input_path = match path {
-        Some(p) => p,
-        None => {
-            eprintln!("usage: basic run <file.bas>");
-            std::process::exit(2);
-        }
-    };
-
-    // Optional: refuse obvious non-source invocations (helps catch /usr/lib/cgi-bin/basil.cgi)
-    if !input_path.ends_with(".bas") {
-        eprintln!("Refusing to run a non-.bas file: {}", input_path);
-        std::process::exit(2);
-    }
-
-    // Resolve absolute path for reading/caching, but set CWD using the user-provided path to avoid Windows \\?\ prefixes.
-    let abs_path: PathBuf = match fs::canonicalize(&input_path) {
-        Ok(p) => p,
-        Err(_) => PathBuf::from(&input_path),
-    };
-    // IMPORTANT (Windows): Do not use canonicalized path for CWD, because it may contain the \\?\ prefix that cmd.exe rejects.
-    let script_dir_for_cwd = Path::new(&input_path)
-        .parent()
-        .map(|p| p.to_path_buf())
-        .unwrap_or_else(|| PathBuf::from("."));
-    if let Err(e) = env::set_current_dir(&script_dir_for_cwd) {
-        eprintln!("warning: failed to set current dir to script dir ({}): {}", script_dir_for_cwd.display(), e);
-    }
-
+// Read, precompile (templates) and compile a script, using or refreshing its .basx cache.
+// Err carries the exit code and message for `basic run`. Shared with `basic serve`.
+fn load_script(abs_path: &Path) -> Result<(basil_bytecode::Program, template::PrecompileResult, String), (i32, String)> {
     // Read the source once, with good error messages (use absolute path to avoid cwd side-effects)
-    let src = match std::fs::read_to_string(&abs_path) {
+    let src = match std::fs::read_to_string(abs_path) {
         Ok(s) => s,
         Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
-            eprintln!("File is not UTF-8 text: {}", abs_path.display());
-            std::process::exit(3);
+            return Err((3, format!("File is not UTF-8 text: {}", abs_path.display())));
         }
         Err(e) => {
-            eprintln!("Failed to read {}: {}", abs_path.display(), e);
-            std::process::exit(1);
+            return Err((1, format!("Failed to read {}: {}", abs_path.display(), e)));
         }
     };
 
@@ -300,29 +292,28 @@ fn cmd_run(path: Option<String>) {
     }
     let pre = if looks_like_template {
         if env::var("BASIL_DEBUG").ok().as_deref() == Some("1") { eprintln!("[basic] Using template precompiler in CLI"); }
-        match precompile_template(&src) {
+        match precompile_template_file(&src, abs_path) {
             Ok(r) => r,
-            Err(e) => { eprintln!("template error: {}", e); std::process::exit(1); }
+            Err(e) => return Err((1, format!("template error: {}", e))),
         }
     } else {
         if env::var("BASIL_DEBUG").ok().as_deref() == Some("1") { eprintln!("[basic] Treating as plain Basic"); }
-        template::PrecompileResult { basil_source: src.clone(), directives: Directives::default() }
+        template::PrecompileResult::plain(src.clone())
     };
 
     // Prepare cache fingerprint
-    let meta = match fs::metadata(&abs_path) { Ok(m)=>m, Err(e)=>{ eprintln!("stat {}: {}", abs_path.display(), e); std::process::exit(1);} };
-    let source_size = meta.len();
-    let source_mtime_ns: u64 = meta.modified().ok()
+    let meta = match fs::metadata(abs_path) { Ok(m)=>m, Err(e)=>return Err((1, format!("stat {}: {}", abs_path.display(), e))) };
+    let (source_size, source_mtime_ns) = pre.fingerprint(meta.len(), meta.modified().ok()
         .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
         .map(|d| d.as_nanos() as u64)
-        .unwrap_or(0);
+        .unwrap_or(0));
     // Flags for cache: bit0 = short_tags_on, bit1 = templating_used
     let templating_used = src.contains("<?");
     let flags: u32 = (if pre.directives.short_tags_on { 1u32 } else { 0u32 })
                    | (if templating_used { 2u32 } else { 0u32 });
 
     // Cache path (next to the script)
-    let mut cache_path = abs_path.clone();
+    let mut cache_path = abs_path.to_path_buf();
     cache_path.set_extension("basx");
 
     // Try cache load
@@ -343,8 +334,8 @@ fn cmd_run(path: Option<String>) {
 
     let program = if let Some(p) = program_opt { p } else {
         // Parse → compile the precompiled Basil source
-        let ast = match parse(&pre.basil_source) { Ok(a)=>a, Err(e)=>{ eprintln!("parse error: {}", e); std::process::exit(1);} };
-        let prog = match compile(&ast) { Ok(p)=>p, Err(e)=>{ eprintln!("compile error: {}", e); std::process::exit(1);} };
+        let ast = match parse(&pre.basil_source) { Ok(a)=>a, Err(e)=>return Err((1, format!("parse error: {}", pre.source_map.remap_message(&e.to_string())))) };
+        let prog = match compile(&ast) { Ok(p)=>p, Err(e)=>return Err((1, format!("compile error: {}", pre.source_map.remap_message(&e.to_string())))) };
         // Write cache atomically
         let body = serialize_program(&prog);
         let mut hdr = Vec::with_capacity(32 + body.len());
@@ -363,24 +354,110 @@ fn cmd_run(path: Option<String>) {
         }
         prog
     };
+    Ok((program, pre, src))
+}
+
+// How the router answers for the script at `abs_path` with source `src`
+fn router_options(src: &str, abs_path: &Path, web: bool) -> router::Options {
+    let (dirs, _) = parse_directives_and_bom(src);
+    router::Options {
+        web,
+        default_header: if dirs.cgi_no_header { None } else {
+            Some(dirs.cgi_default_header.unwrap_or_else(|| "Content-Type: text/html; charset=utf-8".to_string()))
+        },
+        static_root: abs_path.parent().map(|p| p.to_path_buf()),
+    }
+}
+
+fn cmd_run(path: Option<String>) {
+    // Require a path
+    let input_path = match path {
+        Some(p) => p,
+        None => {
+            eprintln!("usage: basic run <file.bas>");
+            std::process::exit(2);
+        }
+    };
+
+    // Optional: refuse obvious non-source invocations (helps catch /usr/lib/cgi-bin/basil.cgi)
+    if !input_path.ends_with(".bas") {
+        eprintln!("Refusing to run a non-.bas file: {}", input_path);
+        std::process::exit(2);
+    }
+
+    // Resolve absolute path for reading/caching, but set CWD using the user-provided path to avoid Windows \\?\ prefixes.
+    let abs_path: PathBuf = match fs::canonicalize(&input_path) {
+        Ok(p) => p,
+        Err(_) => PathBuf::from(&input_path),
+    };
+    // IMPORTANT (Windows): Do not use canonicalized path for CWD, because it may contain the \\?\ prefix that cmd.exe rejects.
+    let script_dir_for_cwd = Path::new(&input_path)
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."));
+    if let Err(e) = env::set_current_dir(&script_dir_for_cwd) {
+        eprintln!("warning: failed to set current dir to script dir ({}): {}", script_dir_for_cwd.display(), e);
+    }
+
+    let (program, pre, src) = match load_script(&abs_path) {
+        Ok(r) => r,
+        Err((code, msg)) => { eprintln!("{}", msg); std::process::exit(code); }
+    };
 
     // Run VM
     let mut vm = VM::new(program);
     // Provide script path so CLASS() can resolve relative class files
     vm.set_script_path(abs_path.to_string_lossy().to_string());
+    vm.set_source_map(pre.source_map.clone());
     if let Err(e) = vm.run() {
-        let line = vm.current_line();
-        if line > 0 { eprintln!("runtime error at line {}: {}", line, e); }
-        else { eprintln!("runtime error: {}", e); }
+        eprintln!("{}", runtime_error_text(&vm, &pre, &e));
         std::process::exit(1);
     } else if vm.is_suspended() {
         // In RUN mode, when STOP is encountered, remain suspended with no prompt.
         loop { std::thread::sleep(std::time::Duration::from_secs(3600)); }
     }
+
+    // Scripts that registered ROUTEs now dispatch the request to a handler
+    if !vm.routes().is_empty() {
+        let opts = router_options(&src, &abs_path, env::var("BASIL_ROUTER").ok().as_deref() == Some("1"));
+        let req = router::Request::from_env();
+        let fmt_err = |vm: &VM, e: &basil_common::BasilError| runtime_error_text(vm, &pre, e);
+        if let Err(msg) = router::dispatch(&mut vm, &req, &opts, &fmt_err) {
+            let _ = io::stdout().flush();
+            eprintln!("{}", msg);
+            std::process::exit(1);
+        }
+    }
 }
 
 
 
+fn cmd_serve(args: Vec<String>) {
+    let mut dir: Option<String> = None;
+    let mut host = "127.0.0.1".to_string();
+    let mut port: u16 = 8080;
+    let mut it = args.into_iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--port" | "-p" => match it.next().and_then(|v| v.parse().ok()) {
+                Some(p) => port = p,
+                None => { eprintln!("usage: basic serve [dir] [--port N] [--host H]"); std::process::exit(2); }
+            },
+            "--host" => match it.next() {
+                Some(h) => host = h,
+                None => { eprintln!("usage: basic serve [dir] [--port N] [--host H]"); std::process::exit(2); }
+            },
+            _ if dir.is_none() => dir = Some(a),
+            other => { eprintln!("serve: unexpected argument '{}'", other); std::process::exit(2); }
+        }
+    }
+    let root = PathBuf::from(dir.unwrap_or_else(|| ".".to_string()));
+    if let Err(e) = server::serve(root, &host, port) {
+        eprintln!("serve error: {}", e);
+        std::process::exit(1);
+    }
+}
+
 /// --- New: mode detection ---
 
 fn is_cgi_invocation() -> bool {
@@ -460,7 +537,10 @@ fn cli_main() {
         "test" => {
             cmd_test(args);
         }
-        "build" | "fmt" | "add" | "clean" | "dev" | "serve" | "doc" => {
+        "serve" => {
+            cmd_serve(args);
+        }
+        "build" | "fmt" | "add" | "clean" | "dev" | "doc" => {
             println!("[stub] '{}' not implemented yet in the prototype", cmd);
         }
         "lex" => { cmd_lex(args.get(0).cloned()); }
@@ -476,8 +556,15 @@ fn cli_main() {
 /// --- New: CGI entrypoint that wraps your CLI 'run' ---
 
 fn cgi_main() {
-    // 1) Resolve the Basil script path the request mapped to
-    let script_path = resolve_script_path().unwrap_or_else(|| "/var/www/html/index.bas".to_string());
+    // 1) Resolve the Basil script path the request mapped to. A routes.bas front controller
+    //    handles the request when it is addressed directly or when no other script matches.
+    let (script_path, routed) = match resolve_script_path() {
+        Some(p) => { let r = is_routes_script(&p); (p, r) }
+        None => match routes_fallback() {
+            Some(p) => (p, true),
+            None => ("/var/www/html/index.bas".to_string(), false),
+        },
+    };
 
     // let script_path = env::var("SCRIPT_FILENAME")
     //     .or_else(|_| env::var("PATH_TRANSLATED"))
@@ -498,98 +585,238 @@ fn cgi_main() {
     let ctype  = env::var("CONTENT_TYPE").unwrap_or_default();
     let clen: usize = env::var("CONTENT_LENGTH").ok().and_then(|s| s.parse().ok()).unwrap_or(0);
 
-    let mut body = Vec::with_capacity(clen);
+    let mut body = Vec::new();
     if clen > 0 {
         let stdin = io::stdin();
         stdin.take(clen as u64).read_to_end(&mut body).ok();
     }
 
-    // 3) Spawn *this* binary in CLI mode to run the script
-    //    We force CLI mode so the child doesn't enter cgi_main() again.
-    let self_exe = match env::current_exe() {
-        Ok(p) => p,
-        Err(e) => {
-            println!("Status: 500 Internal Server Error");
-            println!("Content-Type: text/plain; charset=utf-8");
-            println!();
-            println!("Failed to locate current executable: {e}");
-            return;
-        }
-    };
+    let mut envs: Vec<(String, String)> = vec![
+        ("QUERY_STRING".into(), query),           // pass through web context
+        ("REQUEST_METHOD".into(), method),
+        ("CONTENT_TYPE".into(), ctype),
+        ("CONTENT_LENGTH".into(), clen.to_string()),
+        ("SCRIPT_FILENAME".into(), script_path.clone()),
+    ];
+    if routed {
+        envs.push(("BASIL_ROUTER".into(), "1".into()));
+        envs.push(("PATH_INFO".into(), route_path(&script_path)));
+    }
 
-    let mut child = match Command::new(self_exe)
-        .arg("run")
-        .arg(&script_path)
-        .env("BASIL_FORCE_MODE", "cli")       // <- prevents recursion
-        .env("QUERY_STRING", &query)          // pass through web context
-        .env("REQUEST_METHOD", &method)
-        .env("CONTENT_TYPE", &ctype)
-        .env("CONTENT_LENGTH", clen.to_string())
-        .env("SCRIPT_FILENAME", &script_path)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-    {
-        Ok(child) => child,
+    // 3) Spawn *this* binary in CLI mode to run the script, 4) pipe the request body, 5) collect output
+    let mut out = CgiWriter { out: io::stdout(), started: false };
+    let stderr = match run_web_request(&script_path, routed, envs, &body, &mut out, &ChildRunner) {
+        Ok(e) => e,
         Err(e) => {
-            println!("Status: 500 Internal Server Error");
-            println!("Content-Type: text/plain; charset=utf-8");
-            println!();
-            println!("Failed to spawn Basil runner: {e}");
+            if !out.started {
+                println!("Status: 500 Internal Server Error");
+                println!("Content-Type: text/plain; charset=utf-8");
+                println!();
+                println!("Failed to run Basil script: {e}");
+            }
+            eprintln!("Failed to run Basil script: {e}");
             return;
         }
     };
 
-    // 4) Pipe request body to the child (if your Basil runtime wants it)
-    if clen > 0 {
-        if let Some(mut sin) = child.stdin.take() {
-            let _ = sin.write_all(&body);
-        }
+    // Send child's stderr to Apache error log (very helpful)
+    if !stderr.is_empty() {
+        eprintln!("{}", String::from_utf8_lossy(&stderr));
     }
+}
 
-    // 5) Collect output
-    let output = match child.wait_with_output() {
-        Ok(o) => o,
-        Err(e) => {
-            println!("Status: 500 Internal Server Error");
-            println!("Content-Type: text/plain; charset=utf-8");
-            println!();
-            println!("Failed to run Basil script: {e}");
-            return;
-        }
-    };
+// Receives a script's response while it runs: the CGI header block (without the
+// terminating blank line) once, then body chunks as the script flushes them.
+trait ResponseWriter {
+    fn head(&mut self, head: &str) -> io::Result<()>;
+    fn body(&mut self, chunk: &[u8]) -> io::Result<()>;
+    fn finish(&mut self) -> io::Result<()>;
+}
 
-    // Send child's stderr to Apache error log (very helpful)
-    if !output.stderr.is_empty() {
-        eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+// CGI: pass everything to the web server as it arrives.
+struct CgiWriter<W: Write> {
+    out: W,
+    started: bool,
+}
+
+impl<W: Write> ResponseWriter for CgiWriter<W> {
+    fn head(&mut self, head: &str) -> io::Result<()> {
+        self.started = true;
+        write!(self.out, "{}\r\n\r\n", head)?;
+        self.out.flush()
     }
+    fn body(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.out.write_all(chunk)?;
+        self.out.flush()
+    }
+    fn finish(&mut self) -> io::Result<()> { self.out.flush() }
+}
 
-    // Parse directives from the source to determine header policy
-    let src_for_dirs = match fs::read_to_string(&script_path) { Ok(s)=>s, Err(_)=>String::new() };
-    let (dirs, _) = parse_directives_and_bom(&src_for_dirs);
+// Runs a web request's script, handing its CGI output to `consume` while it is produced.
+// Returns what the script reported on stderr.
+trait ScriptRunner {
+    fn run(&self, script_path: &str, envs: Vec<(String, String)>, body: &[u8], consume: &mut dyn FnMut(&mut dyn Read) -> io::Result<()>) -> io::Result<Vec<u8>>;
+}
 
-    let stdout = output.stdout;
+// A child process of this binary per request (CGI, and `basic serve` for .bas pages).
+struct ChildRunner;
+
+impl ScriptRunner for ChildRunner {
+    fn run(&self, script_path: &str, envs: Vec<(String, String)>, body: &[u8], consume: &mut dyn FnMut(&mut dyn Read) -> io::Result<()>) -> io::Result<Vec<u8>> {
+        let mut child = spawn_runner(script_path, &envs)?;
+        // Feed stdin and drain stderr on their own threads so a chatty script can't block us
+        let stdin = child.stdin.take();
+        let body = body.to_vec();
+        let writer = std::thread::spawn(move || {
+            if let Some(mut sin) = stdin { let _ = sin.write_all(&body); }
+        });
+        let stderr_pipe = child.stderr.take();
+        let reader = std::thread::spawn(move || {
+            let mut v = Vec::new();
+            if let Some(mut e) = stderr_pipe { let _ = e.read_to_end(&mut v); }
+            v
+        });
+        let res = match child.stdout.take() {
+            Some(mut stdout) => consume(&mut stdout),
+            None => Err(io::Error::other("child stdout unavailable")),
+        };
+        if res.is_err() { let _ = child.kill(); }
+        let _ = child.wait();
+        let _ = writer.join();
+        let stderr = reader.join().unwrap_or_default();
+        res.map(|_| stderr)
+    }
+}
 
-    if dirs.cgi_no_header {
-        // Manual header mode: verify the program sent valid CGI headers (terminated by blank line)
-        let has_blank = stdout.windows(4).any(|w| w == b"\r\n\r\n");
-        if has_blank {
-            io::stdout().write_all(&stdout).ok();
-        } else {
-            println!("Status: 500 Internal Server Error");
-            println!("Content-Type: text/plain; charset=utf-8");
-            println!();
-            println!("No CGI header sent. Add headers or remove #CGI_NO_HEADER.");
+// Run a script for one web request (CGI or `basic serve`): enforce the script's CSRF
+// directive, run it with `runner` and stream its response to `out`. Returns its stderr.
+fn run_web_request(script_path: &str, routed: bool, mut envs: Vec<(String, String)>, body: &[u8], out: &mut dyn ResponseWriter, runner: &dyn ScriptRunner) -> io::Result<Vec<u8>> {
+    let dirs = script_directives(script_path);
+    // Request variables come from `envs` (serve) or from our own environment (CGI)
+    let var = |k: &str| envs.iter().find(|(n, _)| n == k).map(|(_, v)| v.clone()).or_else(|| env::var(k).ok()).unwrap_or_default();
+    let mut set_cookie = None;
+    if dirs.csrf {
+        let (method, cookies, ctype, header) = (var("REQUEST_METHOD"), var("HTTP_COOKIE"), var("CONTENT_TYPE"), var("HTTP_X_CSRF_TOKEN"));
+        let req = security::RequestInfo { method: &method, cookie_header: &cookies, content_type: &ctype, body, token_header: &header };
+        match security::check_csrf(&req) {
+            Some(c) => {
+                envs.push((basil_vm::web::CSRF_ENV.to_string(), c.token));
+                set_cookie = c.set_cookie;
+            }
+            None => {
+                out.head(security::FORBIDDEN_HEAD)?;
+                out.body(security::FORBIDDEN_BODY.as_bytes())?;
+                out.finish()?;
+                return Ok(Vec::new());
+            }
         }
-        return;
     }
+    let mut extra = security::response_headers(&dirs);
+    extra.extend(set_cookie);
 
-    // Automatic header mode: send default header (override if provided) right before body
-    let header = if let Some(h) = dirs.cgi_default_header { h } else { "Content-Type: text/html; charset=utf-8".to_string() };
-    println!("{}", header);
-    println!("");
-    io::stdout().write_all(&stdout).ok();
+    runner.run(script_path, envs, body, &mut |src| stream_response(&dirs, routed, &extra, src, out))
+}
+
+// Longest CGI header block we wait for before deciding the script sent none.
+const MAX_CGI_HEAD: usize = 64 * 1024;
+
+// Apply the script's header policy (#CGI_NO_HEADER / #CGI_DEFAULT_HEADER) and our extra
+// headers, then pass the body through chunk by chunk. Routed scripts write their own
+// header (see router.rs).
+fn stream_response(dirs: &template::Directives, routed: bool, extra: &[String], src: &mut dyn Read, out: &mut dyn ResponseWriter) -> io::Result<()> {
+    let mut buf = vec![0u8; 16 * 1024];
+    if !routed && !dirs.cgi_no_header {
+        // Automatic header mode: send default header (override if provided) right before body
+        let header = if let Some(h) = &dirs.cgi_default_header { h.clone() } else { "Content-Type: text/html; charset=utf-8".to_string() };
+        out.head(&security::merge_headers(&header, extra))?;
+        return pump(src, out, &mut buf);
+    }
+
+    // Manual header mode: collect the header block the program sent (terminated by a blank line)
+    let mut acc: Vec<u8> = Vec::new();
+    loop {
+        if let Some((end, body_start)) = header_end(&acc) {
+            let head = String::from_utf8_lossy(&acc[..end]).into_owned();
+            out.head(&security::merge_headers(&head, extra))?;
+            if body_start < acc.len() { out.body(&acc[body_start..])?; }
+            return pump(src, out, &mut buf);
+        }
+        let n = src.read(&mut buf)?;
+        if n == 0 || acc.len() > MAX_CGI_HEAD { break; }
+        acc.extend_from_slice(&buf[..n]);
+    }
+    // Routed scripts always send a header unless they failed before dispatching
+    let msg = if routed { "Internal Server Error\n" } else { "No CGI header sent. Add headers or remove #CGI_NO_HEADER.\n" };
+    out.head(&security::merge_headers("Status: 500 Internal Server Error\r\nContent-Type: text/plain; charset=utf-8", extra))?;
+    out.body(msg.as_bytes())?;
+    io::copy(src, &mut io::sink())?;
+    out.finish()
+}
+
+fn pump(src: &mut dyn Read, out: &mut dyn ResponseWriter, buf: &mut [u8]) -> io::Result<()> {
+    loop {
+        let n = src.read(buf)?;
+        if n == 0 { break; }
+        out.body(&buf[..n])?;
+    }
+    out.finish()
+}
+
+// (end of header lines, start of body) for a CGI header block ending in CRLFCRLF or LFLF.
+fn header_end(b: &[u8]) -> Option<(usize, usize)> {
+    let crlf = b.windows(4).position(|w| w == b"\r\n\r\n").map(|i| (i, i + 4));
+    let lf = b.windows(2).position(|w| w == b"\n\n").map(|i| (i, i + 2));
+    match (crlf, lf) {
+        (Some(c), Some(l)) => Some(if l.0 < c.0 { l } else { c }),
+        (c, l) => c.or(l),
+    }
+}
+
+// Run a script in a child process of this binary, forced into CLI mode so it doesn't
+// enter cgi_main() again. Shared by cgi_main and `basic serve`.
+fn spawn_runner(script_path: &str, envs: &[(String, String)]) -> io::Result<std::process::Child> {
+    let self_exe = env::current_exe()?;
+    Command::new(self_exe)
+        .arg("run")
+        .arg(script_path)
+        .env("BASIL_FORCE_MODE", "cli")       // <- prevents recursion
+        .envs(envs.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+}
+
+// Parse directives from the source to determine header policy
+fn script_directives(script_path: &str) -> template::Directives {
+    let src_for_dirs = match fs::read_to_string(script_path) { Ok(s)=>s, Err(_)=>String::new() };
+    parse_directives_and_bom(&src_for_dirs).0
+}
+
+fn is_routes_script(path: &str) -> bool {
+    Path::new(path).file_name().and_then(|n| n.to_str()).map(|n| n.eq_ignore_ascii_case("routes.bas")).unwrap_or(false)
+}
+
+// DOCUMENT_ROOT/routes.bas, used when the request does not map to a .bas file.
+fn routes_fallback() -> Option<String> {
+    let docroot = env::var("DOCUMENT_ROOT").ok()?;
+    let cand = PathBuf::from(docroot).join("routes.bas");
+    if cand.is_file() { Some(cand.to_string_lossy().into_owned()) } else { None }
+}
+
+// Path handed to the router: PATH_INFO when routes.bas was addressed directly
+// (/routes.bas/users/1), otherwise the path part of REQUEST_URI.
+fn route_path(script_path: &str) -> String {
+    let pi = env::var("PATH_INFO").unwrap_or_default();
+    let direct = env::var("SCRIPT_FILENAME").map(|sf| sf == script_path).unwrap_or(false);
+    let uri_path = env::var("REQUEST_URI").ok()
+        .map(|u| u.split('?').next().unwrap_or("").to_string())
+        .filter(|p| !p.is_empty());
+    match (direct, uri_path) {
+        (false, Some(p)) => p,
+        _ if !pi.is_empty() => pi,
+        _ => "/".to_string(),
+    }
 }
 
 // use std::env;
@@ -781,18 +1008,17 @@ fn cmd_test(mut args: Vec<String>) {
 
     let looks_like_template = src.contains("<?");
     let pre = if looks_like_template {
-        match precompile_template(&src) { Ok(r)=>r, Err(e)=>{ eprintln!("template error: {}", e); std::process::exit(1); } }
+        match precompile_template_file(&src, Path::new(&path)) { Ok(r)=>r, Err(e)=>{ eprintln!("template error: {}", e); std::process::exit(1); } }
     } else {
-        template::PrecompileResult { basil_source: src.clone(), directives: Directives::default() }
+        template::PrecompileResult::plain(src.clone())
     };
 
     // Cache path and fingerprint like cmd_run
     let meta = match fs::metadata(&path) { Ok(m)=>m, Err(e)=>{ eprintln!("stat {}: {}", path, e); std::process::exit(1);} };
-    let source_size = meta.len();
-    let source_mtime_ns: u64 = meta.modified().ok()
+    let (source_size, source_mtime_ns) = pre.fingerprint(meta.len(), meta.modified().ok()
         .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
         .map(|d| d.as_nanos() as u64)
-        .unwrap_or(0);
+        .unwrap_or(0));
     let templating_used = src.contains("<?");
     let flags: u32 = (if pre.directives.short_tags_on { 1u32 } else { 0u32 })
                    | (if templating_used { 2u32 } else { 0u32 });
@@ -815,7 +1041,7 @@ fn cmd_test(mut args: Vec<String>) {
         }
     }
     let program = if let Some(p) = program_opt { p } else {
-        let ast = match parse(&pre.basil_source) { Ok(a)=>a, Err(e)=>{ eprintln!("parse error: {}", e); std::process::exit(1);} };
+        let ast = match parse(&pre.basil_source) { Ok(a)=>a, Err(e)=>{ eprintln!("parse error: {}", pre.source_map.remap_message(&e.to_string())); std::process::exit(1);} };
         match compile(&ast) { Ok(p)=>{
             let body = serialize_program(&p);
             let mut hdr = Vec::with_capacity(32 + body.len());
@@ -829,7 +1055,7 @@ fn cmd_test(mut args: Vec<String>) {
             let tmp = cache_path.with_extension("basilx.tmp");
             if let Ok(mut f) = File::create(&tmp) { let _ = f.write_all(&hdr); let _ = f.sync_all(); let _ = fs::rename(&tmp, &cache_path); }
             p
-        }, Err(e)=>{ eprintln!("compile error: {}", e); std::process::exit(1)} }
+        }, Err(e)=>{ eprintln!("compile error: {}", pre.source_map.remap_message(&e.to_string())); std::process::exit(1)} }
     };
 
     let comments_map = extract_comments_map(&pre.basil_source);
@@ -838,10 +1064,18 @@ fn cmd_test(mut args: Vec<String>) {
     });
     let mock = MockInputProvider::new(seed);
     let mut vm = VM::new_with_test(program, mock, trace, Some(path.clone()), Some(comments_map), max_inputs);
+    vm.set_source_map(pre.source_map.clone());
     if let Err(e) = vm.run() {
-        let line = vm.current_line();
-        if line > 0 { eprintln!("runtime error at line {}: {}", line, e); }
-        else { eprintln!("runtime error: {}", e); }
+        eprintln!("{}", runtime_error_text(&vm, &pre, &e));
         std::process::exit(1);
     }
 }
+
+// Describe where a runtime error happened; template pages report the template file, line and column.
+fn runtime_error_text(vm: &VM, pre: &template::PrecompileResult, e: &basil_common::BasilError) -> String {
+    match vm.current_location() {
+        Some((file, line, col)) if !pre.source_map.lines.is_empty() => format!("runtime error at {}:{}:{}: {}", file, line, col, e),
+        Some((_, line, _)) => format!("runtime error at line {}: {}", line, e),
+        None => format!("runtime error: {}", e),
+    }
+}
@@ -1,14 +1,15 @@
 use std::collections::{HashMap, BTreeMap};
 use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 use basil_parser::parse;
-use basil_compiler::compile;
-use basil_bytecode::Value;
+use basil_compiler::compile_with_host;
+use basil_bytecode::{HostFunctions, Value};
 use basil_vm::VM;
 
-use crate::template::{precompile_template, Directives};
+use crate::template::precompile_template_file;
 use basil_bytecode::{serialize_program, deserialize_program};
 use std::time::UNIX_EPOCH;
 
@@ -30,11 +31,13 @@ pub struct Session {
     script_path: Option<String>,
     // If a program has executed STOP, keep its VM to allow RESUME
     suspended_vm: Option<VM>,
+    // Host functions available to programs and snippets (listed by :host)
+    pub host: Rc<HostFunctions>,
 }
 
 impl Session {
     pub fn new(settings: SessionSettings) -> Self {
-        Self { globals: HashMap::new(), order: Vec::new(), origins: HashMap::new(), history: Vec::new(), next_snippet_id: 0, settings, script_path: None, suspended_vm: None }
+        Self { globals: HashMap::new(), order: Vec::new(), origins: HashMap::new(), history: Vec::new(), next_snippet_id: 0, settings, script_path: None, suspended_vm: None, host: Rc::new(HostFunctions::new()) }
     }
 
     pub fn run_program(&mut self, path: &str) -> Result<(), String> {
@@ -42,16 +45,15 @@ impl Session {
         let src = fs::read_to_string(path).map_err(|e| format!("read {}: {}", path, e))?;
         let looks_like_template = src.contains("<?");
         let pre = if looks_like_template {
-            precompile_template(&src).map_err(|e| format!("template error: {}", e))?
+            precompile_template_file(&src, Path::new(path)).map_err(|e| format!("template error: {}", e))?
         } else {
-            crate::template::PrecompileResult { basil_source: src.clone(), directives: Directives::default() }
+            crate::template::PrecompileResult::plain(src.clone())
         };
         let meta = fs::metadata(path).map_err(|e| format!("stat {}: {}", path, e))?;
-        let source_size = meta.len();
-        let source_mtime_ns: u64 = meta.modified().ok()
+        let (source_size, source_mtime_ns) = pre.fingerprint(meta.len(), meta.modified().ok()
             .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
             .map(|d| d.as_nanos() as u64)
-            .unwrap_or(0);
+            .unwrap_or(0));
         let templating_used = src.contains("<?");
         let flags: u32 = (if pre.directives.short_tags_on { 1u32 } else { 0u32 })
                        | (if templating_used { 2u32 } else { 0u32 });
@@ -72,8 +74,8 @@ impl Session {
             }
         }
         let program = if let Some(p) = program_opt { p } else {
-            let ast = parse(&pre.basil_source).map_err(|e| format!("parse error: {}", e))?;
-            let prog = compile(&ast).map_err(|e| format!("compile error: {}", e))?;
+            let ast = parse(&pre.basil_source).map_err(|e| format!("parse error: {}", pre.source_map.remap_message(&e.to_string())))?;
+            let prog = compile_with_host(&ast, &self.host).map_err(|e| format!("compile error: {}", pre.source_map.remap_message(&e.to_string())))?;
             let body = serialize_program(&prog);
             let mut hdr = Vec::with_capacity(32 + body.len());
             hdr.extend_from_slice(b"BSLX");
@@ -92,7 +94,9 @@ impl Session {
             prog
         };
         let mut vm = VM::new(program);
+        vm.set_host_functions(self.host.clone());
         vm.set_script_path(path.to_string());
+        vm.set_source_map(pre.source_map.clone());
         self.script_path = Some(path.to_string());
         // Seed known globals into this VM so the program can reference preloaded names
         for name in vm.globals_snapshot().0.iter() {
@@ -102,8 +106,7 @@ impl Session {
         }
         let run_res = vm.run();
         if let Err(e) = run_res {
-            let line = vm.current_line();
-            let msg = if self.settings.show_backtraces { format!("runtime error at line {}: {}", line, e) }
+            let msg = if self.settings.show_backtraces { crate::runtime_error_text(&vm, &pre, &e) }
                       else { format!("runtime error: {}", e) };
             return Err(msg);
         }
@@ -153,8 +156,9 @@ impl Session {
                 vec![basil_ast::Stmt::Print { expr: e }]
             } else { ast.clone() }
         } else { ast.clone() };
-        let prog = compile(&ast2).map_err(|e| format!("compile error: {}", e))?;
+        let prog = compile_with_host(&ast2, &self.host).map_err(|e| format!("compile error: {}", e))?;
         let mut vm = VM::new(prog);
+        vm.set_host_functions(self.host.clone());
         if let Some(p) = &self.script_path { vm.set_script_path(p.clone()); }
         // Seed known globals into this snippet VM
         for name in vm.globals_snapshot().0.iter() { // get names cheaply
@@ -364,6 +368,8 @@ pub fn start_repl(mut sess: Session, maybe_path: Option<String>) {
                             Value::Object(obj) => { let _ = obj; "OBJECT" },
                             Value::List(_) => "LIST",
                             Value::Dict(_) => "DICT",
+                            Value::Bytes(_) => "BYTES",
+                            Value::Decimal(_) => "DECIMAL",
                             Value::StrArray2D { .. } => "STRARRAY2D",
                         };
                         let origins = sess.origins.get(name).cloned().unwrap_or_default();
@@ -408,7 +414,7 @@ pub fn start_repl(mut sess: Session, maybe_path: Option<String>) {
             let parts: Vec<&str> = trimmed.split_whitespace().collect();
             match parts.get(0).copied().unwrap_or("") {
                 ":help" => {
-                    println!(":help, :vars [filter], :types [name], :methods <var>, :disasm <name>, :history, :save <file>, :load <file>, :bt on|off, :env, :exit");
+                    println!(":help, :vars [filter], :types [name], :methods <var>, :host, :disasm <name>, :history, :save <file>, :load <file>, :bt on|off, :env, :exit");
                 }
                 ":vars" => {
                     let filt = parts.get(1).map(|s| *s);
@@ -429,6 +435,8 @@ pub fn start_repl(mut sess: Session, maybe_path: Option<String>) {
                                 Value::Object(_) => "OBJECT",
                                 Value::List(_) => "LIST",
                                 Value::Dict(_) => "DICT",
+                                Value::Bytes(_) => "BYTES",
+                                Value::Decimal(_) => "DECIMAL",
                                 Value::StrArray2D { .. } => "STRARRAY2D",
                             };
                             println!("{} : {}", name, ty);
@@ -437,6 +445,10 @@ pub fn start_repl(mut sess: Session, maybe_path: Option<String>) {
                         println!("types: functions/classes not indexed in this build");
                     }
                 }
+                ":host" => {
+                    if sess.host.is_empty() { println!("no host functions registered"); }
+                    for f in sess.host.list() { println!("{}", f.signature()); }
+                }
                 ":methods" => {
                     println!("methods: reflection metadata not available in this build");
                 }
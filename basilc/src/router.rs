@@ -0,0 +1,211 @@
+// Request dispatch for scripts that register ROUTE handlers.
+//
+// The script's top level runs first (registering routes, hooks, shared setup); afterwards
+// the request is matched against the route table and the handler is called on the same VM.
+// Under CGI the parent process sets BASIL_ROUTER=1 (`basic serve` dispatches in-process
+// with Options::web set), and the router writes the CGI header itself so that it can
+// choose the status (200/404/405). All output goes
+// through the VM's buffered output, so a handler error that happens before anything was
+// flushed replaces the partial page with a 500 response.
+
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use basil_bytecode::{Dict, Value};
+use basil_common::BasilError;
+use basil_vm::VM;
+use basil_vm::router::percent_decode;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+}
+
+impl Request {
+    // REQUEST_METHOD (default GET) and PATH_INFO (default "/").
+    pub fn from_env() -> Self { Request::from_vars(|k| env::var(k).ok()) }
+
+    // The same, from request variables held elsewhere (`basic serve` runs routes.bas in-process)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let method = var("REQUEST_METHOD").unwrap_or_else(|| "GET".into()).to_ascii_uppercase();
+        let path = var("PATH_INFO").filter(|p| !p.is_empty()).unwrap_or_else(|| "/".into());
+        Request { method, path }
+    }
+}
+
+pub struct Options {
+    // Running behind CGI or `basic serve` (as opposed to `basic run` on a terminal)
+    pub web: bool,
+    // Header sent before handler output; None when the script uses #CGI_NO_HEADER
+    pub default_header: Option<String>,
+    // Directory whose files may be served when no route matches
+    pub static_root: Option<PathBuf>,
+}
+
+// Dispatch one request. Returns Err(message) if a handler or hook failed.
+pub fn dispatch(vm: &mut VM, req: &Request, opts: &Options, fmt_err: &dyn Fn(&VM, &BasilError) -> String) -> Result<(), String> {
+    let found = vm.routes().find(&req.method, &req.path);
+    let (handler, params, status) = match found {
+        Some((h, p)) => (Some(h), p, "200 OK"),
+        None => {
+            if opts.web && matches!(req.method.as_str(), "GET" | "HEAD") {
+                if let Some(root) = &opts.static_root {
+                    if let Some((file, mime)) = static_file(root, &req.path) {
+                        let res = send_static(vm, &file, mime, req.method == "HEAD");
+                        let _ = vm.flush_output();
+                        return res.map_err(|e| e.to_string());
+                    }
+                }
+            }
+            if !vm.routes().methods_for(&req.path).is_empty() {
+                (None, Vec::new(), "405 Method Not Allowed")
+            } else {
+                (vm.routes().not_found.clone(), Vec::new(), "404 Not Found")
+            }
+        }
+    };
+
+    if opts.web {
+        write_header(vm, status, opts.default_header.as_deref(), handler.is_some());
+    }
+
+    vm.set_route_params(&req.path, params.clone());
+    let res = (|| -> basil_common::Result<()> {
+        let before = vm.routes().before.clone();
+        for h in &before {
+            let r = call_with_params(vm, h, &params)?;
+            if matches!(r, Value::Bool(false) | Value::Int(0)) || matches!(r, Value::Num(n) if n == 0.0) {
+                return Ok(());
+            }
+        }
+        match &handler {
+            Some(h) => { call_with_params(vm, h, &params)?; }
+            None => { vm.write_output(format!("{}\n", status).as_bytes()); }
+        }
+        let after = vm.routes().after.clone();
+        for h in &after {
+            call_with_params(vm, h, &params)?;
+        }
+        Ok(())
+    })();
+
+    let Err(e) = res else {
+        let _ = vm.flush_output();
+        return Ok(());
+    };
+    let msg = fmt_err(vm, &e);
+    let on_error = vm.routes().on_error.clone();
+    // Nothing reached the client yet: start over with a 500 response
+    if vm.discard_output() && opts.web {
+        write_header(vm, "500 Internal Server Error", opts.default_header.as_deref(), on_error.is_some());
+    }
+    let res = match on_error {
+        Some(h) => {
+            let arity = if let Value::Func(f) = &h { f.arity } else { 0 };
+            let args = if arity == 1 { vec![Value::Str(e.0.clone())] } else { vec![] };
+            vm.call_value(&h, args).map(|_| ()).map_err(|e2| format!("{}\n(while handling: {})", fmt_err(vm, &e2), msg.clone()))
+        }
+        None => { vm.write_output(b"Internal Server Error\n"); Ok(()) }
+    };
+    let _ = vm.flush_output();
+    res.and(Err(msg))
+}
+
+// CGI header for a router response. Without a default header (#CGI_NO_HEADER) a script
+// handler writes its own; responses produced by the router itself are plain text.
+fn write_header(vm: &mut VM, status: &str, default_header: Option<&str>, script_handler: bool) {
+    match (default_header, script_handler) {
+        (Some(h), _) => vm.write_output(format!("Status: {}\r\n{}\r\n\r\n", status, h).as_bytes()),
+        (None, false) => vm.write_output(format!("Status: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n", status).as_bytes()),
+        (None, true) => {}
+    }
+}
+
+// Hooks and handlers take either no parameter or a DICT of the route's path params.
+fn call_with_params(vm: &mut VM, f: &Value, params: &[(String, String)]) -> basil_common::Result<Value> {
+    let arity = if let Value::Func(func) = f { func.arity } else { 0 };
+    let args = if arity == 1 {
+        let map: Dict = params.iter().map(|(k, v)| (k.clone(), Value::Str(v.clone()))).collect();
+        vec![Value::Dict(Rc::new(RefCell::new(map)))]
+    } else { vec![] };
+    vm.call_value(f, args)
+}
+
+// Extensions that may be served as static files, with their content types.
+// Scripts, caches and anything unlisted are never served.
+fn static_mime(ext: &str) -> Option<&'static str> {
+    Some(match ext.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "pdf" => "application/pdf",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "wasm" => "application/wasm",
+        _ => return None,
+    })
+}
+
+// Resolve a URL path to a servable file under `root`: no dot-segments or hidden files,
+// whitelisted extension only, and the canonical path must stay inside the root.
+pub fn static_file(root: &Path, url_path: &str) -> Option<(PathBuf, &'static str)> {
+    let path = url_path.split(['?', '#']).next().unwrap_or("");
+    let decoded = percent_decode(path);
+    if decoded.contains('\0') || decoded.contains('\\') { return None; }
+    let rel = PathBuf::from(decoded.trim_start_matches('/'));
+    for c in rel.components() {
+        match c {
+            Component::Normal(s) if !s.to_string_lossy().starts_with('.') => {}
+            _ => return None,
+        }
+    }
+    let mime = static_mime(rel.extension()?.to_str()?)?;
+    let root = fs::canonicalize(root).ok()?;
+    let full = fs::canonicalize(root.join(&rel)).ok()?;
+    if !full.starts_with(&root) || !full.is_file() { return None; }
+    Some((full, mime))
+}
+
+fn send_static(vm: &mut VM, file: &Path, mime: &str, head_only: bool) -> std::io::Result<()> {
+    let bytes = fs::read(file)?;
+    vm.write_output(format!("Status: 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n", mime, bytes.len()).as_bytes());
+    if !head_only { vm.write_output(&bytes); }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_file_rejects_scripts_and_traversal() {
+        let dir = env::temp_dir().join(format!("basil_static_{}", std::process::id()));
+        fs::create_dir_all(dir.join("css")).unwrap();
+        fs::write(dir.join("css/site.css"), "body{}").unwrap();
+        fs::write(dir.join("routes.bas"), "PRINT 1").unwrap();
+        fs::write(dir.join(".env"), "x").unwrap();
+
+        assert!(static_file(&dir, "/css/site.css?v=2").is_some());
+        assert!(static_file(&dir, "/css/%73ite.css").is_some());
+        assert!(static_file(&dir, "/routes.bas").is_none());
+        assert!(static_file(&dir, "/.env").is_none());
+        assert!(static_file(&dir, "/css/../css/site.css").is_none());
+        assert!(static_file(&dir, "/css/%2e%2e/routes.bas").is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,164 @@
+// Security policy applied by the CGI front end (cgi_main and `basic serve`) around a script
+// run, driven by the top-of-file directives:
+//
+//   #CGI_CSRF                 issue a basil_csrf cookie and refuse state-changing requests
+//                             (POST/PUT/PATCH/DELETE) that don't echo it back as the
+//                             csrf_token form field or an X-CSRF-Token header; pages
+//                             add the field with CSRF_FIELD$() or CSRF_FORMS$(html$)
+//   #CGI_SECURITY_HEADERS     add the default header set below
+//   #CGI_CSP "policy"         Content-Security-Policy ("" to omit)
+//   #CGI_FRAME_OPTIONS "DENY" X-Frame-Options ("" to omit)
+//   #CGI_HEADER "Name: value" any extra header (repeatable)
+//
+// Headers are merged into the response header block, whether the script relies on the
+// default header or writes its own (#CGI_NO_HEADER); headers the script already sent win.
+
+use basil_vm::{crypto, web};
+
+use crate::template::Directives;
+
+const DEFAULT_CSP: &str = "default-src 'self'; object-src 'none'; base-uri 'self'; frame-ancestors 'self'; form-action 'self'";
+const DEFAULT_FRAME_OPTIONS: &str = "SAMEORIGIN";
+
+pub struct RequestInfo<'a> {
+    pub method: &'a str,
+    pub cookie_header: &'a str,
+    pub content_type: &'a str,
+    pub body: &'a [u8],
+    // X-CSRF-Token request header (HTTP_X_CSRF_TOKEN)
+    pub token_header: &'a str,
+}
+
+pub struct Csrf {
+    pub token: String,
+    // Set-Cookie line when the browser has no valid token yet
+    pub set_cookie: Option<String>,
+}
+
+pub const FORBIDDEN_HEAD: &str = "Status: 403 Forbidden\r\nContent-Type: text/plain; charset=utf-8";
+pub const FORBIDDEN_BODY: &str = "CSRF token missing or invalid. Reload the form and try again.\n";
+
+// Find or issue the request's CSRF token and verify it on state-changing methods.
+// None means the request must be refused (see FORBIDDEN_HEAD/FORBIDDEN_BODY).
+pub fn check_csrf(req: &RequestInfo) -> Option<Csrf> {
+    let existing = web::cookie_value(req.cookie_header, web::CSRF_COOKIE).filter(|t| web::is_valid_token(t));
+    let unsafe_method = matches!(req.method.to_ascii_uppercase().as_str(), "POST" | "PUT" | "PATCH" | "DELETE");
+    if unsafe_method {
+        let sent = if !req.token_header.is_empty() {
+            Some(req.token_header.trim().to_string())
+        } else {
+            submitted_field(req.content_type, req.body, web::CSRF_FIELD)
+        };
+        let ok = matches!((&existing, &sent), (Some(c), Some(s)) if crypto::secure_eq(c.as_bytes(), s.as_bytes()));
+        if !ok { return None; }
+    }
+    match existing {
+        Some(token) => Some(Csrf { token, set_cookie: None }),
+        None => {
+            // Without OS randomness refuse the request rather than issue a guessable token
+            let token = web::new_token().ok()?;
+            let cookie = web::set_cookie_header(web::CSRF_COOKIE, &token, "SameSite=Strict").ok()?;
+            Some(Csrf { token, set_cookie: Some(cookie) })
+        }
+    }
+}
+
+// Value of a form field in a urlencoded or multipart body.
+fn submitted_field(content_type: &str, body: &[u8], name: &str) -> Option<String> {
+    let ct = content_type.to_ascii_lowercase();
+    if ct.starts_with("multipart/form-data") {
+        let boundary = content_type.split(';')
+            .find_map(|p| p.trim().strip_prefix("boundary="))
+            .map(|b| b.trim_matches('"').to_string())?;
+        let text = String::from_utf8_lossy(body);
+        let marker = format!("name=\"{}\"", name);
+        for part in text.split(&format!("--{}", boundary)) {
+            let Some((head, value)) = part.split_once("\r\n\r\n") else { continue };
+            if head.contains(&marker) {
+                return Some(value.trim_end_matches("\r\n").to_string());
+            }
+        }
+        return None;
+    }
+    let text = String::from_utf8_lossy(body);
+    text.split('&').find_map(|kv| {
+        let (k, v) = kv.split_once('=')?;
+        if k == name { Some(v.replace('+', " ")) } else { None }
+    })
+}
+
+// Header lines requested by the directives, in a stable order.
+pub fn response_headers(dirs: &Directives) -> Vec<String> {
+    let mut out = Vec::new();
+    let csp = dirs.csp.clone().or_else(|| dirs.security_headers.then(|| DEFAULT_CSP.to_string()));
+    if let Some(csp) = csp.filter(|c| !c.is_empty()) {
+        out.push(format!("Content-Security-Policy: {}", csp));
+    }
+    let fo = dirs.frame_options.clone().or_else(|| dirs.security_headers.then(|| DEFAULT_FRAME_OPTIONS.to_string()));
+    if let Some(fo) = fo.filter(|f| !f.is_empty()) {
+        out.push(format!("X-Frame-Options: {}", fo));
+    }
+    if dirs.security_headers {
+        out.push("X-Content-Type-Options: nosniff".to_string());
+        out.push("Referrer-Policy: strict-origin-when-cross-origin".to_string());
+    }
+    out.extend(dirs.extra_headers.iter().cloned());
+    out
+}
+
+// Append `headers` to a CGI header block (lines without the terminating blank line).
+// A header the script already sent is kept as is (Set-Cookie lines are always added).
+pub fn merge_headers(head: &str, headers: &[String]) -> String {
+    let mut lines: Vec<&str> = head.lines().filter(|l| !l.trim().is_empty()).collect();
+    let present: Vec<String> = lines.iter()
+        .filter_map(|l| l.split_once(':').map(|(k, _)| k.trim().to_ascii_lowercase()))
+        .collect();
+    for h in headers {
+        let name = h.split(':').next().unwrap_or("").trim().to_ascii_lowercase();
+        if name != "set-cookie" && present.contains(&name) { continue; }
+        lines.push(h);
+    }
+    lines.join("\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req<'a>(method: &'a str, cookie: &'a str, body: &'a [u8]) -> RequestInfo<'a> {
+        RequestInfo { method, cookie_header: cookie, content_type: "application/x-www-form-urlencoded", body, token_header: "" }
+    }
+
+    #[test]
+    fn csrf_issue_and_verify() {
+        let first = check_csrf(&req("GET", "", b"")).unwrap();
+        assert!(first.set_cookie.unwrap().starts_with("Set-Cookie: basil_csrf="));
+        let cookie = format!("basil_csrf={}", first.token);
+
+        let again = check_csrf(&req("GET", &cookie, b"")).unwrap();
+        assert_eq!(again.token, first.token);
+        assert!(again.set_cookie.is_none());
+
+        let good = format!("user=a&csrf_token={}", first.token);
+        assert!(check_csrf(&req("POST", &cookie, good.as_bytes())).is_some());
+        assert!(check_csrf(&req("POST", &cookie, b"user=a")).is_none());
+        assert!(check_csrf(&req("POST", "", good.as_bytes())).is_none());
+
+        let mp = format!("--XY\r\nContent-Disposition: form-data; name=\"csrf_token\"\r\n\r\n{}\r\n--XY--\r\n", first.token);
+        let r = RequestInfo { content_type: "multipart/form-data; boundary=XY", ..req("POST", &cookie, mp.as_bytes()) };
+        assert!(check_csrf(&r).is_some());
+    }
+
+    #[test]
+    fn headers_merge_into_block() {
+        let dirs = Directives { security_headers: true, frame_options: Some("DENY".into()), ..Default::default() };
+        let hs = response_headers(&dirs);
+        assert!(hs.contains(&"X-Frame-Options: DENY".to_string()));
+        assert!(hs.contains(&"X-Content-Type-Options: nosniff".to_string()));
+
+        let s = merge_headers("Status: 200 OK\nX-Frame-Options: SAMEORIGIN\n", &hs);
+        assert!(s.starts_with("Status: 200 OK\r\nX-Frame-Options: SAMEORIGIN\r\nContent-Security-Policy: "));
+        assert!(!s.contains("DENY"));
+        assert!(s.ends_with("nosniff\r\nReferrer-Policy: strict-origin-when-cross-origin"));
+    }
+}
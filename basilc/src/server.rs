@@ -0,0 +1,426 @@
+// `basic serve [dir] [--port N] [--host H]`: a small development web server.
+//
+// - If <dir>/routes.bas exists, every request goes through it (the router serves static
+//   files itself when no route matches). routes.bas is compiled once into a pool of VMs
+//   (basil_vm::workers::WorkerPool, one per CPU): each runs the top level once to register
+//   the routes, then dispatches requests with the request's variables, body and output.
+//   Every request starts from the globals the top level left (objects excepted, see
+//   workers.rs), as it would in a child process. EXIT ends the server. The pool is
+//   rebuilt when routes.bas changes.
+// - Otherwise /path/page.bas (or /path/ -> index.bas) runs as a CGI script in a child
+//   process, exactly like the CGI entry point does (each page in its own directory), and
+//   other paths are served as static files.
+// The CGI output is converted into an HTTP/1.1 response and streamed with chunked
+// transfer encoding as the script flushes it. One request per connection.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use basil_bytecode::SharedProgram;
+use basil_vm::providers::{Environment, MapEnvironment};
+use basil_vm::workers::WorkerPool;
+use basil_vm::VM;
+
+use crate::{router, template, ScriptRunner};
+
+const MAX_HEAD: usize = 64 * 1024;
+// Larger request bodies are refused with 413 before anything is allocated for them
+const MAX_BODY: usize = 16 * 1024 * 1024;
+const BAD_REQUEST: &str = "400 Bad Request";
+
+struct HttpRequest {
+    method: String,
+    target: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+pub fn serve(root: PathBuf, host: &str, port: u16) -> io::Result<()> {
+    let root = std::fs::canonicalize(&root)?;
+    // routes.bas runs in this process, in its own directory like a child would
+    std::env::set_current_dir(&root)?;
+    let listener = TcpListener::bind((host, port))?;
+    println!("Serving {} on http://{}:{}/ (Ctrl+C to stop)", root.display(), host, port);
+    let routes = Arc::new(RoutesCache::default());
+    for stream in listener.incoming() {
+        let stream = match stream { Ok(s) => s, Err(e) => { eprintln!("accept: {}", e); continue; } };
+        let (root, routes) = (root.clone(), routes.clone());
+        std::thread::spawn(move || {
+            if let Err(e) = handle(stream, &root, port, &routes) {
+                eprintln!("request error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, root: &Path, port: u16, routes_cache: &RoutesCache) -> io::Result<()> {
+    let peer = stream.peer_addr().map(|a| a.ip().to_string()).unwrap_or_default();
+    let req = match read_request(&mut stream)? {
+        Ok(r) => r,
+        Err(status) => {
+            let text = format!("{}\n", &status[4..]);
+            return write_response(&mut stream, status, &[("Content-Type".into(), "text/plain; charset=utf-8".into())], text.as_bytes(), false);
+        }
+    };
+    let (path, query) = match req.target.split_once('?') {
+        Some((p, q)) => (p.to_string(), q.to_string()),
+        None => (req.target.clone(), String::new()),
+    };
+    let head_only = req.method == "HEAD";
+
+    let routes = root.join("routes.bas");
+    let cgi = if routes.is_file() {
+        Some((routes, true, path.clone()))
+    } else {
+        script_for(root, &path).map(|s| (s, false, String::new()))
+    };
+
+    let status = if let Some((script, routed, path_info)) = cgi {
+        let script_s = script.to_string_lossy().into_owned();
+        let envs = cgi_env(&req, root, &script_s, &path, &path_info, &query, &peer, port, routed);
+        let mut out = HttpWriter { stream: &mut stream, head_only, chunked: false, status: String::new() };
+        let stderr = if routed {
+            let runner = routes_cache.get(&script);
+            crate::run_web_request(&script_s, routed, envs, &req.body, &mut out, &*runner)?
+        } else {
+            crate::run_web_request(&script_s, routed, envs, &req.body, &mut out, &crate::ChildRunner)?
+        };
+        if !stderr.is_empty() {
+            eprintln!("{}", String::from_utf8_lossy(&stderr));
+        }
+        out.status
+    } else if let Some((file, mime)) = static_for(root, &path) {
+        let bytes = std::fs::read(&file)?;
+        write_response(&mut stream, "200 OK", &[("Content-Type".into(), mime.into())], &bytes, head_only)?;
+        "200 OK".to_string()
+    } else {
+        write_response(&mut stream, "404 Not Found", &[("Content-Type".into(), "text/plain; charset=utf-8".into())], b"Not Found\n", head_only)?;
+        "404 Not Found".to_string()
+    };
+    println!("{} {} -> {}", req.method, req.target, status);
+    Ok(())
+}
+
+// The request, or the error status to answer with when it is malformed or too large
+fn read_request(stream: impl Read) -> io::Result<Result<HttpRequest, &'static str>> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    let mut total = reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(m), Some(t)) => (m.to_ascii_uppercase(), t.to_string()),
+        _ => return Ok(Err(BAD_REQUEST)),
+    };
+    let mut headers = Vec::new();
+    loop {
+        let mut h = String::new();
+        let n = reader.read_line(&mut h)?;
+        total += n;
+        if n == 0 || total > MAX_HEAD { return Ok(Err(BAD_REQUEST)); }
+        let h = h.trim_end_matches(['\r', '\n']);
+        if h.is_empty() { break; }
+        if let Some((k, v)) = h.split_once(':') {
+            headers.push((k.trim().to_string(), v.trim().to_string()));
+        }
+    }
+    let clen: usize = headers.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("Content-Length"))
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(0);
+    if clen > MAX_BODY { return Ok(Err("413 Payload Too Large")); }
+    let mut body = vec![0u8; clen];
+    reader.read_exact(&mut body)?;
+    Ok(Ok(HttpRequest { method, target, headers, body }))
+}
+
+// routes.bas as last loaded, with its modification time
+#[derive(Default)]
+struct RoutesCache(Mutex<Option<(Option<SystemTime>, Arc<Routes>)>>);
+
+impl RoutesCache {
+    // The loaded routes.bas, reloaded first if the file changed. Requests wait while it loads.
+    fn get(&self, path: &Path) -> Arc<Routes> {
+        let mtime = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut cur = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((t, routes)) = &*cur {
+            if *t == mtime { return routes.clone(); }
+        }
+        let routes = Arc::new(Routes::load(path).unwrap_or_else(Routes::Failed));
+        *cur = Some((mtime, routes.clone()));
+        routes
+    }
+}
+
+enum Routes {
+    Pool { pool: WorkerPool, opts: Arc<router::Options>, pre: Arc<template::PrecompileResult> },
+    // Did not compile or its top level failed: every request gets a 500 and this message
+    Failed(String),
+}
+
+impl Routes {
+    fn load(path: &Path) -> Result<Routes, String> {
+        let (program, pre, src) = crate::load_script(path).map_err(|(_, msg)| msg)?;
+        let opts = Arc::new(crate::router_options(&src, path, true));
+        let pre = Arc::new(pre);
+        let program = SharedProgram::new(program).map_err(|e| e.0)?;
+        let (script, setup_pre) = (path.to_string_lossy().into_owned(), pre.clone());
+        let size = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        let pool = WorkerPool::new(program, size, move |vm: &mut VM| {
+            vm.set_script_path(script.clone());
+            vm.set_source_map(setup_pre.source_map.clone());
+            // The top level registers the routes; anything it prints has no request to go to
+            vm.set_output(Box::new(io::sink()), false);
+            // Workers a handler SPAWNs run on the real disk and environment like the server;
+            // they have no response to write to, so what they print goes to the server log
+            vm.set_worker_setup(Arc::new(|w: &mut VM| { w.set_output(Box::new(io::stderr()), true); Ok(()) }));
+            vm.run().map_err(|e| basil_common::BasilError(crate::runtime_error_text(vm, &setup_pre, &e)))
+        }).map_err(|e| e.0)?;
+        Ok(Routes::Pool { pool, opts, pre })
+    }
+}
+
+impl ScriptRunner for Routes {
+    fn run(&self, _script_path: &str, envs: Vec<(String, String)>, body: &[u8], consume: &mut dyn FnMut(&mut dyn Read) -> io::Result<()>) -> io::Result<Vec<u8>> {
+        let (pool, opts, pre) = match self {
+            Routes::Pool { pool, opts, pre } => (pool, opts.clone(), pre.clone()),
+            Routes::Failed(msg) => return consume(&mut io::empty()).map(|_| msg.clone().into_bytes()),
+        };
+        let (tx, rx) = mpsc::channel();
+        let body = body.to_vec();
+        let done = pool.execute(move |vm: &mut VM| {
+            // The variables a child would get: ours plus the request's
+            let env = MapEnvironment::from_process();
+            for (k, v) in &envs { env.set_var(k, v); }
+            let req = router::Request::from_vars(|k| env.var(k));
+            vm.set_environment(Rc::new(env));
+            vm.set_request_body(body);
+            vm.set_output(Box::new(Sent(tx)), false);
+            let res = router::dispatch(vm, &req, &opts, &|vm: &VM, e: &basil_common::BasilError| crate::runtime_error_text(vm, &pre, e));
+            // Dropping the sender ends the response
+            vm.set_output(Box::new(io::sink()), false);
+            res
+        });
+        let res = consume(&mut Received { rx, buf: Vec::new(), pos: 0 });
+        let stderr = match done.recv() {
+            Ok(Ok(())) => Vec::new(),
+            Ok(Err(msg)) => msg.into_bytes(),
+            Err(_) => b"routes.bas: the worker running the request panicked".to_vec(),
+        };
+        res.map(|_| stderr)
+    }
+}
+
+// A pooled VM's output, passed to the connection's thread as the VM flushes it
+struct Sent(Sender<Vec<u8>>);
+
+impl Write for Sent {
+    // A closed connection is not the script's error; the rest of its output is dropped
+    fn write(&mut self, b: &[u8]) -> io::Result<usize> { let _ = self.0.send(b.to_vec()); Ok(b.len()) }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+struct Received {
+    rx: Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for Received {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            match self.rx.recv() {
+                Ok(b) => { self.buf = b; self.pos = 0; }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+// .bas script for a URL path: "/dir/" -> dir/index.bas, "/page.bas" -> page.bas.
+fn script_for(root: &Path, url_path: &str) -> Option<PathBuf> {
+    let rel = safe_relative(url_path)?;
+    let cand = if url_path.ends_with('/') || rel.as_os_str().is_empty() { rel.join("index.bas") } else { rel };
+    if cand.extension().and_then(|e| e.to_str()) != Some("bas") { return None; }
+    let full = root.join(cand);
+    if full.is_file() { Some(full) } else { None }
+}
+
+fn static_for(root: &Path, url_path: &str) -> Option<(PathBuf, &'static str)> {
+    if url_path.ends_with('/') {
+        return router::static_file(root, &format!("{}index.html", url_path));
+    }
+    router::static_file(root, url_path)
+}
+
+fn safe_relative(url_path: &str) -> Option<PathBuf> {
+    let decoded = basil_vm::router::percent_decode(url_path);
+    if decoded.contains('\0') || decoded.contains('\\') { return None; }
+    let rel = PathBuf::from(decoded.trim_start_matches('/'));
+    if rel.components().all(|c| matches!(c, Component::Normal(s) if !s.to_string_lossy().starts_with('.'))) {
+        Some(rel)
+    } else { None }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cgi_env(req: &HttpRequest, root: &Path, script: &str, path: &str, path_info: &str, query: &str, peer: &str, port: u16, routed: bool) -> Vec<(String, String)> {
+    let header = |name: &str| req.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.clone()).unwrap_or_default();
+    let mut envs: HashMap<String, String> = HashMap::new();
+    for (k, v) in &req.headers {
+        let key = format!("HTTP_{}", k.to_ascii_uppercase().replace('-', "_"));
+        if key == "HTTP_CONTENT_TYPE" || key == "HTTP_CONTENT_LENGTH" { continue; }
+        envs.entry(key).and_modify(|e| { e.push_str(", "); e.push_str(v); }).or_insert_with(|| v.clone());
+    }
+    let mut out: Vec<(String, String)> = envs.into_iter().collect();
+    out.extend([
+        ("GATEWAY_INTERFACE".to_string(), "CGI/1.1".to_string()),
+        ("SERVER_SOFTWARE".to_string(), "basic-serve".to_string()),
+        ("SERVER_PROTOCOL".to_string(), "HTTP/1.1".to_string()),
+        ("SERVER_NAME".to_string(), header("Host").split(':').next().unwrap_or("localhost").to_string()),
+        ("SERVER_PORT".to_string(), port.to_string()),
+        ("REMOTE_ADDR".to_string(), peer.to_string()),
+        ("REQUEST_METHOD".to_string(), req.method.clone()),
+        ("REQUEST_URI".to_string(), req.target.clone()),
+        ("QUERY_STRING".to_string(), query.to_string()),
+        ("CONTENT_TYPE".to_string(), header("Content-Type")),
+        ("CONTENT_LENGTH".to_string(), req.body.len().to_string()),
+        ("DOCUMENT_ROOT".to_string(), root.to_string_lossy().into_owned()),
+        ("SCRIPT_FILENAME".to_string(), script.to_string()),
+        ("SCRIPT_NAME".to_string(), if routed { String::new() } else { path.to_string() }),
+        ("PATH_INFO".to_string(), path_info.to_string()),
+    ]);
+    if routed { out.push(("BASIL_ROUTER".to_string(), "1".to_string())); }
+    out
+}
+
+// Status and headers of a CGI header block; a Location without Status means 302.
+fn parse_head(head: &str) -> (String, Vec<(String, String)>) {
+    let mut status = String::new();
+    let mut headers = Vec::new();
+    for line in head.lines() {
+        let Some((k, v)) = line.split_once(':') else { continue };
+        let (k, v) = (k.trim(), v.trim());
+        if k.eq_ignore_ascii_case("Status") { status = v.to_string(); continue; }
+        if k.eq_ignore_ascii_case("Location") && status.is_empty() { status = "302 Found".into(); }
+        headers.push((k.to_string(), v.to_string()));
+    }
+    if status.is_empty() { status = "200 OK".into(); }
+    (status, headers)
+}
+
+// Streams a script's response as HTTP/1.1, chunked unless the script set Content-Length.
+struct HttpWriter<'a> {
+    stream: &'a mut TcpStream,
+    head_only: bool,
+    chunked: bool,
+    status: String,
+}
+
+impl crate::ResponseWriter for HttpWriter<'_> {
+    fn head(&mut self, head: &str) -> io::Result<()> {
+        let (status, headers) = parse_head(head);
+        let has_len = headers.iter().any(|(k, _)| k.eq_ignore_ascii_case("Content-Length"));
+        self.chunked = !has_len && !self.head_only;
+        let mut out = format!("HTTP/1.1 {}\r\n", status);
+        for (k, v) in &headers {
+            if k.eq_ignore_ascii_case("Connection") || k.eq_ignore_ascii_case("Transfer-Encoding") { continue; }
+            out.push_str(&format!("{}: {}\r\n", k, v));
+        }
+        if self.chunked { out.push_str("Transfer-Encoding: chunked\r\n"); }
+        out.push_str("Connection: close\r\n\r\n");
+        self.status = status;
+        self.stream.write_all(out.as_bytes())?;
+        self.stream.flush()
+    }
+    fn body(&mut self, chunk: &[u8]) -> io::Result<()> {
+        if self.head_only || chunk.is_empty() { return Ok(()); }
+        if self.chunked {
+            write!(self.stream, "{:x}\r\n", chunk.len())?;
+            self.stream.write_all(chunk)?;
+            self.stream.write_all(b"\r\n")?;
+        } else {
+            self.stream.write_all(chunk)?;
+        }
+        self.stream.flush()
+    }
+    fn finish(&mut self) -> io::Result<()> {
+        if self.chunked { self.stream.write_all(b"0\r\n\r\n")?; }
+        self.stream.flush()
+    }
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, headers: &[(String, String)], body: &[u8], head_only: bool) -> io::Result<()> {
+    let mut out = format!("HTTP/1.1 {}\r\n", status).into_bytes();
+    for (k, v) in headers {
+        if k.eq_ignore_ascii_case("Content-Length") || k.eq_ignore_ascii_case("Connection") { continue; }
+        out.extend_from_slice(format!("{}: {}\r\n", k, v).as_bytes());
+    }
+    out.extend_from_slice(format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()).as_bytes());
+    if !head_only { out.extend_from_slice(body); }
+    stream.write_all(&out)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cgi_head_to_http() {
+        let (st, h) = parse_head("Status: 404 Not Found\r\nContent-Type: text/plain");
+        assert_eq!(st, "404 Not Found");
+        assert_eq!(h, vec![("Content-Type".to_string(), "text/plain".to_string())]);
+        let (st, h) = parse_head("Location: /home\nSet-Cookie: a=1");
+        assert_eq!(st, "302 Found");
+        assert_eq!(h.len(), 2);
+        assert_eq!(parse_head("Content-Type: text/html").0, "200 OK");
+    }
+
+    #[test]
+    fn request_body_size_is_capped() {
+        let req = read_request(&b"POST /f HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc"[..]).unwrap().ok().unwrap();
+        assert_eq!((req.method.as_str(), req.body.as_slice()), ("POST", &b"abc"[..]));
+        let huge = format!("POST /f HTTP/1.1\r\nContent-Length: {}\r\n\r\n", usize::MAX);
+        assert_eq!(read_request(huge.as_bytes()).unwrap().err(), Some("413 Payload Too Large"));
+        assert_eq!(read_request(&b"\r\n"[..]).unwrap().err(), Some(BAD_REQUEST));
+    }
+
+    #[test]
+    fn routes_run_on_the_worker_pool() {
+        let dir = std::env::temp_dir().join(format!("basil_serve_pool_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("routes.bas");
+        std::fs::write(&path, "hits = 0\nFUNC Form()\n  hits = hits + 1\n  FOR EACH x IN POST$()\n    PRINTLN x\n  NEXT\n  PRINTLN hits\n  RETURN 0\nEND FUNC\nROUTE \"POST\", \"/form\", Form\n").unwrap();
+        let routes = Routes::load(&path).unwrap();
+        let run = |method: &str, path_info: &str, body: &str| {
+            let envs = vec![
+                ("REQUEST_METHOD".to_string(), method.to_string()),
+                ("PATH_INFO".to_string(), path_info.to_string()),
+                ("CONTENT_TYPE".to_string(), "application/x-www-form-urlencoded".to_string()),
+                ("CONTENT_LENGTH".to_string(), body.len().to_string()),
+            ];
+            let mut out = Vec::new();
+            let stderr = routes.run("", envs, body.as_bytes(), &mut |src| src.read_to_end(&mut out).map(|_| ())).unwrap();
+            (String::from_utf8(out).unwrap(), String::from_utf8(stderr).unwrap())
+        };
+        assert_eq!(run("POST", "/form", "a=1&b=2"), ("Status: 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\na=1\nb=2\n1\n".to_string(), String::new()));
+        // Nothing carries over from the first request, whichever worker takes the second
+        assert!(run("POST", "/form", "c=3").0.ends_with("\r\n\r\nc=3\n1\n"));
+        assert!(run("GET", "/nope", "").0.starts_with("Status: 404 Not Found\r\n"));
+
+        std::fs::write(&path, "ROUTE \"GET\", \"/\", Nope\n").unwrap();
+        let Err(msg) = Routes::load(&path) else { panic!("loaded a broken routes.bas") };
+        assert!(msg.contains("ROUTE handler must be a FUNC or SUB name"), "{}", msg);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
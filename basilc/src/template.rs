@@ -1,9 +1,23 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use basil_common::{SourceLoc, SourceMap};
 
 #[derive(Debug, Clone, Default)]
 pub struct Directives {
     pub cgi_no_header: bool,
     pub cgi_default_header: Option<String>,
+    // #CGI_CSRF: issue a CSRF cookie and reject POST/PUT/PATCH/DELETE without a matching token
+    pub csrf: bool,
+    // #CGI_SECURITY_HEADERS: send the default CSP / X-Frame-Options / nosniff / Referrer-Policy set
+    pub security_headers: bool,
+    // #CGI_CSP "..." and #CGI_FRAME_OPTIONS "..." override single headers of that set ("" drops it)
+    pub csp: Option<String>,
+    pub frame_options: Option<String>,
+    // #CGI_HEADER "Name: value" (repeatable)
+    pub extra_headers: Vec<String>,
     pub short_tags_on: bool,
     pub reserved_basil_dev: bool,
     pub reserved_basil_debug: bool,
@@ -13,14 +27,52 @@ pub struct Directives {
 pub struct PrecompileResult {
     pub basil_source: String,
     pub directives: Directives,
-    // future: source map entries
+    /// Partials and layouts read while precompiling (canonical paths, first-seen order).
+    pub dependencies: Vec<PathBuf>,
+    /// Generated line → template file/line/column (empty for plain Basil source).
+    pub source_map: SourceMap,
+}
+
+impl PrecompileResult {
+    /// Wrap plain Basil source that did not go through the template precompiler.
+    pub fn plain(src: String) -> Self {
+        PrecompileResult { basil_source: src, directives: Directives::default(), dependencies: Vec::new(), source_map: SourceMap::default() }
+    }
+
+    /// Fold the size and mtime of every dependency into a source fingerprint, so that
+    /// editing a partial or layout invalidates the cached bytecode of the page.
+    pub fn fingerprint(&self, size: u64, mtime_ns: u64) -> (u64, u64) {
+        let mut size = size;
+        let mut mtime_ns = mtime_ns;
+        for dep in &self.dependencies {
+            if let Ok(meta) = fs::metadata(dep) {
+                size = size.wrapping_add(meta.len());
+                let mt = meta.modified().ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_nanos() as u64)
+                    .unwrap_or(0);
+                mtime_ns = mtime_ns.max(mt);
+            }
+        }
+        (size, mtime_ns)
+    }
 }
 
 #[derive(Debug)]
 pub enum TplError {
     Msg(String),
+    /// Error located in a template file (1-based line and column).
+    At { file: Option<String>, line: usize, col: usize, msg: String },
+}
+impl fmt::Display for TplError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TplError::Msg(s) => write!(f, "{}", s),
+            TplError::At { file: Some(file), line, col, msg } => write!(f, "{}:{}:{}: {}", file, line, col, msg),
+            TplError::At { file: None, line, col, msg } => write!(f, "line {}, column {}: {}", line, col, msg),
+        }
+    }
 }
-impl fmt::Display for TplError { fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { match self { TplError::Msg(s) => write!(f, "{}", s) } } }
 impl std::error::Error for TplError {}
 
 pub fn parse_directives_and_bom(src: &str) -> (Directives, usize) {
@@ -41,14 +93,18 @@ pub fn parse_directives_and_bom(src: &str) -> (Directives, usize) {
         // match directives
         if line.starts_with("#CGI_NO_HEADER") { dir.cgi_no_header = true; }
         else if let Some(rest) = line.strip_prefix("#CGI_DEFAULT_HEADER") {
-            // Expect quoted string
-            if let Some(qpos) = rest.find('"') {
-                let after = &rest[qpos+1..];
-                if let Some(endq) = after.rfind('"') {
-                    let val = &after[..endq];
-                    dir.cgi_default_header = Some(val.to_string());
-                }
-            }
+            if let Some(val) = quoted_arg(rest) { dir.cgi_default_header = Some(val); }
+        }
+        else if line.starts_with("#CGI_CSRF") { dir.csrf = true; }
+        else if line.starts_with("#CGI_SECURITY_HEADERS") { dir.security_headers = true; }
+        else if let Some(rest) = line.strip_prefix("#CGI_CSP") {
+            if let Some(val) = quoted_arg(rest) { dir.csp = Some(val); }
+        }
+        else if let Some(rest) = line.strip_prefix("#CGI_FRAME_OPTIONS") {
+            if let Some(val) = quoted_arg(rest) { dir.frame_options = Some(val); }
+        }
+        else if let Some(rest) = line.strip_prefix("#CGI_HEADER") {
+            if let Some(val) = quoted_arg(rest) { if val.contains(':') { dir.extra_headers.push(val); } }
         }
         else if line.starts_with("#CGI_SHORT_TAGS_ON") { dir.short_tags_on = true; }
         else if line.starts_with("#BASIL_DEV") { dir.reserved_basil_dev = true; }
@@ -61,98 +117,572 @@ pub fn parse_directives_and_bom(src: &str) -> (Directives, usize) {
     (dir, i)
 }
 
+// Directive argument: the text between the first and the last double quote.
+fn quoted_arg(rest: &str) -> Option<String> {
+    let qpos = rest.find('"')?;
+    let after = &rest[qpos+1..];
+    let endq = after.rfind('"')?;
+    Some(after[..endq].to_string())
+}
+
+/// Precompile a template that has no file of its own; `<?include ?>`/`<?extends ?>`
+/// paths are resolved against the current directory.
+#[cfg(test)]
 pub fn precompile_template(src: &str) -> Result<PrecompileResult, TplError> {
-    let (directives, mut i) = parse_directives_and_bom(src);
-    let bytes = src.as_bytes();
-    let mut out = String::new();
-
-
-    // Helper to append PRINT of raw text
-    let emit_text = |text: &str, out: &mut String| {
-        if text.is_empty() { return; }
-        let mut s = String::with_capacity(text.len()+2);
-        s.push('"');
-        for ch in text.chars() {
-            match ch {
-                '"' => s.push_str("\\\""),
-                '\\' => s.push_str("\\\\"),
-                '\n' => s.push_str("\\n"),
-                '\r' => s.push_str("\\r"),
-                '\t' => s.push_str("\\t"),
-                _ => s.push(ch),
+    Precompiler::default().run(src, None)
+}
+
+/// Precompile the template stored at `path`; `<?include ?>`/`<?extends ?>` paths are
+/// resolved relative to the file that names them.
+pub fn precompile_template_file(src: &str, path: &Path) -> Result<PrecompileResult, TplError> {
+    Precompiler::default().run(src, Some(path))
+}
+
+// ---------------------------------------------------------------------------
+// Template syntax
+//
+//   text                    → PRINT "text";
+//   <?= expr ?>             → PRINT with escaping picked from the HTML context
+//   <?== expr ?>            → PRINT (expr); unescaped (so is <?= RAW(x) ?>)
+//   <?= HTML$(x) ?>         → same as <?= x ?>: the context's escaping already ends in HTML$
+//   <?basil code ?>         → code as-is (<?bas ... ?> with #CGI_SHORT_TAGS_ON)
+//   <?include "file" ?>     → the other template inlined here
+//   <?extends "file" ?>     → render the layout `file`, filling its blocks from this template
+//   <?block name ?>...<?endblock ?>
+//                           → a named, overridable region
+//
+// In a template that extends a layout, only blocks and code may appear outside of
+// blocks: code runs before the layout is rendered (handy for LET title$ = ...),
+// everything else must live inside a block.
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy)]
+struct Pos { file: usize, offset: usize }
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String, Pos),
+    Echo { expr: String, raw: bool, pos: Pos },
+    Code(String, Pos),
+    Block { name: String, body: Vec<Node> },
+}
+
+struct Parsed {
+    extends: Option<(String, Pos)>,
+    nodes: Vec<Node>,
+}
+
+struct SrcFile {
+    name: Option<String>,
+    dir: PathBuf,
+    canon: Option<PathBuf>,
+    line_starts: Vec<usize>,
+    src: String,
+}
+
+#[derive(Default)]
+struct Precompiler {
+    files: Vec<SrcFile>,
+    /// Canonical paths of the files currently being parsed/extended (cycle detection).
+    active: Vec<PathBuf>,
+    deps: Vec<PathBuf>,
+    short_tags_on: bool,
+}
+
+impl Precompiler {
+    fn run(mut self, src: &str, path: Option<&Path>) -> Result<PrecompileResult, TplError> {
+        let (directives, start) = parse_directives_and_bom(src);
+        self.short_tags_on = directives.short_tags_on;
+        let root = self.add_file(src.to_string(), path);
+        if let Some(c) = self.files[root].canon.clone() { self.active.push(c); }
+        let parsed = self.parse_file(root, start)?;
+        let mut overrides: HashMap<String, Vec<Node>> = HashMap::new();
+        let nodes = self.resolve(parsed, &mut overrides)?;
+
+        let mut out = String::new();
+        let mut ctx = HtmlCtx::default();
+        let mut map = SourceMap {
+            files: self.files.iter().map(|f| f.name.clone().unwrap_or_default()).collect(),
+            lines: Vec::new(),
+        };
+        for n in &nodes {
+            let start = out.len();
+            self.emit(n, &mut ctx, &mut out)?;
+            // Every generated line of a node maps to where the node starts; code keeps its own line breaks
+            let pos = match n { Node::Text(_, p) | Node::Code(_, p) | Node::Echo { pos: p, .. } => *p, Node::Block { .. } => continue };
+            let (line, col) = self.line_col(pos);
+            let is_code = matches!(n, Node::Code(..));
+            for k in 0..out[start..].matches('\n').count() {
+                let loc = if is_code && k > 0 { SourceLoc { file: pos.file as u32, line: (line + k) as u32, col: 1 } }
+                          else { SourceLoc { file: pos.file as u32, line: line as u32, col: col as u32 } };
+                map.lines.push(loc);
+            }
+        }
+        Ok(PrecompileResult { basil_source: out, directives, dependencies: self.deps, source_map: map })
+    }
+
+    fn add_file(&mut self, src: String, path: Option<&Path>) -> usize {
+        let mut line_starts = vec![0usize];
+        for (i, b) in src.bytes().enumerate() { if b == b'\n' { line_starts.push(i + 1); } }
+        let dir = path.and_then(|p| p.parent()).map(|d| d.to_path_buf()).unwrap_or_else(|| PathBuf::from("."));
+        let canon = path.map(|p| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf()));
+        self.files.push(SrcFile { name: path.map(|p| p.display().to_string()), dir, canon, line_starts, src });
+        self.files.len() - 1
+    }
+
+    /// 1-based line and column of `pos` in its file.
+    fn line_col(&self, pos: Pos) -> (usize, usize) {
+        let f = &self.files[pos.file];
+        let line_idx = match f.line_starts.binary_search(&pos.offset) { Ok(i) => i, Err(i) => i - 1 };
+        let line_start = f.line_starts[line_idx];
+        (line_idx + 1, f.src[line_start..pos.offset].chars().count() + 1)
+    }
+
+    fn err(&self, pos: Pos, msg: impl Into<String>) -> TplError {
+        let (line, col) = self.line_col(pos);
+        TplError::At { file: self.files[pos.file].name.clone(), line, col, msg: msg.into() }
+    }
+
+    /// Read a template named by an include/extends tag at `pos`.
+    fn load(&mut self, spec: &str, pos: Pos) -> Result<usize, TplError> {
+        let path = self.files[pos.file].dir.join(spec);
+        let canon = fs::canonicalize(&path).map_err(|e| self.err(pos, format!("cannot open '{}': {}", spec, e)))?;
+        if self.active.contains(&canon) {
+            return Err(self.err(pos, format!("'{}' includes or extends itself", spec)));
+        }
+        let src = fs::read_to_string(&canon).map_err(|e| self.err(pos, format!("cannot read '{}': {}", spec, e)))?;
+        if !self.deps.contains(&canon) { self.deps.push(canon.clone()); }
+        Ok(self.add_file(src, Some(&path)))
+    }
+
+    fn parse_file(&mut self, file: usize, start: usize) -> Result<Parsed, TplError> {
+        let src = self.files[file].src.clone();
+        let bytes = src.as_bytes();
+        let mut i = start;
+        // Partials and layouts only get their BOM stripped; directives belong to the page being run.
+        if file != 0 && bytes.starts_with(&[0xEF, 0xBB, 0xBF]) { i = 3; }
+        let mut extends: Option<(String, Pos)> = None;
+        // Stack of open blocks; index 0 is the template's top level
+        let mut stack: Vec<(String, Pos, Vec<Node>)> = vec![(String::new(), Pos { file, offset: i }, Vec::new())];
+        let mut seen_blocks: HashSet<String> = HashSet::new();
+
+        let mut text_start = i;
+        while i < bytes.len() {
+            let Some(ltq) = src[i..].find("<?").map(|n| i + n) else { break; };
+            if ltq > text_start {
+                stack.last_mut().unwrap().2.push(Node::Text(src[text_start..ltq].to_string(), Pos { file, offset: text_start }));
+            }
+            let pos = Pos { file, offset: ltq };
+            let after = ltq + 2;
+            if after >= bytes.len() { return Err(self.err(pos, "unterminated tag")); }
+            let rest = &src[after..];
+
+            // Echo shorthand: <?= expr ?> (escaped) and <?== expr ?> (raw)
+            if rest.starts_with('=') {
+                let raw_form = rest.starts_with("==");
+                let es = if raw_form { after + 2 } else { after + 1 };
+                let end = self.closing(&src, es, pos)?;
+                let expr = src[es..end].trim();
+                // Basic validation: no semicolons or block keywords
+                if expr.contains(';') || contains_kw(expr, &["BEGIN","END","WHILE","FOR","IF","ELSE","FUNC"]) {
+                    return Err(self.err(pos, "Echo block accepts a single expression only"));
+                }
+                if expr.is_empty() { return Err(self.err(pos, "Echo block needs an expression")); }
+                let (expr, raw) = match outer_call(expr) {
+                    Some((name, inner)) if name.eq_ignore_ascii_case("RAW") => (inner.trim().to_string(), true),
+                    // Escaping x again would double it; dropping the call must not skip the
+                    // URL checks or the errors of contexts HTML$ does not make safe
+                    Some((name, inner)) if !raw_form && (name.eq_ignore_ascii_case("HTML") || name.eq_ignore_ascii_case("HTML$")) => (inner.trim().to_string(), false),
+                    _ => (expr.to_string(), raw_form),
+                };
+                stack.last_mut().unwrap().2.push(Node::Echo { expr, raw, pos });
+                i = end + 2; text_start = i;
+                continue;
+            }
+
+            // Code block <?basil ... ?> or short <?bas ... ?> if enabled
+            if rest.starts_with("basil") || (self.short_tags_on && rest.starts_with("bas")) {
+                let code_start = if rest.starts_with("basil") { after + 5 } else { after + 3 };
+                // skip optional whitespace
+                let mut cs = code_start;
+                while cs < bytes.len() && bytes[cs].is_ascii_whitespace() { cs += 1; }
+                let end = self.closing(&src, cs, pos)?;
+                stack.last_mut().unwrap().2.push(Node::Code(src[cs..end].to_string(), Pos { file, offset: cs }));
+                i = end + 2; text_start = i;
+                continue;
             }
+
+            // Keyword tags: include / extends / block / endblock
+            let word_len = rest.bytes().take_while(|b| b.is_ascii_alphabetic()).count();
+            let word = rest[..word_len].to_ascii_lowercase();
+            if matches!(word.as_str(), "include" | "extends" | "block" | "endblock") {
+                let end = self.closing(&src, after + word_len, pos)?;
+                let arg = src[after + word_len..end].trim();
+                i = end + 2; text_start = i;
+                match word.as_str() {
+                    "include" => {
+                        let spec = self.string_arg(arg, "include", pos)?;
+                        let inc = self.load(&spec, pos)?;
+                        let canon = self.files[inc].canon.clone().unwrap();
+                        self.active.push(canon);
+                        let parsed = self.parse_file(inc, 0)?;
+                        self.active.pop();
+                        if let Some((_, epos)) = parsed.extends {
+                            return Err(self.err(epos, "an included template cannot use <?extends ?>"));
+                        }
+                        for n in parsed.nodes {
+                            if let Node::Block { name, .. } = &n {
+                                if !seen_blocks.insert(name.to_ascii_lowercase()) {
+                                    return Err(self.err(pos, format!("block '{}' is defined more than once", name)));
+                                }
+                            }
+                            stack.last_mut().unwrap().2.push(n);
+                        }
+                    }
+                    "extends" => {
+                        let spec = self.string_arg(arg, "extends", pos)?;
+                        if extends.is_some() { return Err(self.err(pos, "a template can extend only one layout")); }
+                        let only_ws = stack.len() == 1 && stack[0].2.iter().all(|n| matches!(n, Node::Text(t, _) if t.trim().is_empty()));
+                        if !only_ws { return Err(self.err(pos, "<?extends ?> must come before any other content")); }
+                        extends = Some((spec, pos));
+                    }
+                    "block" => {
+                        if !is_ident(arg) { return Err(self.err(pos, "expected a block name: <?block name ?>")); }
+                        if !seen_blocks.insert(arg.to_ascii_lowercase()) {
+                            return Err(self.err(pos, format!("block '{}' is defined more than once", arg)));
+                        }
+                        stack.push((arg.to_string(), pos, Vec::new()));
+                    }
+                    _ => {
+                        if stack.len() == 1 { return Err(self.err(pos, "<?endblock ?> without a matching <?block ?>")); }
+                        let (name, _, body) = stack.pop().unwrap();
+                        if !arg.is_empty() && !arg.eq_ignore_ascii_case(&name) {
+                            return Err(self.err(pos, format!("<?endblock {} ?> closes block '{}'", arg, name)));
+                        }
+                        stack.last_mut().unwrap().2.push(Node::Block { name, body });
+                    }
+                }
+                continue;
+            }
+
+            // Illegal bare '<?...'
+            return Err(self.err(pos, "Illegal bare '<? ... ?>'. Use <?basil ... ?>, <?bas ... ?> (with #CGI_SHORT_TAGS_ON), or <?= expr ?>."));
         }
-        s.push('"');
-        out.push_str("PRINT ");
-        out.push_str(&s);
-        out.push_str(";\n");
-    };
-
-    // Scan TEXT/ECHO/CODE
-    let mut text_start = i; // start of current TEXT segment
-    while i < bytes.len() {
-        // find next '<?' by scanning forward
-        let mut scan = i;
-        let mut ltq_opt: Option<usize> = None;
-        while scan + 1 < bytes.len() {
-            if bytes[scan] == b'<' && bytes[scan+1] == b'?' { ltq_opt = Some(scan); break; }
-            scan += 1;
+        // Tail text
+        if text_start < src.len() {
+            stack.last_mut().unwrap().2.push(Node::Text(src[text_start..].to_string(), Pos { file, offset: text_start }));
         }
-        let Some(ltq) = ltq_opt else { break; };
-        // Emit text up to ltq
-        if ltq > text_start {
-            let text = &src[text_start..ltq];
-            emit_text(text, &mut out);
+        if stack.len() > 1 {
+            let (name, pos, _) = stack.pop().unwrap();
+            return Err(self.err(pos, format!("block '{}' is missing <?endblock ?>", name)));
         }
-        // Determine tag type
-        if ltq + 2 > bytes.len() { return Err(TplError::Msg("unterminated tag opener".into())); }
-        let after = ltq + 2;
-        if after >= bytes.len() { return Err(TplError::Msg("unterminated tag".into())); }
-        // Echo shorthand
-        if bytes.get(after) == Some(&b'=') {
-            // Find closing '?>' honoring Basil string/comment syntax
-            let (end, _) = find_closing(src, after+1)?; // start after '='<
-            let expr = &src[after+1 .. end];
-            // Basic validation: no semicolons or block keywords
-            if expr.contains(';') || contains_kw(expr, &["BEGIN","END","WHILE","FOR","IF","ELSE","FUNC"]) {
-                return Err(TplError::Msg("Echo block accepts a single expression only".into()));
+        let (_, _, nodes) = stack.pop().unwrap();
+        Ok(Parsed { extends, nodes })
+    }
+
+    fn closing(&self, src: &str, start: usize, pos: Pos) -> Result<usize, TplError> {
+        find_closing(src, start).map(|(end, _)| end).map_err(|_| self.err(pos, "Unterminated block: expected '?>'"))
+    }
+
+    fn string_arg(&self, arg: &str, tag: &str, pos: Pos) -> Result<String, TplError> {
+        let inner = arg.strip_prefix('"').and_then(|a| a.strip_suffix('"'));
+        match inner {
+            Some(s) if !s.is_empty() && !s.contains('"') => Ok(s.to_string()),
+            _ => Err(self.err(pos, format!("expected a quoted file name: <?{} \"file\" ?>", tag))),
+        }
+    }
+
+    /// Flatten a parsed template into the node sequence that is actually rendered,
+    /// following `<?extends ?>` up to the base layout.
+    fn resolve(&mut self, parsed: Parsed, overrides: &mut HashMap<String, Vec<Node>>) -> Result<Vec<Node>, TplError> {
+        let Some((spec, pos)) = parsed.extends else {
+            let mut out = Vec::new();
+            let mut filling = Vec::new();
+            flatten(parsed.nodes, overrides, &mut filling, &mut out);
+            return Ok(out);
+        };
+        let mut pre = Vec::new();
+        for n in parsed.nodes {
+            match n {
+                Node::Code(..) => pre.push(n),
+                Node::Text(t, _) if t.trim().is_empty() => {}
+                Node::Block { name, body } => collect_blocks(name, body, overrides),
+                Node::Echo { pos, .. } => return Err(self.err(pos, "output outside of a <?block ?> in a template that extends a layout")),
+                Node::Text(t, pos) => {
+                    // Point at the first non-blank character of the stray text
+                    let skip = t.len() - t.trim_start().len();
+                    return Err(self.err(Pos { file: pos.file, offset: pos.offset + skip }, "text outside of a <?block ?> in a template that extends a layout"));
+                }
             }
-            let expr_trim = expr.trim();
-            out.push_str("PRINT ("); out.push_str(expr_trim); out.push_str(");\n");
-            i = end + 2; // skip '?>'
-            text_start = i;
-            continue;
         }
-        // Code block <?basil ... ?> or short <?bas ... ?> if enabled
-        let rest = &src[after..];
-        if rest.starts_with("basil") || (directives.short_tags_on && rest.starts_with("bas")) {
-            // compute start of code content
-            let code_start = if rest.starts_with("basil") { after + 5 } else { after + 3 };
-            // skip optional whitespace
-            let mut cs = code_start;
-            while cs < src.len() && src.as_bytes()[cs].is_ascii_whitespace() { cs += 1; }
-            // Find '?>' honoring strings/comments
-            let (end, _) = find_closing(src, cs)?;
-            let code = &src[cs..end];
-            out.push_str(code);
-            let code_trim = code.trim_end();
-            if !code_trim.ends_with(';') && !code_trim.is_empty() { out.push_str(";\n"); }
-            else { out.push('\n'); }
-            i = end + 2; text_start = i;
-            continue;
+        let parent = self.load(&spec, pos)?;
+        let canon = self.files[parent].canon.clone().unwrap();
+        self.active.push(canon);
+        let parent_parsed = self.parse_file(parent, 0)?;
+        let mut out = pre;
+        out.extend(self.resolve(parent_parsed, overrides)?);
+        self.active.pop();
+        Ok(out)
+    }
+
+    fn emit(&self, n: &Node, ctx: &mut HtmlCtx, out: &mut String) -> Result<(), TplError> {
+        match n {
+            Node::Text(t, _) => {
+                ctx.feed(t);
+                emit_text(t, out);
+            }
+            Node::Code(code, _) => {
+                out.push_str(code);
+                let code_trim = code.trim_end();
+                if !code_trim.ends_with(';') && !code_trim.is_empty() { out.push_str(";\n"); }
+                else { out.push('\n'); }
+            }
+            Node::Echo { expr, raw, pos } => {
+                if *raw {
+                    out.push_str("PRINT ("); out.push_str(expr); out.push_str(");\n");
+                } else {
+                    let wrapped = ctx.escape(expr).map_err(|m| self.err(*pos, m))?;
+                    out.push_str("PRINT "); out.push_str(&wrapped); out.push_str(";\n");
+                }
+                ctx.after_echo();
+            }
+            Node::Block { .. } => unreachable!("blocks are flattened before emission"),
+        }
+        Ok(())
+    }
+}
+
+/// Record the bodies of a child template's blocks (nested ones included) unless a
+/// more-derived template already overrode them.
+fn collect_blocks(name: String, body: Vec<Node>, overrides: &mut HashMap<String, Vec<Node>>) {
+    for n in &body {
+        if let Node::Block { name, body } = n { collect_blocks(name.clone(), body.clone(), overrides); }
+    }
+    overrides.entry(name.to_ascii_lowercase()).or_insert(body);
+}
+
+fn flatten(nodes: Vec<Node>, overrides: &HashMap<String, Vec<Node>>, filling: &mut Vec<String>, out: &mut Vec<Node>) {
+    for n in nodes {
+        match n {
+            Node::Block { name, body } => {
+                let key = name.to_ascii_lowercase();
+                // A block nested in its own override renders its default body
+                match overrides.get(&key) {
+                    Some(ov) if !filling.contains(&key) => {
+                        filling.push(key);
+                        flatten(ov.clone(), overrides, filling, out);
+                        filling.pop();
+                    }
+                    _ => flatten(body, overrides, filling, out),
+                }
+            }
+            other => out.push(other),
+        }
+    }
+}
+
+// Append PRINT of raw text
+fn emit_text(text: &str, out: &mut String) {
+    if text.is_empty() { return; }
+    let mut s = String::with_capacity(text.len()+2);
+    s.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            _ => s.push(ch),
+        }
+    }
+    s.push('"');
+    out.push_str("PRINT ");
+    out.push_str(&s);
+    out.push_str(";\n");
+}
+
+// --- HTML context tracking for auto-escaping ---
+
+#[derive(Debug, Clone, PartialEq, Default)]
+enum HState {
+    #[default]
+    Text,
+    TagOpen,
+    TagName,
+    Tag,
+    AttrName,
+    AfterAttrName,
+    BeforeValue,
+    Value { quote: Option<char>, start: bool },
+    /// After "<!": becomes a comment on "--", otherwise a declaration such as <!doctype>
+    Bang { dashes: u8 },
+    Decl,
+    Comment { dashes: u8 },
+    RawText,
+}
+
+/// Tracks where in an HTML document the output currently is, so `<?= ?>` can pick
+/// the escaping that is safe there.
+#[derive(Debug, Default)]
+struct HtmlCtx {
+    state: HState,
+    tag: String,
+    end_tag: bool,
+    attr: String,
+    /// Lowercased tail of the text seen inside <script>/<style>, to spot the end tag
+    raw_tail: String,
+}
+
+impl HtmlCtx {
+    fn feed(&mut self, text: &str) {
+        for ch in text.chars() { self.step(ch); }
+    }
+
+    fn close_tag(&mut self) {
+        let raw = !self.end_tag && (self.tag == "script" || self.tag == "style");
+        self.state = if raw { HState::RawText } else { HState::Text };
+        self.raw_tail.clear();
+    }
+
+    fn step(&mut self, ch: char) {
+        match self.state.clone() {
+            HState::Text => if ch == '<' { self.state = HState::TagOpen; },
+            HState::TagOpen => {
+                self.tag.clear(); self.end_tag = false;
+                if ch == '!' { self.state = HState::Bang { dashes: 0 }; }
+                else if ch == '/' { self.end_tag = true; self.state = HState::TagName; }
+                else if ch.is_ascii_alphabetic() { self.tag.push(ch.to_ascii_lowercase()); self.state = HState::TagName; }
+                else { self.state = HState::Text; }
+            }
+            HState::TagName => {
+                if ch == '>' { self.close_tag(); }
+                else if ch.is_whitespace() || ch == '/' { self.state = HState::Tag; }
+                else { self.tag.push(ch.to_ascii_lowercase()); }
+            }
+            HState::Tag => {
+                if ch == '>' { self.close_tag(); }
+                else if !ch.is_whitespace() && ch != '/' { self.attr.clear(); self.attr.push(ch.to_ascii_lowercase()); self.state = HState::AttrName; }
+            }
+            HState::AttrName => {
+                if ch == '=' { self.state = HState::BeforeValue; }
+                else if ch == '>' { self.close_tag(); }
+                else if ch.is_whitespace() { self.state = HState::AfterAttrName; }
+                else { self.attr.push(ch.to_ascii_lowercase()); }
+            }
+            HState::AfterAttrName => {
+                if ch == '=' { self.state = HState::BeforeValue; }
+                else if ch == '>' { self.close_tag(); }
+                else if !ch.is_whitespace() { self.attr.clear(); self.attr.push(ch.to_ascii_lowercase()); self.state = HState::AttrName; }
+            }
+            HState::BeforeValue => {
+                if ch == '"' || ch == '\'' { self.state = HState::Value { quote: Some(ch), start: true }; }
+                else if ch == '>' { self.close_tag(); }
+                else if !ch.is_whitespace() { self.state = HState::Value { quote: None, start: false }; }
+            }
+            HState::Value { quote: Some(q), .. } => {
+                if ch == q { self.state = HState::Tag; } else { self.state = HState::Value { quote: Some(q), start: false }; }
+            }
+            HState::Value { quote: None, .. } => {
+                if ch == '>' { self.close_tag(); } else if ch.is_whitespace() { self.state = HState::Tag; }
+            }
+            HState::Bang { dashes } => {
+                if ch == '-' && dashes == 1 { self.state = HState::Comment { dashes: 0 }; }
+                else if ch == '-' { self.state = HState::Bang { dashes: 1 }; }
+                else if ch == '>' { self.state = HState::Text; }
+                else { self.state = HState::Decl; }
+            }
+            HState::Decl => if ch == '>' { self.state = HState::Text; },
+            HState::Comment { dashes } => {
+                if ch == '-' { self.state = HState::Comment { dashes: dashes.saturating_add(1) }; }
+                else if ch == '>' && dashes >= 2 { self.state = HState::Text; }
+                else { self.state = HState::Comment { dashes: 0 }; }
+            }
+            HState::RawText => {
+                self.raw_tail.push(ch.to_ascii_lowercase());
+                let close = format!("</{}", self.tag);
+                if self.raw_tail.ends_with(&close) {
+                    self.end_tag = true;
+                    self.state = HState::TagName;
+                    self.raw_tail.clear();
+                } else if self.raw_tail.chars().count() > 16 {
+                    self.raw_tail = self.raw_tail.chars().skip(8).collect();
+                }
+            }
+        }
+    }
+
+    /// Wrap `expr` in the escaping call for the current context.
+    fn escape(&self, expr: &str) -> Result<String, String> {
+        match &self.state {
+            HState::Text | HState::Comment { .. } => Ok(format!("HTML$({})", expr)),
+            HState::Value { quote: Some(_), start } => {
+                if self.attr.starts_with("on") || self.attr == "style" {
+                    Err(format!("cannot auto-escape inside the '{}' attribute; use <?== expr ?> with your own escaping", self.attr))
+                } else if is_url_attr(&self.attr) {
+                    if *start { Ok(format!("HTML$(SAFE_URL$({}))", expr)) }
+                    else { Ok(format!("HTML$(URLENCODE$(\"\" + ({})))", expr)) }
+                } else {
+                    Ok(format!("HTML$({})", expr))
+                }
+            }
+            HState::Value { quote: None, .. } | HState::BeforeValue =>
+                Err(format!("attribute '{}' must be quoted to hold <?= expr ?>", self.attr)),
+            HState::RawText =>
+                Err(format!("cannot auto-escape inside <{}>; use <?== expr ?> with your own escaping", self.tag)),
+            _ => Err("<?= expr ?> cannot appear inside a tag; put it in a quoted attribute value or use <?== expr ?>".into()),
         }
-        // Illegal bare '<?...'
-        return Err(TplError::Msg("Illegal bare '<? ... ?>'. Use <?basil ... ?>, <?bas ... ?> (with #CGI_SHORT_TAGS_ON), or <?= expr ?>.".into()));
     }
-    // Tail text
-    if text_start < src.len() { emit_text(&src[text_start..], &mut out); }
 
-    Ok(PrecompileResult { basil_source: out, directives })
+    fn after_echo(&mut self) {
+        if let HState::Value { quote: Some(q), .. } = self.state { self.state = HState::Value { quote: Some(q), start: false }; }
+    }
+}
+
+fn is_url_attr(attr: &str) -> bool {
+    matches!(attr, "href" | "src" | "action" | "formaction" | "poster" | "cite" | "background" | "data" | "srcset" | "xlink:href" | "manifest")
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut it = s.chars();
+    matches!(it.next(), Some(c) if c.is_ascii_alphabetic() || c == '_') && it.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// If `expr` is a single call `NAME(args)`, return the name and the argument text.
+fn outer_call(expr: &str) -> Option<(&str, &str)> {
+    let name_len = expr.find('(')?;
+    let name = expr[..name_len].trim_end();
+    if name.is_empty() || !name.trim_end_matches('$').chars().all(|c| c.is_ascii_alphanumeric() || c == '_') { return None; }
+    if !expr.ends_with(')') { return None; }
+    // The '(' after the name must be closed by the final ')'
+    let mut depth = 0i32;
+    let mut in_str = false;
+    for (i, c) in expr.char_indices().skip_while(|(i, _)| *i < name_len) {
+        if in_str { if c == '"' { in_str = false; } continue; }
+        match c {
+            '"' => in_str = true,
+            '(' => depth += 1,
+            ')' => { depth -= 1; if depth == 0 { return if i == expr.len() - 1 { Some((name, &expr[name_len + 1..i])) } else { None }; } }
+            _ => {}
+        }
+    }
+    None
 }
 
+// Whole-word, case-insensitive keyword search that skips string literals
 fn contains_kw(s: &str, kws: &[&str]) -> bool {
-    let up = s.to_ascii_uppercase();
-    for k in kws { if up.contains(k) { return true; } }
+    let mut word = String::new();
+    let mut in_str = false;
+    for ch in s.chars().chain(std::iter::once(' ')) {
+        if in_str { if ch == '"' { in_str = false; } continue; }
+        if ch.is_ascii_alphanumeric() || ch == '_' || ch == '$' || ch == '%' {
+            word.push(ch.to_ascii_uppercase());
+            continue;
+        }
+        if kws.contains(&word.as_str()) { return true; }
+        word.clear();
+        if ch == '"' { in_str = true; }
+    }
     false
 }
 
@@ -217,7 +747,15 @@ mod tests {
     fn echo_shorthand_and_html() {
         let tpl = "<?= HTML(\"<x>\") ?>";
         let pre = precompile_template(tpl).unwrap();
-        assert!(pre.basil_source.contains("PRINT (HTML(\"<x>\"));"));
+        assert!(pre.basil_source.contains("PRINT HTML$(\"<x>\");"));
+        // An explicit HTML$ is not an opt-out of the context's other checks
+        let src = precompile_template("<a href=\"<?= HTML$(u$) ?>\" title=\"<?= HTML$(t$) ?>\">").unwrap().basil_source;
+        assert!(src.contains("PRINT HTML$(SAFE_URL$(u$));"));
+        assert!(src.contains("PRINT HTML$(t$);"));
+        assert!(precompile_template("<script><?= HTML$(x) ?></script>").is_err());
+        assert!(precompile_template("<button onclick=\"<?= HTML(js$) ?>\">").is_err());
+        let src = precompile_template("<?== HTML$(x) ?>").unwrap().basil_source;
+        assert!(src.contains("PRINT (HTML$(x));"));
     }
 
     #[test]
@@ -264,4 +802,108 @@ mod tests {
         assert!(pre.basil_source.contains("NEXT"));
     }
 
+    #[test]
+    fn echo_escapes_by_default_and_raw_forms_do_not() {
+        let pre = precompile_template("<p><?= name$ ?></p><?== html$ ?><?= RAW(x$) ?>").unwrap();
+        assert!(pre.basil_source.contains("PRINT HTML$(name$);"));
+        assert!(pre.basil_source.contains("PRINT (html$);"));
+        assert!(pre.basil_source.contains("PRINT (x$);"));
+        // Keywords are matched as whole words only
+        let pre = precompile_template("<?= FORMAT$(n) ?>").unwrap();
+        assert!(pre.basil_source.contains("PRINT HTML$(FORMAT$(n));"));
+    }
+
+    #[test]
+    fn attribute_and_url_contexts() {
+        let tpl = "<a title=\"<?= t$ ?>\" href=\"<?= u$ ?>\">x</a><a href=\"/find?q=<?= q$ ?>\">y</a>";
+        let src = precompile_template(tpl).unwrap().basil_source;
+        assert!(src.contains("PRINT HTML$(t$);"));
+        assert!(src.contains("PRINT HTML$(SAFE_URL$(u$));"));
+        assert!(src.contains("PRINT HTML$(URLENCODE$(\"\" + (q$)));"));
+        assert!(precompile_template("<a title=<?= t$ ?>>").is_err());
+        assert!(precompile_template("<div <?= a$ ?>>").is_err());
+        assert!(precompile_template("<button onclick=\"<?= js$ ?>\">").is_err());
+        assert!(precompile_template("<script>var x = <?= v ?>;</script>").is_err());
+        // Back in text context after </script>
+        let src = precompile_template("<script>var x = 1;</script><b><?= v ?></b>").unwrap().basil_source;
+        assert!(src.contains("PRINT HTML$(v);"));
+    }
+
+    #[test]
+    fn errors_point_at_template_line_and_column() {
+        let err = precompile_template("line one\n  <?oops ?>\n").unwrap_err();
+        match err {
+            TplError::At { line, col, .. } => { assert_eq!(line, 2); assert_eq!(col, 3); }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let msg = precompile_template("<p>\n<?block a ?>\n").unwrap_err().to_string();
+        assert!(msg.starts_with("line 2, column 1:"), "{}", msg);
+    }
+
+    #[test]
+    fn source_map_points_generated_lines_at_template() {
+        let tpl = "<h1>\n<?basil\n  LET a = 1\n  LET b = 2\n?>\n  <p><?= a ?></p>";
+        let pre = precompile_template(tpl).unwrap();
+        let lines: Vec<&str> = pre.basil_source.lines().collect();
+        assert_eq!(lines.len(), pre.source_map.lines.len());
+        let find = |needle: &str| lines.iter().position(|l| l.contains(needle)).unwrap() as u32 + 1;
+        let (_, loc) = pre.source_map.lookup(find("LET b = 2")).unwrap();
+        assert_eq!((loc.line, loc.col), (4, 1));
+        let (_, loc) = pre.source_map.lookup(find("HTML$(a)")).unwrap();
+        assert_eq!((loc.line, loc.col), (6, 6));
+        let msg = format!("parse error at line {}: oops", find("LET a = 1"));
+        assert_eq!(pre.source_map.remap_message(&msg), "parse error at line 3, column 3: oops");
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("basil_tpl_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn include_partial_relative_to_template() {
+        let dir = scratch_dir("include");
+        fs::create_dir_all(dir.join("parts")).unwrap();
+        fs::write(dir.join("parts/nav.basil"), "<nav><?include \"item.basil\" ?></nav>").unwrap();
+        fs::write(dir.join("parts/item.basil"), "<i><?= n$ ?></i>").unwrap();
+        let page = dir.join("page.bas");
+        let src = "<body><?include \"parts/nav.basil\" ?></body>";
+        let pre = precompile_template_file(src, &page).unwrap();
+        assert!(pre.basil_source.contains("PRINT \"<nav>\";"));
+        assert!(pre.basil_source.contains("PRINT HTML$(n$);"));
+        assert_eq!(pre.dependencies.len(), 2);
+
+        fs::write(dir.join("loop.basil"), "<?include \"loop.basil\" ?>").unwrap();
+        let err = precompile_template_file("<?include \"loop.basil\" ?>", &page).unwrap_err().to_string();
+        assert!(err.contains("loop.basil:1:1"), "{}", err);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn layout_blocks_are_overridden_by_child() {
+        let dir = scratch_dir("layout");
+        fs::write(dir.join("base.basil"),
+            "<html><title><?block title ?>Site<?endblock ?></title><main><?block content ?>empty<?endblock ?></main><footer><?block foot ?>(c)<?endblock ?></footer></html>").unwrap();
+        fs::write(dir.join("section.basil"),
+            "<?extends \"base.basil\" ?><?block content ?><section><?block body ?>none<?endblock ?></section><?endblock ?>").unwrap();
+        let child = "#CGI_NO_HEADER\n<?extends \"section.basil\" ?>\n<?basil LET t$ = \"Users\" ?>\n<?block title ?><?= t$ ?><?endblock ?>\n<?block body ?>list<?endblock ?>\n";
+        let pre = precompile_template_file(child, &dir.join("users.bas")).unwrap();
+        let src = pre.basil_source;
+        assert!(pre.directives.cgi_no_header);
+        // Child code runs before the layout renders
+        let code_at = src.find("LET t$ = \"Users\"").unwrap();
+        assert!(code_at < src.find("PRINT \"<html><title>\";").unwrap());
+        assert!(src.contains("PRINT HTML$(t$);"));
+        assert!(!src.contains("\"Site\""));
+        assert!(src.contains("PRINT \"<section>\";"));
+        assert!(src.contains("PRINT \"list\";"));
+        assert!(!src.contains("\"none\"") && !src.contains("\"empty\""));
+        assert!(src.contains("PRINT \"(c)\";"));
+
+        let err = precompile_template_file("<?extends \"base.basil\" ?>\nstray", &dir.join("bad.bas")).unwrap_err();
+        assert!(err.to_string().contains("outside of a <?block ?>"));
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -43,8 +43,13 @@ SOFTWARE.
 #[derive(Debug, Clone)]
 pub enum Expr {
     Number(f64),
+    // Whole-number literal too large for a FLOAT to hold exactly
+    Int(i64),
+    // DECIMAL literal (12.34@), digits as written
+    Decimal(String),
     Str(String),
     Bool(bool),
+    Null,
     Var(String),
     UnaryNeg(Box<Expr>),
     UnaryNot(Box<Expr>),
@@ -62,6 +67,8 @@ pub enum Expr {
     NewClass { filename: Box<Expr> },
     // EVAL("expr") expression: parse+compile at runtime and push result
     Eval(Box<Expr>),
+    // expr INSTANCEOF ClassName
+    InstanceOf { target: Box<Expr>, class_name: String },
     // New: list and dictionary literals, and square-bracket indexing
     List(Vec<Expr>),
     Dict(Vec<(String, Expr)>),
@@ -91,6 +98,8 @@ pub enum Stmt {
     DimFixedStr { name: String, len: usize },
     // TYPE ... END TYPE (struct definition)
     TypeDef { name: String, fields: Vec<StructField> },
+    // CLASS Name [EXTENDS Base] ... END CLASS
+    ClassDef { name: String, base: Option<String>, fields: Vec<ClassField>, methods: Vec<ClassMethod> },
     // Property set: obj.Prop = expr (without LET)
     SetProp { target: Expr, prop: String, value: Expr },
     // Square-bracket index set: list[i] = expr or dict["k"] = expr
@@ -136,6 +145,8 @@ pub enum Stmt {
     Try { try_body: Vec<Stmt>, catch_var: Option<String>, catch_body: Option<Vec<Stmt>>, finally_body: Option<Vec<Stmt>> },
     // RAISE statement
     Raise(Option<Expr>),
+    // YIELD expr (makes the enclosing FUNC a generator)
+    Yield(Expr),
     // Line marker for runtime error reporting
     Line(u32),
 }
@@ -177,3 +188,21 @@ pub enum StructFieldKind {
     FixedString(usize),     // fixed-length string with declared byte size
     Struct(String),         // nested struct by type name
 }
+
+// Field of an inline CLASS: [PUBLIC|PRIVATE] name [= expr]
+#[derive(Debug, Clone)]
+pub struct ClassField {
+    pub name: String,
+    pub private: bool,
+    pub init: Option<Expr>,
+}
+
+// FUNC/SUB declared inside an inline CLASS (SUB NEW is the constructor)
+#[derive(Debug, Clone)]
+pub struct ClassMethod {
+    pub kind: FuncKind,
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<Stmt>,
+    pub private: bool,
+}
@@ -0,0 +1,157 @@
+// Conversions between Rust values and Basil Values for embedding hosts:
+//
+//   let r = vm.call_function("Total", &[vec![1i64, 2, 3].into_value()])?;
+//   let total = i64::from_value(&r)?;
+//
+// Integers and floats convert into each other when no precision is lost; Vec maps to a
+// LIST (arrays are accepted too) and HashMap<String, _> to a DICT. Option maps None to NULL.
+// Decimal maps to DECIMAL and also accepts integers, which it holds exactly.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use basil_common::{BasilError, Result};
+
+use crate::{Decimal, Value};
+
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+pub trait FromValue: Sized {
+    fn from_value(v: &Value) -> Result<Self>;
+}
+
+fn mismatch(want: &str, got: &Value) -> BasilError {
+    let got = match got {
+        Value::Null => "NULL",
+        Value::Bool(_) => "BOOL",
+        Value::Num(_) => "FLOAT",
+        Value::Int(_) => "INTEGER",
+        Value::Str(_) => "STRING",
+        Value::Func(_) => "FUNCTION",
+        Value::Array(_) => "ARRAY",
+        Value::Object(_) => "OBJECT",
+        Value::List(_) => "LIST",
+        Value::Dict(_) => "DICT",
+        Value::Bytes(_) => "BYTES",
+        Value::Decimal(_) => "DECIMAL",
+        Value::StrArray2D { .. } => "STRARRAY2D",
+    };
+    BasilError(format!("expected {}, got {}", want, got))
+}
+
+impl IntoValue for Value { fn into_value(self) -> Value { self } }
+impl IntoValue for bool { fn into_value(self) -> Value { Value::Bool(self) } }
+impl IntoValue for i64 { fn into_value(self) -> Value { Value::Int(self) } }
+impl IntoValue for i32 { fn into_value(self) -> Value { Value::Int(self as i64) } }
+impl IntoValue for f64 { fn into_value(self) -> Value { Value::Num(self) } }
+impl IntoValue for String { fn into_value(self) -> Value { Value::Str(self) } }
+impl IntoValue for &str { fn into_value(self) -> Value { Value::Str(self.to_string()) } }
+impl IntoValue for Decimal { fn into_value(self) -> Value { Value::Decimal(Rc::new(self)) } }
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value { self.map(IntoValue::into_value).unwrap_or(Value::Null) }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::List(Rc::new(RefCell::new(self.into_iter().map(IntoValue::into_value).collect())))
+    }
+}
+
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Value {
+        Value::Dict(Rc::new(RefCell::new(self.into_iter().map(|(k, v)| (k, v.into_value())).collect())))
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(v: &Value) -> Result<Self> { Ok(v.clone()) }
+}
+
+impl FromValue for bool {
+    fn from_value(v: &Value) -> Result<Self> {
+        match v { Value::Bool(b) => Ok(*b), other => Err(mismatch("BOOL", other)) }
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(v: &Value) -> Result<Self> {
+        match v {
+            Value::Int(i) => Ok(*i),
+            Value::Num(n) if n.fract() == 0.0 && n.abs() < 9.0e15 => Ok(*n as i64),
+            other => Err(mismatch("INTEGER", other)),
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(v: &Value) -> Result<Self> {
+        match v {
+            Value::Num(n) => Ok(*n),
+            Value::Int(i) => Ok(*i as f64),
+            other => Err(mismatch("FLOAT", other)),
+        }
+    }
+}
+
+impl FromValue for Decimal {
+    fn from_value(v: &Value) -> Result<Self> {
+        match v {
+            Value::Decimal(d) => Ok((**d).clone()),
+            Value::Int(i) => Ok(Decimal::from_i64(*i)),
+            other => Err(mismatch("DECIMAL", other)),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(v: &Value) -> Result<Self> {
+        match v { Value::Str(s) => Ok(s.clone()), other => Err(mismatch("STRING", other)) }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(v: &Value) -> Result<Self> {
+        match v { Value::Null => Ok(None), other => T::from_value(other).map(Some) }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(v: &Value) -> Result<Self> {
+        match v {
+            Value::List(items) => items.borrow().iter().map(T::from_value).collect(),
+            Value::Array(arr) => arr.data.borrow().iter().map(T::from_value).collect(),
+            other => Err(mismatch("LIST", other)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(v: &Value) -> Result<Self> {
+        match v {
+            Value::Dict(map) => map.borrow().iter().map(|(k, v)| Ok((k.clone(), T::from_value(v)?))).collect(),
+            other => Err(mismatch("DICT", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let v = vec![1i64, 2, 3].into_value();
+        assert_eq!(Vec::<i64>::from_value(&v).unwrap(), vec![1, 2, 3]);
+        let mut m = HashMap::new();
+        m.insert("a".to_string(), "x".to_string());
+        assert_eq!(HashMap::<String, String>::from_value(&m.clone().into_value()).unwrap(), m);
+        assert_eq!(i64::from_value(&Value::Num(4.0)).unwrap(), 4);
+        assert_eq!(i64::from_value(&Value::Num(4.5)).unwrap_err().0, "expected INTEGER, got FLOAT");
+        assert_eq!(Option::<String>::from_value(&Value::Null).unwrap(), None);
+        assert!(String::from_value(&Value::Int(1)).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+// The number behind Value::Decimal: an exact signed decimal of any size.
+//
+// A value is coefficient x 10^-scale, the coefficient kept as base 10^9 limbs (least
+// significant first) so reading and printing never leave base ten. The scale is the number
+// of digits after the point and is kept as written: 1.50 and 1.5 are equal but print
+// differently, as money should. Sums keep the larger scale and products add the two; a
+// quotient is cut to whatever scale the caller asks for, using one of the rounding modes.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+const BASE: u64 = 1_000_000_000;
+
+// Most digits a literal or a requested scale may have after the point, and most zeros an
+// exponent may add before it; keeps "1E-999999" from eating memory
+pub const MAX_SCALE: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    HalfEven,
+    HalfUp,
+    HalfDown,
+    Up,
+    Down,
+    Ceiling,
+    Floor,
+}
+
+impl Rounding {
+    pub fn from_name(name: &str) -> Option<Rounding> {
+        Some(match name.trim().to_ascii_uppercase().replace(['-', ' '], "_").as_str() {
+            "HALF_EVEN" | "BANKERS" => Rounding::HalfEven,
+            "HALF_UP" => Rounding::HalfUp,
+            "HALF_DOWN" => Rounding::HalfDown,
+            "UP" => Rounding::Up,
+            "DOWN" | "TRUNCATE" => Rounding::Down,
+            "CEILING" => Rounding::Ceiling,
+            "FLOOR" => Rounding::Floor,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rounding::HalfEven => "HALF_EVEN",
+            Rounding::HalfUp => "HALF_UP",
+            Rounding::HalfDown => "HALF_DOWN",
+            Rounding::Up => "UP",
+            Rounding::Down => "DOWN",
+            Rounding::Ceiling => "CEILING",
+            Rounding::Floor => "FLOOR",
+        }
+    }
+
+    // Whether a cut magnitude `q` goes up by one. `rest` compares what was cut off with one
+    // half of the last kept digit, None when nothing was.
+    fn bumps(self, q: &[u32], neg: bool, rest: Option<Ordering>) -> bool {
+        let Some(rest) = rest else { return false; };
+        match self {
+            Rounding::Down => false,
+            Rounding::Up => true,
+            Rounding::Ceiling => !neg,
+            Rounding::Floor => neg,
+            Rounding::HalfUp => rest != Ordering::Less,
+            Rounding::HalfDown => rest == Ordering::Greater,
+            Rounding::HalfEven => rest == Ordering::Greater || (rest == Ordering::Equal && q.first().is_some_and(|d| d % 2 == 1)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Decimal {
+    neg: bool,
+    coeff: Vec<u32>,
+    scale: u32,
+}
+
+impl Decimal {
+    fn new(neg: bool, mut coeff: Vec<u32>, scale: u32) -> Decimal {
+        trim(&mut coeff);
+        Decimal { neg: neg && !coeff.is_empty(), coeff, scale }
+    }
+
+    pub fn from_i64(i: i64) -> Decimal {
+        let mut u = i.unsigned_abs();
+        let mut coeff = Vec::new();
+        while u > 0 {
+            coeff.push((u % BASE) as u32);
+            u /= BASE;
+        }
+        Decimal::new(i < 0, coeff, 0)
+    }
+
+    // The shortest decimal that reads back as `x`, so 0.1 becomes 0.1 and not the binary
+    // value next to it; None for NaN and the infinities
+    pub fn from_f64(x: f64) -> Option<Decimal> {
+        if !x.is_finite() { return None; }
+        Decimal::parse(&x.to_string())
+    }
+
+    // "12.34", "-0.5", "+7", "1.5E3", "25e-4"; blanks around the number are ignored
+    pub fn parse(text: &str) -> Option<Decimal> {
+        let s = text.trim();
+        let (neg, s) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (num, exp) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i64>().ok()?),
+            None => (s, 0),
+        };
+        let (int, frac) = num.split_once('.').unwrap_or((num, ""));
+        if int.is_empty() && frac.is_empty() { return None; }
+        if !int.bytes().chain(frac.bytes()).all(|c| c.is_ascii_digit()) { return None; }
+        let scale = frac.len() as i64 - exp;
+        if scale > MAX_SCALE as i64 || scale < -(MAX_SCALE as i64) { return None; }
+        let mut digits = format!("{}{}", int, frac);
+        if scale < 0 { digits.push_str(&"0".repeat((-scale) as usize)); }
+        Some(Decimal::new(neg, limbs(&digits), scale.max(0) as u32))
+    }
+
+    pub fn scale(&self) -> u32 { self.scale }
+
+    pub fn is_zero(&self) -> bool { self.coeff.is_empty() }
+
+    pub fn is_negative(&self) -> bool { self.neg }
+
+    pub fn abs(&self) -> Decimal { Decimal::new(false, self.coeff.clone(), self.scale) }
+
+    pub fn to_f64(&self) -> f64 { self.to_string().parse().unwrap_or(0.0) }
+
+    // The integer part, None when it does not fit
+    pub fn to_i64(&self) -> Option<i64> {
+        self.round(0, Rounding::Down).to_string().parse().ok()
+    }
+
+    // The coefficient at a scale at least as large as our own
+    fn coeff_at(&self, scale: u32) -> Vec<u32> {
+        if scale == self.scale { self.coeff.clone() } else { mul_mag(&self.coeff, &pow10(scale - self.scale)) }
+    }
+
+    // self / other with `scale` digits after the point; None when other is zero
+    pub fn quotient(&self, other: &Decimal, scale: u32, mode: Rounding) -> Option<Decimal> {
+        if other.is_zero() { return None; }
+        // self/other x 10^scale as a ratio of whole numbers
+        let shift = scale as i64 + other.scale as i64 - self.scale as i64;
+        let (num, den) = if shift >= 0 {
+            (mul_mag(&self.coeff, &pow10(shift as u32)), other.coeff.clone())
+        } else {
+            (self.coeff.clone(), mul_mag(&other.coeff, &pow10((-shift) as u32)))
+        };
+        let (mut q, r) = divrem_mag(&num, &den);
+        let neg = self.neg != other.neg;
+        let rest = if r.is_empty() { None } else { Some(cmp_mag(&add_mag(&r, &r), &den)) };
+        if mode.bumps(&q, neg, rest) { q = add_mag(&q, &[1]); }
+        Some(Decimal::new(neg, q, scale))
+    }
+
+    // Exactly `scale` digits after the point, rounding or padding with zeros
+    pub fn round(&self, scale: u32, mode: Rounding) -> Decimal {
+        if scale >= self.scale { return Decimal::new(self.neg, self.coeff_at(scale), scale); }
+        self.quotient(&Decimal::from_i64(1), scale, mode).unwrap_or_else(|| self.clone())
+    }
+
+    // What is left after taking out whole multiples of other, with the sign of self (MOD)
+    pub fn remainder(&self, other: &Decimal) -> Option<Decimal> {
+        let whole = self.quotient(other, 0, Rounding::Down)?;
+        Some(self - &(&whole * other))
+    }
+}
+
+impl Add for &Decimal {
+    type Output = Decimal;
+    fn add(self, other: &Decimal) -> Decimal {
+        let scale = self.scale.max(other.scale);
+        let (a, b) = (self.coeff_at(scale), other.coeff_at(scale));
+        if self.neg == other.neg { return Decimal::new(self.neg, add_mag(&a, &b), scale); }
+        match cmp_mag(&a, &b) {
+            Ordering::Less => Decimal::new(other.neg, sub_mag(&b, &a), scale),
+            _ => Decimal::new(self.neg, sub_mag(&a, &b), scale),
+        }
+    }
+}
+
+impl Sub for &Decimal {
+    type Output = Decimal;
+    fn sub(self, other: &Decimal) -> Decimal { self + &-other }
+}
+
+impl Mul for &Decimal {
+    type Output = Decimal;
+    fn mul(self, other: &Decimal) -> Decimal {
+        Decimal::new(self.neg != other.neg, mul_mag(&self.coeff, &other.coeff), self.scale + other.scale)
+    }
+}
+
+impl Neg for &Decimal {
+    type Output = Decimal;
+    fn neg(self) -> Decimal { Decimal::new(!self.neg, self.coeff.clone(), self.scale) }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        match (self.neg, other.neg) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            _ => {
+                let scale = self.scale.max(other.scale);
+                let c = cmp_mag(&self.coeff_at(scale), &other.coeff_at(scale));
+                if self.neg { c.reverse() } else { c }
+            }
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl Eq for Decimal {}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut digits = match self.coeff.split_last() {
+            None => "0".to_string(),
+            Some((top, rest)) => {
+                let mut s = top.to_string();
+                for limb in rest.iter().rev() { s.push_str(&format!("{:09}", limb)); }
+                s
+            }
+        };
+        if self.neg { f.write_str("-")?; }
+        let scale = self.scale as usize;
+        if scale == 0 { return f.write_str(&digits); }
+        if digits.len() <= scale { digits.insert_str(0, &"0".repeat(scale + 1 - digits.len())); }
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{}.{}", int, frac)
+    }
+}
+
+// --- magnitudes: base 10^9 limbs, least significant first, no zero limbs on top ---
+
+fn trim(v: &mut Vec<u32>) {
+    while v.last() == Some(&0) { v.pop(); }
+}
+
+fn limbs(digits: &str) -> Vec<u32> {
+    let mut out = Vec::with_capacity(digits.len() / 9 + 1);
+    let mut end = digits.len();
+    while end > 0 {
+        let start = end.saturating_sub(9);
+        out.push(digits[start..end].parse().unwrap_or(0));
+        end = start;
+    }
+    trim(&mut out);
+    out
+}
+
+fn pow10(n: u32) -> Vec<u32> {
+    let mut v = vec![0u32; (n / 9) as usize];
+    v.push(10u32.pow(n % 9));
+    v
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let s = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        out.push((s % BASE) as u32);
+        carry = s / BASE;
+    }
+    if carry > 0 { out.push(carry as u32); }
+    out
+}
+
+// a - b for a >= b
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &x) in a.iter().enumerate() {
+        let mut d = x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = if d < 0 { d += BASE as i64; 1 } else { 0 };
+        out.push(d as u32);
+    }
+    trim(&mut out);
+    out
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() { return Vec::new(); }
+    let mut out = vec![0u64; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = out[i + j] + x as u64 * y as u64 + carry;
+            out[i + j] = t % BASE;
+            carry = t / BASE;
+        }
+        let mut k = i + b.len();
+        while carry > 0 {
+            let t = out[k] + carry;
+            out[k] = t % BASE;
+            carry = t / BASE;
+            k += 1;
+        }
+    }
+    let mut out: Vec<u32> = out.into_iter().map(|d| d as u32).collect();
+    trim(&mut out);
+    out
+}
+
+fn mul_small(a: &[u32], m: u32) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for &x in a {
+        let t = x as u64 * m as u64 + carry;
+        out.push((t % BASE) as u32);
+        carry = t / BASE;
+    }
+    if carry > 0 { out.push(carry as u32); }
+    trim(&mut out);
+    out
+}
+
+// Schoolbook long division, one limb of quotient per step
+fn divrem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let n = b.len();
+    let top = b[n - 1] as u128;
+    let mut q = vec![0u32; a.len()];
+    let mut r: Vec<u32> = Vec::new();
+    for i in (0..a.len()).rev() {
+        r.insert(0, a[i]);
+        trim(&mut r);
+        if cmp_mag(&r, b) == Ordering::Less { continue; }
+        // r < b x BASE here, so the digit is one limb; the leading limbs of r and b bound it
+        // and a binary search between the bounds finds it
+        let lead = if r.len() > n { r[n] as u128 * BASE as u128 + r[n - 1] as u128 } else { r[n - 1] as u128 };
+        let mut lo = (lead / (top + 1)) as u32;
+        let mut hi = ((lead + 1) / top).min(BASE as u128 - 1) as u32;
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            if cmp_mag(&mul_small(b, mid), &r) == Ordering::Greater { hi = mid - 1; } else { lo = mid; }
+        }
+        q[i] = lo;
+        r = sub_mag(&r, &mul_small(b, lo));
+    }
+    trim(&mut q);
+    (q, r)
+}
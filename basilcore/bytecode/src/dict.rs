@@ -0,0 +1,95 @@
+// The map behind Value::Dict: string keys kept in insertion order.
+//
+// FOR EACH, PRINT, JSON and every other walk over a DICT see keys in the order they were
+// first added; assigning an existing key keeps its place and removing one closes the gap.
+// Two DICTs are equal when they hold the same keys and values, whatever the order.
+// Keys are strings only: the VM rejects other key types instead of converting them.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::Value;
+
+#[derive(Clone, Default)]
+pub struct Dict {
+    entries: Vec<(String, Value)>,
+    index: HashMap<String, usize>,
+}
+
+impl Dict {
+    pub fn new() -> Self { Dict::default() }
+
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.index.get(key).map(|&i| &mut self.entries[i].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool { self.index.contains_key(key) }
+
+    // Set `key`, returning its previous value. New keys go last.
+    pub fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        match self.index.get(&key) {
+            Some(&i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        let i = self.index.remove(key)?;
+        let (_, v) = self.entries.remove(i);
+        for (_, pos) in self.index.iter_mut() {
+            if *pos > i { *pos -= 1; }
+        }
+        Some(v)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> { self.entries.iter().map(|(k, _)| k) }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> { self.entries.iter().map(|(_, v)| v) }
+}
+
+impl FromIterator<(String, Value)> for Dict {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(iter: I) -> Self {
+        let mut d = Dict::new();
+        for (k, v) in iter { d.insert(k, v); }
+        d
+    }
+}
+
+impl IntoIterator for Dict {
+    type Item = (String, Value);
+    type IntoIter = std::vec::IntoIter<(String, Value)>;
+    fn into_iter(self) -> Self::IntoIter { self.entries.into_iter() }
+}
+
+impl PartialEq for Dict {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl fmt::Debug for Dict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,148 @@
+// Host functions: Rust closures an embedding application exposes to Basil scripts.
+//
+// The same HostFunctions table is handed to the compiler (basil_compiler::compile_with_host),
+// which resolves otherwise unknown call names against it and checks arity, and to the VM
+// (VM::set_host_functions), which runs the closure for Op::CallHost. Names follow the usual
+// suffix rules: MYAPP_LOOKUP$ must return a string, COUNT% an integer, FIND@ an object.
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use basil_common::{BasilError, Result};
+
+use crate::Value;
+
+// The calling VM as seen from a host function.
+pub trait VmCtx {
+    // Write to the script's output (honours buffering and OB_START captures).
+    fn print(&mut self, s: &str);
+    fn get_global(&self, name: &str) -> Option<Value>;
+    // False if the program has no global of that name.
+    fn set_global(&mut self, name: &str, v: Value) -> bool;
+    // Call a Basil function value (e.g. a callback passed in as an argument).
+    fn call(&mut self, f: &Value, args: Vec<Value>) -> Result<Value>;
+    fn script_path(&self) -> Option<String>;
+}
+
+pub type HostFnImpl = Rc<dyn Fn(&mut dyn VmCtx, &[Value]) -> Result<Value>>;
+
+#[derive(Clone)]
+pub struct HostFn {
+    pub name: String,
+    pub min_args: usize,
+    pub max_args: usize,
+    pub func: HostFnImpl,
+}
+
+impl HostFn {
+    // Type implied by the name's suffix: STRING, INTEGER, OBJECT or ANY.
+    pub fn return_type(&self) -> &'static str {
+        match self.name.chars().last() {
+            Some('$') => "STRING",
+            Some('%') => "INTEGER",
+            Some('@') => "OBJECT",
+            _ => "ANY",
+        }
+    }
+
+    pub fn check_arity(&self, argc: usize) -> Result<()> {
+        if argc >= self.min_args && argc <= self.max_args { return Ok(()); }
+        let expected = if self.min_args == self.max_args { format!("{}", self.min_args) } else { format!("{} to {}", self.min_args, self.max_args) };
+        Err(BasilError(format!("{} expects {} argument(s), got {}", self.name, expected, argc)))
+    }
+
+    // Enforce the suffix type on a returned value; INTEGER accepts whole floats.
+    pub fn check_return(&self, v: Value) -> Result<Value> {
+        let ok = match (self.return_type(), v) {
+            ("STRING", v @ Value::Str(_)) => v,
+            ("INTEGER", v @ Value::Int(_)) => v,
+            ("INTEGER", Value::Num(n)) if n.fract() == 0.0 => Value::Int(n as i64),
+            ("OBJECT", v @ (Value::Object(_) | Value::Null)) => v,
+            ("ANY", v) => v,
+            (ty, v) => return Err(BasilError(format!("{} must return {}, got {}", self.name, ty, type_label(&v)))),
+        };
+        Ok(ok)
+    }
+
+    pub fn signature(&self) -> String {
+        let args = if self.min_args == self.max_args { format!("{}", self.min_args) } else { format!("{}..{}", self.min_args, self.max_args) };
+        format!("{}({} args) -> {}", self.name, args, self.return_type())
+    }
+}
+
+fn type_label(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "NULL",
+        Value::Bool(_) => "BOOL",
+        Value::Num(_) => "FLOAT",
+        Value::Int(_) => "INTEGER",
+        Value::Str(_) => "STRING",
+        Value::Func(_) => "FUNCTION",
+        Value::Array(_) => "ARRAY",
+        Value::Object(_) => "OBJECT",
+        Value::List(_) => "LIST",
+        Value::Dict(_) => "DICT",
+        Value::Bytes(_) => "BYTES",
+        Value::Decimal(_) => "DECIMAL",
+        Value::StrArray2D { .. } => "STRARRAY2D",
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct HostFunctions {
+    fns: HashMap<String, HostFn>,
+}
+
+impl HostFunctions {
+    pub fn new() -> Self { HostFunctions::default() }
+
+    // Register (or replace) a function. Names are case-insensitive: letters, digits and
+    // underscores with an optional $, % or @ suffix.
+    pub fn register<F>(&mut self, name: &str, arity: RangeInclusive<usize>, f: F) -> Result<()>
+    where F: Fn(&mut dyn VmCtx, &[Value]) -> Result<Value> + 'static {
+        let body = name.strip_suffix(['$', '%', '@']).unwrap_or(name);
+        let valid = body.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && body.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(BasilError(format!("invalid host function name \"{}\"", name)));
+        }
+        if arity.is_empty() || *arity.end() > u8::MAX as usize {
+            return Err(BasilError(format!("invalid arity for host function {}", name)));
+        }
+        let hf = HostFn { name: name.to_ascii_uppercase(), min_args: *arity.start(), max_args: *arity.end(), func: Rc::new(f) };
+        self.fns.insert(hf.name.clone(), hf);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&HostFn> {
+        self.fns.get(&name.to_ascii_uppercase())
+    }
+
+    pub fn is_empty(&self) -> bool { self.fns.is_empty() }
+
+    // Registered functions sorted by name.
+    pub fn list(&self) -> Vec<&HostFn> {
+        let mut v: Vec<&HostFn> = self.fns.values().collect();
+        v.sort_by(|a, b| a.name.cmp(&b.name));
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_and_check() {
+        let mut h = HostFunctions::new();
+        h.register("myapp_lookup$", 1..=2, |_, args| Ok(Value::Str(format!("{}", args[0])))).unwrap();
+        assert!(h.register("bad name", 0..=0, |_, _| Ok(Value::Null)).is_err());
+        let f = h.get("MYAPP_LOOKUP$").unwrap();
+        assert_eq!(f.signature(), "MYAPP_LOOKUP$(1..2 args) -> STRING");
+        assert!(f.check_arity(2).is_ok());
+        assert_eq!(f.check_arity(0).unwrap_err().0, "MYAPP_LOOKUP$ expects 1 to 2 argument(s), got 0");
+        assert!(f.check_return(Value::Int(1)).is_err());
+        assert!(f.check_return(Value::Str("x".into())).is_ok());
+    }
+}
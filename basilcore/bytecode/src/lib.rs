@@ -42,9 +42,19 @@ SOFTWARE.
 use std::fmt;
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::collections::HashMap;
 use basil_common::Result;
 
+pub mod convert;
+pub mod decimal;
+pub mod dict;
+pub mod host;
+pub mod send;
+pub use convert::{FromValue, IntoValue};
+pub use decimal::{Decimal, Rounding};
+pub use dict::Dict;
+pub use host::{HostFn, HostFunctions, VmCtx};
+pub use send::{SendValue, SharedProgram};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ElemType { Num, Int, Str, Obj(Option<String>) }
 
@@ -89,6 +99,9 @@ pub trait BasicObject {
     fn set_prop(&mut self, name: &str, v: Value) -> Result<()>;
     fn call(&mut self, method: &str, args: &[Value]) -> Result<Value>;
     fn descriptor(&self) -> ObjectDescriptor;
+    // Concrete type access for objects the VM itself implements (e.g. inline CLASS instances)
+    fn as_any(&self) -> Option<&dyn std::any::Any> { None }
+    fn as_any_mut(&mut self) -> Option<&mut dyn std::any::Any> { None }
 }
 
 pub type ObjectRef = Rc<RefCell<dyn BasicObject>>;
@@ -105,7 +118,11 @@ pub enum Value {
     Object(ObjectRef),
     // Dynamic containers
     List(Rc<RefCell<Vec<Value>>>),
-    Dict(Rc<RefCell<HashMap<String, Value>>>),
+    Dict(Rc<RefCell<Dict>>),
+    // Binary data (file contents, packed structs); immutable like Str
+    Bytes(Rc<[u8]>),
+    // Exact decimal (12.34@, DEC("12.34")) for money; immutable like Str
+    Decimal(Rc<Decimal>),
     // Special runtime-only value: 2-D string array, row-major order.
     // Used as RHS for whole-array assignment (auto-redimensioning target array).
     StrArray2D { rows: usize, cols: usize, data: Vec<String> },
@@ -129,12 +146,9 @@ impl PartialEq for Value {
                 for (i, v) in av.iter().enumerate() { if *v != bv[i] { return false; } }
                 true
             }
-            (Value::Dict(a), Value::Dict(b)) => {
-                let av = a.borrow();
-                let bv = b.borrow();
-                if av.len() != bv.len() { return false; }
-                av.iter().all(|(k, v)| bv.get(k).map(|w| v == w).unwrap_or(false))
-            }
+            (Value::Dict(a), Value::Dict(b)) => *a.borrow() == *b.borrow(),
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
+            (Value::Decimal(a), Value::Decimal(b)) => a == b,
             _ => false,
         }
     }
@@ -181,6 +195,8 @@ impl fmt::Display for Value {
                 }
                 write!(f, "}}")
             }
+            Value::Bytes(data) => write!(f, "b\"{}\"", data.escape_ascii()),
+            Value::Decimal(d) => write!(f, "{d}"),
             Value::StrArray2D { rows, cols, .. } => {
                 write!(f, "<StrArray2D {}x{}>", rows, cols)
             }
@@ -221,6 +237,8 @@ impl fmt::Debug for Value {
                 }
                 write!(f, "}}")
             }
+            Value::Bytes(data) => write!(f, "Bytes(b\"{}\")", data.escape_ascii()),
+            Value::Decimal(d) => write!(f, "Decimal({d})"),
             Value::StrArray2D { rows, cols, data } => {
                 write!(f, "StrArray2D(rows={}, cols={}, data_len={})", rows, cols, data.len())
             }
@@ -260,6 +278,9 @@ pub enum Op {
     // calls
     Call = 50,           // +u8 (argc)
     Ret  = 51,
+    CallHost = 52,       // +u16 (const index of host function name), +u8 (argc)
+    MakeGen  = 53,       // first op of a FUNC containing YIELD: return the call as a generator object
+    Yield    = 54,       // pop value; suspend the generator frame and hand the value to its caller
 
     // misc
     Print = 60,
@@ -279,6 +300,9 @@ pub enum Op {
     SetProp     = 82,   // +u8 (const index of property name). Stack: [..., obj, value] -> (store)
     CallMethod  = 83,   // +u8 (const index of method name), +u8 (argc). Stack: [..., obj, args...] -> push ret
     DescribeObj = 84,   // no extra. Stack: [..., obj or array] -> push string
+    MakeClass   = 85,   // +u16 (const index of class name), +u8 (nfields), +u8 (nmethods). Stack: [..., base|null, (name, private)*, (func, private, params)*, init|null] -> push class
+    NewInstance = 86,   // +u8 (argc). Stack: [..., class, args...] -> push instance (runs initializers and NEW)
+    CallSuper   = 87,   // +u16 (const index of method name), +u8 (argc). Stack: [..., base class, me, args...] -> push ret
 
     // classes
     NewClass        = 100, // pop filename (string) → push instance (object)
@@ -342,6 +366,9 @@ pub struct Function {
     pub arity: u8,
     pub name: Option<String>,
     pub chunk: Rc<Chunk>,
+    // For a FUNC of a CLASS file instance, the VM's handle on the instance globals it runs
+    // against; None for the program's own FUNCs
+    pub bound: Option<std::rc::Weak<dyn std::any::Any>>,
 }
 
 #[derive(Debug, Clone)]
@@ -362,6 +389,7 @@ pub fn serialize_program(p: &Program) -> Vec<u8> {
             Value::Num(n) => { w_u8(b,2); b.extend_from_slice(&n.to_le_bytes()); }
             Value::Int(i) => { w_u8(b,3); b.extend_from_slice(&i.to_le_bytes()); }
             Value::Str(s) => { w_u8(b,4); w_str(b, s); }
+            Value::Decimal(d) => { w_u8(b,6); w_str(b, &d.to_string()); }
             Value::Func(f) => {
                 w_u8(b,5);
                 w_u8(b, f.arity);
@@ -373,6 +401,7 @@ pub fn serialize_program(p: &Program) -> Vec<u8> {
             Value::StrArray2D { .. } => { w_u8(b,252); }
             Value::List(_) => { w_u8(b,253); }
             Value::Dict(_) => { w_u8(b,254); }
+            Value::Bytes(_) => { w_u8(b,249); }
         }
     }
     fn ser_chunk(b: &mut Vec<u8>, c: &Chunk) {
@@ -410,12 +439,16 @@ pub fn deserialize_program(data: &[u8]) -> basil_common::Result<Program> {
             2 => { let n = r_f64(p,data)?; Value::Num(n) },
             3 => { let i = r_i64(p,data)?; Value::Int(i) },
             4 => { let s = r_str(p,data)?; Value::Str(s) },
+            6 => {
+                let s = r_str(p,data)?;
+                Value::Decimal(Rc::new(Decimal::parse(&s).ok_or_else(|| BasilError("bad decimal const".into()))?))
+            }
             5 => {
                 let ar = r_u8(p,data)?;
                 let has = r_u8(p,data)? != 0;
                 let name = if has { Some(r_str(p,data)?) } else { None };
                 let chunk = de_chunk(p,data)?;
-                Value::Func(Rc::new(Function { arity: ar, name, chunk: std::rc::Rc::new(chunk) }))
+                Value::Func(Rc::new(Function { arity: ar, name, chunk: std::rc::Rc::new(chunk), bound: None }))
             }
             250|251|252 => Value::Null, // placeholder for unsupported in consts
             253|254 => Value::Null,
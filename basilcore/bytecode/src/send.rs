@@ -0,0 +1,151 @@
+// Thread-safe copies of programs and values.
+//
+// Value is built on Rc/RefCell, so it (and a VM) stays on the thread that made it. To run
+// a compiled program on several threads, wrap it once in a SharedProgram (Send + Sync,
+// cheap to clone) and build a Program per VM:
+//
+//   let shared = SharedProgram::new(program)?;
+//   let p = shared.clone();
+//   std::thread::spawn(move || VM::new(p.program()).run());
+//
+// SendValue carries data between VMs: a deep copy of numbers, strings, bytes, lists,
+// dicts, arrays and functions. Objects belong to their VM and cannot be sent.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use basil_common::{BasilError, Result};
+
+use crate::{ArrayObj, Chunk, Decimal, Dict, ElemType, Function, Program, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SendValue {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Int(i64),
+    Str(String),
+    Func(Arc<SendFunction>),
+    Array { elem: ElemType, dims: Vec<usize>, data: Vec<SendValue> },
+    List(Vec<SendValue>),
+    Dict(Vec<(String, SendValue)>),
+    Bytes(Arc<[u8]>),
+    Decimal(Decimal),
+    StrArray2D { rows: usize, cols: usize, data: Vec<String> },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SendChunk {
+    code: Vec<u8>,
+    consts: Vec<SendValue>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SendFunction {
+    arity: u8,
+    name: Option<String>,
+    chunk: SendChunk,
+}
+
+// Lists nested deeper than this are taken to be cyclic
+const MAX_DEPTH: usize = 256;
+
+impl SendValue {
+    // Deep copy of `v`; fails for objects and cyclic containers.
+    pub fn from_value(v: &Value) -> Result<SendValue> { copy_value(v, 0) }
+
+    // A fresh Value owned by the current thread.
+    pub fn to_value(&self) -> Value {
+        match self {
+            SendValue::Null => Value::Null,
+            SendValue::Bool(b) => Value::Bool(*b),
+            SendValue::Num(n) => Value::Num(*n),
+            SendValue::Int(i) => Value::Int(*i),
+            SendValue::Str(s) => Value::Str(s.clone()),
+            SendValue::Func(f) => Value::Func(Rc::new(f.to_function())),
+            SendValue::Array { elem, dims, data } => Value::Array(Rc::new(ArrayObj {
+                elem: elem.clone(),
+                dims: dims.clone(),
+                data: RefCell::new(data.iter().map(SendValue::to_value).collect()),
+            })),
+            SendValue::List(items) => Value::List(Rc::new(RefCell::new(items.iter().map(SendValue::to_value).collect()))),
+            SendValue::Dict(entries) => {
+                let map: Dict = entries.iter().map(|(k, v)| (k.clone(), v.to_value())).collect();
+                Value::Dict(Rc::new(RefCell::new(map)))
+            }
+            SendValue::Bytes(data) => Value::Bytes(Rc::from(&data[..])),
+            SendValue::Decimal(d) => Value::Decimal(Rc::new(d.clone())),
+            SendValue::StrArray2D { rows, cols, data } => Value::StrArray2D { rows: *rows, cols: *cols, data: data.clone() },
+        }
+    }
+}
+
+fn copy_value(v: &Value, depth: usize) -> Result<SendValue> {
+    if depth > MAX_DEPTH {
+        return Err(BasilError("cannot send a value nested this deeply (is a list inside itself?)".into()));
+    }
+    Ok(match v {
+        Value::Null => SendValue::Null,
+        Value::Bool(b) => SendValue::Bool(*b),
+        Value::Num(n) => SendValue::Num(*n),
+        Value::Int(i) => SendValue::Int(*i),
+        Value::Str(s) => SendValue::Str(s.clone()),
+        Value::Func(f) => SendValue::Func(Arc::new(copy_function(f, depth)?)),
+        Value::Array(arr) => SendValue::Array {
+            elem: arr.elem.clone(),
+            dims: arr.dims.clone(),
+            data: arr.data.borrow().iter().map(|x| copy_value(x, depth + 1)).collect::<Result<_>>()?,
+        },
+        Value::List(items) => SendValue::List(items.borrow().iter().map(|x| copy_value(x, depth + 1)).collect::<Result<_>>()?),
+        Value::Dict(map) => SendValue::Dict(
+            map.borrow().iter().map(|(k, x)| Ok((k.clone(), copy_value(x, depth + 1)?))).collect::<Result<_>>()?,
+        ),
+        Value::Bytes(data) => SendValue::Bytes(Arc::from(&data[..])),
+        Value::Decimal(d) => SendValue::Decimal((**d).clone()),
+        Value::StrArray2D { rows, cols, data } => SendValue::StrArray2D { rows: *rows, cols: *cols, data: data.clone() },
+        Value::Object(o) => {
+            return Err(BasilError(format!("cannot send {} objects between VMs", o.borrow().type_name())));
+        }
+    })
+}
+
+fn copy_chunk(c: &Chunk, depth: usize) -> Result<SendChunk> {
+    let consts = c.consts.iter().map(|x| copy_value(x, depth + 1)).collect::<Result<_>>()?;
+    Ok(SendChunk { code: c.code.clone(), consts })
+}
+
+fn copy_function(f: &Function, depth: usize) -> Result<SendFunction> {
+    if f.bound.is_some() { return Err(BasilError("cannot send a FUNC of a CLASS instance between VMs".into())); }
+    Ok(SendFunction { arity: f.arity, name: f.name.clone(), chunk: copy_chunk(&f.chunk, depth)? })
+}
+
+impl SendChunk {
+    fn to_chunk(&self) -> Chunk {
+        Chunk { code: self.code.clone(), consts: self.consts.iter().map(SendValue::to_value).collect() }
+    }
+}
+
+impl SendFunction {
+    fn to_function(&self) -> Function {
+        Function { arity: self.arity, name: self.name.clone(), chunk: Rc::new(self.chunk.to_chunk()), bound: None }
+    }
+}
+
+// A compiled program any number of threads can build VMs from.
+#[derive(Debug, Clone)]
+pub struct SharedProgram {
+    chunk: Arc<SendChunk>,
+    globals: Arc<[String]>,
+}
+
+impl SharedProgram {
+    pub fn new(p: Program) -> Result<Self> {
+        Ok(SharedProgram { chunk: Arc::new(copy_chunk(&p.chunk, 0)?), globals: p.globals.into() })
+    }
+
+    // A Program for one VM on the current thread.
+    pub fn program(&self) -> Program {
+        Program { chunk: self.chunk.to_chunk(), globals: self.globals.to_vec() }
+    }
+}
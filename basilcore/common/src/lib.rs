@@ -41,6 +41,51 @@ SOFTWARE.
 pub struct Span { pub start: u32, pub end: u32 }
 impl Span { pub fn new(start: usize, end: usize) -> Self { Self { start: start as u32, end: end as u32 } } }
 
+/// Position in an original source file (1-based line and column).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SourceLoc { pub file: u32, pub line: u32, pub col: u32 }
+
+/// Maps lines of generated Basil source (e.g. a precompiled template) back to the
+/// files the author edited. `lines[n]` describes generated line `n + 1`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap { pub files: Vec<String>, pub lines: Vec<SourceLoc> }
+
+impl SourceMap {
+    /// Original file name and position for a 1-based generated line.
+    pub fn lookup(&self, gen_line: u32) -> Option<(&str, SourceLoc)> {
+        let loc = *self.lines.get((gen_line as usize).checked_sub(1)?)?;
+        let file = self.files.get(loc.file as usize).map(|s| s.as_str()).unwrap_or("");
+        Some((file, loc))
+    }
+
+    /// Original location of the first "line N" reference in an error message.
+    pub fn locate_message(&self, msg: &str) -> Option<(&str, SourceLoc)> {
+        let p = msg.find("line ")?;
+        let digits: String = msg[p + 5..].chars().take_while(|c| c.is_ascii_digit()).collect();
+        self.lookup(digits.parse().ok()?)
+    }
+
+    /// Rewrite "line N" references in an error message (as produced by the parser)
+    /// into "FILE:LINE:COL" of the original source.
+    pub fn remap_message(&self, msg: &str) -> String {
+        let mut out = String::with_capacity(msg.len());
+        let mut rest = msg;
+        while let Some(p) = rest.find("line ") {
+            out.push_str(&rest[..p]);
+            let after = &rest[p + 5..];
+            let digits = after.bytes().take_while(|b| b.is_ascii_digit()).count();
+            match after[..digits].parse::<u32>().ok().and_then(|n| self.lookup(n)) {
+                Some((file, loc)) if !file.is_empty() => out.push_str(&format!("{}:{}:{}", file, loc.line, loc.col)),
+                Some((_, loc)) => out.push_str(&format!("line {}, column {}", loc.line, loc.col)),
+                None => out.push_str(&rest[p..p + 5 + digits]),
+            }
+            rest = &after[digits..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
 
 #[derive(Debug)]
 pub struct BasilError(pub String);
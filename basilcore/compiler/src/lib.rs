@@ -44,12 +44,19 @@ use std::rc::Rc;
 
 use basil_common::{Result, BasilError};
 use basil_ast::{Program, Stmt, Expr, BinOp};
-use basil_bytecode::{Chunk, Program as BCProgram, Value, Op, Function};
+use basil_bytecode::{Chunk, Program as BCProgram, Value, Op, Function, HostFunctions, Decimal};
 
 pub mod service;
 
 pub fn compile(ast: &Program) -> Result<BCProgram> {
+    compile_with_host(ast, &HostFunctions::new())
+}
+
+// Like compile(), resolving calls that are neither builtins nor FUNC/SUBs of the program
+// against the embedding application's host functions.
+pub fn compile_with_host(ast: &Program, host: &HostFunctions) -> Result<BCProgram> {
     let mut c = C::new();
+    c.host = host.clone();
     // Pre-scan top-level constants so function bodies can safely reference them.
     // This mirrors Basil's behavior: CONST names exist before functions are compiled
     // and are treated as immutable globals. Also reserve their global slots now so
@@ -77,16 +84,28 @@ pub fn compile(ast: &Program) -> Result<BCProgram> {
             _ => {}
         }
     }
+    // Inline CLASS names, so NEW/INSTANCEOF/EXTENDS resolve regardless of definition order
+    for s in ast {
+        if let Stmt::ClassDef { name, base, .. } = s {
+            let uname = name.to_ascii_uppercase();
+            if c.classes.contains_key(&uname) || c.routines.contains_key(&uname) {
+                return Err(BasilError(format!("CLASS {} is already defined", name)));
+            }
+            c.classes.insert(uname, ClassInfo { name: name.clone(), base: base.as_ref().map(|b| b.to_ascii_uppercase()) });
+        }
+    }
     // Two-phase top-level emission to honor forward calls via DECLARE:
-    // 1) Emit all function definitions first so their globals are initialized.
+    // 1) Emit all function definitions first so their globals are initialized,
+    //    then the classes (base classes before their subclasses).
     for s in ast {
         if matches!(s, Stmt::Func { .. }) {
             c.emit_stmt_toplevel(s)?;
         }
     }
+    c.emit_classes(ast)?;
     // 2) Emit the rest of the top-level statements (non-function forms)
     for s in ast {
-        if !matches!(s, Stmt::Func { .. }) {
+        if !matches!(s, Stmt::Func { .. } | Stmt::ClassDef { .. }) {
             c.emit_stmt_toplevel(s)?;
         }
     }
@@ -129,6 +148,9 @@ pub fn compile(ast: &Program) -> Result<BCProgram> {
 
 struct RoutineInfo { arity: usize, is_sub: bool }
 
+// Declared name and base class (uppercase) of an inline CLASS
+struct ClassInfo { name: String, base: Option<String> }
+
 fn expr_contains_sub_call(routines: &HashMap<String, RoutineInfo>, e: &Expr) -> bool {
     match e {
         Expr::Call { callee, args } => {
@@ -158,6 +180,22 @@ fn expr_contains_sub_call(routines: &HashMap<String, RoutineInfo>, e: &Expr) ->
     }
 }
 
+// Whether a FUNC body contains YIELD (outside nested definitions).
+fn stmt_yields(s: &Stmt) -> bool {
+    let any = |body: &[Stmt]| body.iter().any(stmt_yields);
+    match s {
+        Stmt::Yield(_) => true,
+        Stmt::If { then_branch, else_branch, .. } => stmt_yields(then_branch) || else_branch.as_deref().is_some_and(stmt_yields),
+        Stmt::While { body, .. } | Stmt::For { body, .. } | Stmt::ForEach { body, .. } => stmt_yields(body),
+        Stmt::Block(body) | Stmt::With { body, .. } => any(body),
+        Stmt::SelectCase { arms, else_body, .. } => arms.iter().any(|a| any(&a.body)) || else_body.as_deref().is_some_and(any),
+        Stmt::Try { try_body, catch_body, finally_body, .. } => {
+            any(try_body) || catch_body.as_deref().is_some_and(any) || finally_body.as_deref().is_some_and(any)
+        }
+        _ => false,
+    }
+}
+
 struct C {
     cur_line: u32,
     chunk: Chunk,
@@ -192,6 +230,12 @@ struct C {
     const_globs: HashSet<String>,
     // Track which CONSTs have been initialized/emitted to catch duplicates
     const_inited_globs: HashSet<String>,
+    // Host functions visible to the program (compile_with_host)
+    host: HostFunctions,
+    // Inline CLASS definitions by uppercase name
+    classes: HashMap<String, ClassInfo>,
+    // Class (uppercase) whose methods are being compiled
+    cur_class: Option<String>,
 }
 
 impl C {
@@ -220,6 +264,9 @@ impl C {
             var_struct_array_globs: HashMap::new(),
             const_globs: HashSet::new(),
             const_inited_globs: HashSet::new(),
+            host: HostFunctions::new(),
+            classes: HashMap::new(),
+            cur_class: None,
         }
     }
 
@@ -235,6 +282,8 @@ impl C {
         match s {
             // No code emission for forward declarations
             Stmt::Declare { .. } => { /* ignore at codegen */ }
+            // Classes are emitted up front (emit_classes)
+            Stmt::ClassDef { .. } => {}
             // CONST at top level: evaluate once and store to a global; mark immutable
             Stmt::Const { name, value } => {
                 let uname = name.to_ascii_uppercase();
@@ -259,7 +308,7 @@ impl C {
             Stmt::Func { name, params, body, .. } => {
                 // remember function name for call vs array indexing disambiguation
                 self.fn_names.insert(name.to_ascii_uppercase());
-                let f = self.compile_function(name.clone(), params.clone(), body);
+                let f = self.compile_function(name.clone(), params.clone(), body)?;
                 self.chunk.push_op(Op::Const);
                 let idx = self.chunk.add_const(f);
                 self.chunk.push_u16(idx);
@@ -447,6 +496,7 @@ impl C {
                 self.with_stack_tl.pop();
                 self.chunk = chunk;
             }
+            Stmt::Yield(_) => return Err(BasilError("YIELD is only allowed inside a FUNC or SUB".into())),
             Stmt::Raise(expr_opt) => {
                 let mut chunk = std::mem::take(&mut self.chunk);
                 match expr_opt {
@@ -639,10 +689,8 @@ impl C {
                     let g = self.gslot(name);
                     chunk.push_op(Op::StoreGlobal); chunk.push_u8(g);
                 } else {
-                    // Fallback: object instance via registry
-                    for a in args { self.emit_expr_in(&mut chunk, a, None)?; }
-                    let tci = chunk.add_const(Value::Str(type_name.clone()));
-                    chunk.push_op(Op::NewObj); chunk.push_u16(tci); chunk.push_u8(args.len() as u8);
+                    // Fallback: inline CLASS or object instance via registry
+                    self.emit_new_object(&mut chunk, type_name, args, None)?;
                     let g = self.gslot(name);
                     chunk.push_op(Op::StoreGlobal); chunk.push_u8(g);
                 }
@@ -915,7 +963,104 @@ impl C {
         Ok(())
     }
 
-    fn compile_function(&mut self, name: String, params: Vec<String>, body: &Vec<Stmt>) -> Value {
+    // NEW Type(args): an inline CLASS or a type from the object registry.
+    fn emit_new_object(&mut self, chunk: &mut Chunk, type_name: &str, args: &[Expr], env: Option<&LocalEnv>) -> Result<()> {
+        if let Some(ci) = self.classes.get(&type_name.to_ascii_uppercase()) {
+            let g = self.gslot(&ci.name.clone());
+            chunk.push_op(Op::LoadGlobal); chunk.push_u8(g);
+            for a in args { self.emit_expr_in(chunk, a, env)?; }
+            chunk.push_op(Op::NewInstance); chunk.push_u8(args.len() as u8);
+            return Ok(());
+        }
+        for a in args { self.emit_expr_in(chunk, a, env)?; }
+        let tci = chunk.add_const(Value::Str(type_name.to_string()));
+        chunk.push_op(Op::NewObj); chunk.push_u16(tci); chunk.push_u8(args.len() as u8);
+        Ok(())
+    }
+
+    fn emit_classes(&mut self, ast: &Program) -> Result<()> {
+        let defs: HashMap<String, &Stmt> = ast.iter()
+            .filter_map(|s| match s { Stmt::ClassDef { name, .. } => Some((name.to_ascii_uppercase(), s)), _ => None })
+            .collect();
+        let mut done: HashSet<String> = HashSet::new();
+        for s in ast {
+            if let Stmt::ClassDef { name, .. } = s {
+                self.emit_class_with_bases(&name.to_ascii_uppercase(), &defs, &mut done, &mut Vec::new())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_class_with_bases(&mut self, uname: &str, defs: &HashMap<String, &Stmt>, done: &mut HashSet<String>, path: &mut Vec<String>) -> Result<()> {
+        if done.contains(uname) { return Ok(()); }
+        if path.iter().any(|p| p == uname) {
+            return Err(BasilError(format!("CLASS {} inherits from itself", self.classes[uname].name)));
+        }
+        let Some(Stmt::ClassDef { name, base, fields, methods }) = defs.get(uname).copied() else { return Ok(()) };
+        if let Some(b) = base {
+            let ub = b.to_ascii_uppercase();
+            if !defs.contains_key(&ub) {
+                return Err(BasilError(format!("CLASS {}: unknown base class {}", name, b)));
+            }
+            path.push(uname.to_string());
+            self.emit_class_with_bases(&ub, defs, done, path)?;
+            path.pop();
+        }
+        self.emit_class(name, base.as_deref(), fields, methods)?;
+        done.insert(uname.to_string());
+        Ok(())
+    }
+
+    // Stack for Op::MakeClass: base class, (field name, private) pairs, (method, private,
+    // parameter names) triples and the field initializer function (or null); the class
+    // goes into a global.
+    fn emit_class(&mut self, name: &str, base: Option<&str>, fields: &[basil_ast::ClassField], methods: &[basil_ast::ClassMethod]) -> Result<()> {
+        let uname = name.to_ascii_uppercase();
+        self.cur_class = Some(uname.clone());
+        let mut funcs = Vec::with_capacity(methods.len());
+        for m in methods {
+            let mut params = vec!["ME".to_string()];
+            params.extend(m.params.iter().cloned());
+            let f = self.compile_function(m.name.clone(), params, &m.body);
+            funcs.push((f, m.private, m.params.join(", ")));
+        }
+        let inits: Vec<Stmt> = fields.iter()
+            .filter_map(|f| f.init.as_ref().map(|e| Stmt::SetProp { target: Expr::Var("ME".into()), prop: f.name.clone(), value: e.clone() }))
+            .collect();
+        let init = if inits.is_empty() { Ok(Value::Null) } else { self.compile_function(format!("{}.INIT", name), vec!["ME".into()], &inits) };
+        self.cur_class = None;
+        let init = init?;
+
+        let mut chunk = std::mem::take(&mut self.chunk);
+        match base.and_then(|b| self.classes.get(&b.to_ascii_uppercase())).map(|ci| ci.name.clone()) {
+            Some(bname) => { let g = self.gslot(&bname); chunk.push_op(Op::LoadGlobal); chunk.push_u8(g); }
+            None => { let ci = chunk.add_const(Value::Null); chunk.push_op(Op::Const); chunk.push_u16(ci); }
+        }
+        for f in fields {
+            let ci = chunk.add_const(Value::Str(f.name.clone()));
+            chunk.push_op(Op::Const); chunk.push_u16(ci);
+            let ci = chunk.add_const(Value::Bool(f.private));
+            chunk.push_op(Op::Const); chunk.push_u16(ci);
+        }
+        for (f, private, params) in funcs {
+            let ci = chunk.add_const(f?);
+            chunk.push_op(Op::Const); chunk.push_u16(ci);
+            let ci = chunk.add_const(Value::Bool(private));
+            chunk.push_op(Op::Const); chunk.push_u16(ci);
+            let ci = chunk.add_const(Value::Str(params));
+            chunk.push_op(Op::Const); chunk.push_u16(ci);
+        }
+        let ci = chunk.add_const(init);
+        chunk.push_op(Op::Const); chunk.push_u16(ci);
+        let nci = chunk.add_const(Value::Str(name.to_string()));
+        chunk.push_op(Op::MakeClass); chunk.push_u16(nci); chunk.push_u8(fields.len() as u8); chunk.push_u8(methods.len() as u8);
+        let g = self.gslot(name);
+        chunk.push_op(Op::StoreGlobal); chunk.push_u8(g);
+        self.chunk = chunk;
+        Ok(())
+    }
+
+    fn compile_function(&mut self, name: String, params: Vec<String>, body: &Vec<Stmt>) -> Result<Value> {
         let mut fchunk = Chunk::default();
         let mut env = LocalEnv::new();
 
@@ -928,9 +1073,14 @@ impl C {
         self.fn_labels.clear();
         self.fn_goto_fixups.clear();
 
+        // A FUNC containing YIELD returns a generator instead of running its body
+        if body.iter().any(stmt_yields) {
+            fchunk.push_op(Op::MakeGen);
+        }
+
         // body
         for s in body {
-            self.emit_stmt_func(&mut fchunk, s, &mut env).unwrap();
+            self.emit_stmt_func(&mut fchunk, s, &mut env)?;
         }
 
         // resolve function-level GOTOs now that all labels are known
@@ -971,16 +1121,20 @@ impl C {
         fchunk.push_u16(cid);
         fchunk.push_op(Op::Ret);
 
-        Value::Func(Rc::new(Function {
+        Ok(Value::Func(Rc::new(Function {
             arity: params.len() as u8,
             name: Some(name),
             chunk: Rc::new(fchunk),
-        }))
+            bound: None,
+        })))
     }
 
     fn emit_stmt_func(&mut self, chunk: &mut Chunk, s: &Stmt, env: &mut LocalEnv) -> Result<()> {
         match s {
             Stmt::Declare { .. } => { /* no-op inside bodies */ },
+            Stmt::ClassDef { name, .. } => {
+                return Err(BasilError(format!("CLASS {} must be defined at the top level of the program", name)));
+            }
             // Local constant: evaluate once, assign into a local slot, and mark immutable
             Stmt::Const { name, value } => {
                 let uname = name.to_ascii_uppercase();
@@ -1187,6 +1341,10 @@ impl C {
                 self.with_current_stack.pop();
                 self.with_stack_fn.pop();
             }
+            Stmt::Yield(e) => {
+                self.emit_expr_in(chunk, e, Some(env))?;
+                chunk.push_op(Op::Yield);
+            }
             Stmt::Raise(expr_opt) => {
                 match expr_opt {
                     Some(e) => { self.emit_expr_in(chunk, e, Some(env))?; chunk.push_op(Op::Raise); }
@@ -1346,9 +1504,7 @@ impl C {
                     let argc = (fields.len() * 2) as u8;
                     chunk.push_op(Op::Builtin); chunk.push_u8(252u8); chunk.push_u8(argc);
                 } else {
-                    for a in args { self.emit_expr_in(chunk, a, Some(env))?; }
-                    let tci = chunk.add_const(Value::Str(type_name.clone()));
-                    chunk.push_op(Op::NewObj); chunk.push_u16(tci); chunk.push_u8(args.len() as u8);
+                    self.emit_new_object(chunk, type_name, args, Some(env))?;
                 }
                 let slot = env.bind_next_if_absent(name.clone());
                 chunk.push_op(Op::StoreLocal); chunk.push_u8(slot);
@@ -1676,6 +1832,15 @@ impl C {
                 let idx = chunk.add_const(Value::Num(*n));
                 chunk.push_op(Op::Const); chunk.push_u16(idx);
             }
+            Expr::Int(i) => {
+                let idx = chunk.add_const(Value::Int(*i));
+                chunk.push_op(Op::Const); chunk.push_u16(idx);
+            }
+            Expr::Decimal(d) => {
+                let dec = Decimal::parse(d).ok_or_else(|| BasilError(format!("invalid decimal literal {}@", d)))?;
+                let idx = chunk.add_const(Value::Decimal(Rc::new(dec)));
+                chunk.push_op(Op::Const); chunk.push_u16(idx);
+            }
             Expr::Str(s) => {
                 let idx = chunk.add_const(Value::Str(s.clone()));
                 chunk.push_op(Op::Const); chunk.push_u16(idx);
@@ -1684,6 +1849,10 @@ impl C {
                 let idx = chunk.add_const(Value::Bool(*b));
                 chunk.push_op(Op::Const); chunk.push_u16(idx);
             }
+            Expr::Null => {
+                let idx = chunk.add_const(Value::Null);
+                chunk.push_op(Op::Const); chunk.push_u16(idx);
+            }
             Expr::List(items) => {
                 // Evaluate items left-to-right, then call MAKE_LIST builtin with argc
                 for it in items { self.emit_expr_in(chunk, it, env)?; }
@@ -1716,12 +1885,23 @@ impl C {
                     chunk.push_op(Op::Const); chunk.push_u16(ci);
                     return Ok(());
                 }
+                // ME/SELF: the receiver, local slot 0 of a CLASS method
+                if self.cur_class.is_some() && env.is_some() && (uname == "ME" || uname == "SELF") {
+                    chunk.push_op(Op::LoadLocal); chunk.push_u8(0);
+                    return Ok(());
+                }
                 if let Some(env) = env {
                     if let Some(slot) = env.lookup(name) {
                         chunk.push_op(Op::LoadLocal); chunk.push_u8(slot);
                         return Ok(());
                     }
                 }
+                // The clock reads and PI work without parentheses, as in classic BASIC (unless shadowed by a variable)
+                let clock_id = match uname.as_str() { "NOW" => Some(98u8), "TIMER" => Some(99u8), "DATE$" => Some(100u8), "TIME$" => Some(101u8), "PI" => Some(88u8), _ => None };
+                if let Some(id) = clock_id.filter(|_| !self.gmap.contains_key(name)) {
+                    chunk.push_op(Op::Builtin); chunk.push_u8(id); chunk.push_u8(0);
+                    return Ok(());
+                }
                 let g = self.gslot(name);
                 chunk.push_op(Op::LoadGlobal); chunk.push_u8(g);
             }
@@ -1907,7 +2087,20 @@ impl C {
                         "UNESCAPE$" => Some(21u8),
                         "URLENCODE$" => Some(22u8),
                         "URLDECODE$" => Some(23u8),
+                        "SAFE_URL$" => Some(25u8),
                         "STRING$" => Some(26u8),
+                        "ROUTE" => Some(27u8),
+                        "ROUTE_HOOK" => Some(28u8),
+                        "ROUTE_PARAM$" => Some(29u8),
+                        "ROUTE_PATH$" => Some(30u8),
+                        "COOKIE$" => Some(31u8),
+                        "CSRF_TOKEN$" => Some(32u8),
+                        "CSRF_FIELD$" => Some(33u8),
+                        "CSRF_FORMS$" => Some(39u8),
+                        "FLUSH" => Some(34u8),
+                        "OB_START" => Some(35u8),
+                        "OB_GET$" => Some(36u8),
+                        "OB_END" => Some(37u8),
                         "SLEEP" => Some(24u8),
                         // --- Math builtins ---
                         "ABS" => Some(70u8),
@@ -1960,10 +2153,58 @@ impl C {
                         #[cfg(feature = "obj-zip")] "ZIP_ARRAY$[]" => Some(135u8),
                         #[cfg(feature = "obj-curl")] "HTTP_GET$" => Some(124u8),
                         #[cfg(feature = "obj-curl")] "HTTP_POST$" => Some(125u8),
-                        #[cfg(feature = "obj-json")] "JSON_PARSE$" => Some(126u8),
-                        #[cfg(feature = "obj-json")] "JSON_STRINGIFY$" => Some(127u8),
-                        #[cfg(feature = "obj-csv")] "CSV_PARSE$" => Some(128u8),
-                        #[cfg(feature = "obj-csv")] "CSV_WRITE$" => Some(129u8),
+                        "JSON_PARSE$" => Some(126u8),
+                        "JSON_STRINGIFY$" => Some(127u8),
+                        "JSON_PARSE" => Some(154u8),
+                        "REGEX_MATCH%" => Some(92u8),
+                        "REGEX_FIND$" => Some(93u8),
+                        "REGEX_FINDALL" => Some(94u8),
+                        "REGEX_CAPTURES" => Some(95u8),
+                        "REGEX_REPLACE$" => Some(96u8),
+                        "REGEX_SPLIT" => Some(97u8),
+                        "NOW" => Some(98u8),
+                        "TIMER" => Some(99u8),
+                        "DATE$" => Some(100u8),
+                        "TIME$" => Some(101u8),
+                        "DATETIME" => Some(102u8),
+                        "DATETIME_PARSE" => Some(103u8),
+                        "UCHR$" => Some(104u8),
+                        "UASC%" => Some(105u8),
+                        "GRAPHEMES" => Some(106u8),
+                        "GLEN%" => Some(107u8),
+                        "BYTES" => Some(108u8),
+                        "BYTES_TEXT$" => Some(109u8),
+                        "BYTES_HEX$" => Some(110u8),
+                        "HEX_BYTES" => Some(111u8),
+                        "BYTES_BASE64$" => Some(112u8),
+                        "BASE64_BYTES" => Some(113u8),
+                        "READFILE_BYTES" => Some(114u8),
+                        "FREAD_BYTES" => Some(115u8),
+                        "SHA256$" => Some(165u8),
+                        "SHA1$" => Some(166u8),
+                        "MD5$" => Some(167u8),
+                        "HMAC_SHA256$" => Some(168u8),
+                        "SECURE_EQ%" => Some(169u8),
+                        "RANDOM_BYTES$" => Some(170u8),
+                        "UUID$" => Some(171u8),
+                        "PASSWORD_HASH$" => Some(172u8),
+                        "PASSWORD_VERIFY%" => Some(173u8),
+                        "FORMAT$" => Some(116u8),
+                        "STR$" => Some(117u8),
+                        "VAL" => Some(118u8),
+                        "DEC" => Some(119u8),
+                        "DEC_DIV" => Some(155u8),
+                        "DEC_ROUND" => Some(156u8),
+                        "DEC_SCALE%" => Some(157u8),
+                        "DEC_ROUNDING$" => Some(158u8),
+                        "SQL_BIND$" => Some(159u8),
+                        "SQL_BIND_MYSQL$" => Some(136u8),
+                        "CSV_PARSE$" => Some(128u8),
+                        "CSV_WRITE$" => Some(129u8),
+                        "CSV_READER" => Some(174u8),
+                        "CSV_READ_ALL" => Some(175u8),
+                        "CSV_WRITE_ROW" => Some(176u8),
+                        "CSV_FORMAT$" => Some(177u8),
                         #[cfg(feature = "obj-sqlite")] "SQLITE_OPEN%" => Some(130u8),
                         #[cfg(feature = "obj-sqlite")] "SQLITE_CLOSE" => Some(131u8),
                         #[cfg(feature = "obj-sqlite")] "SQLITE_EXEC%" => Some(132u8),
@@ -2032,13 +2273,60 @@ impl C {
                         #[cfg(feature = "obj-midi")]  "MIDI_CLOSE%" => Some(214u8),
                         "ARRAY_ROWS%" => Some(139u8),
                         "ARRAY_COLS%" => Some(140u8),
+                        "SORT" => Some(141u8),
+                        "SETTIMEOUT" => Some(142u8),
+                        "SETINTERVAL" => Some(143u8),
+                        "CLEARTIMER" | "CLEARTIMEOUT" | "CLEARINTERVAL" => Some(144u8),
+                        "ON_KEY" => Some(145u8),
+                        "ON_SIGNAL" => Some(146u8),
+                        "AWAIT" => Some(147u8),
+                        "EVENT_LOOP" => Some(148u8),
+                        "STOP_LOOP" => Some(149u8),
+                        "SPAWN" => Some(150u8),
+                        "SEND" => Some(151u8),
+                        "RECEIVE" => Some(152u8),
+                        "JOIN" => Some(153u8),
                         _ => None,
                     };
+                    // The math library's names are common words: a program FUNC/SUB of the same name wins
+                    let bid = bid.or_else(|| if self.routines.contains_key(&uname) { None } else { match &*uname {
+                        "ROUND" => Some(66u8),
+                        "FIX" => Some(67u8),
+                        "SGN" => Some(68u8),
+                        "RANDOMIZE" => Some(69u8),
+                        "CINT" => Some(83u8),
+                        "CLNG" => Some(84u8),
+                        "CDBL" => Some(85u8),
+                        "MIN" => Some(86u8),
+                        "MAX" => Some(87u8),
+                        "PI" => Some(88u8),
+                        "HYPOT" => Some(89u8),
+                        "ATAN2" => Some(215u8),
+                        "LOG10" => Some(216u8),
+                        "POW" => Some(217u8),
+                        "GCD" => Some(218u8),
+                        "LCM" => Some(219u8),
+                        "SUM" => Some(225u8),
+                        "AVG" => Some(226u8),
+                        "MEDIAN" => Some(227u8),
+                        "STDDEV" => Some(228u8),
+                        _ => None,
+                    } });
                     if let Some(id) = bid {
                         for a in args { self.emit_expr_in(chunk, a, env)?; }
                         chunk.push_op(Op::Builtin); chunk.push_u8(id); chunk.push_u8(args.len() as u8);
                         return Ok(());
                     }
+                    // Host function of the embedding application (program FUNC/SUBs take precedence)
+                    if !self.routines.contains_key(&uname) {
+                        if let Some(hf) = self.host.get(&uname) {
+                            hf.check_arity(args.len())?;
+                            let nci = chunk.add_const(Value::Str(hf.name.clone()));
+                            for a in args { self.emit_expr_in(chunk, a, env)?; }
+                            chunk.push_op(Op::CallHost); chunk.push_u16(nci); chunk.push_u8(args.len() as u8);
+                            return Ok(());
+                        }
+                    }
                     // If not builtin, treat as array access when not a known function
                     if args.len() >= 1 && args.len() <= 4 {
                         let is_func = self.routines.contains_key(&uname);
@@ -2129,15 +2417,38 @@ impl C {
                         }
                     }
                 }
+                // SUPER.Method(...) inside a CLASS method: the base class implementation on ME
+                if let (Expr::Var(tn), Some(cur)) = (&**target, &self.cur_class) {
+                    if tn.eq_ignore_ascii_case("SUPER") {
+                        let ci = &self.classes[cur];
+                        let base = ci.base.clone()
+                            .ok_or_else(|| BasilError(format!("SUPER used in CLASS {} which has no base class", ci.name)))?;
+                        let bname = self.classes[&base].name.clone();
+                        let g = self.gslot(&bname);
+                        chunk.push_op(Op::LoadGlobal); chunk.push_u8(g);
+                        chunk.push_op(Op::LoadLocal); chunk.push_u8(0);
+                        for a in args { self.emit_expr_in(chunk, a, env)?; }
+                        let ci = chunk.add_const(Value::Str(method.clone()));
+                        chunk.push_op(Op::CallSuper); chunk.push_u16(ci); chunk.push_u8(args.len() as u8);
+                        return Ok(());
+                    }
+                }
                 self.emit_expr_in(chunk, target, env)?;
                 for a in args { self.emit_expr_in(chunk, a, env)?; }
                 let ci = chunk.add_const(Value::Str(method.clone()));
                 chunk.push_op(Op::CallMethod); chunk.push_u16(ci); chunk.push_u8(args.len() as u8);
             }
             Expr::NewObject { type_name, args } => {
-                for a in args { self.emit_expr_in(chunk, a, env)?; }
-                let tci = chunk.add_const(Value::Str(type_name.clone()));
-                chunk.push_op(Op::NewObj); chunk.push_u16(tci); chunk.push_u8(args.len() as u8);
+                self.emit_new_object(chunk, type_name, args, env)?;
+            }
+            Expr::InstanceOf { target, class_name } => {
+                self.emit_expr_in(chunk, target, env)?;
+                // Inline classes compare by class (subclasses match); other objects by type name
+                match self.classes.get(&class_name.to_ascii_uppercase()).map(|ci| ci.name.clone()) {
+                    Some(cname) => { let g = self.gslot(&cname); chunk.push_op(Op::LoadGlobal); chunk.push_u8(g); }
+                    None => { let ci = chunk.add_const(Value::Str(class_name.clone())); chunk.push_op(Op::Const); chunk.push_u16(ci); }
+                }
+                chunk.push_op(Op::Builtin); chunk.push_u8(38u8); chunk.push_u8(2u8);
             }
             Expr::NewClass { filename } => {
                 // Evaluate filename and instantiate class at runtime
@@ -2346,6 +2657,9 @@ impl C {
     fn emit_stmt_tl_in_chunk(&mut self, chunk: &mut Chunk, s: &Stmt) -> Result<()> {
         match s {
             Stmt::Declare { .. } => { /* no-op */ },
+            Stmt::ClassDef { name, .. } => {
+                return Err(BasilError(format!("CLASS {} must be defined at the top level of the program", name)));
+            }
             // Handle CONST seen inside top-level blocks (e.g., within BEGIN/END at T/L)
             Stmt::Const { name, value } => {
                 let uname = name.to_ascii_uppercase();
@@ -2449,6 +2763,7 @@ impl C {
                 let g = self.gslot(name);
                 chunk.push_op(Op::StoreGlobal); chunk.push_u8(g);
             }
+            Stmt::Yield(_) => return Err(BasilError("YIELD is only allowed inside a FUNC or SUB".into())),
             Stmt::Raise(expr_opt) => {
                 match expr_opt {
                     Some(e) => { self.emit_expr_in(chunk, e, None)?; chunk.push_op(Op::Raise); }
@@ -2600,9 +2915,7 @@ impl C {
                 }
             }
             Stmt::DimObject { name, type_name, args } => {
-                for a in args { self.emit_expr_in(chunk, a, None)?; }
-                let tci = chunk.add_const(Value::Str(type_name.clone()));
-                chunk.push_op(Op::NewObj); chunk.push_u16(tci); chunk.push_u8(args.len() as u8);
+                self.emit_new_object(chunk, type_name, args, None)?;
                 let g = self.gslot(name);
                 chunk.push_op(Op::StoreGlobal); chunk.push_u8(g);
             }
@@ -2667,7 +2980,7 @@ impl C {
                 for s2 in stmts { self.emit_stmt_tl_in_chunk(chunk, s2)?; }
             }
             Stmt::Func { name, params, body, .. } => {
-                let f = self.compile_function(name.clone(), params.clone(), body);
+                let f = self.compile_function(name.clone(), params.clone(), body)?;
                 chunk.push_op(Op::Const);
                 let idx = chunk.add_const(f);
                 chunk.push_u16(idx);
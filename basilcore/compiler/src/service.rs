@@ -2,6 +2,7 @@ use serde::{Serialize, Deserialize};
 
 use basil_parser::parse;
 use basil_ast::{Program, Stmt};
+use basil_bytecode::HostFunctions;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DiagnosticSeverity { Error, Warning, Information }
@@ -15,7 +16,7 @@ pub struct Diagnostic {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum SymbolKind { Function, Variable, Label }
+pub enum SymbolKind { Function, Variable, Label, HostFunction, Class }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SymbolInfo {
@@ -51,6 +52,15 @@ pub fn analyze_source(source: &str, _filename: &str) -> CompilerDiagnostics {
     out
 }
 
+// analyze_source() plus the embedding application's host functions as symbols.
+pub fn analyze_source_with_host(source: &str, filename: &str, host: &HostFunctions) -> CompilerDiagnostics {
+    let mut out = analyze_source(source, filename);
+    for f in host.list() {
+        out.symbols.push(SymbolInfo { name: f.name.clone(), kind: SymbolKind::HostFunction, line: 0, col: 0 });
+    }
+    out
+}
+
 fn collect_symbols(ast: &Program, syms: &mut Vec<SymbolInfo>) {
     for s in ast {
         match s {
@@ -63,6 +73,9 @@ fn collect_symbols(ast: &Program, syms: &mut Vec<SymbolInfo>) {
             Stmt::Const { name, .. } => {
                 syms.push(SymbolInfo { name: name.clone(), kind: SymbolKind::Variable, line: 0, col: 0 });
             }
+            Stmt::ClassDef { name, .. } => {
+                syms.push(SymbolInfo { name: name.clone(), kind: SymbolKind::Class, line: 0, col: 0 });
+            }
             Stmt::Label(lbl) => {
                 syms.push(SymbolInfo { name: lbl.clone(), kind: SymbolKind::Label, line: 0, col: 0 });
             }
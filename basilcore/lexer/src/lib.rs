@@ -84,7 +84,7 @@ pub enum TokenKind {
 }
 
 #[derive(Debug, Clone)]
-pub enum Literal { Num(f64), Str(String) }
+pub enum Literal { Num(f64), Int(i64), Dec(String), Str(String) }
 
 #[derive(Debug, Clone)]
 pub struct Token {
@@ -108,6 +108,8 @@ pub struct Lexer<'a> {
     // --- line continuation state ---
     paren_depth: i32,
     last_was_continuation: bool,
+    // The previous token was PRINT's USING, whose format string is read as written
+    after_using: bool,
 }
 
 impl<'a> Lexer<'a> {
@@ -124,6 +126,7 @@ impl<'a> Lexer<'a> {
             pending: VecDeque::new(),
             paren_depth: 0,
             last_was_continuation: false,
+            after_using: false,
         };
         l.advance(); // prime `cur` and `pos`
         l
@@ -219,6 +222,7 @@ impl<'a> Lexer<'a> {
                 else { self.make(TokenKind::Gt) }
             }
 
+            '"' if self.after_using => self.using_mask()?,
             '"' => self.string()?,
             c if c.is_ascii_digit() => self.number()?,
             c if is_ident_start(c)  => self.ident_or_kw()?,
@@ -233,6 +237,7 @@ impl<'a> Lexer<'a> {
     // Adjust lexer state after emitting a token (track paren depth and continuation contexts)
     fn post_emit_adjust(&mut self, tok: &Token) {
         use TokenKind::*;
+        self.after_using = tok.kind == Ident && tok.lexeme.eq_ignore_ascii_case("USING");
         match tok.kind {
             LParen => { self.paren_depth += 1; self.last_was_continuation = true; },
             RParen => { if self.paren_depth > 0 { self.paren_depth -= 1; } self.last_was_continuation = false; },
@@ -499,6 +504,23 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    // PRINT USING "\  \"; s$ - as in GW-BASIC the mask has no escapes or interpolation, so a
+    // backslash field can end right before the closing quote
+    fn using_mask(&mut self) -> Result<Token> {
+        let tok_line = self.tok_line as u32;
+        let content_start = self.pos;
+        self.advance();
+        loop {
+            match self.cur {
+                None => return Err(BasilError(format!("parse error at line {}: unterminated string", tok_line))),
+                Some('"') => break,
+                Some(_) => self.advance(),
+            }
+        }
+        let mask = self.src[content_start..self.pos - 1].to_string();
+        self.advance();
+        Ok(Token { kind: TokenKind::String, lexeme: self.src[self.start..self.pos].to_string(), literal: Some(Literal::Str(mask)), span: Span::new(self.start, self.pos), line: tok_line })
+    }
 
     fn number(&mut self) -> Result<Token> {
         let start = self.start;
@@ -522,10 +544,24 @@ impl<'a> Lexer<'a> {
             }
         }
 
+        // 12.34@ is an exact DECIMAL; the digits are kept as written
+        if self.cur == Some('@') {
+            let digits = self.src[start..end].to_string();
+            end = self.pos;
+            self.advance();
+            let mut tok = self.make_with_span(TokenKind::Number, start, end);
+            tok.literal = Some(Literal::Dec(digits));
+            return Ok(tok);
+        }
+
         let lex = &self.src[start..end];
         let n: f64 = lex.parse().map_err(|e| BasilError(format!("invalid number '{}': {}", lex, e)))?;
         let mut tok = self.make_with_span(TokenKind::Number, start, end);
-        tok.literal = Some(Literal::Num(n));
+        // A whole number that a FLOAT would round (past 2^53) keeps its exact INTEGER value
+        tok.literal = Some(match lex.parse::<i64>() {
+            Ok(i) if i as f64 as i128 != i as i128 => Literal::Int(i),
+            _ => Literal::Num(n),
+        });
         Ok(tok)
     }
 
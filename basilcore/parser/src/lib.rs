@@ -450,6 +450,14 @@ impl Parser {
             return Ok(Stmt::Raise(expr_opt));
         }
 
+        // YIELD expr (contextual: a variable named yield may still be assigned)
+        if self.check_word("YIELD") && !matches!(self.tokens.get(self.i + 1).map(|t| &t.kind), Some(TokenKind::Assign) | Some(TokenKind::Dot) | Some(TokenKind::LBracket)) {
+            let _ = self.next();
+            let expr = self.parse_expr_bp(0)?;
+            self.terminate_stmt()?;
+            return Ok(Stmt::Yield(expr));
+        }
+
         // CONST name = expr
         if self.match_k(TokenKind::Const) {
             let name = self.expect_ident()?;
@@ -521,6 +529,10 @@ impl Parser {
         }
 
         if self.match_k(TokenKind::Print) {
+            if let Some(e) = self.parse_print_using()? {
+                self.terminate_stmt()?;
+                return Ok(Stmt::Print { expr: e });
+            }
             // Support PRINT with comma-separated expressions joined by TABs
             let mut e = self.parse_expr_bp(0)?;
             while self.match_k(TokenKind::Comma) {
@@ -535,7 +547,10 @@ impl Parser {
 
         if self.match_k(TokenKind::Println) {
             // PRINTLN works like PRINT but always appends a newline
-            let mut e = self.parse_expr_bp(0)?;
+            let mut e = match self.parse_print_using()? {
+                Some(e) => e,
+                None => self.parse_expr_bp(0)?,
+            };
             while self.match_k(TokenKind::Comma) {
                 let next = self.parse_expr_bp(0)?;
                 e = Expr::Binary { op: BinOp::Add, lhs: Box::new(e), rhs: Box::new(Expr::Str("\t".to_string())) };
@@ -781,6 +796,12 @@ impl Parser {
             return Ok(Stmt::Block(inner));
         }
 
+        // CLASS Name [EXTENDS Base] ... END CLASS  (CLASS("file") is an expression)
+        if self.check(TokenKind::Class) && matches!(self.tokens.get(self.i + 1).map(|t| &t.kind), Some(TokenKind::Ident)) {
+            let _ = self.next();
+            return self.parse_class();
+        }
+
         // TYPE ... END TYPE (struct definition) or TYPE Name { ... }
         if self.match_k(TokenKind::Type) {
             let type_name = self.expect_ident()?;
@@ -1072,11 +1093,11 @@ impl Parser {
             }
         }
 
-        // Special-case: SLEEP expr or SLEEP(expr) as a statement without requiring parentheses
+        // Special-case: SLEEP expr or SLEEP(expr) (and AWAIT) as a statement without requiring parentheses
         if self.check(TokenKind::Ident) {
             let save_i = self.i;
             let name = self.expect_ident()?;
-            if name.eq_ignore_ascii_case("SLEEP") {
+            if (name.eq_ignore_ascii_case("SLEEP") || name.eq_ignore_ascii_case("AWAIT")) && !self.check(TokenKind::Assign) {
                 // Accept either SLEEP(expr) or SLEEP expr
                 let arg = if self.match_k(TokenKind::LParen) {
                     let e = self.parse_expr_bp(0)?;
@@ -1086,7 +1107,52 @@ impl Parser {
                     self.parse_expr_bp(0)?
                 };
                 self.terminate_stmt()?;
-                let call = Expr::Call { callee: Box::new(Expr::Var("SLEEP".to_string())), args: vec![arg] };
+                let call = Expr::Call { callee: Box::new(Expr::Var(name.to_ascii_uppercase())), args: vec![arg] };
+                return Ok(Stmt::ExprStmt(call));
+            } else if name.eq_ignore_ascii_case("RANDOMIZE") && !self.check(TokenKind::Assign) {
+                // RANDOMIZE, RANDOMIZE seed or RANDOMIZE(seed)
+                let args = if self.check(TokenKind::Semicolon) || self.check(TokenKind::Eof) { vec![] } else { vec![self.parse_expr_bp(0)?] };
+                self.terminate_stmt()?;
+                let call = Expr::Call { callee: Box::new(Expr::Var("RANDOMIZE".to_string())), args };
+                return Ok(Stmt::ExprStmt(call));
+            } else if name.eq_ignore_ascii_case("ON") && (self.check_word("KEY") || self.check_word("SIGNAL")) {
+                // ON KEY handler              -> ON_KEY(handler)
+                // ON SIGNAL name$, handler    -> ON_SIGNAL(name$, handler)
+                let call = if self.match_word("KEY") {
+                    let handler = self.parse_expr_bp(0)?;
+                    Expr::Call { callee: Box::new(Expr::Var("ON_KEY".to_string())), args: vec![handler] }
+                } else {
+                    let _ = self.next();
+                    let sig = self.parse_expr_bp(0)?;
+                    self.expect(TokenKind::Comma)?;
+                    let handler = self.parse_expr_bp(0)?;
+                    Expr::Call { callee: Box::new(Expr::Var("ON_SIGNAL".to_string())), args: vec![sig, handler] }
+                };
+                self.terminate_stmt()?;
+                return Ok(Stmt::ExprStmt(call));
+            } else if name.eq_ignore_ascii_case("ROUTE") && !self.check(TokenKind::LParen) && !self.check(TokenKind::Assign) {
+                // ROUTE method$, pattern$, handler      -> ROUTE(method$, pattern$, handler)
+                // ROUTE BEFORE|AFTER|NOT_FOUND|ERROR h  -> ROUTE_HOOK("BEFORE", h)
+                let hook = match self.tokens.get(self.i) {
+                    Some(t) if t.kind == TokenKind::Ident
+                        && matches!(t.lexeme.to_ascii_uppercase().as_str(), "BEFORE" | "AFTER" | "NOT_FOUND" | "ERROR")
+                        && self.tokens.get(self.i + 1).map(|n| n.kind != TokenKind::Comma).unwrap_or(false) =>
+                        Some(t.lexeme.to_ascii_uppercase()),
+                    _ => None,
+                };
+                let call = if let Some(kind) = hook {
+                    self.i += 1;
+                    let handler = self.parse_expr_bp(0)?;
+                    Expr::Call { callee: Box::new(Expr::Var("ROUTE_HOOK".to_string())), args: vec![Expr::Str(kind), handler] }
+                } else {
+                    let mut args = vec![self.parse_expr_bp(0)?];
+                    while self.match_k(TokenKind::Comma) { args.push(self.parse_expr_bp(0)?); }
+                    if args.len() != 3 {
+                        return Err(BasilError(format!("parse error at line {}: ROUTE expects method, pattern, handler", self.peek_line())));
+                    }
+                    Expr::Call { callee: Box::new(Expr::Var("ROUTE".to_string())), args }
+                };
+                self.terminate_stmt()?;
                 return Ok(Stmt::ExprStmt(call));
             } else {
                 // Support zero-arg terminal commands as bare statements without parentheses
@@ -1098,7 +1164,11 @@ impl Parser {
                     "CURSOR_SAVE", "CURSOR_RESTORE",
                     "CURSOR_HIDE", "CURSOR_SHOW",
                 ];
-                if ZERO_ARG_TERMINAL_CMDS.contains(&uname.as_str()) {
+                // Output buffering commands: FLUSH; OB_START; OB_END
+                const ZERO_ARG_OUTPUT_CMDS: [&str; 3] = ["FLUSH", "OB_START", "OB_END"];
+                // Event loop commands: EVENT_LOOP; STOP_LOOP
+                const ZERO_ARG_EVENT_CMDS: [&str; 2] = ["EVENT_LOOP", "STOP_LOOP"];
+                if ZERO_ARG_TERMINAL_CMDS.contains(&uname.as_str()) || ZERO_ARG_OUTPUT_CMDS.contains(&uname.as_str()) || ZERO_ARG_EVENT_CMDS.contains(&uname.as_str()) {
                     // Optionally accept empty parentheses: NAME or NAME()
                     if self.match_k(TokenKind::LParen) {
                         // For these commands, only empty parens are allowed in statement form
@@ -1203,6 +1273,36 @@ impl Parser {
     }
 
     // Accept ';' OR EOF after a statement
+    // PRINT USING mask$; v1, v2 ... becomes USING$(mask$, v1, v2 ...). Values are separated by
+    // commas or semicolons, and a semicolon at the end of the line is allowed.
+    fn parse_print_using(&mut self) -> Result<Option<Expr>> {
+        let is_using = self.tokens.get(self.i).is_some_and(|t| t.kind == TokenKind::Ident && t.lexeme.eq_ignore_ascii_case("USING"));
+        if !is_using { return Ok(None); }
+        let _ = self.next();
+        let mut args = vec![self.parse_expr_bp(0)?];
+        if !self.at_written_semicolon(0) {
+            return Err(BasilError(format!("parse error at line {}: expected ';' after the PRINT USING format", self.peek_line())));
+        }
+        let _ = self.next();
+        loop {
+            args.push(self.parse_expr_bp(0)?);
+            if self.match_k(TokenKind::Comma) { continue; }
+            if !self.at_written_semicolon(0) { break; }
+            let after = self.tokens.get(self.i + 1).map(|t| t.kind.clone());
+            let more = matches!(after, Some(TokenKind::Ident | TokenKind::Number | TokenKind::String | TokenKind::LParen
+                | TokenKind::Minus | TokenKind::Not | TokenKind::True | TokenKind::False | TokenKind::Null | TokenKind::LBracket | TokenKind::LBrace));
+            let line_end = matches!(after, None | Some(TokenKind::Eof)) || (after == Some(TokenKind::Semicolon) && !self.at_written_semicolon(1));
+            if more || line_end { let _ = self.next(); }
+            if !more { break; }
+        }
+        Ok(Some(Expr::Call { callee: Box::new(Expr::Var("USING$".into())), args }))
+    }
+
+    // A ';' in the source, as opposed to the one the lexer puts at the end of each line
+    fn at_written_semicolon(&self, ahead: usize) -> bool {
+        self.tokens.get(self.i + ahead).is_some_and(|t| t.kind == TokenKind::Semicolon && t.lexeme == ";")
+    }
+
     fn terminate_stmt(&mut self) -> Result<()> {
         if self.match_k(TokenKind::Semicolon) { return Ok(()); }
         if self.check(TokenKind::Eof) { return Ok(()); }
@@ -1254,6 +1354,14 @@ impl Parser {
         }
 
         loop {
+            // x INSTANCEOF ClassName binds like a comparison
+            if self.check_word("INSTANCEOF") {
+                if 50 < min_bp { break; }
+                self.next();
+                let class_name = self.expect_ident()?;
+                lhs = Expr::InstanceOf { target: Box::new(lhs), class_name };
+                continue;
+            }
             // binary operator?
             let (op, lbp, rbp) = if let Some((op, lb, rb)) = self.peek_binop_bp() { (op, lb, rb) } else { break };
             if lbp < min_bp { break; }
@@ -1296,7 +1404,12 @@ impl Parser {
             }
             Some(TokenKind::Number) => {
                 let t = self.next().unwrap();
-                if let Some(Literal::Num(n)) = t.literal { Ok(Expr::Number(n)) } else { Err(BasilError(format!("parse error at line {}: number literal missing", t.line))) }
+                match t.literal {
+                    Some(Literal::Num(n)) => Ok(Expr::Number(n)),
+                    Some(Literal::Int(i)) => Ok(Expr::Int(i)),
+                    Some(Literal::Dec(d)) => Ok(Expr::Decimal(d)),
+                    _ => Err(BasilError(format!("parse error at line {}: number literal missing", t.line))),
+                }
             }
             Some(TokenKind::String) => {
                 let t = self.next().unwrap();
@@ -1304,6 +1417,7 @@ impl Parser {
             }
             Some(TokenKind::True) => { let _ = self.next().unwrap(); Ok(Expr::Bool(true)) }
             Some(TokenKind::False) => { let _ = self.next().unwrap(); Ok(Expr::Bool(false)) }
+            Some(TokenKind::Null) => { let _ = self.next().unwrap(); Ok(Expr::Null) }
             Some(TokenKind::Author) => {
                 // Consume AUTHOR token
                 let _ = self.next().unwrap();
@@ -1445,6 +1559,54 @@ impl Parser {
         Ok(Stmt::Func { kind, name, params, body })
     }
 
+    fn parse_class(&mut self) -> Result<Stmt> {
+        let name = self.expect_ident()?;
+        let base = if self.match_word("EXTENDS") { Some(self.expect_ident()?) } else { None };
+        let mut fields: Vec<basil_ast::ClassField> = Vec::new();
+        let mut methods: Vec<basil_ast::ClassMethod> = Vec::new();
+        loop {
+            while self.match_k(TokenKind::Semicolon) {}
+            if self.check(TokenKind::End) {
+                let _ = self.next();
+                if !self.match_k(TokenKind::Class) { return Err(BasilError(format!("parse error at line {}: expected 'END CLASS'", self.peek_line()))); }
+                break;
+            }
+            if self.check(TokenKind::Eof) { return Err(BasilError(format!("parse error at line {}: unterminated CLASS {} ... END CLASS", self.peek_line(), name))); }
+            let private = self.match_word("PRIVATE");
+            if !private { let _ = self.match_word("PUBLIC"); }
+            if self.check(TokenKind::Func) {
+                let kw = self.next().unwrap();
+                let kind = if kw.lexeme.eq_ignore_ascii_case("SUB") { basil_ast::FuncKind::Sub } else { basil_ast::FuncKind::Func };
+                // SUB NEW: NEW is a keyword everywhere else
+                if self.check(TokenKind::New) {
+                    let t = self.tokens.get_mut(self.i).unwrap();
+                    t.kind = TokenKind::Ident;
+                }
+                match self.parse_func(kind)? {
+                    Stmt::Func { kind, name, params, body } => methods.push(basil_ast::ClassMethod { kind, name, params, body, private }),
+                    _ => unreachable!(),
+                }
+                continue;
+            }
+            // Field: [DIM] name [= expr]
+            let _ = self.match_k(TokenKind::Dim);
+            let fname = self.expect_ident()?;
+            let init = if self.match_k(TokenKind::Assign) { Some(self.parse_expr_bp(0)?) } else { None };
+            self.terminate_stmt()?;
+            fields.push(basil_ast::ClassField { name: fname, private, init });
+        }
+        self.terminate_stmt().ok();
+        Ok(Stmt::ClassDef { name, base, fields, methods })
+    }
+
+    // Contextual keyword written as an identifier (EXTENDS, PUBLIC, ...)
+    fn check_word(&self, w: &str) -> bool {
+        matches!(self.tokens.get(self.i), Some(t) if t.kind == TokenKind::Ident && t.lexeme.eq_ignore_ascii_case(w))
+    }
+    fn match_word(&mut self, w: &str) -> bool {
+        if self.check_word(w) { self.next(); true } else { false }
+    }
+
     fn peek_binop_bp(&self) -> Option<(BinOp, u8, u8)> {
         match self.peek_kind()? {
             // logical (lowest precedence)
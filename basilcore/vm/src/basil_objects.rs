@@ -1,18 +1,105 @@
+use std::collections::HashMap;
+
 use basil_common::{BasilError, Result};
-use basil_bytecode::{ObjectRef, Value};
+use basil_bytecode::{ObjectDescriptor, ObjectRef, Value};
+
+// Object types available to `NEW Type(...)` and `DIM x@ AS Type(...)`.
+//
+// The lean build ships no object libraries of its own; embedding hosts register their
+// Rust types through VM::registry_mut() before run(). Type names are case-insensitive.
+// A method named NEW in the descriptor declares the constructor: its arity is checked
+// before the factory runs. Without one, the constructor takes no arguments.
+
+pub type ObjectFactory = Box<dyn Fn(&[Value]) -> Result<ObjectRef>>;
+
+struct Entry {
+    descriptor: ObjectDescriptor,
+    factory: ObjectFactory,
+}
 
-// Minimal in-crate stub to remove external basil-objects dependency.
-// Objects are not supported in the lean Basic build.
 #[derive(Default)]
-pub struct Registry;
+pub struct Registry {
+    types: HashMap<String, Entry>,
+}
 
 impl Registry {
-    pub fn new() -> Self { Registry }
-    pub fn make(&self, _type_name: &str, _args: &[Value]) -> Result<ObjectRef> {
-        Err(BasilError("Object system is not available in this build".into()))
+    pub fn new() -> Self { Registry::default() }
+
+    // Register (or replace) a type.
+    pub fn register(&mut self, type_name: &str, descriptor: ObjectDescriptor, factory: ObjectFactory) {
+        self.types.insert(type_name.to_ascii_uppercase(), Entry { descriptor, factory });
+    }
+
+    pub fn contains(&self, type_name: &str) -> bool {
+        self.types.contains_key(&type_name.to_ascii_uppercase())
+    }
+
+    pub fn descriptor(&self, type_name: &str) -> Option<&ObjectDescriptor> {
+        self.types.get(&type_name.to_ascii_uppercase()).map(|e| &e.descriptor)
+    }
+
+    // Registered type names as given to register(), sorted.
+    pub fn type_names(&self) -> Vec<String> {
+        let mut v: Vec<String> = self.types.values().map(|e| e.descriptor.type_name.clone()).collect();
+        v.sort_by_key(|n| n.to_ascii_uppercase());
+        v
+    }
+
+    pub fn make(&self, type_name: &str, args: &[Value]) -> Result<ObjectRef> {
+        let entry = self.types.get(&type_name.to_ascii_uppercase())
+            .ok_or_else(|| BasilError(format!("Unknown object type: {}", type_name)))?;
+        let arity = entry.descriptor.methods.iter()
+            .find(|m| m.name.eq_ignore_ascii_case("NEW"))
+            .map(|m| m.arity as usize)
+            .unwrap_or(0);
+        if args.len() != arity {
+            return Err(BasilError(format!("{} expects {} constructor argument(s), got {}", entry.descriptor.type_name, arity, args.len())));
+        }
+        (entry.factory)(args)
     }
 }
 
 pub fn register_objects(_reg: &mut Registry) {
-    // no-op in lean build
+    // no built-in object libraries in the lean build
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use basil_bytecode::{BasicObject, MethodDesc};
+
+    struct Counter { n: i64 }
+    impl BasicObject for Counter {
+        fn type_name(&self) -> &str { "Counter" }
+        fn get_prop(&self, _name: &str) -> Result<Value> { Ok(Value::Int(self.n)) }
+        fn set_prop(&mut self, _name: &str, _v: Value) -> Result<()> { Ok(()) }
+        fn call(&mut self, _method: &str, _args: &[Value]) -> Result<Value> { Ok(Value::Null) }
+        fn descriptor(&self) -> ObjectDescriptor { counter_desc() }
+    }
+
+    fn counter_desc() -> ObjectDescriptor {
+        ObjectDescriptor {
+            type_name: "Counter".into(), version: "1.0".into(), summary: "test".into(), properties: Vec::new(),
+            methods: vec![MethodDesc { name: "NEW".into(), arity: 1, arg_names: vec!["start%".into()], return_type: "Counter".into() }],
+            examples: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn lookup_and_arity() {
+        let mut reg = Registry::new();
+        reg.register("Counter", counter_desc(), Box::new(|args: &[Value]| {
+            let n = match &args[0] { Value::Int(i) => *i, _ => 0 };
+            let obj: ObjectRef = Rc::new(RefCell::new(Counter { n }));
+            Ok(obj)
+        }));
+        assert!(reg.contains("COUNTER"));
+        assert_eq!(reg.type_names(), vec!["Counter".to_string()]);
+        let o = reg.make("counter", &[Value::Int(5)]).unwrap();
+        assert!(matches!(o.borrow().get_prop("N").unwrap(), Value::Int(5)));
+        assert!(reg.make("Counter", &[]).is_err());
+        assert!(reg.make("Nope", &[]).is_err());
+    }
 }
@@ -0,0 +1,273 @@
+// Byte strings and Unicode text helpers.
+//
+//   BYTES(x)                   from a string (UTF-8), a LIST of 0..255, other BYTES, or n zero bytes
+//   BYTES_TEXT$(b)             decode UTF-8, failing on invalid sequences
+//   BYTES_HEX$(b) / HEX_BYTES(h$)
+//   BYTES_BASE64$(b) / BASE64_BYTES(s$)
+//   READFILE_BYTES(path$)      FREAD_BYTES(fh%, n)
+//   UCHR$(code)  UASC%(s$ [, pos])  GRAPHEMES(s$)  GLEN%(s$)
+//
+// Strings count Unicode scalar values everywhere; BYTES count bytes. LEN, MID$, LEFT$,
+// RIGHT$, INSTR, b[i], FOR EACH and + work on BYTES byte by byte, and the file and
+// struct packing builtins read and write them without any text conversion. Grapheme
+// clusters follow the common cases of UAX #29: CR LF, combining marks, variation
+// selectors, emoji modifiers and ZWJ sequences, flag pairs and Hangul jamo.
+
+use std::rc::Rc;
+
+use basil_bytecode::Value;
+use basil_common::{BasilError, Result};
+
+const ZWJ: char = '\u{200D}';
+const B64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// The most bytes one builtin will set aside at once (1 GiB)
+pub const MAX_LEN: usize = 1 << 30;
+
+pub fn value(b: Vec<u8>) -> Value { Value::Bytes(Rc::from(b)) }
+
+// n zero bytes, or an error instead of aborting when n is too large to allocate
+pub fn zeroed(n: usize, who: &str) -> Result<Vec<u8>> {
+    if n > MAX_LEN { return Err(BasilError(format!("{}: {} bytes is more than the limit of {}", who, n, MAX_LEN))); }
+    let mut buf = Vec::new();
+    buf.try_reserve_exact(n).map_err(|_| BasilError(format!("{}: cannot allocate {} bytes", who, n)))?;
+    buf.resize(n, 0);
+    Ok(buf)
+}
+
+fn int_of(v: &Value) -> Option<i64> {
+    match v {
+        Value::Int(i) => Some(*i),
+        Value::Num(n) if n.is_finite() => Some(n.trunc() as i64),
+        Value::Bool(b) => Some(*b as i64),
+        _ => None,
+    }
+}
+
+// BYTES(x); `ty` is the argument's TYPE$ for the error message
+pub fn from_value(v: &Value, ty: &str) -> Result<Vec<u8>> {
+    match v {
+        Value::Bytes(b) => Ok(b.to_vec()),
+        Value::Str(s) => Ok(s.as_bytes().to_vec()),
+        Value::List(items) => items.borrow().iter().enumerate().map(|(i, it)| match int_of(it) {
+            Some(n) if (0..=255).contains(&n) => Ok(n as u8),
+            _ => Err(BasilError(format!("BYTES: list item {} is not a byte (0 to 255)", i + 1))),
+        }).collect(),
+        other => match int_of(other) {
+            Some(n) if n >= 0 => zeroed(usize::try_from(n).unwrap_or(usize::MAX), "BYTES"),
+            Some(n) => Err(BasilError(format!("BYTES: size must not be negative, got {}", n))),
+            None => Err(BasilError(format!("BYTES expects a STRING, LIST or size, got {}", ty))),
+        },
+    }
+}
+
+// Arguments that take raw data: BYTES as they are, anything else as its UTF-8 text
+pub fn data_of(v: &Value) -> Vec<u8> {
+    match v {
+        Value::Bytes(b) => b.to_vec(),
+        Value::Str(s) => s.as_bytes().to_vec(),
+        other => other.to_string().into_bytes(),
+    }
+}
+
+pub fn text(b: &[u8]) -> Result<String> {
+    String::from_utf8(b.to_vec()).map_err(|e| {
+        BasilError(format!("BYTES_TEXT$: invalid UTF-8 at byte {}", e.utf8_error().valid_up_to() + 1))
+    })
+}
+
+pub fn to_hex(b: &[u8]) -> String {
+    b.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+pub fn from_hex(s: &str) -> Result<Vec<u8>> {
+    let digits: Vec<char> = s.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(BasilError("HEX_BYTES: odd number of hex digits".into()));
+    }
+    digits.chunks(2).map(|pair| {
+        let hi = pair[0].to_digit(16);
+        let lo = pair[1].to_digit(16);
+        match (hi, lo) {
+            (Some(h), Some(l)) => Ok((h * 16 + l) as u8),
+            _ => {
+                let bad = if hi.is_none() { pair[0] } else { pair[1] };
+                Err(BasilError(format!("HEX_BYTES: invalid hex digit '{}'", bad)))
+            }
+        }
+    }).collect()
+}
+
+pub fn to_base64(b: &[u8]) -> String {
+    let mut out = String::with_capacity(b.len().div_ceil(3) * 4);
+    for chunk in b.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() { out.push(B64[((n >> (18 - 6 * i)) & 63) as usize] as char); }
+            else { out.push('='); }
+        }
+    }
+    out
+}
+
+// Standard or URL-safe alphabet; padding and whitespace are optional
+pub fn from_base64(s: &str) -> Result<Vec<u8>> {
+    let bad = || BasilError("BASE64_BYTES: invalid Base64 text".into());
+    let body = s.trim_end_matches(|c: char| c == '=' || c.is_ascii_whitespace());
+    let mut out = Vec::with_capacity(body.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0u32);
+    for c in body.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let d = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(bad()),
+        };
+        acc = acc << 6 | d as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    // A lone trailing character cannot encode a whole byte
+    if bits >= 6 { return Err(bad()); }
+    Ok(out)
+}
+
+// LEN, MID$, LEFT$, RIGHT$ and INSTR on BYTES, by byte offset
+pub fn string_builtin(bid: u8, b: &[u8], args: &[Value]) -> Result<Value> {
+    let num = |i: usize, what: &str| -> Result<i64> {
+        int_of(&args[i]).ok_or_else(|| BasilError(format!("{} must be numeric", what)))
+    };
+    let len = b.len() as i64;
+    let sub = |from: i64, to: i64| value(b[from.clamp(0, len) as usize..to.clamp(from.clamp(0, len), len) as usize].to_vec());
+    match bid {
+        1 => {
+            if args.len() != 1 { return Err(BasilError("LEN expects 1 argument".into())); }
+            Ok(Value::Int(len))
+        }
+        2 => {
+            if !(args.len() == 2 || args.len() == 3) { return Err(BasilError("MID$ expects 2 or 3 arguments".into())); }
+            let start = num(1, "MID$ start")?.max(1) - 1;
+            let end = if args.len() == 3 { start.saturating_add(num(2, "MID$ length")?.max(0)) } else { len };
+            Ok(sub(start, end))
+        }
+        3 => {
+            if args.len() != 2 { return Err(BasilError("LEFT$ expects 2 arguments".into())); }
+            Ok(sub(0, num(1, "LEFT$ count")?.max(0)))
+        }
+        4 => {
+            if args.len() != 2 { return Err(BasilError("RIGHT$ expects 2 arguments".into())); }
+            Ok(sub(len - num(1, "RIGHT$ count")?.clamp(0, len), len))
+        }
+        _ => {
+            if !(args.len() == 2 || args.len() == 3) { return Err(BasilError("INSTR expects 2 or 3 arguments".into())); }
+            let needle = data_of(&args[1]);
+            let start = if args.len() == 3 { num(2, "INSTR start")?.clamp(0, len) as usize } else { 0 };
+            if needle.is_empty() { return Ok(Value::Int(start as i64)); }
+            let found = b[start..].windows(needle.len()).position(|w| w == needle.as_slice());
+            Ok(Value::Int(found.map(|i| (start + i) as i64).unwrap_or(0)))
+        }
+    }
+}
+
+// b[i], 1-based
+pub fn index(b: &[u8], idx: i64) -> Result<Value> {
+    if idx <= 0 || idx as usize > b.len() {
+        return Err(BasilError(format!("Bytes index out of range: {}", idx)));
+    }
+    Ok(Value::Int(b[idx as usize - 1] as i64))
+}
+
+pub fn uchr(code: i64) -> Result<String> {
+    u32::try_from(code).ok().and_then(char::from_u32).map(|c| c.to_string())
+        .ok_or_else(|| BasilError(format!("UCHR$: {} is not a Unicode scalar value", code)))
+}
+
+// STRING$'s character code; same check as UCHR$
+pub fn code_char(code: i64, who: &str) -> Result<char> {
+    u32::try_from(code).ok().and_then(char::from_u32)
+        .ok_or_else(|| BasilError(format!("{}: {} is not a Unicode scalar value", who, code)))
+}
+
+// Code point of the character at 1-based `pos`, 0 past the end
+pub fn uasc(s: &str, pos: i64) -> Result<i64> {
+    if pos < 1 { return Err(BasilError(format!("UASC%: position must be 1 or more, got {}", pos))); }
+    Ok(s.chars().nth(pos as usize - 1).map(|c| c as i64).unwrap_or(0))
+}
+
+pub fn graphemes(s: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut prev: Option<char> = None;
+    // Regional indicators seen in a row, so flags pair up from the left
+    let mut flags = 0usize;
+    for (i, c) in s.char_indices() {
+        if let Some(p) = prev {
+            if !joins(p, c, flags) {
+                out.push(&s[start..i]);
+                start = i;
+            }
+        }
+        flags = if is_regional(c) { flags + 1 } else { 0 };
+        prev = Some(c);
+    }
+    if start < s.len() { out.push(&s[start..]); }
+    out
+}
+
+fn joins(prev: char, c: char, flags: usize) -> bool {
+    if prev == '\r' { return c == '\n'; }
+    if prev.is_control() || c.is_control() { return false; }
+    if is_extend(c) || c == ZWJ { return true; }
+    if prev == ZWJ { return is_pictographic(c); }
+    if is_regional(prev) && is_regional(c) { return flags % 2 == 1; }
+    matches!((jamo(prev), jamo(c)),
+        (Some(Jamo::L), Some(_))
+        | (Some(Jamo::V | Jamo::Lv), Some(Jamo::V | Jamo::T))
+        | (Some(Jamo::T | Jamo::Lvt), Some(Jamo::T)))
+}
+
+fn is_regional(c: char) -> bool { ('\u{1F1E6}'..='\u{1F1FF}').contains(&c) }
+
+fn is_extend(c: char) -> bool {
+    let u = c as u32;
+    // Indic scripts share one block layout: signs before the consonants, then vowel signs
+    // and virama, stress marks and the two vocalic vowel signs
+    if (0x0900..=0x0D7F).contains(&u) {
+        return matches!(u & 0x7F, 0x00..=0x03 | 0x3A..=0x3C | 0x3E..=0x4F | 0x51..=0x57 | 0x62..=0x63);
+    }
+    matches!(u,
+        0x0300..=0x036F | 0x0483..=0x0489 | 0x0591..=0x05BD | 0x05BF | 0x05C1..=0x05C2
+        | 0x05C4..=0x05C5 | 0x05C7 | 0x0610..=0x061A | 0x064B..=0x065F | 0x0670
+        | 0x06D6..=0x06DC | 0x06DF..=0x06E4 | 0x06E7..=0x06E8 | 0x06EA..=0x06ED
+        | 0x0E31 | 0x0E34..=0x0E3A | 0x0E47..=0x0E4E | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF
+        | 0x200C | 0x20D0..=0x20FF | 0x302A..=0x302F | 0x3099..=0x309A | 0xFE00..=0xFE0F
+        | 0xFE20..=0xFE2F | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F | 0xE0100..=0xE01EF)
+}
+
+fn is_pictographic(c: char) -> bool {
+    matches!(c as u32,
+        0x00A9 | 0x00AE | 0x203C | 0x2049 | 0x2122 | 0x2139 | 0x2194..=0x21AA
+        | 0x231A..=0x23FF | 0x24C2 | 0x25AA..=0x27BF | 0x2934..=0x2935 | 0x2B05..=0x2B55
+        | 0x3030 | 0x303D | 0x3297 | 0x3299 | 0x1F000..=0x1FAFF | 0x1FC00..=0x1FFFD)
+}
+
+// Hangul leading, vowel and trailing jamo, and precomposed syllables with and without a trailing one
+enum Jamo { L, V, T, Lv, Lvt }
+
+fn jamo(c: char) -> Option<Jamo> {
+    match c as u32 {
+        0x1100..=0x115F | 0xA960..=0xA97C => Some(Jamo::L),
+        0x1160..=0x11A7 | 0xD7B0..=0xD7C6 => Some(Jamo::V),
+        0x11A8..=0x11FF | 0xD7CB..=0xD7FB => Some(Jamo::T),
+        u @ 0xAC00..=0xD7A3 => Some(if (u - 0xAC00).is_multiple_of(28) { Jamo::Lv } else { Jamo::Lvt }),
+        _ => None,
+    }
+}
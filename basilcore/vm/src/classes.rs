@@ -0,0 +1,196 @@
+// Inline classes: CLASS Name [EXTENDS Base] ... END CLASS
+//
+// Op::MakeClass builds one Class per definition with the flattened field layout and
+// method table (inherited entries first, overrides replacing them). Instances only carry
+// their field values and share the table. Methods are ordinary Functions whose local
+// slot 0 is ME; the VM runs them (and field initializers and SUB NEW) as frames of the
+// calling VM. PRIVATE members are only reachable from methods of the declaring class.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use basil_bytecode::{BasicObject, Function, MethodDesc, ObjectDescriptor, PropDesc, Value};
+use basil_common::{BasilError, Result};
+
+pub struct Field {
+    pub name: String,
+    pub private: bool,
+    pub owner: Rc<str>,
+}
+
+#[derive(Clone)]
+pub struct Method {
+    pub func: Rc<Function>,
+    pub private: bool,
+    pub owner: Rc<str>,
+    // Declared parameter names (without ME), for DESCRIBE
+    pub params: Vec<String>,
+}
+
+pub struct Class {
+    pub name: String,
+    pub base: Option<Rc<Class>>,
+    pub fields: Vec<Field>,
+    index: HashMap<String, usize>,
+    methods: HashMap<String, Method>,
+    // Field initializers of the base classes and this class, base first
+    pub inits: Vec<Method>,
+}
+
+impl Class {
+    pub fn new(
+        name: String,
+        base: Option<Rc<Class>>,
+        fields: Vec<(String, bool)>,
+        methods: Vec<(Rc<Function>, bool, Vec<String>)>,
+        init: Option<Rc<Function>>,
+    ) -> Result<Class> {
+        let owner: Rc<str> = Rc::from(name.to_ascii_uppercase());
+        let mut layout: Vec<Field> = Vec::new();
+        let mut table: HashMap<String, Method> = HashMap::new();
+        let mut inits = Vec::new();
+        if let Some(b) = &base {
+            for f in &b.fields { layout.push(Field { name: f.name.clone(), private: f.private, owner: f.owner.clone() }); }
+            table.extend(b.methods.iter().map(|(k, m)| (k.clone(), m.clone())));
+            inits.extend(b.inits.iter().cloned());
+        }
+        let mut index: HashMap<String, usize> = layout.iter().enumerate().map(|(i, f)| (f.name.to_ascii_uppercase(), i)).collect();
+        for (fname, private) in fields {
+            let key = fname.to_ascii_uppercase();
+            if index.contains_key(&key) {
+                return Err(BasilError(format!("CLASS {}: field {} is already defined", name, fname)));
+            }
+            index.insert(key, layout.len());
+            layout.push(Field { name: fname, private, owner: owner.clone() });
+        }
+        for (func, private, params) in methods {
+            let key = func.name.as_deref().unwrap_or_default().to_ascii_uppercase();
+            if index.contains_key(&key) {
+                return Err(BasilError(format!("CLASS {}: {} is both a field and a method", name, key)));
+            }
+            table.insert(key, Method { func, private, owner: owner.clone(), params });
+        }
+        if let Some(func) = init { inits.push(Method { func, private: false, owner, params: Vec::new() }); }
+        Ok(Class { name, base, fields: layout, index, methods: table, inits })
+    }
+
+    // Slot of a field, enforcing PRIVATE for callers outside the declaring class.
+    pub fn field(&self, name: &str, caller: Option<&str>) -> Result<usize> {
+        let i = *self.index.get(&name.to_ascii_uppercase())
+            .ok_or_else(|| BasilError(format!("{} has no field {}", self.name, name)))?;
+        let f = &self.fields[i];
+        if f.private && caller != Some(&*f.owner) {
+            return Err(BasilError(format!("{}.{} is private", self.name, f.name)));
+        }
+        Ok(i)
+    }
+
+    pub fn method(&self, name: &str, caller: Option<&str>) -> Result<&Method> {
+        let m = self.methods.get(&name.to_ascii_uppercase())
+            .ok_or_else(|| BasilError(format!("{} has no method {}", self.name, name)))?;
+        if m.private && caller != Some(&*m.owner) {
+            return Err(BasilError(format!("{}.{} is private", self.name, name)));
+        }
+        Ok(m)
+    }
+
+    pub fn constructor(&self) -> Option<&Method> { self.methods.get("NEW") }
+
+    // Same class or a subclass of `other`.
+    pub fn is_a(self: &Rc<Class>, other: &Rc<Class>) -> bool {
+        let mut cur = Some(self);
+        while let Some(c) = cur {
+            if Rc::ptr_eq(c, other) { return true; }
+            cur = c.base.as_ref();
+        }
+        false
+    }
+
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.base.as_ref().is_some_and(|b| b.is_named(name))
+    }
+
+    fn descriptor(&self) -> ObjectDescriptor {
+        let properties = self.fields.iter().filter(|f| !f.private)
+            .map(|f| PropDesc { name: f.name.clone(), type_name: suffix_type(&f.name).into(), readable: true, writable: true })
+            .collect();
+        let mut methods: Vec<MethodDesc> = self.methods.values().filter(|m| !m.private)
+            .map(|m| {
+                let name = m.func.name.clone().unwrap_or_default();
+                MethodDesc { return_type: suffix_type(&name).into(), name, arity: m.func.arity.saturating_sub(1), arg_names: m.params.clone() }
+            })
+            .collect();
+        methods.sort_by_key(|m| m.name.to_ascii_uppercase());
+        let summary = match &self.base { Some(b) => format!("CLASS {} EXTENDS {}", self.name, b.name), None => format!("CLASS {}", self.name) };
+        ObjectDescriptor { type_name: self.name.clone(), version: "1.0".into(), summary, properties, methods, examples: Vec::new() }
+    }
+}
+
+fn suffix_type(name: &str) -> &'static str {
+    match name.chars().last() {
+        Some('$') => "STRING",
+        Some('%') => "INTEGER",
+        Some('@') => "OBJECT",
+        _ => "ANY",
+    }
+}
+
+// Value of a field before its initializer (if any) runs.
+fn default_for(name: &str) -> Value {
+    match name.chars().last() {
+        Some('$') => Value::Str(String::new()),
+        Some('%') => Value::Int(0),
+        Some('@') => Value::Null,
+        _ => Value::Num(0.0),
+    }
+}
+
+// The class itself, stored in the global named after it.
+pub struct ClassRef(pub Rc<Class>);
+
+impl BasicObject for ClassRef {
+    fn type_name(&self) -> &str { "CLASS" }
+    fn get_prop(&self, name: &str) -> Result<Value> {
+        Err(BasilError(format!("CLASS {} has no property {}; use NEW {}(...)", self.0.name, name, self.0.name)))
+    }
+    fn set_prop(&mut self, name: &str, _v: Value) -> Result<()> {
+        Err(BasilError(format!("CLASS {} has no property {}", self.0.name, name)))
+    }
+    fn call(&mut self, method: &str, _args: &[Value]) -> Result<Value> {
+        Err(BasilError(format!("{}.{} needs an instance; use NEW {}(...)", self.0.name, method, self.0.name)))
+    }
+    fn descriptor(&self) -> ObjectDescriptor { self.0.descriptor() }
+    fn as_any(&self) -> Option<&dyn std::any::Any> { Some(self) }
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: Vec<Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        let fields = class.fields.iter().map(|f| default_for(&f.name)).collect();
+        Instance { class, fields }
+    }
+}
+
+// Host code sees the public fields; methods need the VM (see Op::CallMethod).
+impl BasicObject for Instance {
+    fn type_name(&self) -> &str { &self.class.name }
+    fn get_prop(&self, name: &str) -> Result<Value> {
+        let i = self.class.field(name, None)?;
+        Ok(self.fields[i].clone())
+    }
+    fn set_prop(&mut self, name: &str, v: Value) -> Result<()> {
+        let i = self.class.field(name, None)?;
+        self.fields[i] = v;
+        Ok(())
+    }
+    fn call(&mut self, method: &str, _args: &[Value]) -> Result<Value> {
+        Err(BasilError(format!("{}.{} can only be called by the VM", self.class.name, method)))
+    }
+    fn descriptor(&self) -> ObjectDescriptor { self.class.descriptor() }
+    fn as_any(&self) -> Option<&dyn std::any::Any> { Some(self) }
+    fn as_any_mut(&mut self) -> Option<&mut dyn std::any::Any> { Some(self) }
+}
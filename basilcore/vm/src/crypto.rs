@@ -0,0 +1,307 @@
+// Hashing, message authentication and OS randomness.
+//
+//   SHA256$(data [, format$])  SHA1$(...)  MD5$(...)     digest as "hex" (default), "base64" or "base64url"
+//   HMAC_SHA256$(key, data [, format$])
+//   SECURE_EQ%(a, b)                 compares without stopping at the first difference
+//   RANDOM_BYTES$(n [, format$])     n bytes from the OS generator, hex by default
+//   UUID$()                          random (version 4) UUID
+//   PASSWORD_HASH$(password$ [, iterations])  PASSWORD_VERIFY%(password$, hash$)
+//
+// Data arguments are BYTES as they are or strings as UTF-8. Passwords are stored as
+// PBKDF2-HMAC-SHA256 in the PHC string format, $pbkdf2-sha256$i=<n>$<salt>$<hash>, so the
+// iteration count can be raised later without breaking stored hashes. SHA-1 and MD5 are
+// here for checksums and old protocols; use SHA-256 for anything new.
+
+use basil_common::{BasilError, Result};
+
+use crate::bytes;
+
+pub const PASSWORD_ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+
+// A Merkle-Damgard hash over 64-byte blocks
+trait Algo: Clone {
+    const BIG_ENDIAN: bool;
+    fn new() -> Self;
+    fn compress(&mut self, block: &[u8]);
+    fn output(&self) -> Vec<u8>;
+}
+
+#[derive(Clone)]
+struct Hasher<A: Algo> {
+    algo: A,
+    block: Vec<u8>,
+    len: u64,
+}
+
+impl<A: Algo> Hasher<A> {
+    fn new() -> Self { Hasher { algo: A::new(), block: Vec::with_capacity(64), len: 0 } }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        while !data.is_empty() {
+            let take = (64 - self.block.len()).min(data.len());
+            self.block.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.block.len() == 64 {
+                self.algo.compress(&self.block);
+                self.block.clear();
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let bits = self.len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.block.len() != 56 { self.update(&[0]); }
+        self.update(&if A::BIG_ENDIAN { bits.to_be_bytes() } else { bits.to_le_bytes() });
+        self.algo.output()
+    }
+}
+
+fn digest<A: Algo>(data: &[u8]) -> Vec<u8> {
+    let mut h = Hasher::<A>::new();
+    h.update(data);
+    h.finish()
+}
+
+#[derive(Clone)]
+struct Sha256([u32; 8]);
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+impl Algo for Sha256 {
+    const BIG_ENDIAN: bool = true;
+
+    fn new() -> Self {
+        Sha256([0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19])
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 64];
+        for (i, c) in block.chunks(4).enumerate() { w[i] = u32::from_be_bytes([c[0], c[1], c[2], c[3]]); }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.0;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g; g = f; f = e; e = d.wrapping_add(t1);
+            d = c; c = b; b = a; a = t1.wrapping_add(t2);
+        }
+        for (s, v) in self.0.iter_mut().zip([a, b, c, d, e, f, g, h]) { *s = s.wrapping_add(v); }
+    }
+
+    fn output(&self) -> Vec<u8> { self.0.iter().flat_map(|w| w.to_be_bytes()).collect() }
+}
+
+#[derive(Clone)]
+struct Sha1([u32; 5]);
+
+impl Algo for Sha1 {
+    const BIG_ENDIAN: bool = true;
+
+    fn new() -> Self { Sha1([0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0]) }
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 80];
+        for (i, c) in block.chunks(4).enumerate() { w[i] = u32::from_be_bytes([c[0], c[1], c[2], c[3]]); }
+        for i in 16..80 { w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1); }
+        let [mut a, mut b, mut c, mut d, mut e] = self.0;
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*wi);
+            e = d; d = c; c = b.rotate_left(30); b = a; a = t;
+        }
+        for (s, v) in self.0.iter_mut().zip([a, b, c, d, e]) { *s = s.wrapping_add(v); }
+    }
+
+    fn output(&self) -> Vec<u8> { self.0.iter().flat_map(|w| w.to_be_bytes()).collect() }
+}
+
+#[derive(Clone)]
+struct Md5([u32; 4]);
+
+const MD5_SHIFT: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+impl Algo for Md5 {
+    const BIG_ENDIAN: bool = false;
+
+    fn new() -> Self { Md5([0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476]) }
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut m = [0u32; 16];
+        for (i, c) in block.chunks(4).enumerate() { m[i] = u32::from_le_bytes([c[0], c[1], c[2], c[3]]); }
+        let [mut a, mut b, mut c, mut d] = self.0;
+        for i in 0..64 {
+            let (f, g) = match i {
+                0..=15 => ((b & c) | (!b & d), i),
+                16..=31 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                32..=47 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            // K[i] is the integer part of |sin(i + 1)| * 2^32
+            let k = (((i + 1) as f64).sin().abs() * 4_294_967_296.0) as u32;
+            let f = f.wrapping_add(a).wrapping_add(k).wrapping_add(m[g]);
+            a = d; d = c; c = b;
+            b = b.wrapping_add(f.rotate_left(MD5_SHIFT[i / 16 * 4 + i % 4]));
+        }
+        for (s, v) in self.0.iter_mut().zip([a, b, c, d]) { *s = s.wrapping_add(v); }
+    }
+
+    fn output(&self) -> Vec<u8> { self.0.iter().flat_map(|w| w.to_le_bytes()).collect() }
+}
+
+pub fn sha256(data: &[u8]) -> Vec<u8> { digest::<Sha256>(data) }
+
+pub fn sha1(data: &[u8]) -> Vec<u8> { digest::<Sha1>(data) }
+
+pub fn md5(data: &[u8]) -> Vec<u8> { digest::<Md5>(data) }
+
+// HMAC-SHA256 with the padded key already absorbed, so each use costs only the message
+#[derive(Clone)]
+struct Hmac {
+    inner: Hasher<Sha256>,
+    outer: Hasher<Sha256>,
+}
+
+impl Hmac {
+    fn new(key: &[u8]) -> Self {
+        let mut k = if key.len() > 64 { sha256(key) } else { key.to_vec() };
+        k.resize(64, 0);
+        let mut inner = Hasher::new();
+        inner.update(&k.iter().map(|b| b ^ 0x36).collect::<Vec<_>>());
+        let mut outer = Hasher::new();
+        outer.update(&k.iter().map(|b| b ^ 0x5c).collect::<Vec<_>>());
+        Hmac { inner, outer }
+    }
+
+    fn mac(&self, data: &[u8]) -> Vec<u8> {
+        let mut inner = self.inner.clone();
+        inner.update(data);
+        let mut outer = self.outer.clone();
+        outer.update(&inner.finish());
+        outer.finish()
+    }
+}
+
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> { Hmac::new(key).mac(data) }
+
+pub fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    let prf = Hmac::new(password);
+    let mut out = Vec::with_capacity(len);
+    let mut block = 1u32;
+    while out.len() < len {
+        let mut u = prf.mac(&[salt, &block.to_be_bytes()].concat());
+        let mut t = u.clone();
+        for _ in 1..iterations {
+            u = prf.mac(&u);
+            for (x, y) in t.iter_mut().zip(&u) { *x ^= y; }
+        }
+        out.extend_from_slice(&t);
+        block += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+// Equal length and content, looking at every byte whatever the earlier ones held
+pub fn secure_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn encode(func: &str, data: &[u8], format: &str) -> Result<String> {
+    match format.to_ascii_lowercase().as_str() {
+        "hex" => Ok(bytes::to_hex(data)),
+        "base64" => Ok(bytes::to_base64(data)),
+        "base64url" => Ok(base64url(data)),
+        _ => Err(BasilError(format!("{}: unknown output format \"{}\" (use hex, base64 or base64url)", func, format))),
+    }
+}
+
+fn base64url(data: &[u8]) -> String {
+    bytes::to_base64(data).trim_end_matches('=').replace('+', "-").replace('/', "_")
+}
+
+pub fn random_bytes(n: usize) -> Result<Vec<u8>> {
+    let mut buf = bytes::zeroed(n, "RANDOM_BYTES$")?;
+    os_random(&mut buf).map_err(|e| BasilError(format!("cannot read the system random generator: {}", e)))?;
+    Ok(buf)
+}
+
+#[cfg(unix)]
+fn os_random(buf: &mut [u8]) -> std::io::Result<()> {
+    use std::io::Read;
+    std::fs::File::open("/dev/urandom")?.read_exact(buf)
+}
+
+#[cfg(windows)]
+fn os_random(buf: &mut [u8]) -> std::io::Result<()> {
+    #[link(name = "advapi32")]
+    extern "system" {
+        #[link_name = "SystemFunction036"]
+        fn rtl_gen_random(buf: *mut u8, len: u32) -> u8;
+    }
+    for chunk in buf.chunks_mut(u32::MAX as usize) {
+        // SAFETY: the pointer and length describe a live, writable slice
+        if unsafe { rtl_gen_random(chunk.as_mut_ptr(), chunk.len() as u32) } == 0 {
+            return Err(std::io::Error::other("RtlGenRandom failed"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn os_random(_buf: &mut [u8]) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "no system random generator on this platform"))
+}
+
+pub fn uuid_v4() -> Result<String> {
+    let mut b = random_bytes(16)?;
+    b[6] = (b[6] & 0x0f) | 0x40;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    let h = bytes::to_hex(&b);
+    Ok(format!("{}-{}-{}-{}-{}", &h[0..8], &h[8..12], &h[12..16], &h[16..20], &h[20..32]))
+}
+
+pub fn password_hash(password: &str, iterations: u32) -> Result<String> {
+    if iterations == 0 { return Err(BasilError("PASSWORD_HASH$: iterations must be at least 1".into())); }
+    let salt = random_bytes(SALT_LEN)?;
+    let hash = pbkdf2_sha256(password.as_bytes(), &salt, iterations, 32);
+    Ok(format!("$pbkdf2-sha256$i={}${}${}", iterations, phc_b64(&salt), phc_b64(&hash)))
+}
+
+pub fn password_verify(password: &str, stored: &str) -> Result<bool> {
+    let bad = || BasilError("PASSWORD_VERIFY%: not a hash from PASSWORD_HASH$".into());
+    let parts: Vec<&str> = stored.split('$').collect();
+    let [_, "pbkdf2-sha256", iters, salt, hash] = parts[..] else { return Err(bad()) };
+    let iterations: u32 = iters.strip_prefix("i=").and_then(|n| n.parse().ok()).filter(|&n| n > 0).ok_or_else(bad)?;
+    let salt = bytes::from_base64(salt).map_err(|_| bad())?;
+    let hash = bytes::from_base64(hash).map_err(|_| bad())?;
+    if hash.is_empty() { return Err(bad()); }
+    Ok(secure_eq(&pbkdf2_sha256(password.as_bytes(), &salt, iterations, hash.len()), &hash))
+}
+
+// PHC strings use standard Base64 without padding
+fn phc_b64(data: &[u8]) -> String { bytes::to_base64(data).trim_end_matches('=').to_string() }
@@ -0,0 +1,495 @@
+// CSV without a feature flag: a streaming reader over FOPEN handles and a writer.
+//
+//   CSV_READER(fh% [, opts])         a reader; FOR EACH row IN r@ (or r@._NEXT()) gives one
+//                                    row at a time, NULL at the end
+//   CSV_READ_ALL(fh% | path$ [, opts])  every remaining row as a LIST
+//   CSV_WRITE_ROW(fh%, row [, opts]) write one LIST or DICT row and its line end
+//   CSV_FORMAT$(row [, opts])        the same row as text, without the line end
+//   CSV_PARSE$(text$) / CSV_WRITE$(json$)  the older JSON-string forms (header row, strings)
+//
+// opts is a DICT: delimiter (","), quote ("\"", "" for none), escape (none, so quotes inside
+// quotes are doubled; "\\" reads and writes \" instead), header and quoting. header TRUE
+// (the default) takes names from the first row and gives DICTs; FALSE gives LISTs; a LIST
+// of names gives DICTs and reads the first row as data. On writing, a DICT row takes its
+// columns from a header LIST or else its own keys, and the header line is written first
+// when the file is still empty. quoting is MINIMAL (only fields that need it), ALL,
+// NONNUMERIC (everything but numbers) or NONE (escape instead; an error without one).
+//
+// Fields are read as strings; quoted fields may span lines, \r\n and \n both end a row,
+// blank lines are skipped and a UTF-8 BOM before the header is dropped. The reader takes
+// the file in 64 KiB chunks that it keeps with the handle between rows, so memory stays the
+// same for any file size and every byte is read once. Before any other file builtin uses
+// the handle the unused part is given back, so FTELL&/FREADLINE$ carry on after the last row.
+
+use std::cell::RefCell;
+use std::io::{Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+use basil_bytecode::{BasicObject, Dict, MethodDesc, ObjectDescriptor, ObjectRef, PropDesc, Value};
+use basil_common::{BasilError, Result};
+
+use crate::providers::{FileStream, OpenMode};
+use crate::VM;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Quoting {
+    Minimal,
+    All,
+    NonNumeric,
+    None,
+}
+
+#[derive(Clone)]
+pub enum Header {
+    FirstRow,
+    Absent,
+    Names(Vec<String>),
+}
+
+#[derive(Clone)]
+pub struct Options {
+    pub delimiter: u8,
+    pub quote: Option<u8>,
+    pub escape: Option<u8>,
+    pub header: Header,
+    pub quoting: Quoting,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { delimiter: b',', quote: Some(b'"'), escape: None, header: Header::FirstRow, quoting: Quoting::Minimal }
+    }
+}
+
+impl Options {
+    pub fn from_value(v: Option<&Value>, who: &str) -> Result<Options> {
+        let mut o = Options::default();
+        let map = match v {
+            None | Some(Value::Null) => return Ok(o),
+            Some(Value::Dict(d)) => d.borrow().clone(),
+            Some(_) => return Err(BasilError(format!("{}: options must be a DICT", who))),
+        };
+        for (key, val) in map.iter() {
+            match key.to_ascii_lowercase().as_str() {
+                "delimiter" => o.delimiter = char_opt(val, who, "delimiter")?.ok_or_else(|| BasilError(format!("{}: delimiter cannot be empty", who)))?,
+                "quote" => o.quote = char_opt(val, who, "quote")?,
+                "escape" => o.escape = char_opt(val, who, "escape")?,
+                "header" => o.header = match val {
+                    Value::List(items) => Header::Names(items.borrow().iter().map(field_text).collect()),
+                    Value::Bool(true) => Header::FirstRow,
+                    Value::Bool(false) | Value::Null => Header::Absent,
+                    Value::Int(i) => if *i != 0 { Header::FirstRow } else { Header::Absent },
+                    Value::Num(n) => if *n != 0.0 { Header::FirstRow } else { Header::Absent },
+                    _ => return Err(BasilError(format!("{}: header must be TRUE, FALSE or a LIST of names", who))),
+                },
+                "quoting" => o.quoting = match field_text(val).to_ascii_uppercase().as_str() {
+                    "MINIMAL" => Quoting::Minimal,
+                    "ALL" => Quoting::All,
+                    "NONNUMERIC" => Quoting::NonNumeric,
+                    "NONE" => Quoting::None,
+                    other => return Err(BasilError(format!("{}: unknown quoting \"{}\" (use MINIMAL, ALL, NONNUMERIC or NONE)", who, other))),
+                },
+                other => return Err(BasilError(format!("{}: unknown option \"{}\"", who, other))),
+            }
+        }
+        if o.quote == Some(o.delimiter) || o.escape == Some(o.delimiter) {
+            return Err(BasilError(format!("{}: delimiter must differ from quote and escape", who)));
+        }
+        Ok(o)
+    }
+}
+
+fn char_opt(v: &Value, who: &str, name: &str) -> Result<Option<u8>> {
+    let s = field_text(v);
+    match s.as_bytes() {
+        [] => Ok(None),
+        [b] if b.is_ascii() && *b != b'\n' && *b != b'\r' => Ok(Some(*b)),
+        _ => Err(BasilError(format!("{}: {} must be a single ASCII character, got \"{}\"", who, name, s))),
+    }
+}
+
+fn field_text(v: &Value) -> String {
+    match v {
+        Value::Str(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+// One record from `next` (a byte source), or None at the end of the input. Blank lines are
+// skipped.
+pub fn read_record(next: &mut impl FnMut() -> Result<Option<u8>>, o: &Options) -> Result<Option<Vec<String>>> {
+    let mut fields: Vec<String> = Vec::new();
+    let mut field: Vec<u8> = Vec::new();
+    let (mut in_quotes, mut quoted, mut started) = (false, false, false);
+    let mut pending: Option<Option<u8>> = None;
+    let take = |field: &mut Vec<u8>| String::from_utf8_lossy(&std::mem::take(field)).into_owned();
+    loop {
+        let b = match pending.take() { Some(p) => p, None => next()? };
+        let Some(b) = b else {
+            if in_quotes { return Err(BasilError("unterminated quoted field".into())); }
+            if !started { return Ok(None); }
+            fields.push(take(&mut field));
+            return Ok(Some(fields));
+        };
+        started = true;
+        if in_quotes {
+            if Some(b) == o.escape && o.escape != o.quote {
+                let n = next()?.ok_or_else(|| BasilError("escape character at the end of the input".into()))?;
+                field.push(n);
+            } else if Some(b) == o.quote {
+                let n = next()?;
+                if n == o.quote { field.push(b); } else { in_quotes = false; pending = Some(n); }
+            } else {
+                field.push(b);
+            }
+            continue;
+        }
+        let line_end = match b {
+            b'\n' => true,
+            b'\r' => {
+                let n = next()?;
+                if n == Some(b'\n') { true } else { pending = Some(n); false }
+            }
+            _ => false,
+        };
+        if line_end {
+            if fields.is_empty() && field.is_empty() && !quoted {
+                started = false;
+                continue;
+            }
+            fields.push(take(&mut field));
+            return Ok(Some(fields));
+        }
+        if b == o.delimiter {
+            fields.push(take(&mut field));
+            quoted = false;
+        } else if Some(b) == o.quote && field.is_empty() && !quoted {
+            in_quotes = true;
+            quoted = true;
+        } else if Some(b) == o.escape {
+            match next()? {
+                Some(n) => field.push(n),
+                None => return Err(BasilError("escape character at the end of the input".into())),
+            }
+        } else {
+            field.push(b);
+        }
+    }
+}
+
+// Every record of `text`
+pub fn parse_text(text: &str, o: &Options) -> Result<Vec<Vec<String>>> {
+    let mut bytes = text.bytes();
+    let mut next = || Ok(bytes.next());
+    let mut rows = Vec::new();
+    while let Some(r) = read_record(&mut next, o)? { rows.push(r); }
+    Ok(rows)
+}
+
+const CHUNK: usize = 64 * 1024;
+
+// Bytes read from a file ahead of the rows handed out so far
+#[derive(Default)]
+pub struct ReadAhead {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl ReadAhead {
+    fn byte(&mut self, file: &mut dyn FileStream) -> Result<Option<u8>> {
+        if self.pos == self.buf.len() {
+            self.buf.resize(CHUNK, 0);
+            self.pos = 0;
+            let n = match file.read(&mut self.buf) {
+                Ok(n) => n,
+                Err(e) => { self.buf.clear(); return Err(BasilError(e.to_string())); }
+            };
+            self.buf.truncate(n);
+            if n == 0 { return Ok(None); }
+        }
+        self.pos += 1;
+        Ok(Some(self.buf[self.pos - 1]))
+    }
+
+    // Put the file back just after the last byte used
+    pub fn give_back(&mut self, file: &mut dyn FileStream) -> Result<()> {
+        let unread = (self.buf.len() - self.pos) as i64;
+        self.buf.clear();
+        self.pos = 0;
+        if unread > 0 { file.seek(SeekFrom::Current(-unread)).map_err(|e| BasilError(e.to_string()))?; }
+        Ok(())
+    }
+}
+
+// The row as the options shape it: a LIST, or a DICT keyed by the header names. Missing
+// fields are empty strings; more fields than names is an error.
+fn shape(fields: Vec<String>, names: Option<&[String]>, row: i64) -> Result<Value> {
+    let Some(names) = names else {
+        return Ok(Value::List(Rc::new(RefCell::new(fields.into_iter().map(Value::Str).collect()))));
+    };
+    if fields.len() > names.len() {
+        return Err(BasilError(format!("row {} has {} fields but the header has {}", row, fields.len(), names.len())));
+    }
+    let mut d = Dict::new();
+    let mut fields = fields.into_iter();
+    for n in names { d.insert(n.clone(), Value::Str(fields.next().unwrap_or_default())); }
+    Ok(Value::Dict(Rc::new(RefCell::new(d))))
+}
+
+fn header_names(fields: Vec<String>) -> Vec<String> {
+    let mut names = fields;
+    if let Some(first) = names.first_mut() {
+        if let Some(rest) = first.strip_prefix('\u{feff}') { *first = rest.to_string(); }
+    }
+    names
+}
+
+pub struct CsvReader {
+    fh: i64,
+    opts: Options,
+    names: Option<Vec<String>>,
+    row: i64,
+    done: bool,
+}
+
+impl BasicObject for CsvReader {
+    fn type_name(&self) -> &str { "CSV_READER" }
+    fn get_prop(&self, name: &str) -> Result<Value> {
+        match name.to_ascii_uppercase().as_str() {
+            "ROW" => Ok(Value::Int(self.row)),
+            "HANDLE" => Ok(Value::Int(self.fh)),
+            "DONE" => Ok(Value::Bool(self.done)),
+            "HEADERS" => Ok(Value::List(Rc::new(RefCell::new(
+                self.names.iter().flatten().cloned().map(Value::Str).collect(),
+            )))),
+            _ => Err(BasilError(format!("CSV_READER has no property {}", name))),
+        }
+    }
+    fn set_prop(&mut self, name: &str, _v: Value) -> Result<()> {
+        Err(BasilError(format!("CSV_READER property {} is read-only", name)))
+    }
+    fn call(&mut self, method: &str, _args: &[Value]) -> Result<Value> {
+        Err(BasilError(format!("CSV_READER.{} can only be called by the VM", method)))
+    }
+    fn descriptor(&self) -> ObjectDescriptor {
+        let prop = |name: &str, type_name: &str| PropDesc { name: name.into(), type_name: type_name.into(), readable: true, writable: false };
+        ObjectDescriptor {
+            type_name: "CSV_READER".into(),
+            version: "1.0".into(),
+            summary: "Rows of a CSV file, read one at a time".into(),
+            properties: vec![prop("Row", "INTEGER"), prop("Handle", "INTEGER"), prop("Done", "BOOL"), prop("Headers", "LIST")],
+            methods: vec![MethodDesc { name: "_NEXT".into(), arity: 0, arg_names: Vec::new(), return_type: "ANY".into() }],
+            examples: vec!["FOR EACH row IN CSV_READER(fh%)".into(), "LET row = r@._NEXT()".into()],
+        }
+    }
+    fn as_any(&self) -> Option<&dyn std::any::Any> { Some(self) }
+    fn as_any_mut(&mut self) -> Option<&mut dyn std::any::Any> { Some(self) }
+}
+
+pub(crate) fn is_reader(rc: &ObjectRef) -> bool {
+    rc.borrow().as_any().is_some_and(|a| a.is::<CsvReader>())
+}
+
+// How a value becomes a field, and whether it is a number for NONNUMERIC
+fn cell(v: &Value) -> (String, bool) {
+    match v {
+        Value::Int(_) | Value::Num(_) | Value::Decimal(_) => (v.to_string(), true),
+        other => (field_text(other), false),
+    }
+}
+
+// One record as text, without the line end
+pub fn format_record(cells: &[Value], o: &Options, who: &str) -> Result<String> {
+    let delim = o.delimiter as char;
+    let mut out = String::new();
+    for (i, v) in cells.iter().enumerate() {
+        if i > 0 { out.push(delim); }
+        let (text, numeric) = cell(v);
+        let special = |c: char| {
+            c == delim || c == '\n' || c == '\r' || Some(c) == o.quote.map(char::from) || Some(c) == o.escape.map(char::from)
+        };
+        let needs = text.chars().any(special) || (cells.len() == 1 && text.is_empty());
+        let quote = match (o.quoting, o.quote) {
+            (_, None) | (Quoting::None, _) => None,
+            (Quoting::All, Some(q)) => Some(q as char),
+            (Quoting::NonNumeric, Some(q)) if !numeric => Some(q as char),
+            (_, Some(q)) => needs.then_some(q as char),
+        };
+        match (quote, o.escape.map(char::from)) {
+            (Some(q), esc) => {
+                out.push(q);
+                for c in text.chars() {
+                    if c == q || Some(c) == esc { out.push(esc.unwrap_or(q)); }
+                    out.push(c);
+                }
+                out.push(q);
+            }
+            (None, Some(e)) => {
+                for c in text.chars() {
+                    if special(c) { out.push(e); }
+                    out.push(c);
+                }
+            }
+            (None, None) if needs && !text.is_empty() => {
+                return Err(BasilError(format!("{}: field {:?} needs quoting, but quoting is off and no escape is set", who, text)));
+            }
+            (None, None) => out.push_str(&text),
+        }
+    }
+    Ok(out)
+}
+
+// The cells of a LIST or DICT row, and the header line it implies (if any)
+fn row_cells(row: &Value, o: &Options, who: &str) -> Result<(Vec<Value>, Option<Vec<Value>>)> {
+    let names = match &o.header { Header::Names(n) => Some(n.clone()), _ => None };
+    match row {
+        Value::List(items) => Ok((items.borrow().clone(), names.map(|n| n.into_iter().map(Value::Str).collect()))),
+        Value::Dict(d) => {
+            let d = d.borrow();
+            let keys = names.unwrap_or_else(|| d.keys().cloned().collect());
+            let cells = keys.iter().map(|k| d.get(k).cloned().unwrap_or(Value::Null)).collect();
+            let header = (!matches!(o.header, Header::Absent)).then(|| keys.into_iter().map(Value::Str).collect());
+            Ok((cells, header))
+        }
+        _ => Err(BasilError(format!("{}: a row must be a LIST or a DICT", who))),
+    }
+}
+
+pub fn format_row(row: &Value, o: &Options, who: &str) -> Result<String> {
+    format_record(&row_cells(row, o, who)?.0, o, who)
+}
+
+// CSV_PARSE$: the first row names the columns; the rest become a JSON array of objects
+pub fn parse_json(text: &str) -> Result<String> {
+    let o = Options::default();
+    let mut rows = parse_text(text, &o)?.into_iter();
+    let names = rows.next().map(header_names).unwrap_or_default();
+    let mut out = Vec::new();
+    for (i, r) in rows.enumerate() {
+        out.push(shape(r, Some(&names), i as i64 + 1).map_err(|e| BasilError(format!("CSV_PARSE$: {}", e.0)))?);
+    }
+    crate::json::stringify(&Value::List(Rc::new(RefCell::new(out))), 0)
+}
+
+// CSV_WRITE$: a JSON array of objects as CSV, with every key seen as a column
+pub fn write_json(json: &str) -> Result<String> {
+    let rows = match crate::json::parse(json).map_err(|e| BasilError(format!("CSV_WRITE$: invalid JSON: {}", e.0)))? {
+        Value::List(items) => items.borrow().clone(),
+        _ => return Err(BasilError("CSV_WRITE$: expected JSON array of objects".into())),
+    };
+    let mut names: Vec<String> = Vec::new();
+    for r in &rows {
+        let Value::Dict(d) = r else { return Err(BasilError("CSV_WRITE$: array items must be objects".into())) };
+        for k in d.borrow().keys() {
+            if !names.contains(k) { names.push(k.clone()); }
+        }
+    }
+    let o = Options { header: Header::Names(names.clone()), ..Options::default() };
+    let header: Vec<Value> = names.into_iter().map(Value::Str).collect();
+    let mut out = format_record(&header, &o, "CSV_WRITE$")? + "\n";
+    for r in &rows {
+        let cells: Vec<Value> = row_cells(r, &o, "CSV_WRITE$")?.0.into_iter().map(json_cell).collect();
+        out.push_str(&format_record(&cells, &o, "CSV_WRITE$")?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn json_cell(v: Value) -> Value {
+    match v {
+        Value::List(_) | Value::Dict(_) => Value::Str(crate::json::stringify(&v, 0).unwrap_or_default()),
+        other => other,
+    }
+}
+
+impl VM {
+    fn csv_record(&mut self, fh: i64, o: &Options) -> Result<Option<Vec<String>>> {
+        let e = self.fh_entry(fh)?;
+        if !e.readable { return Err(BasilError("handle not opened for reading".into())); }
+        let (file, ahead) = (e.file.as_mut(), &mut e.read_ahead);
+        read_record(&mut || ahead.byte(file), o)
+    }
+
+    // A reader on `fh`; the header row is read straight away when there is one
+    fn new_reader(&mut self, fh: i64, o: Options) -> Result<CsvReader> {
+        let names = match &o.header {
+            Header::FirstRow => Some(self.csv_record(fh, &o)?.map(header_names).unwrap_or_default()),
+            Header::Names(n) => Some(n.clone()),
+            Header::Absent => None,
+        };
+        Ok(CsvReader { fh, opts: o, names, row: 0, done: false })
+    }
+
+    // The next row, or NULL after the last; errors are not yet prefixed with the builtin
+    fn next_row(&mut self, r: &mut CsvReader) -> Result<Value> {
+        if r.done { return Ok(Value::Null); }
+        let rec = self.csv_record(r.fh, &r.opts).map_err(|e| BasilError(format!("row {}: {}", r.row + 1, e.0)))?;
+        match rec {
+            None => { r.done = true; Ok(Value::Null) }
+            Some(fields) => {
+                r.row += 1;
+                shape(fields, r.names.as_deref(), r.row)
+            }
+        }
+    }
+
+    // CSV_READER(fh% [, opts])
+    pub(crate) fn csv_reader(&mut self, fh: i64, o: Options) -> Result<Value> {
+        let r = self.new_reader(fh, o).map_err(|e| BasilError(format!("CSV_READER: {}", e.0)))?;
+        Ok(Value::Object(Rc::new(RefCell::new(r))))
+    }
+
+    // r@._NEXT() and FOR EACH over a CSV_READER
+    pub(crate) fn csv_next(&mut self, rc: &ObjectRef) -> Result<Value> {
+        let mut obj = rc.borrow_mut();
+        let r = obj.as_any_mut().and_then(|a| a.downcast_mut::<CsvReader>()).ok_or_else(|| BasilError("not a CSV_READER".into()))?;
+        self.next_row(r).map_err(|e| BasilError(format!("CSV_READER: {}", e.0)))
+    }
+
+    // CSV_READ_ALL(fh% | path$ [, opts])
+    pub(crate) fn csv_read_all(&mut self, src: &Value, o: Options) -> Result<Value> {
+        let who = |e: BasilError| BasilError(format!("CSV_READ_ALL: {}", e.0));
+        let mut rows = Vec::new();
+        match src {
+            Value::Str(path) => {
+                let mut file = self.fs.open(path, OpenMode { read: true, ..OpenMode::default() })
+                    .map_err(|e| BasilError(format!("CSV_READ_ALL {}: {}", path, e)))?;
+                let mut ahead = ReadAhead::default();
+                let mut names = match &o.header { Header::Names(n) => Some(n.clone()), _ => None };
+                let mut first = matches!(o.header, Header::FirstRow);
+                while let Some(fields) = read_record(&mut || ahead.byte(file.as_mut()), &o).map_err(|e| BasilError(format!("CSV_READ_ALL: row {}: {}", rows.len() + 1, e.0)))? {
+                    if first { names = Some(header_names(fields)); first = false; continue; }
+                    rows.push(shape(fields, names.as_deref(), rows.len() as i64 + 1).map_err(who)?);
+                }
+            }
+            _ => {
+                let fh = self.to_i64(src)?;
+                let mut r = self.new_reader(fh, o).map_err(who)?;
+                loop {
+                    match self.next_row(&mut r).map_err(who)? {
+                        Value::Null => break,
+                        row => rows.push(row),
+                    }
+                }
+            }
+        }
+        Ok(Value::List(Rc::new(RefCell::new(rows))))
+    }
+
+    // CSV_WRITE_ROW(fh%, row [, opts]): the header line goes first into an empty file
+    pub(crate) fn csv_write_row(&mut self, fh: i64, row: &Value, o: &Options) -> Result<()> {
+        let (cells, header) = row_cells(row, o, "CSV_WRITE_ROW")?;
+        let mut text = format_record(&cells, o, "CSV_WRITE_ROW")? + "\n";
+        let e = self.fh_get_mut(fh)?;
+        if !e.writable { return Err(BasilError("CSV_WRITE_ROW: handle not opened for writing".into())); }
+        let io = |er: std::io::Error| BasilError(format!("CSV_WRITE_ROW: {}", er));
+        if let Some(h) = header {
+            let cur = e.file.stream_position().map_err(io)?;
+            let end = e.file.seek(SeekFrom::End(0)).map_err(io)?;
+            e.file.seek(SeekFrom::Start(cur)).map_err(io)?;
+            if end == 0 { text = format_record(&h, o, "CSV_WRITE_ROW")? + "\n" + &text; }
+        }
+        e.file.write_all(text.as_bytes()).map_err(io)
+    }
+}
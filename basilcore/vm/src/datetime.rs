@@ -0,0 +1,534 @@
+// Dates and times. Everything reads the VM's Clock provider, so a ManualClock pins "now".
+//
+//   NOW [(zone)]                              the current DateTime, in UTC or `zone`
+//   TIMER                                     seconds since midnight UTC, with fractions
+//   DATE$, TIME$                              "MM-DD-YYYY" and "HH:MM:SS" in UTC
+//   DATETIME(y, m, d [, h, mi, s [, zone]])   a DateTime from its fields (s may have fractions)
+//   DATETIME(epoch [, zone])                  a DateTime from seconds since 1970-01-01 UTC
+//   DATETIME_PARSE(text$ [, pattern$])        ISO-8601 or RFC-2822 text, or text laid out as pattern$
+//
+// A DateTime is an instant plus a fixed UTC offset. Zones are written "UTC" (or "Z", "GMT"),
+// "+05:30", "-0800", or given as minutes east of UTC; there is no time zone database.
+// DateTimes never change: the methods that move them return a new one.
+//
+//   .YEAR .MONTH .DAY .HOUR .MINUTE .SECOND .MILLISECOND .WEEKDAY (1 = Monday) .YEARDAY
+//   .EPOCH (seconds since 1970, FLOAT) .OFFSET (minutes east of UTC) .ZONE ("UTC", "+05:30")
+//   .FORMAT$(pattern$)   strftime: %Y %y %m %d %e %H %I %M %S %f (microseconds) %p %j %u %w
+//                        %a %A %b %B %z %:z %Z %s %F %T %%; DATETIME_PARSE patterns use the same
+//   .ISO$() .RFC2822$() .HTTP$()   HTTP$ is RFC 2822 in GMT, as headers and cookies use
+//   .ADD(seconds) .ADD_DAYS(n) .ADD_MONTHS(n)   months keep the day, or use the month's last
+//   .DIFF(other)         seconds from other to this one, FLOAT
+//   .TO_UTC() .TO_ZONE(zone)
+//
+// DateTimes PRINT as ISO-8601, and =, <, > and SORT compare the instants.
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use basil_bytecode::{BasicObject, MethodDesc, ObjectDescriptor, PropDesc, Value};
+use basil_common::{BasilError, Result};
+
+const NANOS: i128 = 1_000_000_000;
+const DAY: i64 = 86_400;
+// Comfortably more seconds than years 1 to 9999 span on either side of 1970, so that
+// anything past it is out of range before it can overflow a conversion
+const SPAN_SECS: i64 = 400_000_000_000;
+const MONTHS: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    secs: i64,
+    nanos: u32,
+    // Seconds east of UTC
+    offset: i32,
+}
+
+// The calendar fields of a DateTime in its own zone
+struct Fields {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    weekday: u32,
+    yearday: u32,
+}
+
+fn is_leap(y: i64) -> bool { (y % 4 == 0 && y % 100 != 0) || y % 400 == 0 }
+
+fn days_in_month(y: i64, m: u32) -> u32 {
+    match m {
+        4 | 6 | 9 | 11 => 30,
+        2 => if is_leap(y) { 29 } else { 28 },
+        _ => 31,
+    }
+}
+
+// Days from 1970-01-01 to y-m-d in the proleptic Gregorian calendar
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+fn zone_name(offset: i32, colon: bool) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let m = offset.unsigned_abs() / 60;
+    if colon { format!("{}{:02}:{:02}", sign, m / 60, m % 60) } else { format!("{}{:02}{:02}", sign, m / 60, m % 60) }
+}
+
+// A zone argument: "UTC", "+05:30", "-0800", ... or minutes east of UTC
+pub fn parse_zone(v: &Value) -> Result<i32> {
+    let bad = || BasilError(format!("invalid time zone {} (use \"UTC\", \"+05:30\" or minutes east of UTC)", v));
+    let secs = match v {
+        Value::Int(m) => m.checked_mul(60).ok_or_else(bad)?,
+        Value::Num(m) if m.is_finite() => (m * 60.0).round() as i64,
+        Value::Str(s) => {
+            let mut sc = Scanner::new(s.trim());
+            let z = sc.zone().ok_or_else(bad)?;
+            if !sc.done() { return Err(bad()); }
+            z as i64
+        }
+        _ => return Err(bad()),
+    };
+    if secs.abs() >= DAY { return Err(bad()); }
+    Ok(secs as i32)
+}
+
+impl DateTime {
+    pub fn from_system_time(t: SystemTime, offset: i32) -> DateTime {
+        let (secs, nanos) = match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+            Err(e) => {
+                let d = e.duration();
+                let total = -(d.as_nanos() as i128);
+                (total.div_euclid(NANOS) as i64, total.rem_euclid(NANOS) as u32)
+            }
+        };
+        DateTime { secs, nanos, offset }
+    }
+
+    pub fn from_epoch(seconds: f64, offset: i32) -> Result<DateTime> {
+        if !seconds.is_finite() { return Err(BasilError(format!("DATETIME: invalid epoch seconds {}", seconds))); }
+        DateTime::from_nanos(nanos_of(seconds)?, offset)
+    }
+
+    // The instant at wall-clock time y-m-d h:mi:s in the zone `offset`
+    pub fn from_fields(y: i64, mo: u32, d: u32, h: u32, mi: u32, s: f64, offset: i32) -> Result<DateTime> {
+        if !(1..=9999).contains(&y) { return Err(out_of_range()); }
+        let valid = (1..=12).contains(&mo) && d >= 1 && d <= days_in_month(y, mo) && h < 24 && mi < 60 && (0.0..60.0).contains(&s);
+        if !valid {
+            return Err(BasilError(format!("invalid date/time {:04}-{:02}-{:02} {:02}:{:02}:{:02}", y, mo, d, h, mi, s.trunc())));
+        }
+        let local = days_from_civil(y, mo, d) * DAY + (h * 3600 + mi * 60) as i64;
+        DateTime::from_nanos((local - offset as i64) as i128 * NANOS + (s * 1e9).round() as i128, offset)
+    }
+
+    fn from_nanos(total: i128, offset: i32) -> Result<DateTime> {
+        if total.abs() > SPAN_SECS as i128 * NANOS { return Err(out_of_range()); }
+        let dt = DateTime { secs: total.div_euclid(NANOS) as i64, nanos: total.rem_euclid(NANOS) as u32, offset };
+        // Four-digit years keep every format unambiguous
+        if !(1..=9999).contains(&dt.fields().year) { return Err(out_of_range()); }
+        Ok(dt)
+    }
+
+    fn total_nanos(&self) -> i128 { self.secs as i128 * NANOS + self.nanos as i128 }
+
+    pub fn epoch(&self) -> f64 { self.secs as f64 + self.nanos as f64 / 1e9 }
+
+    pub fn with_offset(&self, offset: i32) -> DateTime { DateTime { offset, ..*self } }
+
+    pub fn add_nanos(&self, delta: i128) -> Result<DateTime> {
+        DateTime::from_nanos(self.total_nanos().checked_add(delta).ok_or_else(out_of_range)?, self.offset)
+    }
+
+    pub fn add_months(&self, n: i64) -> Result<DateTime> {
+        let f = self.fields();
+        let months = (f.year * 12 + (f.month as i64 - 1)).checked_add(n).ok_or_else(out_of_range)?;
+        let (y, m) = (months.div_euclid(12), months.rem_euclid(12) as u32 + 1);
+        DateTime::from_fields(y, m, f.day.min(days_in_month(y, m)), f.hour, f.minute, f.second as f64, self.offset)
+            .and_then(|dt| dt.add_nanos(self.nanos as i128))
+    }
+
+    fn fields(&self) -> Fields {
+        let local = self.secs + self.offset as i64;
+        let (days, tod) = (local.div_euclid(DAY), local.rem_euclid(DAY) as u32);
+        let (year, month, day) = civil_from_days(days);
+        Fields {
+            year, month, day,
+            hour: tod / 3600,
+            minute: tod / 60 % 60,
+            second: tod % 60,
+            weekday: (days + 3).rem_euclid(7) as u32 + 1,
+            yearday: (days - days_from_civil(year, 1, 1)) as u32 + 1,
+        }
+    }
+
+    pub fn iso(&self) -> String {
+        let f = self.fields();
+        let mut out = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", f.year, f.month, f.day, f.hour, f.minute, f.second);
+        if self.nanos != 0 {
+            let frac = format!("{:09}", self.nanos);
+            out.push('.');
+            out.push_str(if self.nanos.is_multiple_of(1_000_000) { &frac[..3] } else if self.nanos.is_multiple_of(1000) { &frac[..6] } else { &frac });
+        }
+        out.push_str(&if self.offset == 0 { "Z".to_string() } else { zone_name(self.offset, true) });
+        out
+    }
+
+    pub fn rfc2822(&self) -> String {
+        self.format("%a, %d %b %Y %H:%M:%S %z").unwrap_or_default()
+    }
+
+    // RFC 2822 in GMT, the form of HTTP Date/Expires headers and cookie expiry
+    pub fn http(&self) -> String {
+        self.with_offset(0).format("%a, %d %b %Y %H:%M:%S GMT").unwrap_or_default()
+    }
+
+    pub fn format(&self, pattern: &str) -> Result<String> {
+        let f = self.fields();
+        let mut out = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            let d = chars.next().ok_or_else(|| BasilError("FORMAT$: pattern ends with %".into()))?;
+            if d == ':' {
+                if chars.next() != Some('z') { return Err(BasilError("FORMAT$: unknown directive %:".into())); }
+                out.push_str(&zone_name(self.offset, true));
+                continue;
+            }
+            if d == 'F' || d == 'T' {
+                out.push_str(&self.format(if d == 'F' { "%Y-%m-%d" } else { "%H:%M:%S" })?);
+                continue;
+            }
+            let hour12 = if f.hour.is_multiple_of(12) { 12 } else { f.hour % 12 };
+            let text = match d {
+                'Y' => format!("{:04}", f.year),
+                'y' => format!("{:02}", f.year % 100),
+                'm' => format!("{:02}", f.month),
+                'd' => format!("{:02}", f.day),
+                'e' => format!("{:2}", f.day),
+                'H' => format!("{:02}", f.hour),
+                'I' => format!("{:02}", hour12),
+                'M' => format!("{:02}", f.minute),
+                'S' => format!("{:02}", f.second),
+                'f' => format!("{:06}", self.nanos / 1000),
+                'p' => (if f.hour < 12 { "AM" } else { "PM" }).to_string(),
+                'j' => format!("{:03}", f.yearday),
+                'u' => f.weekday.to_string(),
+                'w' => (f.weekday % 7).to_string(),
+                'a' => WEEKDAYS[f.weekday as usize - 1][..3].to_string(),
+                'A' => WEEKDAYS[f.weekday as usize - 1].to_string(),
+                'b' | 'h' => MONTHS[f.month as usize - 1][..3].to_string(),
+                'B' => MONTHS[f.month as usize - 1].to_string(),
+                'z' => zone_name(self.offset, false),
+                'Z' => if self.offset == 0 { "UTC".to_string() } else { zone_name(self.offset, true) },
+                's' => self.secs.to_string(),
+                'n' => "\n".to_string(),
+                't' => "\t".to_string(),
+                '%' => "%".to_string(),
+                _ => return Err(BasilError(format!("FORMAT$: unknown directive %{}", d))),
+            };
+            out.push_str(&text);
+        }
+        Ok(out)
+    }
+
+    // ISO-8601 ("2024-03-09", "2024-03-09T14:05:00.5+01:00", a space for the T) or RFC 2822
+    // ("Sat, 09 Mar 2024 14:05:00 +0000"); text without a zone is UTC.
+    pub fn parse(text: &str) -> Result<DateTime> {
+        let t = text.trim();
+        let mut iso = vec!["%Y-%m-%d".to_string()];
+        for sep in ["T", " "] {
+            for secs in [":%S.%f", ":%S", ""] {
+                for zone in ["%z", ""] { iso.push(format!("%Y-%m-%d{}%H:%M{}{}", sep, secs, zone)); }
+            }
+        }
+        // The weekday in front of an RFC 2822 date is redundant
+        let rfc = t.split_once(',').map_or(t, |(_, rest)| rest.trim());
+        iso.iter().find_map(|p| DateTime::parse_with(t, p).ok())
+            .or_else(|| ["%d %b %Y %H:%M:%S %z", "%d %b %Y %H:%M %z"].iter().find_map(|p| DateTime::parse_with(rfc, p).ok()))
+            .ok_or_else(|| BasilError(format!("DATETIME_PARSE: {:?} is not an ISO-8601 or RFC 2822 date", text)))
+    }
+
+    // Read `text` laid out as the strftime `pattern`. Whitespace in the pattern matches any
+    // run of whitespace; fields the pattern leaves out default to 1970-01-01 00:00:00 UTC.
+    pub fn parse_with(text: &str, pattern: &str) -> Result<DateTime> {
+        let mismatch = || BasilError(format!("DATETIME_PARSE: {:?} does not match {:?}", text, pattern));
+        let expanded = pattern.replace("%F", "%Y-%m-%d").replace("%T", "%H:%M:%S");
+        let mut sc = Scanner::new(text);
+        let (mut y, mut mo, mut d, mut h, mut mi, mut s, mut nanos, mut offset) = (1970i64, 1u32, 1u32, 0u32, 0u32, 0u32, 0u32, 0i32);
+        let mut pm = None;
+        let mut epoch = None;
+        let mut pat = expanded.chars().peekable();
+        while let Some(c) = pat.next() {
+            if c.is_whitespace() {
+                sc.skip_space();
+                continue;
+            }
+            if c != '%' {
+                if !sc.eat(c) { return Err(mismatch()); }
+                continue;
+            }
+            let dir = match pat.next() {
+                Some(':') if pat.next_if_eq(&'z').is_some() => 'z',
+                Some(dir) => dir,
+                None => return Err(mismatch()),
+            };
+            let ok = match dir {
+                'Y' => sc.number(1, 4).map(|n| y = n as i64),
+                'y' => sc.number(2, 2).map(|n| y = if n < 69 { 2000 + n as i64 } else { 1900 + n as i64 }),
+                'm' => sc.number(1, 2).map(|n| mo = n),
+                'd' | 'e' => { sc.skip_space(); sc.number(1, 2).map(|n| d = n) }
+                'H' | 'I' => sc.number(1, 2).map(|n| h = n),
+                'M' => sc.number(1, 2).map(|n| mi = n),
+                'S' => sc.number(1, 2).map(|n| s = n),
+                'f' => sc.fraction().map(|n| nanos = n),
+                'p' => sc.word(&["AM", "PM"]).map(|i| pm = Some(i == 1)),
+                'b' | 'h' | 'B' => sc.name(&MONTHS).map(|i| mo = i as u32 + 1),
+                'a' | 'A' => sc.name(&WEEKDAYS).map(|_| ()),
+                'z' | 'Z' => sc.zone().map(|z| offset = z),
+                's' => sc.signed().map(|n| epoch = Some(n)),
+                '%' => sc.eat('%').then_some(()),
+                _ => return Err(BasilError(format!("DATETIME_PARSE: unknown directive %{}", dir))),
+            };
+            if ok.is_none() { return Err(mismatch()); }
+        }
+        if !sc.done() { return Err(mismatch()); }
+        if let Some(secs) = epoch { return DateTime::from_nanos(secs as i128 * NANOS, offset); }
+        if let Some(pm) = pm {
+            if !(1..=12).contains(&h) { return Err(mismatch()); }
+            h = h % 12 + if pm { 12 } else { 0 };
+        }
+        DateTime::from_fields(y, mo, d, h, mi, s as f64, offset).and_then(|dt| dt.add_nanos(nanos as i128))
+    }
+
+    pub fn into_value(self) -> Value { Value::Object(Rc::new(RefCell::new(self))) }
+
+    // The DateTime inside a value, if it holds one
+    pub fn from_value(v: &Value) -> Option<DateTime> {
+        match v {
+            Value::Object(rc) => rc.borrow().as_any().and_then(|a| a.downcast_ref::<DateTime>()).copied(),
+            _ => None,
+        }
+    }
+}
+
+struct Scanner<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str) -> Self { Scanner { rest: text } }
+
+    fn done(&self) -> bool { self.rest.is_empty() }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(r) => { self.rest = r; true }
+            None => false,
+        }
+    }
+
+    fn skip_space(&mut self) { self.rest = self.rest.trim_start(); }
+
+    fn digits(&mut self, min: usize, max: usize) -> Option<&'a str> {
+        let n = self.rest.bytes().take(max).take_while(|b| b.is_ascii_digit()).count();
+        if n < min { return None; }
+        let (d, r) = self.rest.split_at(n);
+        self.rest = r;
+        Some(d)
+    }
+
+    fn number(&mut self, min: usize, max: usize) -> Option<u32> { self.digits(min, max)?.parse().ok() }
+
+    fn signed(&mut self) -> Option<i64> {
+        let neg = self.eat('-');
+        let n: i64 = self.digits(1, 18)?.parse().ok()?;
+        Some(if neg { -n } else { n })
+    }
+
+    // Digits after a decimal point, as nanoseconds
+    fn fraction(&mut self) -> Option<u32> {
+        let d = self.digits(1, 9)?;
+        let n: u32 = d.parse().ok()?;
+        // Precision beyond nanoseconds is dropped
+        self.digits(0, usize::MAX);
+        Some(n * 10u32.pow(9 - d.len() as u32))
+    }
+
+    // Index of the word in `words` the text starts with, ignoring case
+    fn word(&mut self, words: &[&str]) -> Option<usize> {
+        let i = words.iter().position(|w| self.rest.get(..w.len()).is_some_and(|p| p.eq_ignore_ascii_case(w)))?;
+        self.rest = &self.rest[words[i].len()..];
+        Some(i)
+    }
+
+    // A full name or its first three letters
+    fn name(&mut self, names: &[&str]) -> Option<usize> {
+        self.word(names).or_else(|| {
+            let short: Vec<&str> = names.iter().map(|n| &n[..3]).collect();
+            self.word(&short)
+        })
+    }
+
+    // Z, UTC, UT, GMT, US zone abbreviations, +HH, +HHMM or +HH:MM; seconds east of UTC
+    fn zone(&mut self) -> Option<i32> {
+        const NAMED: [(&str, i32); 12] = [
+            ("UTC", 0), ("GMT", 0), ("UT", 0), ("Z", 0), ("EST", -5), ("EDT", -4),
+            ("CST", -6), ("CDT", -5), ("MST", -7), ("MDT", -6), ("PST", -8), ("PDT", -7),
+        ];
+        let names: Vec<&str> = NAMED.iter().map(|(n, _)| *n).collect();
+        if let Some(i) = self.word(&names) { return Some(NAMED[i].1 * 3600); }
+        let sign = if self.eat('+') { 1 } else if self.eat('-') { -1 } else { return None };
+        let h = self.number(2, 2)?;
+        let colon = self.eat(':');
+        let m = match self.number(2, 2) {
+            Some(m) => m,
+            None if colon => return None,
+            None => 0,
+        };
+        (h < 24 && m < 60).then_some(sign * (h * 3600 + m * 60) as i32)
+    }
+}
+
+fn out_of_range() -> BasilError { BasilError("DateTime out of range (years 1 to 9999)".into()) }
+
+// A FLOAT count of seconds as nanoseconds, refused before the cast could saturate
+fn nanos_of(seconds: f64) -> Result<i128> {
+    if !seconds.is_finite() || seconds.abs() > SPAN_SECS as f64 { return Err(out_of_range()); }
+    Ok((seconds * 1e9).round() as i128)
+}
+
+fn num(v: &Value, what: &str) -> Result<f64> {
+    match v {
+        Value::Num(n) => Ok(*n),
+        Value::Int(i) => Ok(*i as f64),
+        _ => Err(BasilError(format!("{} must be a number, got {}", what, v))),
+    }
+}
+
+fn expect_args(method: &str, args: &[Value], n: usize) -> Result<()> {
+    if args.len() == n { return Ok(()); }
+    Err(BasilError(format!("DateTime.{} expects {} argument(s), got {}", method, n, args.len())))
+}
+
+fn other(method: &str, v: &Value) -> Result<DateTime> {
+    DateTime::from_value(v).ok_or_else(|| BasilError(format!("DateTime.{} expects a DateTime", method)))
+}
+
+impl BasicObject for DateTime {
+    fn type_name(&self) -> &str { "DateTime" }
+
+    fn get_prop(&self, name: &str) -> Result<Value> {
+        let f = self.fields();
+        Ok(match name.to_ascii_uppercase().as_str() {
+            "YEAR" => Value::Int(f.year),
+            "MONTH" => Value::Int(f.month as i64),
+            "DAY" => Value::Int(f.day as i64),
+            "HOUR" => Value::Int(f.hour as i64),
+            "MINUTE" => Value::Int(f.minute as i64),
+            "SECOND" => Value::Int(f.second as i64),
+            "MILLISECOND" => Value::Int((self.nanos / 1_000_000) as i64),
+            "WEEKDAY" => Value::Int(f.weekday as i64),
+            "YEARDAY" => Value::Int(f.yearday as i64),
+            "EPOCH" => Value::Num(self.epoch()),
+            "OFFSET" => Value::Int((self.offset / 60) as i64),
+            "ZONE" => Value::Str(if self.offset == 0 { "UTC".into() } else { zone_name(self.offset, true) }),
+            _ => return Err(BasilError(format!("DateTime has no property {}", name))),
+        })
+    }
+
+    fn set_prop(&mut self, name: &str, _v: Value) -> Result<()> {
+        Err(BasilError(format!("DateTime.{} is read-only; use ADD, ADD_DAYS, ADD_MONTHS or DATETIME()", name)))
+    }
+
+    fn call(&mut self, method: &str, args: &[Value]) -> Result<Value> {
+        let m = method.to_ascii_uppercase();
+        let arity = descriptor().methods.iter().find(|d| d.name == m).map(|d| d.arity as usize)
+            .ok_or_else(|| BasilError(format!("DateTime has no method {}", method)))?;
+        expect_args(&m, args, arity)?;
+        Ok(match m.as_str() {
+            "FORMAT$" => Value::Str(self.format(&format!("{}", args[0]))?),
+            "ISO$" | "_STR$" => Value::Str(self.iso()),
+            "RFC2822$" => Value::Str(self.rfc2822()),
+            "HTTP$" => Value::Str(self.http()),
+            "ADD" => self.add_nanos(nanos_of(num(&args[0], "DateTime.ADD seconds")?)?)?.into_value(),
+            "ADD_DAYS" => self.add_nanos(nanos_of(num(&args[0], "DateTime.ADD_DAYS days")? * DAY as f64)?)?.into_value(),
+            "ADD_MONTHS" => self.add_months(num(&args[0], "DateTime.ADD_MONTHS months")?.trunc() as i64)?.into_value(),
+            "DIFF" => Value::Num((self.total_nanos() - other(&m, &args[0])?.total_nanos()) as f64 / 1e9),
+            "TO_UTC" => self.with_offset(0).into_value(),
+            "TO_ZONE" => self.with_offset(parse_zone(&args[0])?).into_value(),
+            "_EQ" => Value::Bool(DateTime::from_value(&args[0]).is_some_and(|o| o.total_nanos() == self.total_nanos())),
+            _ => Value::Int(match self.total_nanos().cmp(&other(&m, &args[0])?.total_nanos()) {
+                Ordering::Less => -1,
+                Ordering::Equal => 0,
+                Ordering::Greater => 1,
+            }),
+        })
+    }
+
+    fn descriptor(&self) -> ObjectDescriptor { descriptor() }
+
+    fn as_any(&self) -> Option<&dyn std::any::Any> { Some(self) }
+}
+
+fn descriptor() -> ObjectDescriptor {
+    let prop = |n: &str, t: &str| PropDesc { name: n.into(), type_name: t.into(), readable: true, writable: false };
+    let method = |n: &str, args: &[&str], ret: &str| MethodDesc {
+        name: n.into(), arity: args.len() as u8, arg_names: args.iter().map(|a| a.to_string()).collect(), return_type: ret.into(),
+    };
+    let ints = ["YEAR", "MONTH", "DAY", "HOUR", "MINUTE", "SECOND", "MILLISECOND", "WEEKDAY", "YEARDAY"];
+    let mut properties: Vec<PropDesc> = ints.iter().map(|n| prop(n, "INTEGER")).collect();
+    properties.extend([prop("EPOCH", "FLOAT"), prop("OFFSET", "INTEGER"), prop("ZONE", "STRING")]);
+    ObjectDescriptor {
+        type_name: "DateTime".into(),
+        version: "1.0".into(),
+        summary: "An instant in time with a fixed UTC offset".into(),
+        properties,
+        methods: vec![
+            method("FORMAT$", &["pattern$"], "STRING"),
+            method("ISO$", &[], "STRING"),
+            method("RFC2822$", &[], "STRING"),
+            method("HTTP$", &[], "STRING"),
+            method("ADD", &["seconds"], "DateTime"),
+            method("ADD_DAYS", &["days"], "DateTime"),
+            method("ADD_MONTHS", &["months"], "DateTime"),
+            method("DIFF", &["other@"], "FLOAT"),
+            method("TO_UTC", &[], "DateTime"),
+            method("TO_ZONE", &["zone"], "DateTime"),
+            method("_STR$", &[], "STRING"),
+            method("_EQ", &["other@"], "BOOL"),
+            method("_CMP", &["other@"], "INTEGER"),
+        ],
+        examples: vec![
+            "PRINTLN NOW.FORMAT$(\"%Y-%m-%d %H:%M\")".into(),
+            "LET d = DATETIME_PARSE(\"2024-03-09T14:05:00+01:00\")".into(),
+            "PRINTLN d.ADD_DAYS(30).ISO$(), d.DIFF(NOW) / 3600".into(),
+        ],
+    }
+}
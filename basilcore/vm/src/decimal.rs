@@ -0,0 +1,98 @@
+// DECIMAL: exact numbers for money, next to FLOAT and INTEGER.
+//
+//   12.34@                          a DECIMAL literal (the @ suffix, as for VB's Currency)
+//   DEC(x [, scale [, mode$]])      from a string, a number or a DECIMAL, rounded if asked
+//   DEC_DIV(a, b, scale [, mode$])  a / b with `scale` digits after the point
+//   DEC_ROUND(x, scale [, mode$])   exactly `scale` digits after the point
+//   DEC_SCALE%([n])                 the scale `/` uses (10); setting it returns the old one
+//   DEC_ROUNDING$([mode$])          the rounding `/` uses (HALF_EVEN), likewise
+//
+// + - * MOD and comparisons are exact whenever one side is a DECIMAL: the other side is
+// converted first, a FLOAT by its shortest decimal form so 0.1 means 0.1. `/` rounds to the
+// DEC_SCALE% digits and then drops trailing zeros down to the dividend's own scale, so
+// 10.00@ / 4 is 2.50 and 1@ / 3 is 0.3333333333. Modes: HALF_EVEN, HALF_UP, HALF_DOWN, UP,
+// DOWN, CEILING and FLOOR. ABS keeps a DECIMAL and INT floors one exactly; other numeric
+// builtins (SQR, SIN, ...) see it as a FLOAT.
+
+use std::rc::Rc;
+
+use basil_bytecode::decimal::MAX_SCALE;
+use basil_bytecode::{Decimal, Rounding, Value};
+use basil_common::{BasilError, Result};
+
+pub const DEFAULT_SCALE: u32 = 10;
+
+#[derive(Clone, Copy)]
+pub enum Arith {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+pub fn value(d: Decimal) -> Value { Value::Decimal(Rc::new(d)) }
+
+pub fn involved(a: &Value, b: &Value) -> bool {
+    matches!(a, Value::Decimal(_)) || matches!(b, Value::Decimal(_))
+}
+
+// A number taking part in arithmetic or a comparison with a DECIMAL
+pub fn operand(v: &Value) -> Result<Decimal> {
+    match v {
+        Value::Decimal(d) => Ok((**d).clone()),
+        Value::Int(i) => Ok(Decimal::from_i64(*i)),
+        Value::Bool(b) => Ok(Decimal::from_i64(*b as i64)),
+        Value::Num(n) => Decimal::from_f64(*n).ok_or_else(|| BasilError(format!("cannot use {} with a DECIMAL", n))),
+        _ => Err(BasilError("expected number".into())),
+    }
+}
+
+// DEC's argument: strings are read as written, numbers converted as for arithmetic
+pub fn from_value(v: &Value, who: &str) -> Result<Decimal> {
+    match v {
+        Value::Str(s) => Decimal::parse(s).ok_or_else(|| BasilError(format!("{}: \"{}\" is not a decimal number", who, s))),
+        Value::Num(n) if !n.is_finite() => Err(BasilError(format!("{}: {} is not a finite number", who, n))),
+        Value::Decimal(_) | Value::Int(_) | Value::Num(_) | Value::Bool(_) => operand(v).map_err(|e| BasilError(format!("{}: {}", who, e.0))),
+        _ => Err(BasilError(format!("{}: expected a number or a string", who))),
+    }
+}
+
+pub fn arith(op: Arith, a: &Value, b: &Value, scale: u32, mode: Rounding) -> Result<Value> {
+    let (x, y) = (operand(a)?, operand(b)?);
+    let zero = || BasilError("DECIMAL division by zero".into());
+    Ok(value(match op {
+        Arith::Add => &x + &y,
+        Arith::Sub => &x - &y,
+        Arith::Mul => &x * &y,
+        Arith::Div => {
+            let q = x.quotient(&y, scale, mode).ok_or_else(zero)?;
+            trim_zeros(q, x.scale().min(scale))
+        }
+        Arith::Mod => x.remainder(&y).ok_or_else(zero)?,
+    }))
+}
+
+// Drop trailing zeros after the point, keeping at least `keep` digits
+fn trim_zeros(mut d: Decimal, keep: u32) -> Decimal {
+    while d.scale() > keep {
+        let shorter = d.round(d.scale() - 1, Rounding::Down);
+        if shorter != d { break; }
+        d = shorter;
+    }
+    d
+}
+
+pub fn scale_arg(n: i64, who: &str) -> Result<u32> {
+    if !(0..=MAX_SCALE as i64).contains(&n) {
+        return Err(BasilError(format!("{}: scale must be from 0 to {}", who, MAX_SCALE)));
+    }
+    Ok(n as u32)
+}
+
+pub fn rounding_arg(v: &Value, who: &str) -> Result<Rounding> {
+    let name = match v { Value::Str(s) => s.clone(), other => other.to_string() };
+    Rounding::from_name(&name).ok_or_else(|| BasilError(format!(
+        "{}: unknown rounding mode \"{}\" (use HALF_EVEN, HALF_UP, HALF_DOWN, UP, DOWN, CEILING or FLOOR)", who, name
+    )))
+}
@@ -0,0 +1,245 @@
+// Event loop: timers, key and signal handlers run by a single-threaded scheduler.
+//
+//   SETTIMEOUT(fn, ms) / SETINTERVAL(fn, ms)  call fn once / every ms; return a timer id
+//   CLEARTIMER(id)                            cancel a timer (also CLEARTIMEOUT, CLEARINTERVAL)
+//   ON KEY fn                                 fn(code%) for each key, codes as INKEY%
+//   ON SIGNAL "INT", fn                       fn(name$) when the process gets SIGINT
+//   AWAIT ms                                  like SLEEP, but timers and handlers keep running
+//   EVENT_LOOP / STOP_LOOP                    run events until STOP_LOOP or nothing is left
+//
+// Handlers are FUNC/SUB values run as nested calls on the VM while a script waits in
+// AWAIT or EVENT_LOOP (and after the top level ends, while timers or ON KEY remain), one
+// at a time and never in the middle of other code. Passing NULL removes a handler.
+// Waiting and the current time go through the VM's Clock, so a ManualClock makes timers
+// run instantly and in a fixed order; hosts can queue signals with VM::raise_signal.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use basil_bytecode::Value;
+use basil_common::{BasilError, Result};
+
+use crate::{terminal_key_code, VM};
+
+// How often keys and OS signals are polled while waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+struct Timer {
+    id: i64,
+    due: SystemTime,
+    every: Option<Duration>,
+    handler: Value,
+}
+
+struct SignalHandler {
+    name: &'static str,
+    handler: Value,
+    flag: Arc<AtomicBool>,
+    sig_id: Option<signal_hook::SigId>,
+}
+
+// Replacing a handler, or dropping the scheduler with its VM, gives the OS registration
+// back, so embedded VMs do not leave signal actions behind
+impl Drop for SignalHandler {
+    fn drop(&mut self) {
+        if let Some(id) = self.sig_id.take() { signal_hook::low_level::unregister(id); }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    // Ordered by due time, then creation
+    timers: Vec<Timer>,
+    next_id: i64,
+    key_handler: Option<Value>,
+    signals: Vec<SignalHandler>,
+    // Signals raised by the host, by name
+    raised: VecDeque<&'static str>,
+    stopped: bool,
+}
+
+impl Scheduler {
+    fn add_timer(&mut self, due: SystemTime, every: Option<Duration>, handler: Value) -> i64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.insert(Timer { id, due, every, handler });
+        id
+    }
+
+    fn insert(&mut self, t: Timer) {
+        let at = self.timers.iter().position(|x| x.due > t.due).unwrap_or(self.timers.len());
+        self.timers.insert(at, t);
+    }
+
+    fn clear_timer(&mut self, id: i64) -> bool {
+        let before = self.timers.len();
+        self.timers.retain(|t| t.id != id);
+        self.timers.len() != before
+    }
+
+    // Whether the loop has anything left to wait for
+    fn has_pending(&self) -> bool {
+        !self.timers.is_empty() || self.key_handler.is_some()
+    }
+
+    // Next signal to deliver: raised by the host first, then received from the OS
+    fn next_signal(&mut self) -> Option<(&'static str, Value)> {
+        while let Some(name) = self.raised.pop_front() {
+            if let Some(h) = self.signals.iter().find(|h| h.name == name) { return Some((name, h.handler.clone())); }
+        }
+        self.signals.iter().find(|h| h.flag.swap(false, Ordering::SeqCst)).map(|h| (h.name, h.handler.clone()))
+    }
+
+    fn set_signal(&mut self, name: &'static str, handler: Option<Value>) -> Result<()> {
+        self.signals.retain(|h| h.name != name);
+        let Some(handler) = handler else { return Ok(()) };
+        let flag = Arc::new(AtomicBool::new(false));
+        let sig_id = match signal_number(name) {
+            Some(n) => Some(signal_hook::flag::register(n, flag.clone())
+                .map_err(|e| BasilError(format!("ON SIGNAL {}: {}", name, e)))?),
+            None => None,
+        };
+        self.signals.push(SignalHandler { name, handler, flag, sig_id });
+        Ok(())
+    }
+}
+
+// Canonical name of a signal given as "INT", "SIGINT", "int", ...
+fn signal_name(s: &str) -> Option<&'static str> {
+    let u = s.trim().to_ascii_uppercase();
+    let u = u.strip_prefix("SIG").unwrap_or(&u);
+    ["INT", "TERM", "HUP", "USR1", "USR2"].into_iter().find(|n| *n == u)
+}
+
+#[cfg(unix)]
+fn signal_number(name: &str) -> Option<i32> {
+    use signal_hook::consts::*;
+    match name {
+        "INT" => Some(SIGINT),
+        "TERM" => Some(SIGTERM),
+        "HUP" => Some(SIGHUP),
+        "USR1" => Some(SIGUSR1),
+        "USR2" => Some(SIGUSR2),
+        _ => None,
+    }
+}
+
+// Other platforms only deliver INT and TERM; the rest can still be raised by the host
+#[cfg(not(unix))]
+fn signal_number(name: &str) -> Option<i32> {
+    use signal_hook::consts::*;
+    match name {
+        "INT" => Some(SIGINT),
+        "TERM" => Some(SIGTERM),
+        _ => None,
+    }
+}
+
+// A handler argument: a FUNC/SUB taking at most `max_params` parameters, or NULL
+fn handler_arg(what: &str, v: &Value, max_params: u8) -> Result<Option<Value>> {
+    match v {
+        Value::Null => Ok(None),
+        Value::Func(f) if f.arity <= max_params => Ok(Some(v.clone())),
+        Value::Func(_) if max_params == 0 => Err(BasilError(format!("{} handler must take no parameters", what))),
+        Value::Func(_) => Err(BasilError(format!("{} handler must take 0 or {} parameters", what, max_params))),
+        _ => Err(BasilError(format!("{} handler must be a FUNC or SUB name", what))),
+    }
+}
+
+impl VM {
+    // SETTIMEOUT / SETINTERVAL: the id of the new timer
+    pub(crate) fn add_timer(&mut self, what: &str, handler: &Value, ms: i64, repeat: bool) -> Result<i64> {
+        let handler = handler_arg(what, handler, 0)?
+            .ok_or_else(|| BasilError(format!("{} handler must be a FUNC or SUB name", what)))?;
+        let delay = Duration::from_millis(if repeat { ms.max(1) } else { ms.max(0) } as u64);
+        let due = self.clock.now() + delay;
+        Ok(self.events.add_timer(due, repeat.then_some(delay), handler))
+    }
+
+    pub(crate) fn clear_timer(&mut self, id: i64) -> bool { self.events.clear_timer(id) }
+
+    pub(crate) fn set_key_handler(&mut self, handler: &Value) -> Result<()> {
+        self.events.key_handler = handler_arg("ON KEY", handler, 1)?;
+        Ok(())
+    }
+
+    pub(crate) fn set_signal_handler(&mut self, name: &str, handler: &Value) -> Result<()> {
+        let canon = signal_name(name)
+            .ok_or_else(|| BasilError(format!("ON SIGNAL: unknown signal \"{}\" (use INT, TERM, HUP, USR1 or USR2)", name)))?;
+        let handler = handler_arg("ON SIGNAL", handler, 1)?;
+        self.events.set_signal(canon, handler)
+    }
+
+    pub(crate) fn stop_loop(&mut self) { self.events.stopped = true; }
+
+    pub(crate) fn has_pending_events(&self) -> bool { self.events.has_pending() }
+
+    // Deliver a signal to its ON SIGNAL handler the next time events run, as if the
+    // process had received it. Signals without a handler are ignored.
+    pub fn raise_signal(&mut self, name: &str) -> Result<()> {
+        let canon = signal_name(name).ok_or_else(|| BasilError(format!("unknown signal \"{}\"", name)))?;
+        self.events.raised.push_back(canon);
+        Ok(())
+    }
+
+    // Number of timers still scheduled.
+    pub fn pending_timers(&self) -> usize { self.events.timers.len() }
+
+    // Run timers and handlers until `until` (AWAIT), or with None until STOP_LOOP is
+    // called or no timers or key handler are left (EVENT_LOOP).
+    pub fn run_events(&mut self, until: Option<SystemTime>) -> Result<()> {
+        if until.is_none() { self.events.stopped = false; }
+        loop {
+            if until.is_none() && std::mem::take(&mut self.events.stopped) { return Ok(()); }
+            if let Some((name, h)) = self.events.next_signal() {
+                self.call_handler(&h, vec![Value::Str(name.to_string())])?;
+                continue;
+            }
+            if let Some(h) = self.events.key_handler.clone() {
+                if let Some(code) = self.poll_key()? {
+                    self.call_handler(&h, vec![Value::Int(code)])?;
+                    continue;
+                }
+            }
+            let now = self.clock.now();
+            if self.events.timers.first().is_some_and(|t| t.due <= now) {
+                let t = self.events.timers.remove(0);
+                if let Some(every) = t.every {
+                    // Rescheduled before the call so the handler can clear its own interval
+                    let due = (t.due + every).max(now);
+                    self.events.insert(Timer { id: t.id, due, every: t.every, handler: t.handler.clone() });
+                }
+                self.call_handler(&t.handler, Vec::new())?;
+                continue;
+            }
+            if until.is_some_and(|u| now >= u) { return Ok(()); }
+            if until.is_none() && !self.events.has_pending() { return Ok(()); }
+            let mut wake = [self.events.timers.first().map(|t| t.due), until].into_iter().flatten().min();
+            if self.events.key_handler.is_some() || !self.events.signals.is_empty() {
+                wake = Some(wake.map_or(now + POLL_INTERVAL, |w| w.min(now + POLL_INTERVAL)));
+            }
+            let _ = self.output.flush();
+            match wake {
+                Some(w) => self.clock.sleep(w.duration_since(now).unwrap_or(Duration::ZERO)),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    // A handler takes the event's arguments it declares parameters for
+    fn call_handler(&mut self, h: &Value, mut args: Vec<Value>) -> Result<()> {
+        if let Value::Func(f) = h { args.truncate(f.arity as usize); }
+        self.call_value(h, args).map(|_| ())
+    }
+
+    // Key waiting for ON KEY, from the input provider or the terminal
+    fn poll_key(&mut self) -> Result<Option<i64>> {
+        let code = match self.input.as_mut() {
+            Some(inp) => inp.read_char().map(|c| c as i64).unwrap_or(0),
+            None => terminal_key_code()?,
+        };
+        Ok((code != 0).then_some(code))
+    }
+}
@@ -0,0 +1,125 @@
+// Generators: a FUNC (or method) containing YIELD.
+//
+// The compiler starts such a function with Op::MakeGen, which packages the call (chunk,
+// ip after MakeGen, arguments) into a Generator and returns that instead of running the
+// body. Resuming pushes the saved frame back onto the calling VM; YIELD saves it again and
+// hands the value to whoever resumed it, like a RETURN that can be continued. Reaching
+// the end of the body (or RETURN) finishes the generator, after which g@._NEXT() returns
+// NULL. FOR EACH and SORT stop when it finishes, so a generator may YIELD NULL itself.
+//
+// Scripts resume with FOR EACH, SORT or g@._NEXT(); the host with VM::generator_next, so
+// many generators can be stepped in turn on one thread.
+
+use std::rc::Rc;
+
+use basil_bytecode::{BasicObject, Chunk, MethodDesc, ObjectDescriptor, ObjectRef, PropDesc, Value};
+use basil_common::{BasilError, Result};
+
+use crate::{ClassEnv, Frame, VM};
+
+enum GenState {
+    // Suspended at ip with the frame's locals (and any loop state above them)
+    Ready { ip: usize, locals: Vec<Value> },
+    Running,
+    Done,
+}
+
+pub struct Generator {
+    chunk: Rc<Chunk>,
+    owner: Option<Rc<str>>,
+    env: Option<Rc<ClassEnv>>,
+    state: GenState,
+}
+
+impl Generator {
+    // The frame a MakeGen popped, with its stack slice.
+    pub(crate) fn from_frame(frame: &Frame, locals: Vec<Value>) -> Self {
+        Generator {
+            chunk: frame.chunk.clone(),
+            owner: frame.owner.clone(),
+            env: frame.env.clone(),
+            state: GenState::Ready { ip: frame.ip, locals },
+        }
+    }
+}
+
+impl BasicObject for Generator {
+    fn type_name(&self) -> &str { "GENERATOR" }
+    fn get_prop(&self, name: &str) -> Result<Value> {
+        match name.to_ascii_uppercase().as_str() {
+            "DONE" => Ok(Value::Bool(matches!(self.state, GenState::Done))),
+            _ => Err(BasilError(format!("GENERATOR has no property {}", name))),
+        }
+    }
+    fn set_prop(&mut self, name: &str, _v: Value) -> Result<()> {
+        Err(BasilError(format!("GENERATOR property {} is read-only", name)))
+    }
+    fn call(&mut self, method: &str, _args: &[Value]) -> Result<Value> {
+        Err(BasilError(format!("GENERATOR.{} can only be called by the VM", method)))
+    }
+    fn descriptor(&self) -> ObjectDescriptor {
+        ObjectDescriptor {
+            type_name: "GENERATOR".into(),
+            version: "1.0".into(),
+            summary: "Values produced by a FUNC containing YIELD".into(),
+            properties: vec![PropDesc { name: "Done".into(), type_name: "BOOL".into(), readable: true, writable: false }],
+            methods: vec![MethodDesc { name: "_NEXT".into(), arity: 0, arg_names: Vec::new(), return_type: "ANY".into() }],
+            examples: vec!["FOR EACH x IN Squares(10)".into(), "LET v = g@._NEXT()".into()],
+        }
+    }
+    fn as_any(&self) -> Option<&dyn std::any::Any> { Some(self) }
+    fn as_any_mut(&mut self) -> Option<&mut dyn std::any::Any> { Some(self) }
+}
+
+pub(crate) fn is_generator(rc: &ObjectRef) -> bool {
+    rc.borrow().as_any().is_some_and(|a| a.is::<Generator>())
+}
+
+fn with_generator<T>(rc: &ObjectRef, f: impl FnOnce(&mut Generator) -> T) -> Option<T> {
+    let mut obj = rc.borrow_mut();
+    obj.as_any_mut().and_then(|a| a.downcast_mut::<Generator>()).map(f)
+}
+
+// Park the generator of a frame that YIELDed.
+pub(crate) fn suspend(rc: &ObjectRef, ip: usize, locals: Vec<Value>) {
+    with_generator(rc, |g| g.state = GenState::Ready { ip, locals });
+}
+
+pub(crate) fn finish(rc: &ObjectRef) {
+    with_generator(rc, |g| g.state = GenState::Done);
+}
+
+impl VM {
+    // Continue a generator on this VM: its next YIELD (or end) leaves the value on the
+    // stack as if returned. False if it has already finished.
+    pub(crate) fn push_generator_frame(&mut self, rc: &ObjectRef) -> Result<bool> {
+        let frame = with_generator(rc, |g| match std::mem::replace(&mut g.state, GenState::Running) {
+            GenState::Ready { ip, locals } => Ok(Some((g.chunk.clone(), ip, locals, g.owner.clone(), g.env.clone()))),
+            GenState::Running => Err(BasilError("GENERATOR is already running".into())),
+            GenState::Done => { g.state = GenState::Done; Ok(None) }
+        }).ok_or_else(|| BasilError("not a GENERATOR".into()))??;
+        let Some((chunk, ip, locals, owner, env)) = frame else { return Ok(false) };
+        let base = self.stack.len();
+        self.stack.extend(locals);
+        self.frames.push(Frame { chunk, ip, base, owner, discard: false, env, gen: Some(rc.clone()) });
+        Ok(true)
+    }
+
+    // Next value of a generator (the result of calling a FUNC that contains YIELD), or
+    // None once it has finished. An error inside the generator finishes it.
+    pub fn generator_next(&mut self, gen: &Value) -> Result<Option<Value>> {
+        let rc = match gen {
+            Value::Object(rc) if is_generator(rc) => rc.clone(),
+            other => return Err(BasilError(format!("expected a GENERATOR, got {}", self.type_of(other)))),
+        };
+        let (depth, base) = (self.frames.len(), self.stack.len());
+        if !self.push_generator_frame(&rc)? { return Ok(None); }
+        match self.finish_call(depth, base) {
+            Ok(v) => {
+                let done = with_generator(&rc, |g| matches!(g.state, GenState::Done)).unwrap_or(true);
+                Ok(if done { None } else { Some(v) })
+            }
+            Err(e) => { finish(&rc); Err(e) }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+// JSON text <-> Basil values.
+//
+//   JSON_PARSE(text$ [, "DECIMAL"])  objects become DICTs, arrays LISTs, null NULL
+//   JSON_STRINGIFY$(v [, indent])    compact, or pretty-printed with `indent` spaces
+//   JSON_PARSE$(text$)               the same JSON, normalized to compact text
+//
+// Numbers behave like Basil literals: integers are FLOAT unless too large for a FLOAT to
+// hold exactly (then INTEGER), and whole FLOATs are written without a fraction. With
+// "DECIMAL" every number is read exactly as written into a DECIMAL instead, and DECIMALs
+// are always written with all their digits, so money survives the round trip. DICT keys
+// keep their order both ways, so the same data always gives the same text. Arrays are
+// written as flat lists and objects as their readable properties. Functions, BYTES, NaN,
+// infinities and values that contain themselves cannot be written.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::rc::Rc;
+
+use basil_bytecode::{Decimal, Dict, Value};
+use basil_common::{BasilError, Result};
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::decimal;
+
+// Largest integer a FLOAT holds exactly
+const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+pub fn parse(text: &str) -> Result<Value> { read(text, false) }
+
+// Every number as a DECIMAL, exactly as written
+pub fn parse_decimal(text: &str) -> Result<Value> { read(text, true) }
+
+fn read(text: &str, decimals: bool) -> Result<Value> {
+    let numbers = decimals.then(|| RefCell::new(number_texts(text)));
+    let mut de = serde_json::Deserializer::from_str(text);
+    let v = Seed(numbers.as_ref()).deserialize(&mut de).and_then(|v| de.end().map(|_| v));
+    v.map_err(|e| BasilError(format!("JSON_PARSE: invalid JSON: {}", e)))
+}
+
+// The numbers of a document as written, in order. The parser meets them in the same order,
+// so in DECIMAL mode each one is read again from its own text instead of from an f64.
+fn number_texts(text: &str) -> VecDeque<&str> {
+    let b = text.as_bytes();
+    let mut out = VecDeque::new();
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'"' => {
+                i += 1;
+                while i < b.len() && b[i] != b'"' { i += if b[i] == b'\\' { 2 } else { 1 }; }
+                i += 1;
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = i;
+                while i < b.len() && matches!(b[i], b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') { i += 1; }
+                out.push_back(&text[start..i]);
+            }
+            _ => i += 1,
+        }
+    }
+    out
+}
+
+// Parses one value; carries the queue of number texts when numbers become DECIMALs
+#[derive(Clone, Copy)]
+struct Seed<'a>(Option<&'a RefCell<VecDeque<&'a str>>>);
+
+impl Seed<'_> {
+    fn number<E: de::Error>(self, plain: Value) -> std::result::Result<Value, E> {
+        let Some(texts) = self.0 else { return Ok(plain); };
+        let text = texts.borrow_mut().pop_front().unwrap_or_default();
+        Decimal::parse(text).map(decimal::value).ok_or_else(|| E::custom(format!("{} is not a decimal number", text)))
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for Seed<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> std::result::Result<Value, D::Error> {
+        d.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Seed<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("a JSON value") }
+
+    fn visit_unit<E>(self) -> std::result::Result<Value, E> { Ok(Value::Null) }
+    fn visit_bool<E>(self, b: bool) -> std::result::Result<Value, E> { Ok(Value::Bool(b)) }
+    fn visit_i64<E: de::Error>(self, i: i64) -> std::result::Result<Value, E> {
+        self.number(if i.unsigned_abs() <= MAX_EXACT as u64 { Value::Num(i as f64) } else { Value::Int(i) })
+    }
+    fn visit_u64<E: de::Error>(self, u: u64) -> std::result::Result<Value, E> {
+        self.number(match i64::try_from(u) {
+            Ok(i) if u > MAX_EXACT as u64 => Value::Int(i),
+            _ => Value::Num(u as f64),
+        })
+    }
+    fn visit_f64<E: de::Error>(self, n: f64) -> std::result::Result<Value, E> { self.number(Value::Num(n)) }
+    fn visit_str<E>(self, s: &str) -> std::result::Result<Value, E> { Ok(Value::Str(s.to_string())) }
+    fn visit_string<E>(self, s: String) -> std::result::Result<Value, E> { Ok(Value::Str(s)) }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(v) = seq.next_element_seed(self)? { items.push(v); }
+        Ok(Value::List(Rc::new(RefCell::new(items))))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Value, A::Error> {
+        let mut out = Dict::new();
+        while let Some(k) = map.next_key::<String>()? {
+            let v = map.next_value_seed(self)?;
+            out.insert(k, v);
+        }
+        Ok(Value::Dict(Rc::new(RefCell::new(out))))
+    }
+}
+
+pub fn stringify(v: &Value, indent: usize) -> Result<String> {
+    let mut w = Writer { out: String::new(), indent, open: Vec::new() };
+    w.value(v, 0)?;
+    Ok(w.out)
+}
+
+struct Writer {
+    out: String,
+    indent: usize,
+    // Containers being written, to catch a value inside itself
+    open: Vec<*const ()>,
+}
+
+impl Writer {
+    fn value(&mut self, v: &Value, depth: usize) -> Result<()> {
+        match v {
+            Value::Null => self.out.push_str("null"),
+            Value::Bool(b) => self.out.push_str(if *b { "true" } else { "false" }),
+            Value::Int(i) => self.out.push_str(&i.to_string()),
+            Value::Num(n) => self.number(*n)?,
+            Value::Decimal(d) => self.out.push_str(&d.to_string()),
+            Value::Str(s) => self.string(s),
+            Value::List(items) => {
+                let items = self.enter(Rc::as_ptr(items) as *const (), || items.borrow().clone())?;
+                self.seq(&items, depth)?;
+            }
+            Value::Array(arr) => {
+                let items = self.enter(Rc::as_ptr(arr) as *const (), || arr.data.borrow().clone())?;
+                self.seq(&items, depth)?;
+            }
+            Value::Dict(map) => {
+                let entries = self.enter(Rc::as_ptr(map) as *const (), || {
+                    map.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect::<Vec<_>>()
+                })?;
+                self.map(&entries, depth)?;
+            }
+            Value::StrArray2D { rows, cols, data } => {
+                let rows: Vec<Value> = (0..*rows)
+                    .map(|r| Value::List(Rc::new(RefCell::new(data[r * cols..(r + 1) * cols].iter().cloned().map(Value::Str).collect()))))
+                    .collect();
+                self.seq(&rows, depth)?;
+            }
+            Value::Object(obj) => {
+                let entries = self.enter(Rc::as_ptr(obj) as *const (), || {
+                    let o = obj.borrow();
+                    o.descriptor().properties.iter()
+                        .filter(|p| p.readable)
+                        .filter_map(|p| o.get_prop(&p.name).ok().map(|pv| (p.name.clone(), pv)))
+                        .collect::<Vec<_>>()
+                })?;
+                self.map(&entries, depth)?;
+            }
+            Value::Func(_) => return Err(BasilError("JSON_STRINGIFY$: cannot stringify a function".into())),
+            Value::Bytes(_) => return Err(BasilError("JSON_STRINGIFY$: cannot stringify BYTES; encode them with BYTES_BASE64$ first".into())),
+        }
+        if matches!(v, Value::List(_) | Value::Array(_) | Value::Dict(_) | Value::Object(_)) { self.open.pop(); }
+        Ok(())
+    }
+
+    // Snapshot of a container's contents, failing if it is already being written
+    fn enter<T>(&mut self, ptr: *const (), contents: impl FnOnce() -> T) -> Result<T> {
+        if self.open.contains(&ptr) {
+            return Err(BasilError("JSON_STRINGIFY$: cannot stringify a value that contains itself".into()));
+        }
+        self.open.push(ptr);
+        Ok(contents())
+    }
+
+    fn number(&mut self, n: f64) -> Result<()> {
+        if !n.is_finite() {
+            return Err(BasilError(format!("JSON_STRINGIFY$: cannot stringify {} (not a JSON number)", n)));
+        }
+        if n.fract() == 0.0 && n.abs() <= MAX_EXACT {
+            self.out.push_str(&(n as i64).to_string());
+        } else if let Some(num) = serde_json::Number::from_f64(n) {
+            self.out.push_str(&num.to_string());
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, s: &str) {
+        // Serializing a str cannot fail
+        self.out.push_str(&serde_json::to_string(s).unwrap_or_default());
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if self.indent > 0 {
+            self.out.push('\n');
+            self.out.push_str(&" ".repeat(self.indent * depth));
+        }
+    }
+
+    fn seq(&mut self, items: &[Value], depth: usize) -> Result<()> {
+        self.out.push('[');
+        for (i, it) in items.iter().enumerate() {
+            if i > 0 { self.out.push(','); }
+            self.newline(depth + 1);
+            self.value(it, depth + 1)?;
+        }
+        if !items.is_empty() { self.newline(depth); }
+        self.out.push(']');
+        Ok(())
+    }
+
+    fn map(&mut self, entries: &[(String, Value)], depth: usize) -> Result<()> {
+        self.out.push('{');
+        for (i, (k, v)) in entries.iter().enumerate() {
+            if i > 0 { self.out.push(','); }
+            self.newline(depth + 1);
+            self.string(k);
+            self.out.push_str(if self.indent > 0 { ": " } else { ":" });
+            self.value(v, depth + 1)?;
+        }
+        if !entries.is_empty() { self.newline(depth); }
+        self.out.push('}');
+        Ok(())
+    }
+}
@@ -44,12 +44,10 @@ use std::rc::Rc;
 use std::sync::Arc;
 use std::io::{self, Write, Read, Seek, SeekFrom};
 use crossterm::terminal::{enable_raw_mode, disable_raw_mode};
-use std::env;
 use std::time::Duration;
 use crossterm::event::{read, Event, KeyEvent, KeyCode};
 use crossterm::event::poll;
 use std::collections::{HashMap, HashSet};
-use std::fs::{self, OpenOptions};
 use std::path::{Path, PathBuf};
 // Note: Write trait is already imported via `use std::io::{self, Write, Read, Seek, SeekFrom};` above.
 
@@ -260,25 +258,46 @@ fn net_download_file_blocking(url: &str, dest_path: &str) -> i32 {
     0
 }
 
+pub mod bytes;
+pub mod classes;
+pub mod crypto;
+pub mod csv;
+pub mod datetime;
 pub mod debug;
+pub mod decimal;
+pub mod events;
+pub mod generators;
+pub mod json;
+pub mod math;
+pub mod output;
+pub mod providers;
+mod protocols;
+pub mod regex;
+pub mod router;
+pub mod sql;
+pub mod using;
+pub mod web;
+pub mod workers;
 mod basil_objects;
 
-use basil_common::{Result, BasilError};
-use basil_bytecode::{Program as BCProgram, Chunk, Value, Op, ElemType, ArrayObj, ObjectDescriptor, PropDesc, MethodDesc};
-use basil_objects::{Registry, register_objects};
+use basil_common::{Result, BasilError, SourceMap};
+use basil_bytecode::{Program as BCProgram, Chunk, Dict, Value, Op, ElemType, ArrayObj, ObjectDescriptor, PropDesc, MethodDesc, Rounding};
+use basil_objects::register_objects;
+use classes::{ClassRef, Instance};
+use providers::{Clock, Environment, FileStream, FileSystem, OpenMode};
+pub use basil_objects::{Registry, ObjectFactory};
+// Embedding API: values, conversions and host functions come from basil-bytecode
+pub use basil_bytecode::{FromValue, IntoValue, HostFunctions, VmCtx};
 use basil_parser::parse as parse_basil;
-use basil_compiler::compile as compile_basil;
+use basil_compiler::compile_with_host as compile_basil;
 use basil_bytecode::{deserialize_program};
+use datetime::DateTime;
 #[cfg(feature = "obj-base64")]
 use base64::{engine::general_purpose, Engine as _};
 #[cfg(feature = "obj-zip")]
 use basil_objects::zip as zip_utils;
 #[cfg(feature = "obj-curl")]
 use basil_objects::curl as curl_utils;
-#[cfg(any(feature = "obj-json", feature = "obj-csv"))]
-use serde_json::{Value as JValue};
-#[cfg(feature = "obj-csv")]
-use csv::{ReaderBuilder, WriterBuilder};
 #[cfg(feature = "obj-sqlite")]
 use basil_objects::sqlite as sqlite_utils;
 #[cfg(feature = "obj-audio")]
@@ -288,69 +307,40 @@ use basil_objects::midi as midi_utils;
 #[cfg(feature = "obj-daw")]
 use basil_objects::daw as daw_utils;
 
-#[cfg(feature = "obj-json")]
-fn value_to_jvalue(v: &Value) -> Result<JValue> {
-    use serde_json::Map;
+// Text shown by DESCRIBE for an object or registered type.
+// Seed RND from the clock, xor a constant; fall back to a nonzero constant
+fn time_seed() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0x1234_5678_9ABC_DEF0);
+    let seed = nanos ^ 0x9E37_79B9_7F4A_7C15u64;
+    if seed == 0 { 0xA5A5_5A5A_DEAD_BEEFu64 } else { seed }
+}
+
+fn class_of(v: &Value) -> Option<Rc<classes::Class>> {
     match v {
-        Value::Null => Ok(JValue::Null),
-        Value::Bool(b) => Ok(JValue::Bool(*b)),
-        Value::Int(i) => Ok(JValue::Number((*i).into())),
-        Value::Num(n) => serde_json::Number::from_f64(*n)
-            .map(JValue::Number)
-            .ok_or_else(|| BasilError("JSON_STRINGIFY$: NaN/Inf not representable".into())),
-        Value::Str(s) => Ok(JValue::String(s.clone())),
-        Value::Array(arr_rc) => {
-            let arr = arr_rc.as_ref();
-            let data = arr.data.borrow();
-            let mut out = Vec::with_capacity(data.len());
-            for elem in data.iter() {
-                out.push(value_to_jvalue(elem)?);
-            }
-            Ok(JValue::Array(out))
-        }
-        Value::Object(obj_rc) => {
-            let obj_ref = obj_rc.borrow();
-            let desc = obj_ref.descriptor();
-            let mut map = Map::new();
-            for prop in desc.properties.iter().filter(|p| p.readable) {
-                // get_prop returns Result<Value>
-                if let Ok(pv) = obj_ref.get_prop(&prop.name) {
-                    let jv = value_to_jvalue(&pv)?;
-                    map.insert(prop.name.clone(), jv);
-                }
-            }
-            Ok(JValue::Object(map))
-        }
-        Value::List(items) => {
-            let v = items.borrow();
-            let mut out = Vec::with_capacity(v.len());
-            for it in v.iter() {
-                out.push(value_to_jvalue(it)?);
-            }
-            Ok(JValue::Array(out))
-        }
-        Value::Dict(map) => {
-            let m = map.borrow();
-            let mut obj = Map::new();
-            for (k, v) in m.iter() {
-                obj.insert(k.clone(), value_to_jvalue(v)?);
-            }
-            Ok(JValue::Object(obj))
-        }
-        Value::StrArray2D { rows, cols, data } => {
-            let mut out: Vec<JValue> = Vec::with_capacity(*rows);
-            for r in 0..*rows {
-                let mut row: Vec<JValue> = Vec::with_capacity(*cols);
-                for c in 0..*cols {
-                    let idx = r * *cols + c;
-                    row.push(JValue::String(data[idx].clone()));
-                }
-                out.push(JValue::Array(row));
-            }
-            Ok(JValue::Array(out))
-        }
-        Value::Func(_) => Err(BasilError("JSON_STRINGIFY$: cannot stringify a function".into())),
+        Value::Object(rc) => rc.borrow().as_any()?.downcast_ref::<ClassRef>().map(|c| c.0.clone()),
+        _ => None,
+    }
+}
+
+fn instance_class(rc: &basil_bytecode::ObjectRef) -> Option<Rc<classes::Class>> {
+    rc.borrow().as_any()?.downcast_ref::<Instance>().map(|i| i.class.clone())
+}
+
+fn format_descriptor(desc: &ObjectDescriptor) -> String {
+    let mut s = String::new();
+    s.push_str(&format!("{} — v{}\n{}\n", desc.type_name, desc.version, desc.summary));
+    if !desc.properties.is_empty() {
+        s.push_str("Properties:\n");
+        for p in &desc.properties { s.push_str(&format!("  {} : {} {}{}\n", p.name, p.type_name, if p.readable {"R"} else {""}, if p.writable {"W"} else {""})); }
+    }
+    if !desc.methods.is_empty() {
+        s.push_str("Methods:\n");
+        for m in &desc.methods { s.push_str(&format!("  {}({}) -> {}\n", m.name, m.arg_names.join(", "), m.return_type)); }
     }
+    s
 }
 
 // --- Input provider abstraction for test mode ---
@@ -411,6 +401,20 @@ struct Frame {
     chunk: Rc<Chunk>,
     ip: usize,
     base: usize,
+    // Class whose method is running (PRIVATE member access), upper-case
+    owner: Option<Rc<str>>,
+    // Drop the return value (field initializers and SUB NEW of NEW Class(...))
+    discard: bool,
+    // Globals of the file-based CLASS instance whose method is running
+    env: Option<Rc<ClassEnv>>,
+    // Generator this frame is the body of (see generators.rs)
+    gen: Option<basil_bytecode::ObjectRef>,
+}
+
+// FOR EACH state: a (materialized) array, or an iterator object answering _NEXT
+enum Enumerator {
+    Arr(ArrEnum),
+    Obj { iter: basil_bytecode::ObjectRef, current: Value },
 }
 
 struct ArrEnum {
@@ -420,14 +424,17 @@ struct ArrEnum {
 }
 
 struct FileHandleEntry {
-    file: std::fs::File,
+    file: Box<dyn FileStream>,
     text: bool,
     readable: bool,
     writable: bool,
     owner_depth: usize,
+    // Bytes a CSV_READER took from the file but has not used yet
+    read_ahead: csv::ReadAhead,
 }
 
-struct HandlerEntry { handler_ip: usize }
+// TRY handler; RAISE unwinds to the frame and stack height the TRY started with.
+struct HandlerEntry { handler_ip: usize, depth: usize, sp: usize }
 
 // --- Struct type descriptors for pack/unpack ---
 #[derive(Clone)]
@@ -446,8 +453,28 @@ pub struct VM {
     // Keep a copy of global names for reflection/snapshots (REPL, :vars, etc.)
     global_names: Vec<String>,
     registry: Registry,
-    enums: Vec<ArrEnum>,
+    // Functions registered by the embedding application (see set_host_functions)
+    host: Rc<HostFunctions>,
+    // Replaceable services (see providers.rs); None input means the terminal
+    input: Option<Box<dyn InputProvider>>,
+    fs: Rc<dyn FileSystem>,
+    env: Rc<dyn Environment>,
+    clock: Rc<dyn Clock>,
+    // Timers and ON KEY/ON SIGNAL handlers (see events.rs)
+    events: events::Scheduler,
+    // Threads started by SPAWN, and the link to the spawning VM (see workers.rs)
+    workers: workers::Workers,
+    // Compiled REGEX_* patterns by pattern text (see regex.rs)
+    regexes: HashMap<String, Rc<regex::Regex>>,
+    // Scale and rounding of `/` on DECIMALs (DEC_SCALE%, DEC_ROUNDING$; see decimal.rs)
+    dec_scale: u32,
+    dec_rounding: Rounding,
+    enums: Vec<Enumerator>,
     current_line: u32,
+    // Optional map from generated lines back to template files (see set_source_map)
+    source_map: Option<SourceMap>,
+    current_file: Option<String>,
+    current_col: u32,
     // Suspension
     suspended: bool,
     // Test mode fields
@@ -461,11 +488,10 @@ pub struct VM {
     // Caches for CGI params
     get_params_cache: Option<Vec<String>>,    // name=value pairs from QUERY_STRING
     post_params_cache: Option<Vec<String>>,   // name=value pairs from stdin (x-www-form-urlencoded)
+    request_body: Option<Vec<u8>>,            // set by a host serving requests in-process; stdin otherwise
     // File I/O
     file_table: HashMap<i64, FileHandleEntry>,
     next_fh: i64,
-    // Control whether to auto-close handles on function return (used for class methods)
-    close_handles_on_ret: bool,
     // GOSUB stack and safety cap
     gosub_stack: Vec<usize>,
     gosub_max_depth: usize,
@@ -476,33 +502,63 @@ pub struct VM {
     current_exception: Option<String>,
     // Struct type descriptor registry
     struct_types: HashMap<String, VMTypeDesc>,
+    // Where PRINT output goes (buffered; see output.rs)
+    output: output::Output,
+    // OB captures open in the output when it was lent to us (see run_child); run() keeps them
+    capture_base: usize,
     // Output column tracking for TAB/AT/SPC helpers
     out_col: usize,
     // Pseudo-random generator state for RND
     rng_state: u64,
+    // Routes registered by ROUTE, and the params of the route being dispatched
+    routes: router::Routes,
+    route_params: Vec<(String, String)>,
+    route_path: String,
 }
 
 // --- Lightweight Class Instance object ---
-struct ClassInstance {
-    globals_names: Vec<String>,
-    values: Vec<Value>,
+// The globals of a file-based CLASS instance. Its methods run as frames of the calling
+// VM with this environment in place of the program globals (see Frame::env).
+struct ClassEnv {
+    names: Vec<String>,
+    values: std::cell::RefCell<Vec<Value>>,
     name_to_index: HashMap<String, usize>,
-    // Persist open file handles across method calls for this instance
-    file_table: HashMap<i64, FileHandleEntry>,
-    next_fh: i64,
+    // Resolved path of the class file, for breakpoints and error locations
+    file: String,
+}
+
+struct ClassInstance {
+    env: Rc<ClassEnv>,
 }
 
 impl ClassInstance {
-    fn new(globals_names: Vec<String>, values: Vec<Value>) -> Self {
+    fn new(names: Vec<String>, values: Vec<Value>, file: String) -> Self {
         let mut name_to_index = HashMap::new();
-        for (i, n) in globals_names.iter().enumerate() {
+        for (i, n) in names.iter().enumerate() {
             name_to_index.insert(n.to_ascii_uppercase(), i);
         }
-        Self { globals_names, values, name_to_index, file_table: HashMap::new(), next_fh: 1 }
+        let env = Rc::new(ClassEnv { names, values: std::cell::RefCell::new(values), name_to_index, file });
+        // Bind the FUNCs to this instance, so they keep its globals when passed around as
+        // values (timer and signal handlers, ROUTE, call_value). Weak: the instance owns them.
+        let weak: std::rc::Weak<dyn std::any::Any> = Rc::downgrade(&env) as std::rc::Weak<ClassEnv>;
+        for v in env.values.borrow_mut().iter_mut() {
+            if let Value::Func(f) = v {
+                *v = Value::Func(Rc::new(basil_bytecode::Function { bound: Some(weak.clone()), ..(**f).clone() }));
+            }
+        }
+        Self { env }
     }
 
     fn get_index(&self, name: &str) -> Option<usize> {
-        self.name_to_index.get(&name.to_ascii_uppercase()).copied()
+        self.env.name_to_index.get(&name.to_ascii_uppercase()).copied()
+    }
+
+    fn method(&self, name: &str) -> Result<Rc<basil_bytecode::Function>> {
+        let i = self.get_index(name).ok_or_else(|| BasilError("Unknown property or function in class.".into()))?;
+        match &self.env.values.borrow()[i] {
+            Value::Func(f) => Ok(f.clone()),
+            _ => Err(BasilError("Unknown property or function in class.".into())),
+        }
     }
 }
 
@@ -510,65 +566,40 @@ impl basil_bytecode::BasicObject for ClassInstance {
     fn type_name(&self) -> &str { "CLASS" }
 
     fn get_prop(&self, name: &str) -> Result<Value> {
-        if let Some(i) = self.get_index(name) {
-            let v = self.values[i].clone();
-            match v {
-                Value::Func(_) => Err(BasilError("Unknown property or function in class.".into())),
-                other => Ok(other),
-            }
-        } else {
-            Err(BasilError("Unknown property or function in class.".into()))
+        let values = self.env.values.borrow();
+        match self.get_index(name).map(|i| &values[i]) {
+            Some(Value::Func(_)) | None => Err(BasilError("Unknown property or function in class.".into())),
+            Some(v) => Ok(v.clone()),
         }
     }
 
     fn set_prop(&mut self, name: &str, v: Value) -> Result<()> {
-        if let Some(i) = self.get_index(name) {
-            if matches!(self.values[i], Value::Func(_)) {
-                return Err(BasilError("Unknown property or function in class.".into()));
-            }
-            self.values[i] = v;
-            Ok(())
-        } else {
-            Err(BasilError("Unknown property or function in class.".into()))
+        let mut values = self.env.values.borrow_mut();
+        match self.get_index(name) {
+            Some(i) if !matches!(values[i], Value::Func(_)) => { values[i] = v; Ok(()) }
+            _ => Err(BasilError("Unknown property or function in class.".into())),
         }
     }
 
+    // Calls from host code; the VM itself runs methods as frames (see Op::CallMethod).
     fn call(&mut self, method: &str, args: &[Value]) -> Result<Value> {
-        let i = self.get_index(method).ok_or_else(|| BasilError("Unknown property or function in class.".into()))?;
-        let f = match &self.values[i] { Value::Func(f) => f.clone(), _ => return Err(BasilError("Unknown property or function in class.".into())) };
-        // Run function in inner VM with this instance's globals
-        // Build a tiny program with empty top chunk (HALT) and same globals names
+        let f = self.method(method)?;
         let mut top = Chunk::default();
         top.push_op(Op::Halt);
-        let prog = BCProgram { chunk: top, globals: self.globals_names.clone() };
-        let mut vm = VM::new(prog);
-        // Move persistent file handles into inner VM and disable auto-close-on-ret for methods
-        vm.file_table = std::mem::take(&mut self.file_table);
-        vm.next_fh = self.next_fh;
-        vm.close_handles_on_ret = false;
-        // Seed globals with our instance values
-        vm.globals = self.values.clone();
-        // Prepare stack: place arguments starting at base 0
-        for a in args { vm.stack.push(a.clone()); }
-        // Push frame directly
-        let frame = Frame { chunk: f.chunk.clone(), ip: 0, base: 0 };
-        vm.frames.push(frame);
-        vm.run()?;
-        // Capture back persistent file handles into this instance
-        self.next_fh = vm.next_fh;
-        self.file_table = std::mem::take(&mut vm.file_table);
-        // Collect return value
-        let ret = vm.stack.pop().unwrap_or(Value::Null);
-        // Update our globals from inner VM (so mutations persist)
-        self.values = vm.globals.clone();
-        Ok(ret)
+        let mut vm = VM::new(BCProgram { chunk: top, globals: Vec::new() });
+        vm.push_class_frame(&self.env, &f, args.to_vec())?;
+        let res = vm.exec(1);
+        let _ = vm.output.flush();
+        res?;
+        Ok(vm.stack.pop().unwrap_or(Value::Null))
     }
 
     fn descriptor(&self) -> ObjectDescriptor {
         let mut props: Vec<PropDesc> = Vec::new();
         let mut methods: Vec<MethodDesc> = Vec::new();
-        for (i, n) in self.globals_names.iter().enumerate() {
-            match &self.values[i] {
+        let values = self.env.values.borrow();
+        for (i, n) in self.env.names.iter().enumerate() {
+            match &values[i] {
                 Value::Func(f) => {
                     methods.push(MethodDesc { name: n.clone(), arity: f.arity, arg_names: Vec::new(), return_type: "ANY".to_string() });
                 }
@@ -581,18 +612,22 @@ impl basil_bytecode::BasicObject for ClassInstance {
                 Value::Null => props.push(PropDesc { name: n.clone(), type_name: "NULL".to_string(), readable: true, writable: true }),
                 Value::List(_) => props.push(PropDesc { name: n.clone(), type_name: "LIST".to_string(), readable: true, writable: true }),
                 Value::Dict(_) => props.push(PropDesc { name: n.clone(), type_name: "DICT".to_string(), readable: true, writable: true }),
+                Value::Bytes(_) => props.push(PropDesc { name: n.clone(), type_name: "BYTES".to_string(), readable: true, writable: true }),
+                Value::Decimal(_) => props.push(PropDesc { name: n.clone(), type_name: "DECIMAL".to_string(), readable: true, writable: true }),
                 Value::StrArray2D { .. } => props.push(PropDesc { name: n.clone(), type_name: "STRARRAY2D".to_string(), readable: true, writable: true }),
             }
         }
         ObjectDescriptor { type_name: "CLASS".to_string(), version: "1.0".to_string(), summary: "Basil file-based class instance".to_string(), properties: props, methods, examples: Vec::new() }
     }
+
+    fn as_any(&self) -> Option<&dyn std::any::Any> { Some(self) }
 }
 
 impl VM {
     pub fn new(p: BCProgram) -> Self {
         let globals = vec![Value::Null; p.globals.len()];
         let top_chunk = Rc::new(p.chunk);
-        let frame = Frame { chunk: top_chunk, ip: 0, base: 0 };
+        let frame = Frame { chunk: top_chunk, ip: 0, base: 0, owner: None, discard: false, env: None, gen: None };
         let mut registry = Registry::new();
         register_objects(&mut registry);
         #[allow(unused_mut)]
@@ -603,8 +638,21 @@ impl VM {
             // snapshot the names so we can reflect later
             global_names: p.globals.clone(),
             registry,
+            host: Rc::new(HostFunctions::new()),
+            input: None,
+            fs: Rc::new(providers::OsFileSystem),
+            env: Rc::new(providers::OsEnvironment),
+            clock: Rc::new(providers::SystemClock),
+            events: events::Scheduler::default(),
+            workers: workers::Workers::default(),
+            regexes: HashMap::new(),
+            dec_scale: decimal::DEFAULT_SCALE,
+            dec_rounding: Rounding::HalfEven,
             enums: Vec::new(),
             current_line: 0,
+            source_map: None,
+            current_file: None,
+            current_col: 0,
             suspended: false,
             test_mode: false,
             trace: false,
@@ -615,25 +663,22 @@ impl VM {
             mock: None,
             get_params_cache: None,
             post_params_cache: None,
+            request_body: None,
             file_table: HashMap::new(),
             next_fh: 1,
-            close_handles_on_ret: true,
             gosub_stack: Vec::new(),
             gosub_max_depth: 4096,
             debugger: None,
             _handlers: Vec::new(),
             current_exception: None,
             struct_types: HashMap::new(),
+            output: output::Output::stdout(),
+            capture_base: 0,
             out_col: 0,
-            rng_state: {
-                // Seed with time-based value xor constant; fallback to a nonzero constant
-                let nanos = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .map(|d| d.as_nanos() as u64)
-                    .unwrap_or(0x1234_5678_9ABC_DEF0);
-                let seed = nanos ^ 0x9E37_79B9_7F4A_7C15u64;
-                if seed == 0 { 0xA5A5_5A5A_DEAD_BEEFu64 } else { seed }
-            },
+            rng_state: time_seed(),
+            routes: router::Routes::default(),
+            route_params: Vec::new(),
+            route_path: String::new(),
         };
         #[cfg(feature = "obj-ai")]
         {
@@ -663,6 +708,18 @@ impl VM {
 
     pub fn current_line(&self) -> u32 { self.current_line }
 
+    // File, line and column of the statement being executed, after source-map translation.
+    // Returns None before the first line marker.
+    pub fn current_location(&self) -> Option<(String, u32, u32)> {
+        if self.current_line == 0 { return None; }
+        let file = self.current_file.clone().or_else(|| self.script_path.clone()).unwrap_or_else(|| "<unknown>".into());
+        Some((file, self.current_line, self.current_col))
+    }
+
+    // Install a map from generated source lines (e.g. a precompiled template) to the
+    // original files; SetLine, error locations and breakpoints then use template positions.
+    pub fn set_source_map(&mut self, map: SourceMap) { self.source_map = Some(map); }
+
     // Suspension state API
     pub fn is_suspended(&self) -> bool { self.suspended }
     pub fn resume(&mut self) -> Result<()> {
@@ -689,10 +746,18 @@ impl VM {
 
     // Extension hook: allow embedding hosts to register additional object types.
     // This is a non-breaking API used by the Basilica GUI to expose APP.*, WEB.*, and BASILICA.MENU.* host objects.
-    pub fn registry_mut(&mut self) -> &mut basil_objects::Registry {
+    pub fn registry_mut(&mut self) -> &mut Registry {
         &mut self.registry
     }
 
+    pub fn registry(&self) -> &Registry { &self.registry }
+
+    // Host functions callable from the program. Compile it with the same table
+    // (basil_compiler::compile_with_host) so calls resolve to them.
+    pub fn set_host_functions(&mut self, host: Rc<HostFunctions>) { self.host = host; }
+
+    pub fn host_functions(&self) -> &Rc<HostFunctions> { &self.host }
+
     fn cur(&mut self) -> &mut Frame { self.frames.last_mut().expect("no frame") }
 
     // --- CGI param helpers ---
@@ -783,7 +848,7 @@ impl VM {
         inner(self, name, &mut seen)
     }
 
-    fn pack_struct_bytes(&self, dict_rc: &std::rc::Rc<std::cell::RefCell<HashMap<String, Value>>>, name: &str) -> Result<Vec<u8>> {
+    fn pack_struct_bytes(&self, dict_rc: &std::rc::Rc<std::cell::RefCell<Dict>>, name: &str) -> Result<Vec<u8>> {
         fn enforce_len(s: &str, n: usize) -> String {
             let bytes = s.as_bytes();
             if bytes.len() == n { return s.to_string(); }
@@ -820,8 +885,8 @@ impl VM {
                     return Err(BasilError("Struct contains variable-length fields; size is not fixed.".into()));
                 }
                 VMFieldKind::Struct(nm) => {
-                    let v = map.get(&f.name).cloned().unwrap_or(Value::Dict(std::rc::Rc::new(std::cell::RefCell::new(HashMap::new()))));
-                    let drc = match v { Value::Dict(rc)=> rc, _ => std::rc::Rc::new(std::cell::RefCell::new(HashMap::new())) };
+                    let v = map.get(&f.name).cloned().unwrap_or(Value::Dict(std::rc::Rc::new(std::cell::RefCell::new(Dict::new()))));
+                    let drc = match v { Value::Dict(rc)=> rc, _ => std::rc::Rc::new(std::cell::RefCell::new(Dict::new())) };
                     let nested = self.pack_struct_bytes(&drc, nm)?;
                     out.extend_from_slice(&nested);
                 }
@@ -836,7 +901,7 @@ impl VM {
         let fixed = self.sizeof_struct(&key).ok_or_else(|| BasilError("Struct contains variable-length fields; size is not fixed.".into()))?;
         if buf.len() != fixed { return Err(BasilError(format!("Unpack: expected {} bytes, got {}.", fixed, buf.len()))); }
         let mut offset = 0usize;
-        let mut map: HashMap<String, Value> = HashMap::new();
+        let mut map = Dict::new();
         for f in &td.fields {
             match &f.kind {
                 VMFieldKind::Int32 => {
@@ -881,23 +946,26 @@ impl VM {
     }
     fn ensure_get_params(&mut self) {
         if self.get_params_cache.is_none() {
-            let q = env::var("QUERY_STRING").unwrap_or_default();
+            let q = self.env.var("QUERY_STRING").unwrap_or_default();
             let v = self.parse_pairs(&q);
             self.get_params_cache = Some(v);
         }
     }
     fn ensure_post_params(&mut self) {
         if self.post_params_cache.is_some() { return; }
-        let clen: usize = env::var("CONTENT_LENGTH").ok().and_then(|s| s.parse().ok()).unwrap_or(0);
+        let clen: usize = self.env.var("CONTENT_LENGTH").and_then(|s| s.parse().ok()).unwrap_or(0);
         if clen == 0 { self.post_params_cache = Some(Vec::new()); return; }
-        let ctype = env::var("CONTENT_TYPE").unwrap_or_default();
+        let ctype = self.env.var("CONTENT_TYPE").unwrap_or_default();
         if !ctype.to_ascii_lowercase().starts_with("application/x-www-form-urlencoded") {
             // unsupported type for now
             self.post_params_cache = Some(Vec::new());
             return;
         }
-        let mut body = Vec::with_capacity(clen);
-        let _ = io::stdin().take(clen as u64).read_to_end(&mut body);
+        let mut body = Vec::new();
+        match self.request_body.take() {
+            Some(b) => body = b.into_iter().take(clen).collect(),
+            None => { let _ = io::stdin().take(clen as u64).read_to_end(&mut body); }
+        }
         let s = String::from_utf8_lossy(&body).to_string();
         let v = self.parse_pairs(&s);
         self.post_params_cache = Some(v);
@@ -910,7 +978,16 @@ impl VM {
         Value::Array(arr)
     }
 
+    // The handle positioned where its user expects it: a CSV_READER's unused read-ahead is
+    // given back first
     fn fh_get_mut(&mut self, h: i64) -> Result<&mut FileHandleEntry> {
+        let e = self.fh_entry(h)?;
+        e.read_ahead.give_back(e.file.as_mut())?;
+        Ok(e)
+    }
+
+    // The handle as it is, read-ahead included
+    fn fh_entry(&mut self, h: i64) -> Result<&mut FileHandleEntry> {
         if !self.file_table.contains_key(&h) {
             let mut keys: Vec<i64> = self.file_table.keys().copied().collect();
             keys.sort();
@@ -935,6 +1012,7 @@ impl VM {
         match v {
             Value::Int(i) => Ok(*i),
             Value::Num(n) => Ok(n.trunc() as i64),
+            Value::Decimal(d) => d.to_i64().ok_or_else(|| BasilError(format!("{} is too large for an integer", d))),
             other => Err(BasilError(format!("expected numeric value, got {}", self.type_of(other)))),
         }
     }
@@ -971,6 +1049,172 @@ impl VM {
 
     pub fn run(&mut self) -> Result<()> {
         if let Some(dbg) = &self.debugger { dbg.emit(debug::DebugEvent::Started); }
+        let mut res = self.exec(0);
+        // Timers and ON KEY outlive the top level until they are cleared or STOP_LOOP
+        if res.is_ok() && !self.suspended && self.has_pending_events() { res = self.run_events(None); }
+        if !self.suspended {
+            let joined = self.join_all_workers();
+            if res.is_ok() { res = joined; }
+        }
+        if !self.suspended { self.output.end_captures_to(self.capture_base); }
+        let _ = self.output.flush();
+        res?;
+        if self.suspended { return Ok(()); }
+        if let Some(dbg) = &self.debugger { dbg.emit(debug::DebugEvent::Exited); }
+        if !self.gosub_stack.is_empty() {
+            eprintln!("warning: program terminated with {} pending GOSUB frames (missing RETURN?)", self.gosub_stack.len());
+        }
+        Ok(())
+    }
+
+    // Call a function value after (or outside of) the main run, e.g. a route handler,
+    // or from a host function while the program runs. Runs until the pushed frame
+    // returns and yields its return value.
+    pub fn call_value(&mut self, f: &Value, args: Vec<Value>) -> Result<Value> {
+        let func = match f {
+            Value::Func(func) => func.clone(),
+            _ => return Err(BasilError("call target is not a function".into())),
+        };
+        if func.arity as usize != args.len() {
+            return Err(BasilError(format!("arity mismatch: expected {}, got {}", func.arity, args.len())));
+        }
+        let env = class_env_of(&func)?;
+        let depth = self.frames.len();
+        let base = self.stack.len();
+        self.stack.extend(args);
+        self.frames.push(Frame { chunk: func.chunk.clone(), ip: 0, base, owner: None, discard: false, env, gen: None });
+        self.finish_call(depth, base)
+    }
+
+    // Run a frame pushed on top of `depth` frames (arguments from stack slot `base`) until
+    // it returns, and pop its return value.
+    fn finish_call(&mut self, depth: usize, base: usize) -> Result<Value> {
+        // TRY handlers of the interrupted code must not catch a RAISE inside the call
+        let handlers = std::mem::take(&mut self._handlers);
+        let exception = self.current_exception.take();
+        let res = self.exec(depth);
+        self._handlers = handlers;
+        self.current_exception = exception;
+        if self.suspended {
+            self.suspended = false;
+            self.frames.truncate(depth);
+            self.stack.truncate(base);
+            return Err(BasilError("STOP inside a called function".into()));
+        }
+        match res {
+            Ok(()) => Ok(self.stack.pop().unwrap_or(Value::Null)),
+            Err(e) => {
+                self.frames.truncate(depth);
+                self.stack.truncate(base);
+                Err(e)
+            }
+        }
+    }
+
+    // Call a FUNC of the program by name (case-insensitive). FUNC definitions exist once
+    // run() has executed the program, and the VM may be called any number of times after.
+    // Output printed by the function is flushed before returning.
+    pub fn call_function(&mut self, name: &str, args: &[Value]) -> Result<Value> {
+        let f = match self.get_global(name) {
+            Some(f @ Value::Func(_)) => f,
+            Some(Value::Null) => return Err(BasilError(format!("{} is not defined yet; run() the program first", name))),
+            Some(other) => return Err(BasilError(format!("{} is not a function (TYPE={})", name, self.type_of(&other)))),
+            None => return Err(BasilError(format!("Unknown function: {}", name))),
+        };
+        let res = self.call_value(&f, args.to_vec());
+        let _ = self.output.flush();
+        res
+    }
+
+    // Value of a global variable (or FUNC) by name, case-insensitive.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        let idx = self.global_names.iter().position(|n| n.eq_ignore_ascii_case(name))?;
+        self.globals.get(idx).cloned()
+    }
+
+    // Keyboard input for INPUT$/INPUTC$/INKEY$/INKEY% (the terminal by default).
+    pub fn set_input(&mut self, input: Box<dyn InputProvider>) {
+        self.input = Some(input);
+        self.workers.custom_providers = true;
+    }
+
+    pub fn set_filesystem(&mut self, fs: Rc<dyn FileSystem>) {
+        self.fs = fs;
+        self.workers.custom_providers = true;
+    }
+
+    pub fn set_environment(&mut self, env: Rc<dyn Environment>) {
+        self.env = env;
+        self.workers.custom_providers = true;
+        self.get_params_cache = None;
+        self.post_params_cache = None;
+        self.request_body = None;
+    }
+
+    // The body of the request POST$ reads, for hosts that serve requests in-process
+    // (after set_environment, which starts a new request)
+    pub fn set_request_body(&mut self, body: Vec<u8>) {
+        self.request_body = Some(body);
+        self.post_params_cache = None;
+    }
+
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = clock;
+        self.workers.custom_providers = true;
+    }
+
+    // How SPAWNed workers get their providers and output; see workers.rs
+    pub fn set_worker_setup(&mut self, setup: workers::WorkerSetup) { self.workers.setup = Some(setup); }
+
+    pub fn clock(&self) -> &Rc<dyn Clock> { &self.clock }
+
+    // VMs started by EXEC/EVAL/CLASS share this VM's host functions and providers.
+    // Pending output is flushed first so it stays ahead of the child's.
+    // Run a child VM (CLASS file top level, EXEC, EVAL) with this VM's providers and output.
+    // The output is lent for the run, so OB captures and the host's sink see what it prints.
+    fn run_child(&mut self, child: &mut VM) -> Result<()> {
+        child.host = self.host.clone();
+        child.fs = self.fs.clone();
+        child.env = self.env.clone();
+        child.clock = self.clock.clone();
+        child.workers.setup = self.workers.setup.clone();
+        child.workers.custom_providers = self.workers.custom_providers;
+        let lent = std::mem::replace(&mut self.output, output::Output::new(Box::new(io::sink()), false));
+        child.capture_base = lent.capture_depth();
+        child.output = lent;
+        let res = child.run();
+        self.output = std::mem::replace(&mut child.output, output::Output::new(Box::new(io::sink()), false));
+        res
+    }
+
+    // Replace the output sink (stdout by default). With auto_flush every PRINT is
+    // flushed immediately, otherwise output is buffered until FLUSH or a full buffer.
+    pub fn set_output(&mut self, sink: Box<dyn Write>, auto_flush: bool) {
+        let _ = self.output.flush();
+        self.output = output::Output::new(sink, auto_flush);
+        self.workers.custom_providers = true;
+    }
+
+    // Write raw bytes to the output (after anything PRINTed so far).
+    pub fn write_output(&mut self, b: &[u8]) { self.output.write_bytes(b); }
+
+    pub fn flush_output(&mut self) -> std::io::Result<()> { self.output.flush() }
+
+    // Drop output that has not been flushed yet. Returns false if some output was
+    // already sent, i.e. a response can no longer be replaced by an error page.
+    pub fn discard_output(&mut self) -> bool { self.output.discard() }
+
+    // Routes registered by ROUTE statements during run().
+    pub fn routes(&self) -> &router::Routes { &self.routes }
+
+    // Params returned by ROUTE_PARAM$ and path returned by ROUTE_PATH$ for the current dispatch.
+    pub fn set_route_params(&mut self, path: &str, params: Vec<(String, String)>) {
+        self.route_path = path.to_string();
+        self.route_params = params;
+    }
+
+    // Execute until the frame stack drops to `stop_depth` (0 = program end).
+    fn exec(&mut self, stop_depth: usize) -> Result<()> {
         loop {
             let op = self.read_op()?;
             match op {
@@ -981,13 +1225,19 @@ impl VM {
                 }
                 Op::LoadGlobal => {
                     let i = self.read_u8()? as usize;
-                    let v = self.globals[i].clone();
+                    let v = match &self.cur().env {
+                        Some(env) => env.values.borrow()[i].clone(),
+                        None => self.globals[i].clone(),
+                    };
                     self.stack.push(v);
                 }
                 Op::StoreGlobal => {
                     let i = self.read_u8()? as usize;
                     let v = self.pop()?;
-                    self.globals[i] = v;
+                    match &self.cur().env {
+                        Some(env) => env.values.borrow_mut()[i] = v,
+                        None => self.globals[i] = v,
+                    }
                 }
 
                 Op::LoadLocal => {
@@ -1008,11 +1258,20 @@ impl VM {
                     let rb = self.pop()?;
                     let lb = self.pop()?;
                     match (&lb, &rb) {
+                        (Value::Bytes(a), Value::Bytes(b)) => {
+                            self.stack.push(bytes::value([&a[..], &b[..]].concat()));
+                        }
                         (Value::Str(_), _) | (_, Value::Str(_)) => {
-                            let ls = format!("{}", lb);
-                            let rs = format!("{}", rb);
+                            let ls = self.display_string(&lb)?;
+                            let rs = self.display_string(&rb)?;
                             self.stack.push(Value::Str(format!("{}{}", ls, rs)));
                         }
+                        _ if decimal::involved(&lb, &rb) => {
+                            self.stack.push(decimal::arith(decimal::Arith::Add, &lb, &rb, self.dec_scale, self.dec_rounding)?);
+                        }
+                        (Value::Int(a), Value::Int(b)) => {
+                            self.stack.push(math::int_arith(decimal::Arith::Add, *a, *b)?);
+                        }
                         _ => {
                             // numeric addition (use existing numeric coercion)
                             let a = self.as_num(lb)?;
@@ -1021,12 +1280,20 @@ impl VM {
                         }
                     }
                 },
-                Op::Sub => self.bin_num(|a,b| a-b)?,
-                Op::Mul => self.bin_num(|a,b| a*b)?,
-                Op::Div => self.bin_num(|a,b| a/b)?,
-                Op::Mod => self.bin_num(|a,b| a % b)?,
+                Op::Sub => self.bin_num(decimal::Arith::Sub, |a,b| a-b)?,
+                Op::Mul => self.bin_num(decimal::Arith::Mul, |a,b| a*b)?,
+                Op::Div => self.bin_num(decimal::Arith::Div, |a,b| a/b)?,
+                Op::Mod => self.bin_num(decimal::Arith::Mod, |a,b| a % b)?,
                 Op::Neg => {
                     let v = self.pop()?;
+                    if let Value::Decimal(d) = &v {
+                        self.stack.push(decimal::value(-&**d));
+                        continue;
+                    }
+                    if let Value::Int(i) = v {
+                        self.stack.push(math::int_neg(i)?);
+                        continue;
+                    }
                     let n = self.as_num(v)?;
                     self.stack.push(Value::Num(-n));
                 }
@@ -1078,7 +1345,7 @@ impl VM {
                     let handler_off = self.read_u16()? as usize;
                     let _finally_off = self.read_u16()? as usize;
                     let target_ip = self.cur().ip + handler_off;
-                    self._handlers.push(HandlerEntry { handler_ip: target_ip });
+                    self._handlers.push(HandlerEntry { handler_ip: target_ip, depth: self.frames.len(), sp: self.stack.len() });
                 }
                 Op::TryPop => {
                     let _ = self._handlers.pop();
@@ -1089,11 +1356,10 @@ impl VM {
                     // Pop message, convert to string, then transfer to nearest handler or abort
                     let msg_v = self.pop()?;
                     let msg = format!("{}", msg_v);
-                    if let Some(h) = self._handlers.last() {
+                    if let Some(target) = self.unwind_to_handler() {
                         // record message and jump to handler; also make it available on stack
                         self.current_exception = Some(msg.clone());
                         self.stack.push(Value::Str(msg));
-                        let target = h.handler_ip;
                         self.cur().ip = target;
                     } else {
                         return Err(BasilError(msg));
@@ -1104,9 +1370,8 @@ impl VM {
                     let msg = match self.current_exception.clone() { Some(m) => m, None => return Err(BasilError("Reraise without active exception".into())) };
                     // Pop current handler if any
                     let _ = self._handlers.pop();
-                    if let Some(h) = self._handlers.last() {
+                    if let Some(target) = self.unwind_to_handler() {
                         self.stack.push(Value::Str(msg));
-                        let target = h.handler_ip;
                         self.cur().ip = target;
                     } else {
                         return Err(BasilError(msg));
@@ -1114,6 +1379,7 @@ impl VM {
                 }
                 Op::Stop => {
                     if self.test_mode {
+                        let _ = self.output.flush();
                         std::process::exit(0);
                     } else {
                         self.suspended = true;
@@ -1131,7 +1397,9 @@ impl VM {
                             if f.arity as usize != argc {
                                 return Err(BasilError(format!("arity mismatch: expected {}, got {}", f.arity, argc)));
                             }
-                            let frame = Frame { chunk: f.chunk.clone(), ip: 0, base };
+                            // Functions of a class file run against their instance's globals
+                            let env = class_env_of(&f)?;
+                            let frame = Frame { chunk: f.chunk.clone(), ip: 0, base, owner: None, discard: false, env, gen: None };
                             self.frames.push(frame);
                         }
                         _ => return Err(BasilError("CALL target is not a function".into())),
@@ -1141,17 +1409,27 @@ impl VM {
                 Op::SetLine => {
                     let line = self.read_u16()? as u32;
                     self.current_line = line;
+                    if let Some(env) = &self.cur().env {
+                        // Lines of a class method are lines of the class file
+                        self.current_file = Some(env.file.clone());
+                    } else if let Some((file, loc)) = self.source_map.as_ref().and_then(|m| m.lookup(line)) {
+                        self.current_line = loc.line;
+                        self.current_col = loc.col;
+                        self.current_file = if file.is_empty() { None } else { Some(file.to_string()) };
+                    } else if self.source_map.is_none() {
+                        self.current_file = None;
+                    }
                     if self.test_mode {
                         if let Some(map) = &self.comments_map {
                             if let Some(list) = map.get(&line) {
-                                for text in list { println!("COMMENT: {}", text); }
+                                for text in list { self.output.write_str(&format!("COMMENT: {}\n", text)); }
                             }
                         }
                     }
                     if let Some(dbg) = &self.debugger {
-                        let file = self.script_path.clone().unwrap_or_else(|| "<unknown>".into());
+                        let file = self.current_file.clone().or_else(|| self.script_path.clone()).unwrap_or_else(|| "<unknown>".into());
                         let cur_depth = self.frames.len();
-                        if dbg.check_pause_point(&file, line as usize, cur_depth) {
+                        if dbg.check_pause_point(&file, self.current_line as usize, cur_depth) {
                             // Wait until resumed
                             loop {
                                 if let Ok(st) = dbg.state.lock() { if !st.paused { break; } }
@@ -1161,24 +1439,56 @@ impl VM {
                     }
                 }
 
+                Op::CallHost => {
+                    let name_cidx = self.read_u16()? as usize;
+                    let argc = self.read_u8()? as usize;
+                    let name = match &self.cur().chunk.consts[name_cidx] { Value::Str(s) => s.clone(), _ => return Err(BasilError("CALLHOST expects function name string const".into())) };
+                    let mut args = Vec::with_capacity(argc);
+                    for _ in 0..argc { args.push(self.pop()?); }
+                    args.reverse();
+                    let hf = self.host.get(&name).cloned().ok_or_else(|| BasilError(format!("Host function not available: {}", name)))?;
+                    hf.check_arity(args.len())?;
+                    let v = (hf.func)(self, &args)?;
+                    let v = hf.check_return(v)?;
+                    self.stack.push(v);
+                }
                 Op::Ret => {
                     let retv = self.pop().unwrap_or(Value::Null);
                     let depth = self.frames.len();
                     let frame = self.frames.pop().ok_or_else(|| BasilError("RET with no frame".into()))?;
                     self.stack.truncate(frame.base);
-                    self.stack.push(retv);
-                    // auto-close any file handles opened in this frame (unless suppressed for class methods)
-                    if self.close_handles_on_ret {
+                    // The end of a generator body finishes it; its resumer gets NULL
+                    let retv = match &frame.gen { Some(g) => { generators::finish(g); Value::Null } None => retv };
+                    if !frame.discard { self.stack.push(retv); }
+                    // auto-close any file handles opened in this frame (class methods and generators keep theirs open)
+                    if frame.env.is_none() && frame.gen.is_none() {
                         self.fh_close_owner_depth(depth);
                     }
-                    if self.frames.is_empty() { break; }
+                    if self.frames.len() <= stop_depth { break; }
+                }
+
+                Op::MakeGen => {
+                    // Return the call itself, suspended just past this op, as a GENERATOR
+                    let frame = self.frames.pop().ok_or_else(|| BasilError("MAKEGEN with no frame".into()))?;
+                    let locals = self.stack.split_off(frame.base);
+                    let g = generators::Generator::from_frame(&frame, locals);
+                    if !frame.discard { self.stack.push(Value::Object(Rc::new(std::cell::RefCell::new(g)))); }
+                    if self.frames.len() <= stop_depth { break; }
+                }
+                Op::Yield => {
+                    let v = self.pop()?;
+                    let frame = self.frames.pop().ok_or_else(|| BasilError("YIELD with no frame".into()))?;
+                    let g = frame.gen.as_ref().ok_or_else(|| BasilError("YIELD outside a generator".into()))?;
+                    generators::suspend(g, frame.ip, self.stack.split_off(frame.base));
+                    self.stack.push(v);
+                    if self.frames.len() <= stop_depth { break; }
                 }
 
                 Op::Print => {
                     let v = self.pop()?;
-                    let s = format!("{}", v);
+                    let s = self.display_string(&v)?;
                     if let Some(dbg) = &self.debugger { dbg.emit(debug::DebugEvent::Output(s.clone())); }
-                    print!("{}", s);
+                    self.output.write_str(&s);
                     // Update output column tracking
                     for ch in s.chars() {
                         match ch {
@@ -1190,14 +1500,14 @@ impl VM {
                             _ => { self.out_col += 1; }
                         }
                     }
-                    let _ = io::stdout().flush();
                 }
                 Op::Pop   => { let _ = self.pop()?; }
                 Op::ToInt => {
                     let v = self.pop()?;
                     match v {
                         Value::Int(i) => self.stack.push(Value::Int(i)),
-                        Value::Num(n) => self.stack.push(Value::Int(n.trunc() as i64)),
+                        Value::Num(n) => self.stack.push(Value::Int(math::checked_int(n.trunc()).ok_or_else(|| BasilError(format!("{} is out of range for an INTEGER", n)))?)),
+                        Value::Decimal(d) => self.stack.push(Value::Int(d.to_i64().ok_or_else(|| BasilError(format!("{} is out of range for an INTEGER", d)))?)),
                         _ => return Err(BasilError("ToInt expects a numeric value".into())),
                     }
                 }
@@ -1241,7 +1551,7 @@ impl VM {
                         ElemType::Num => Value::Num(0.0),
                         ElemType::Int => Value::Int(0),
                         ElemType::Str => Value::Str(String::new()),
-                        ElemType::Obj(Some(_)) => Value::Dict(Rc::new(std::cell::RefCell::new(HashMap::new()))),
+                        ElemType::Obj(Some(_)) => Value::Dict(Rc::new(std::cell::RefCell::new(Dict::new()))),
                         ElemType::Obj(None) => Value::Null,
                     };
                     let mut data = Vec::with_capacity(total);
@@ -1325,12 +1635,16 @@ impl VM {
 
                 // enumeration over arrays
                 Op::EnumNew => {
-                    let it = self.pop()?;
+                    let mut it = self.pop()?;
+                    // _ITER may hand back a native collection or an iterator object
+                    if let Value::Object(rc) = &it {
+                        if let Some(v) = self.call_protocol(&rc.clone(), "_ITER", Vec::new())? { it = v; }
+                    }
                     match it {
                         Value::Array(rc) => {
                             let total = rc.dims.iter().copied().fold(1usize, |acc, d| acc.saturating_mul(d));
                             let handle = self.enums.len();
-                            self.enums.push(ArrEnum { arr: rc, cur: -1, total });
+                            self.enums.push(Enumerator::Arr(ArrEnum { arr: rc, cur: -1, total }));
                             self.stack.push(Value::Int(handle as i64));
                         }
                         Value::List(items_rc) => {
@@ -1341,7 +1655,7 @@ impl VM {
                             let arr = Rc::new(ArrayObj { elem: ElemType::Obj(None), dims: vec![data.len()], data: std::cell::RefCell::new(data) });
                             let total = arr.dims.iter().copied().fold(1usize, |acc, d| acc.saturating_mul(d));
                             let handle = self.enums.len();
-                            self.enums.push(ArrEnum { arr, cur: -1, total });
+                            self.enums.push(Enumerator::Arr(ArrEnum { arr, cur: -1, total }));
                             self.stack.push(Value::Int(handle as i64));
                         }
                         Value::Dict(map_rc) => {
@@ -1352,12 +1666,22 @@ impl VM {
                             let arr = Rc::new(ArrayObj { elem: ElemType::Str, dims: vec![data.len()], data: std::cell::RefCell::new(data) });
                             let total = arr.dims.iter().copied().fold(1usize, |acc, d| acc.saturating_mul(d));
                             let handle = self.enums.len();
-                            self.enums.push(ArrEnum { arr, cur: -1, total });
+                            self.enums.push(Enumerator::Arr(ArrEnum { arr, cur: -1, total }));
                             self.stack.push(Value::Int(handle as i64));
                         }
-                        Value::Object(_) => {
-                            let ty = self.type_of(&it);
-                            return Err(BasilError(format!("FOR EACH expects an array or iterable object after IN (got TYPE={}).", ty)));
+                        Value::Bytes(b) => {
+                            // Enumerate byte values as INTEGERs
+                            let data: Vec<Value> = b.iter().map(|x| Value::Int(*x as i64)).collect();
+                            let arr = Rc::new(ArrayObj { elem: ElemType::Int, dims: vec![data.len()], data: std::cell::RefCell::new(data) });
+                            let total = arr.dims[0];
+                            let handle = self.enums.len();
+                            self.enums.push(Enumerator::Arr(ArrEnum { arr, cur: -1, total }));
+                            self.stack.push(Value::Int(handle as i64));
+                        }
+                        Value::Object(rc) if protocols::has_protocol(&rc, "_NEXT") => {
+                            let handle = self.enums.len();
+                            self.enums.push(Enumerator::Obj { iter: rc, current: Value::Null });
+                            self.stack.push(Value::Int(handle as i64));
                         }
                         other => {
                             let ty = self.type_of(&other);
@@ -1370,19 +1694,32 @@ impl VM {
                         Some(Value::Int(i)) => *i as usize,
                         _ => return Err(BasilError("ENUM_MOVENEXT requires enumerator handle on stack".into())),
                     };
-                    let e = self.enums.get_mut(handle).ok_or_else(|| BasilError("bad enumerator handle".into()))?;
-                    if (e.cur + 1) < e.total as isize { e.cur += 1; self.stack.push(Value::Bool(true)); }
-                    else { self.stack.push(Value::Bool(false)); }
+                    let more = match self.enums.get_mut(handle).ok_or_else(|| BasilError("bad enumerator handle".into()))? {
+                        Enumerator::Arr(e) => {
+                            if (e.cur + 1) < e.total as isize { e.cur += 1; true } else { false }
+                        }
+                        Enumerator::Obj { iter, .. } => {
+                            let iter = iter.clone();
+                            let next = self.iter_next(&iter)?;
+                            let more = next.is_some();
+                            if let Some(Enumerator::Obj { current, .. }) = self.enums.get_mut(handle) { *current = next.unwrap_or(Value::Null); }
+                            more
+                        }
+                    };
+                    self.stack.push(Value::Bool(more));
                 }
                 Op::EnumCurrent => {
                     let handle = match self.stack.last() {
                         Some(Value::Int(i)) => *i as usize,
                         _ => return Err(BasilError("ENUM_CURRENT requires enumerator handle on stack".into())),
                     };
-                    let e = self.enums.get(handle).ok_or_else(|| BasilError("bad enumerator handle".into()))?;
-                    if e.cur < 0 { return Err(BasilError("ENUM_CURRENT before first element".into())); }
-                    let lin = e.cur as usize;
-                    let val = e.arr.data.borrow()[lin].clone();
+                    let val = match self.enums.get(handle).ok_or_else(|| BasilError("bad enumerator handle".into()))? {
+                        Enumerator::Arr(e) => {
+                            if e.cur < 0 { return Err(BasilError("ENUM_CURRENT before first element".into())); }
+                            e.arr.data.borrow()[e.cur as usize].clone()
+                        }
+                        Enumerator::Obj { current, .. } => current.clone(),
+                    };
                     self.stack.push(val);
                 }
                 Op::EnumDispose => {
@@ -1412,7 +1749,14 @@ impl VM {
                     let target = self.pop()?;
                     match target {
                         Value::Object(rc) => {
-                            let v = rc.borrow().get_prop(&prop)?;
+                            let v = match instance_class(&rc) {
+                                Some(class) => {
+                                    let i = class.field(&prop, self.caller_class().as_deref())?;
+                                    let obj = rc.borrow();
+                                    obj.as_any().and_then(|a| a.downcast_ref::<Instance>()).map(|inst| inst.fields[i].clone()).unwrap_or(Value::Null)
+                                }
+                                None => rc.borrow().get_prop(&prop)?,
+                            };
                             self.stack.push(v);
                         }
                         Value::Dict(map_rc) => {
@@ -1431,7 +1775,14 @@ impl VM {
                     let target = self.pop()?;
                     match target {
                         Value::Object(rc) => {
-                            rc.borrow_mut().set_prop(&prop, val)?;
+                            match instance_class(&rc) {
+                                Some(class) => {
+                                    let i = class.field(&prop, self.caller_class().as_deref())?;
+                                    let mut obj = rc.borrow_mut();
+                                    if let Some(inst) = obj.as_any_mut().and_then(|a| a.downcast_mut::<Instance>()) { inst.fields[i] = val; }
+                                }
+                                None => rc.borrow_mut().set_prop(&prop, val)?,
+                            }
                         }
                         Value::Dict(map_rc) => {
                             map_rc.borrow_mut().insert(prop, val);
@@ -1450,28 +1801,110 @@ impl VM {
                     let target = self.pop()?;
                     match target {
                         Value::Object(rc) => {
-                            let v = rc.borrow_mut().call(&method, &args)?;
-                            self.stack.push(v);
+                            if let Some(class) = instance_class(&rc) {
+                                let m = class.method(&method, self.caller_class().as_deref())?.clone();
+                                self.push_method_frame(&class.name, &method, &m, Value::Object(rc), args, false)?;
+                            } else if let Some((env, f)) = class_file_method(&rc, &method)? {
+                                self.push_class_frame(&env, &f, args)?;
+                            } else if generators::is_generator(&rc) && method.eq_ignore_ascii_case("_NEXT") {
+                                if !args.is_empty() { return Err(BasilError(format!("GENERATOR._NEXT expects 0 argument(s), got {}", args.len()))); }
+                                if !self.push_generator_frame(&rc)? { self.stack.push(Value::Null); }
+                            } else if csv::is_reader(&rc) && method.eq_ignore_ascii_case("_NEXT") {
+                                if !args.is_empty() { return Err(BasilError(format!("CSV_READER._NEXT expects 0 argument(s), got {}", args.len()))); }
+                                let v = self.csv_next(&rc)?;
+                                self.stack.push(v);
+                            } else {
+                                let v = rc.borrow_mut().call(&method, &args)?;
+                                self.stack.push(v);
+                            }
                         }
                         _ => return Err(BasilError("CALLMETHOD on non-object".into())),
                     }
                 }
+                Op::MakeClass => {
+                    let name_cidx = self.read_u16()? as usize;
+                    let nfields = self.read_u8()? as usize;
+                    let nmethods = self.read_u8()? as usize;
+                    let name = match &self.cur().chunk.consts[name_cidx] { Value::Str(s) => s.clone(), _ => return Err(BasilError("MAKECLASS expects class name string const".into())) };
+                    let init = match self.pop()? { Value::Func(f) => Some(f), _ => None };
+                    let mut methods = Vec::with_capacity(nmethods);
+                    for _ in 0..nmethods {
+                        let params = format!("{}", self.pop()?).split(", ").filter(|p| !p.is_empty()).map(String::from).collect();
+                        let private = is_truthy(&self.pop()?);
+                        match self.pop()? { Value::Func(f) => methods.push((f, private, params)), _ => return Err(BasilError("MAKECLASS expects method functions".into())) }
+                    }
+                    methods.reverse();
+                    let mut fields = Vec::with_capacity(nfields);
+                    for _ in 0..nfields {
+                        let private = is_truthy(&self.pop()?);
+                        fields.push((format!("{}", self.pop()?), private));
+                    }
+                    fields.reverse();
+                    let base = match self.pop()? {
+                        Value::Null => None,
+                        other => Some(class_of(&other).ok_or_else(|| BasilError(format!("CLASS {}: base is not a class", name)))?),
+                    };
+                    let class = classes::Class::new(name, base, fields, methods, init)?;
+                    let rc: basil_bytecode::ObjectRef = Rc::new(std::cell::RefCell::new(ClassRef(Rc::new(class))));
+                    self.stack.push(Value::Object(rc));
+                }
+                Op::NewInstance => {
+                    let argc = self.read_u8()? as usize;
+                    let mut args = Vec::with_capacity(argc);
+                    for _ in 0..argc { args.push(self.pop()?); }
+                    args.reverse();
+                    let cv = self.pop()?;
+                    let class = class_of(&cv).ok_or_else(|| BasilError(format!("NEW expects a class, got {}", self.type_of(&cv))))?;
+                    let rc: basil_bytecode::ObjectRef = Rc::new(std::cell::RefCell::new(Instance::new(class.clone())));
+                    let inst = Value::Object(rc);
+                    // The instance is the result; SUB NEW and the field initializers run on top of it
+                    // (base initializers first, then NEW) and their return values are dropped.
+                    self.stack.push(inst.clone());
+                    match class.constructor() {
+                        Some(m) => {
+                            if m.func.arity as usize != argc + 1 {
+                                return Err(BasilError(format!("{} expects {} constructor argument(s), got {}", class.name, m.func.arity - 1, argc)));
+                            }
+                            self.push_method_frame(&class.name, "NEW", m, inst.clone(), args, true)?;
+                        }
+                        None if argc > 0 => return Err(BasilError(format!("{} expects 0 constructor argument(s), got {}", class.name, argc))),
+                        None => {}
+                    }
+                    for init in class.inits.iter().rev() {
+                        self.push_method_frame(&class.name, "initializer", init, inst.clone(), Vec::new(), true)?;
+                    }
+                }
+                Op::CallSuper => {
+                    let meth_cidx = self.read_u16()? as usize;
+                    let argc = self.read_u8()? as usize;
+                    let method = match &self.cur().chunk.consts[meth_cidx] { Value::Str(s) => s.clone(), _ => return Err(BasilError("CALLSUPER expects method name string const".into())) };
+                    let mut args = Vec::with_capacity(argc);
+                    for _ in 0..argc { args.push(self.pop()?); }
+                    args.reverse();
+                    let me = self.pop()?;
+                    let cv = self.pop()?;
+                    let class = class_of(&cv).ok_or_else(|| BasilError("SUPER: base class is not defined".into()))?;
+                    let m = class.method(&method, self.caller_class().as_deref())?.clone();
+                    self.push_method_frame(&class.name, &method, &m, me, args, false)?;
+                }
                 Op::DescribeObj => {
                     let target = self.pop()?;
                     match target {
                         Value::Object(rc) => {
                             let desc = rc.borrow().descriptor();
-                            // simple formatting
-                            let mut s = String::new();
-                            s.push_str(&format!("{} — v{}\n{}\n", desc.type_name, desc.version, desc.summary));
-                            if !desc.properties.is_empty() {
-                                s.push_str("Properties:\n");
-                                for p in desc.properties { s.push_str(&format!("  {} : {} {}{}\n", p.name, p.type_name, if p.readable {"R"} else {""}, if p.writable {"W"} else {""})); }
-                            }
-                            if !desc.methods.is_empty() {
-                                s.push_str("Methods:\n");
-                                for m in desc.methods { s.push_str(&format!("  {}({}) -> {}\n", m.name, m.arg_names.join(", "), m.return_type)); }
-                            }
+                            self.stack.push(Value::Str(format_descriptor(&desc)));
+                        }
+                        // DESCRIBE "Type": a registered object type, or the list of types for ""
+                        Value::Str(t) => {
+                            let s = if t.trim().is_empty() {
+                                let names = self.registry.type_names();
+                                if names.is_empty() { "No object types registered\n".to_string() } else { format!("Object types:\n  {}\n", names.join("\n  ")) }
+                            } else {
+                                match self.registry.descriptor(t.trim()) {
+                                    Some(desc) => format_descriptor(desc),
+                                    None => return Err(BasilError(format!("DESCRIBE: unknown object type: {}", t))),
+                                }
+                            };
                             self.stack.push(Value::Str(s));
                         }
                         Value::Array(arr_rc) => {
@@ -1497,13 +1930,12 @@ impl VM {
                     // Pop filename and instantiate class instance
                     let fname_v = self.pop()?;
                     let fname = match fname_v { Value::Str(s)=>s, other=> return Err(BasilError(format!("CLASS(filename) expects a string, got {}", self.type_of(&other)))) };
-                    let (prog, resolved_path) = self.load_class_program(&fname)?;
+                    let (prog, resolved_path) = self.load_program(&fname)?.ok_or_else(|| BasilError("Class file not found.".into()))?;
                     // Run top-level of class program in an inner VM to initialize globals
                     let mut inner = VM::new(prog.clone());
                     inner.set_script_path(resolved_path.clone());
-                    inner.run()?;
-                    let class_vals = inner.globals.clone();
-                    let inst = ClassInstance::new(prog.globals.clone(), class_vals);
+                    self.run_child(&mut inner)?;
+                    let inst = ClassInstance::new(prog.globals, std::mem::take(&mut inner.globals), resolved_path);
                     let rc: basil_bytecode::ObjectRef = Rc::new(std::cell::RefCell::new(inst));
                     self.stack.push(Value::Object(rc));
                 }
@@ -1544,8 +1976,12 @@ impl VM {
                     let target = self.pop()?;
                     match target {
                         Value::Object(rc) => {
-                            let v = rc.borrow_mut().call(&method, &args)?;
-                            self.stack.push(v);
+                            if let Some((env, f)) = class_file_method(&rc, &method)? {
+                                self.push_class_frame(&env, &f, args)?;
+                            } else {
+                                let v = rc.borrow_mut().call(&method, &args)?;
+                                self.stack.push(v);
+                            }
                         }
                         _ => return Err(BasilError("CALLMEMBER on non-object".into())),
                     }
@@ -1558,10 +1994,10 @@ impl VM {
                     let code_v = self.pop()?;
                     let code = match code_v { Value::Str(s)=>s, other=> return Err(BasilError(format!("EXEC expects a STRING, got {}", self.type_of(&other)))) };
                     let ast = parse_basil(&code)?;
-                    let prog = compile_basil(&ast)?;
+                    let prog = compile_basil(&ast, &self.host)?;
                     let mut child = VM::new(prog.clone());
                     if let Some(sp) = &self.script_path { child.set_script_path(sp.clone()); }
-                    child.run()?;
+                    self.run_child(&mut child)?;
                     // no value pushed
                 }
                 Op::EvalString => {
@@ -1569,10 +2005,10 @@ impl VM {
                     let expr = match expr_v { Value::Str(s)=>s, other=> return Err(BasilError(format!("EVAL expects a STRING, got {}", self.type_of(&other)))) };
                     let src = format!("LET __EVAL_RES = ({});", expr);
                     let ast = parse_basil(&src)?;
-                    let prog = compile_basil(&ast)?;
+                    let prog = compile_basil(&ast, &self.host)?;
                     let mut child = VM::new(prog.clone());
                     if let Some(sp) = &self.script_path { child.set_script_path(sp.clone()); }
-                    child.run()?;
+                    self.run_child(&mut child)?;
                     // locate result global
                     let mut idx_opt: Option<usize> = None;
                     for (i, name) in prog.globals.iter().enumerate() {
@@ -1590,13 +2026,22 @@ impl VM {
                     let mut args = Vec::with_capacity(argc);
                     for _ in 0..argc { args.push(self.pop()?); }
                     args.reverse();
+                    // Input, terminal control and SHELL talk to the console directly: send pending output first
+                    if matches!(bid, 6..=8 | 19 | 60 | 230..=255) { let _ = self.output.flush(); }
+                    // LEN, MID$, LEFT$, RIGHT$ and INSTR count bytes when handed BYTES
+                    if let (1..=5, Some(Value::Bytes(b))) = (bid, args.first()) {
+                        let v = bytes::string_builtin(bid, b, &args)?;
+                        self.stack.push(v);
+                        continue;
+                    }
 
                     match bid {
                         // --- Math builtins ---
                         70 => { // ABS(x)
                             if argc != 1 { return Err(BasilError("ABS expects 1 argument".into())); }
                             match &args[0] {
-                                Value::Int(i) => { self.stack.push(Value::Int(i.abs())); }
+                                Value::Int(i) => { self.stack.push(Value::Int(i.checked_abs().ok_or_else(|| BasilError(format!("INTEGER overflow: ABS({})", i)))?)); }
+                                Value::Decimal(d) => { self.stack.push(decimal::value(d.abs())); }
                                 other => { let x = self.as_num(other.clone())?; self.stack.push(Value::Num(x.abs())); }
                             }
                         }
@@ -1616,7 +2061,11 @@ impl VM {
                             if argc != 1 { return Err(BasilError("INT expects 1 argument".into())); }
                             match &args[0] {
                                 Value::Int(i) => self.stack.push(Value::Int(*i)),
-                                other => { let x = self.as_num(other.clone())?; self.stack.push(Value::Int(x.floor() as i64)); }
+                                Value::Decimal(d) => {
+                                    let whole = d.round(0, Rounding::Floor);
+                                    self.stack.push(Value::Int(self.to_i64(&decimal::value(whole))?));
+                                }
+                                other => { let x = self.as_num(other.clone())?; self.stack.push(Value::Int(math::int_of_f64(x.floor(), "INT")?)); }
                             }
                         }
                         75 => { // LOG(x) -> natural logarithm
@@ -1625,10 +2074,18 @@ impl VM {
                             if x <= 0.0 { return Err(BasilError("LOG domain error: x must be > 0".into())); }
                             self.stack.push(Value::Num(x.ln()));
                         }
-                        76 => { // RND() -> random float in [0, 1)
-                            if argc != 0 { return Err(BasilError("RND expects 0 arguments".into())); }
-                            let r = self.rnd_f64();
-                            self.stack.push(Value::Num(r));
+                        76 => { // RND() -> random float in [0, 1); RND(n) -> 1..n; RND(lo, hi) -> lo..hi
+                            if argc > 2 { return Err(BasilError("RND expects 0 to 2 arguments".into())); }
+                            if argc == 0 {
+                                let r = self.rnd_f64();
+                                self.stack.push(Value::Num(r));
+                                continue;
+                            }
+                            let (lo, hi) = if argc == 1 { (1, self.to_i64(&args[0])?) } else { (self.to_i64(&args[0])?, self.to_i64(&args[1])?) };
+                            if hi < lo { return Err(BasilError(if argc == 1 { "RND: n must be at least 1".into() } else { format!("RND: empty range {} to {}", lo, hi) })); }
+                            let span = (hi as i128 - lo as i128 + 1) as u128;
+                            let pick = ((self.rnd_u64() as u128 * span) >> 64) as i128;
+                            self.stack.push(Value::Int((lo as i128 + pick) as i64));
                         }
                         77 => { // SIN(x)
                             if argc != 1 { return Err(BasilError("SIN expects 1 argument".into())); }
@@ -1644,6 +2101,31 @@ impl VM {
                             if argc != 1 { return Err(BasilError("TAN expects 1 argument".into())); }
                             let x = self.as_num(args[0].clone())?; self.stack.push(Value::Num(x.tan()));
                         }
+                        66 => { // ROUND(x [, digits [, mode$]])
+                            if !(1..=3).contains(&argc) { return Err(BasilError("ROUND expects 1 to 3 arguments".into())); }
+                            let digits = if argc >= 2 { self.to_i64(&args[1])? } else { 0 };
+                            let mode = if argc == 3 { decimal::rounding_arg(&args[2], "ROUND")? } else { Rounding::HalfEven };
+                            self.stack.push(math::round(&args[0], digits, mode)?);
+                        }
+                        67 => { // FIX(x) -> INTEGER toward zero
+                            if argc != 1 { return Err(BasilError("FIX expects 1 argument".into())); }
+                            self.stack.push(math::fix(&args[0])?);
+                        }
+                        68 => { // SGN(x) -> -1, 0 or 1
+                            if argc != 1 { return Err(BasilError("SGN expects 1 argument".into())); }
+                            self.stack.push(math::sgn(&args[0])?);
+                        }
+                        69 => { // RANDOMIZE [seed]
+                            if argc > 1 { return Err(BasilError("RANDOMIZE expects 0 or 1 argument".into())); }
+                            let seed = match args.first() {
+                                None => None,
+                                Some(Value::Num(n)) if n.fract() != 0.0 => Some(n.to_bits()),
+                                Some(Value::Str(s)) => Some(s.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01b3))),
+                                Some(v) => Some(self.to_i64(v)? as u64),
+                            };
+                            self.randomize(seed);
+                            self.stack.push(Value::Null);
+                        }
                         // --- Print helpers and formatting ---
                         80 => { // SPC(n) -> string of n spaces
                             if argc != 1 { return Err(BasilError("SPC expects 1 argument".into())); }
@@ -1663,10 +2145,10 @@ impl VM {
                                 self.stack.push(Value::Str(" ".repeat(spaces)));
                             }
                         }
-                        82 => { // USING$(fmt$, ...)
+                        82 => { // USING$(mask$, ...) -- PRINT USING masks, or the older %d/%f/%s style
                             if argc < 1 { return Err(BasilError("USING$ expects at least 1 argument (format)".into())); }
                             let fmt = match &args[0] { Value::Str(s)=>s.clone(), _ => return Err(BasilError("USING$: first argument must be a string format".into())) };
-                            let out = self.using_format(&fmt, &args[1..])?;
+                            let out = if using::is_printf_style(&fmt) { self.using_format(&fmt, &args[1..])? } else { using::using(&fmt, &args[1..])? };
                             self.stack.push(Value::Str(out));
                         }
                         1 => { // LEN(arg)
@@ -1751,14 +2233,13 @@ impl VM {
                             if argc != 2 { return Err(BasilError("STRUCT_PACK expects 2 arguments (value, typeName)".into())); }
                             let tname = match &args[1] { Value::Str(s)=>s.clone(), other=> return Err(BasilError(format!("STRUCT_PACK: type name must be string, got {}", self.type_of(other)))) };
                             let dict_rc = match &args[0] { Value::Dict(rc) => rc.clone(), _ => return Err(BasilError("STRUCT_PACK: value must be a struct/dict".into())) };
-                            let bytes = self.pack_struct_bytes(&dict_rc, &tname)?;
-                            let s = String::from_utf8_lossy(&bytes).to_string();
-                            self.stack.push(Value::Str(s));
+                            let packed = self.pack_struct_bytes(&dict_rc, &tname)?;
+                            self.stack.push(bytes::value(packed));
                         }
-                        164 => { // STRUCT_UNPACK(str, type$)
-                            if argc != 2 { return Err(BasilError("STRUCT_UNPACK expects 2 arguments (buffer$, typeName)".into())); }
+                        164 => { // STRUCT_UNPACK(buffer, type$) -- BYTES, or a STRING's UTF-8
+                            if argc != 2 { return Err(BasilError("STRUCT_UNPACK expects 2 arguments (buffer, typeName)".into())); }
                             let tname = match &args[1] { Value::Str(s)=>s.clone(), other=> return Err(BasilError(format!("STRUCT_UNPACK: type name must be string, got {}", self.type_of(other)))) };
-                            let buf = match &args[0] { Value::Str(s)=> s.as_bytes().to_vec(), other=> return Err(BasilError(format!("STRUCT_UNPACK: buffer must be string, got {}", self.type_of(other)))) };
+                            let buf = match &args[0] { Value::Bytes(b)=> b.to_vec(), Value::Str(s)=> s.as_bytes().to_vec(), other=> return Err(BasilError(format!("STRUCT_UNPACK: buffer must be BYTES or string, got {}", self.type_of(other)))) };
                             let v = self.unpack_struct_from(&buf, &tname)?;
                             self.stack.push(v);
                         }
@@ -1860,8 +2341,8 @@ impl VM {
                             if !(argc == 0 || argc == 1) { return Err(BasilError("INPUT$ expects 0 or 1 argument".into())); }
                             if argc == 1 {
                                 let prompt = match &args[0] { Value::Str(s) => s.clone(), other => format!("{}", other) };
-                                print!("{}", prompt);
-                                let _ = io::stdout().flush();
+                                self.output.write_str(&prompt);
+                                let _ = self.output.flush();
                             }
                             if self.test_mode {
                                 // enforce max inputs
@@ -1873,7 +2354,10 @@ impl VM {
                                 if self.trace {
                                     if let Some(p) = &self.script_path { if self.current_line > 0 { let fname = std::path::Path::new(p).file_name().and_then(|s| s.to_str()).unwrap_or(p); msg.push_str(&format!(" (at {}:{})", fname, self.current_line)); } }
                                 }
-                                println!("{}", msg);
+                                self.output.write_str(&format!("{}\n", msg));
+                                self.stack.push(Value::Str(val));
+                            } else if let Some(inp) = self.input.as_mut() {
+                                let val = inp.read_line();
                                 self.stack.push(Value::Str(val));
                             } else {
                                 let mut input = String::new();
@@ -1892,7 +2376,10 @@ impl VM {
                                 let shown = match ch { Some('\r') => "<ENTER>".to_string(), Some(c) => c.to_string(), None => String::new() };
                                 let mut msg = format!("Mock input to INKEY$ given as {}", shown);
                                 if self.trace { if let Some(p) = &self.script_path { if self.current_line>0 { let fname = std::path::Path::new(p).file_name().and_then(|s| s.to_str()).unwrap_or(p); msg.push_str(&format!(" (at {}:{})", fname, self.current_line)); } } }
-                                println!("{}", msg);
+                                self.output.write_str(&format!("{}\n", msg));
+                                self.stack.push(Value::Str(s));
+                            } else if let Some(inp) = self.input.as_mut() {
+                                let s = inp.read_char().map(|c| c.to_string()).unwrap_or_default();
                                 self.stack.push(Value::Str(s));
                             } else {
                                 enable_raw_mode().map_err(|e| BasilError(format!("enable_raw_mode: {}", e)))?;
@@ -1923,37 +2410,13 @@ impl VM {
                                 let shown = match ch { Some('\r') => "<ENTER>".to_string(), Some(c) => c.to_string(), None => String::new() };
                                 let mut msg = format!("Mock input to INKEY% given as {}", shown);
                                 if self.trace { if let Some(p) = &self.script_path { if self.current_line>0 { let fname = std::path::Path::new(p).file_name().and_then(|s| s.to_str()).unwrap_or(p); msg.push_str(&format!(" (at {}:{})", fname, self.current_line)); } } }
-                                println!("{}", msg);
+                                self.output.write_str(&format!("{}\n", msg));
+                                self.stack.push(Value::Int(code_i));
+                            } else if let Some(inp) = self.input.as_mut() {
+                                let code_i = inp.read_char().map(|c| c as i64).unwrap_or(0);
                                 self.stack.push(Value::Int(code_i));
                             } else {
-                                enable_raw_mode().map_err(|e| BasilError(format!("enable_raw_mode: {}", e)))?;
-                                let code_i: i64 = if poll(Duration::from_millis(0)).map_err(|e| BasilError(format!("poll: {}", e)))? {
-                                    match read().map_err(|e| BasilError(format!("read key: {}", e)))? {
-                                        Event::Key(KeyEvent { code, .. }) => {
-                                            match code {
-                                                KeyCode::Char(c) => c as i64,
-                                                KeyCode::Enter => 13,
-                                                KeyCode::Backspace => 8,
-                                                KeyCode::Tab => 9,
-                                                KeyCode::Esc => 27,
-                                                KeyCode::Up => 1000,
-                                                KeyCode::Down => 1001,
-                                                KeyCode::Left => 1002,
-                                                KeyCode::Right => 1003,
-                                                KeyCode::Home => 1004,
-                                                KeyCode::End => 1005,
-                                                KeyCode::PageUp => 1006,
-                                                KeyCode::PageDown => 1007,
-                                                KeyCode::Insert => 1008,
-                                                KeyCode::Delete => 1009,
-                                                KeyCode::F(n) => 1100 + n as i64,
-                                                _ => 0,
-                                            }
-                                        }
-                                        _ => 0,
-                                    }
-                                } else { 0 };
-                                let _ = disable_raw_mode();
+                                let code_i = terminal_key_code()?;
                                 self.stack.push(Value::Int(code_i));
                             }
                         }
@@ -2036,19 +2499,23 @@ impl VM {
                             if !(argc == 0 || argc == 1) { return Err(BasilError("INPUTC$ expects 0 or 1 argument".into())); }
                             if argc == 1 {
                                 let prompt = match &args[0] { Value::Str(s) => s.clone(), other => format!("{}", other) };
-                                print!("{}", prompt);
-                                let _ = io::stdout().flush();
+                                self.output.write_str(&prompt);
+                                let _ = self.output.flush();
                             }
                             if self.test_mode {
                                 self.mocked_inputs += 1;
                                 if let Some(maxn) = self.max_mocked_inputs { if self.mocked_inputs > maxn { let loc = if let Some(p) = &self.script_path { if self.current_line>0 { format!(" at {}:{}", std::path::Path::new(p).file_name().and_then(|s| s.to_str()).unwrap_or(p), self.current_line) } else { String::new() } } else { String::new() }; return Err(BasilError(format!("Hit --max-inputs={}{}", maxn, loc))); } }
                                 let ch = if let Some(mock) = &mut self.mock { mock.read_char() } else { None };
                                 let s = match ch { Some('\r') => String::new(), Some(c) => c.to_string(), None => String::new() };
-                                if let Some(c) = ch { if c != '\r' { print!("{}", c); let _ = io::stdout().flush(); } }
+                                if let Some(c) = ch { if c != '\r' { self.output.write_str(&c.to_string()); } }
                                 let shown = match ch { Some('\r') => "<ENTER>".to_string(), Some(c) => c.to_string(), None => String::new() };
                                 let mut msg = format!("Mock input to INPUTC$ given as {}", shown);
                                 if self.trace { if let Some(p) = &self.script_path { if self.current_line>0 { let fname = std::path::Path::new(p).file_name().and_then(|s| s.to_str()).unwrap_or(p); msg.push_str(&format!(" (at {}:{})", fname, self.current_line)); } } }
-                                println!("{}", msg);
+                                self.output.write_str(&format!("{}\n", msg));
+                                self.stack.push(Value::Str(s));
+                            } else if let Some(inp) = self.input.as_mut() {
+                                let s = match inp.read_char() { Some(c) if c != '\r' => c.to_string(), _ => String::new() };
+                                self.output.write_str(&s);
                                 self.stack.push(Value::Str(s));
                             } else {
                                 // Enable raw mode and ensure we only capture a single key (no echo from console)
@@ -2075,7 +2542,7 @@ impl VM {
                                     }
                                 };
                                 // Echo the captured ASCII character exactly once
-                                if !s.is_empty() { print!("{}", s); let _ = io::stdout().flush(); }
+                                if !s.is_empty() { self.output.write_str(&s); let _ = self.output.flush(); }
                                 let _ = disable_raw_mode();
                                 self.stack.push(Value::Str(s));
                             }
@@ -2110,11 +2577,94 @@ impl VM {
                             let out = self.url_decode_form(s);
                             self.stack.push(Value::Str(out));
                         }
+                        25 => { // SAFE_URL$(u) - pass http(s)/mailto/tel/ftp or scheme-less URLs; anything else becomes "#"
+                            if argc != 1 { return Err(BasilError("SAFE_URL$ expects 1 argument".into())); }
+                            let s = format!("{}", args[0]);
+                            let t = s.trim();
+                            // A scheme is everything before the first ':' that precedes any '/', '?' or '#'
+                            let scheme = match t.find([':', '/', '?', '#']) {
+                                Some(p) if t.as_bytes()[p] == b':' => Some(t[..p].to_ascii_lowercase()),
+                                _ => None,
+                            };
+                            let ok = match scheme.as_deref() {
+                                None => true,
+                                Some(sc) => matches!(sc, "http" | "https" | "mailto" | "tel" | "ftp"),
+                            };
+                            self.stack.push(Value::Str(if ok { s.clone() } else { "#".to_string() }));
+                        }
                         24 => { // SLEEP(ms)
                             if argc != 1 { return Err(BasilError("SLEEP expects 1 argument".into())); }
                             let ms = self.to_i64(&args[0])?;
                             let msu = if ms < 0 { 0 } else { ms as u64 };
-                            std::thread::sleep(std::time::Duration::from_millis(msu));
+                            self.clock.sleep(Duration::from_millis(msu));
+                            self.stack.push(Value::Int(0));
+                        }
+                        142 | 143 => { // SETTIMEOUT(fn, ms) / SETINTERVAL(fn, ms) - timer id
+                            let what = if bid == 142 { "SETTIMEOUT" } else { "SETINTERVAL" };
+                            if argc != 2 { return Err(BasilError(format!("{} expects 2 arguments", what))); }
+                            let ms = self.to_i64(&args[1])?;
+                            let tid = self.add_timer(what, &args[0], ms, bid == 143)?;
+                            self.stack.push(Value::Int(tid));
+                        }
+                        144 => { // CLEARTIMER(id) - 1 if the timer was still scheduled
+                            if argc != 1 { return Err(BasilError("CLEARTIMER expects 1 argument".into())); }
+                            let tid = self.to_i64(&args[0])?;
+                            let cleared = self.clear_timer(tid);
+                            self.stack.push(Value::Int(cleared as i64));
+                        }
+                        145 => { // ON_KEY(fn or NULL)
+                            if argc != 1 { return Err(BasilError("ON KEY expects 1 argument".into())); }
+                            self.set_key_handler(&args[0])?;
+                            self.stack.push(Value::Int(0));
+                        }
+                        146 => { // ON_SIGNAL(name$, fn or NULL)
+                            if argc != 2 { return Err(BasilError("ON SIGNAL expects 2 arguments".into())); }
+                            let name = format!("{}", args[0]);
+                            self.set_signal_handler(&name, &args[1])?;
+                            self.stack.push(Value::Int(0));
+                        }
+                        147 => { // AWAIT(ms) - wait while timers and handlers run
+                            if argc != 1 { return Err(BasilError("AWAIT expects 1 argument".into())); }
+                            let ms = self.to_i64(&args[0])?.max(0) as u64;
+                            let until = self.clock.now() + Duration::from_millis(ms);
+                            self.run_events(Some(until))?;
+                            self.stack.push(Value::Int(0));
+                        }
+                        148 => { // EVENT_LOOP() - run events until STOP_LOOP or none are left
+                            if argc != 0 { return Err(BasilError("EVENT_LOOP expects 0 arguments".into())); }
+                            self.run_events(None)?;
+                            self.stack.push(Value::Int(0));
+                        }
+                        149 => { // STOP_LOOP() - end the running EVENT_LOOP
+                            if argc != 0 { return Err(BasilError("STOP_LOOP expects 0 arguments".into())); }
+                            self.stop_loop();
+                            self.stack.push(Value::Int(0));
+                        }
+                        150 => { // SPAWN(file$) - worker id
+                            if argc != 1 { return Err(BasilError("SPAWN expects 1 argument".into())); }
+                            let fname = format!("{}", args[0]);
+                            let wid = self.spawn_worker(&fname)?;
+                            self.stack.push(Value::Int(wid));
+                        }
+                        151 => { // SEND(worker%, value) - 1 if delivered, 0 if the worker has finished
+                            if argc != 2 { return Err(BasilError("SEND expects 2 arguments".into())); }
+                            let wid = self.to_i64(&args[0])?;
+                            let ok = self.send_to_worker(wid, &args[1])?;
+                            self.stack.push(Value::Int(ok as i64));
+                        }
+                        152 => { // RECEIVE(worker% [, ms]) - next value, or NULL
+                            if argc != 1 && argc != 2 { return Err(BasilError("RECEIVE expects 1 or 2 arguments".into())); }
+                            let wid = self.to_i64(&args[0])?;
+                            let timeout = if argc == 2 { Some(Duration::from_millis(self.to_i64(&args[1])?.max(0) as u64)) } else { None };
+                            let _ = self.output.flush();
+                            let v = self.receive_from_worker(wid, timeout)?;
+                            self.stack.push(v);
+                        }
+                        153 => { // JOIN(worker%) - wait for the worker to finish
+                            if argc != 1 { return Err(BasilError("JOIN expects 1 argument".into())); }
+                            let wid = self.to_i64(&args[0])?;
+                            let _ = self.output.flush();
+                            self.join_worker(wid)?;
                             self.stack.push(Value::Int(0));
                         }
                         26 => { // STRING$(n, ch$ or code%)
@@ -2125,13 +2675,106 @@ impl VM {
                                 Value::Str(s) => s.clone(),
                                 other => {
                                     let code = self.to_i64(other)?;
-                                    let ch = std::char::from_u32((code as u32) & 0xFF).unwrap_or('\u{0000}');
-                                    ch.to_string()
+                                    bytes::code_char(code, "STRING$")?.to_string()
                                 }
                             };
+                            if unit.len().saturating_mul(n) > bytes::MAX_LEN {
+                                return Err(BasilError(format!("STRING$: result would be longer than {} bytes", bytes::MAX_LEN)));
+                            }
                             let out = if unit.is_empty() || n == 0 { String::new() } else { unit.repeat(n) };
                             self.stack.push(Value::Str(out));
                         }
+                        27 => { // ROUTE(method$, pattern$, handler) - register a route; dispatched by the host after run
+                            if argc != 3 { return Err(BasilError("ROUTE expects 3 arguments".into())); }
+                            let method = format!("{}", args[0]);
+                            let pattern = format!("{}", args[1]);
+                            match &args[2] {
+                                Value::Func(f) if f.arity <= 1 => {}
+                                Value::Func(_) => return Err(BasilError("ROUTE handler must take 0 or 1 parameters".into())),
+                                _ => return Err(BasilError("ROUTE handler must be a FUNC or SUB name".into())),
+                            }
+                            self.routes.add(&method, &pattern, args[2].clone())?;
+                            self.stack.push(Value::Int(0));
+                        }
+                        28 => { // ROUTE_HOOK(kind$, handler) - BEFORE / AFTER / NOT_FOUND / ERROR
+                            if argc != 2 { return Err(BasilError("ROUTE_HOOK expects 2 arguments".into())); }
+                            let kind_s = format!("{}", args[0]);
+                            let kind = router::HookKind::parse(&kind_s)
+                                .ok_or_else(|| BasilError(format!("ROUTE: unknown hook \"{}\" (use BEFORE, AFTER, NOT_FOUND or ERROR)", kind_s)))?;
+                            match &args[1] {
+                                Value::Func(f) if f.arity <= 1 => {}
+                                Value::Func(_) => return Err(BasilError("ROUTE hook must take 0 or 1 parameters".into())),
+                                _ => return Err(BasilError("ROUTE hook must be a FUNC or SUB name".into())),
+                            }
+                            self.routes.add_hook(kind, args[1].clone());
+                            self.stack.push(Value::Int(0));
+                        }
+                        29 => { // ROUTE_PARAM$(name$) - path parameter of the current route ("" if absent)
+                            if argc != 1 { return Err(BasilError("ROUTE_PARAM$ expects 1 argument".into())); }
+                            let name = format!("{}", args[0]);
+                            let v = self.route_params.iter()
+                                .find(|(k, _)| k.eq_ignore_ascii_case(&name))
+                                .map(|(_, v)| v.clone())
+                                .unwrap_or_default();
+                            self.stack.push(Value::Str(v));
+                        }
+                        30 => { // ROUTE_PATH$() - request path being dispatched
+                            if argc != 0 { return Err(BasilError("ROUTE_PATH$ expects 0 arguments".into())); }
+                            self.stack.push(Value::Str(self.route_path.clone()));
+                        }
+                        31 => { // COOKIE$(name$, value$[, attrs$]) - "Set-Cookie: ..." line with HttpOnly, Secure and SameSite
+                            if !(2..=3).contains(&argc) { return Err(BasilError("COOKIE$ expects 2 or 3 arguments".into())); }
+                            let name = format!("{}", args[0]);
+                            let value = format!("{}", args[1]);
+                            let attrs = if argc == 3 { format!("{}", args[2]) } else { String::new() };
+                            let line = web::set_cookie_header(&name, &value, &attrs)?;
+                            self.stack.push(Value::Str(line));
+                        }
+                        32 => { // CSRF_TOKEN$() - token to echo back in forms (see #CGI_CSRF)
+                            if argc != 0 { return Err(BasilError("CSRF_TOKEN$ expects 0 arguments".into())); }
+                            self.stack.push(Value::Str(web::current_csrf_token(&*self.env)));
+                        }
+                        34 => { // FLUSH - send buffered output to the client/console now
+                            if argc != 0 { return Err(BasilError("FLUSH expects 0 arguments".into())); }
+                            self.output.flush().map_err(|e| BasilError(format!("FLUSH: {}", e)))?;
+                            self.stack.push(Value::Int(0));
+                        }
+                        35 => { // OB_START - capture subsequent output (nestable)
+                            if argc != 0 { return Err(BasilError("OB_START expects 0 arguments".into())); }
+                            self.output.ob_start();
+                            self.stack.push(Value::Int(0));
+                        }
+                        36 => { // OB_GET$() - end the innermost capture and return its text
+                            if argc != 0 { return Err(BasilError("OB_GET$ expects 0 arguments".into())); }
+                            let s = self.output.ob_get().ok_or_else(|| BasilError("OB_GET$ without OB_START".into()))?;
+                            self.stack.push(Value::Str(s));
+                        }
+                        37 => { // OB_END - end the innermost capture, writing its text to the enclosing output
+                            if argc != 0 { return Err(BasilError("OB_END expects 0 arguments".into())); }
+                            if !self.output.ob_end() { return Err(BasilError("OB_END without OB_START".into())); }
+                            self.stack.push(Value::Int(0));
+                        }
+                        38 => { // x INSTANCEOF Class: (value, class or type name)
+                            let res = match &args[0] {
+                                Value::Object(rc) => match (instance_class(rc), &args[1]) {
+                                    (Some(c), Value::Str(t)) => c.is_named(t),
+                                    (Some(c), cls) => class_of(cls).is_some_and(|k| c.is_a(&k)),
+                                    (None, Value::Str(t)) => rc.borrow().type_name().eq_ignore_ascii_case(t),
+                                    (None, _) => false,
+                                },
+                                _ => false,
+                            };
+                            self.stack.push(Value::Bool(res));
+                        }
+                        33 => { // CSRF_FIELD$() - hidden <input> carrying the CSRF token
+                            if argc != 0 { return Err(BasilError("CSRF_FIELD$ expects 0 arguments".into())); }
+                            self.stack.push(Value::Str(web::csrf_field(&web::current_csrf_token(&*self.env))));
+                        }
+                        39 => { // CSRF_FORMS$(html$) - html$ with CSRF_FIELD$() in every form that posts
+                            if argc != 1 { return Err(BasilError("CSRF_FORMS$ expects 1 argument".into())); }
+                            let field = web::csrf_field(&web::current_csrf_token(&*self.env));
+                            self.stack.push(Value::Str(web::add_csrf_fields(&format!("{}", args[0]), &field)));
+                        }
                         40 => { // FOPEN(path$, mode$) -> fh%
                             if argc != 2 { return Err(BasilError("FOPEN expects 2 arguments".into())); }
                             let path = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
@@ -2140,16 +2783,16 @@ impl VM {
                             let m = mode.to_ascii_lowercase();
                             let text = !m.contains('b');
                             let plus = m.contains('+');
-                            let mut opts = OpenOptions::new();
+                            let mut opts = OpenMode::default();
                             let (mut readable, mut writable) = (false, false);
-                            if m.starts_with('r') { readable = true; opts.read(true); if plus { writable = true; opts.write(true); } }
-                            else if m.starts_with('w') { writable = true; opts.write(true).create(true).truncate(true); if plus { readable = true; opts.read(true); } }
-                            else if m.starts_with('a') { writable = true; opts.append(true).create(true); if plus { readable = true; opts.read(true); opts.write(true); } }
+                            if m.starts_with('r') { readable = true; opts.read = true; if plus { writable = true; opts.write = true; } }
+                            else if m.starts_with('w') { writable = true; opts.write = true; opts.create = true; opts.truncate = true; if plus { readable = true; opts.read = true; } }
+                            else if m.starts_with('a') { writable = true; opts.append = true; opts.create = true; if plus { readable = true; opts.read = true; opts.write = true; } }
                             else { return Err(BasilError(format!("FOPEN: invalid mode '{}'; expected r/w/a variants", mode))); }
-                            match opts.open(&path) {
+                            match self.fs.open(&path, opts) {
                                 Ok(file) => {
                                     let fh = self.next_fh; self.next_fh += 1;
-                                    let entry = FileHandleEntry { file, text, readable, writable, owner_depth: self.frames.len() };
+                                    let entry = FileHandleEntry { file, text, readable, writable, owner_depth: self.fh_owner_depth(), read_ahead: csv::ReadAhead::default() };
                                     self.file_table.insert(fh, entry);
                                     self.stack.push(Value::Int(fh));
                                 }
@@ -2202,11 +2845,11 @@ impl VM {
                             let s = if e.text { String::from_utf8_lossy(&out).to_string() } else { String::from_utf8_lossy(&out).to_string() };
                             self.stack.push(Value::Str(s));
                         }
-                        48 => { // FWRITE fh%, s$
+                        48 => { // FWRITE fh%, data (STRING or BYTES)
                             if argc != 2 { return Err(BasilError("FWRITE expects 2 arguments".into())); }
                             let h = self.to_i64(&args[0])?; let e = self.fh_get_mut(h)?; if !e.writable { return Err(BasilError("FWRITE: handle not opened for writing".into())); }
-                            let s = match &args[1] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            e.file.write_all(s.as_bytes()).map_err(|er| BasilError(format!("FWRITE: {}", er)))?; self.stack.push(Value::Bool(true));
+                            let data = bytes::data_of(&args[1]);
+                            e.file.write_all(&data).map_err(|er| BasilError(format!("FWRITE: {}", er)))?; self.stack.push(Value::Bool(true));
                         }
                         49 => { // FWRITELN fh%, s$
                             if argc != 2 { return Err(BasilError("FWRITELN expects 2 arguments".into())); }
@@ -2217,43 +2860,43 @@ impl VM {
                         50 => { // READFILE$(path$)
                             if argc != 1 { return Err(BasilError("READFILE$ expects 1 argument".into())); }
                             let path = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            let data = fs::read(&path).map_err(|e| BasilError(format!("READFILE$ {}: {}", path, e)))?; let s = String::from_utf8_lossy(&data).to_string(); self.stack.push(Value::Str(s));
+                            let data = self.fs.read(&path).map_err(|e| BasilError(format!("READFILE$ {}: {}", path, e)))?; let s = String::from_utf8_lossy(&data).to_string(); self.stack.push(Value::Str(s));
                         }
-                        51 => { // WRITEFILE path$, data$
+                        51 => { // WRITEFILE path$, data (STRING or BYTES)
                             if argc != 2 { return Err(BasilError("WRITEFILE expects 2 arguments".into())); }
                             let path = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            let s = match &args[1] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            let mut f = OpenOptions::new().write(true).create(true).truncate(true).open(&path).map_err(|e| BasilError(format!("WRITEFILE {}: {}", path, e)))?; f.write_all(s.as_bytes()).map_err(|e| BasilError(format!("WRITEFILE {}: {}", path, e)))?; f.flush().ok(); self.stack.push(Value::Null);
+                            let data = bytes::data_of(&args[1]);
+                            self.fs.write(&path, &data, false).map_err(|e| BasilError(format!("WRITEFILE {}: {}", path, e)))?; self.stack.push(Value::Null);
                         }
-                        52 => { // APPENDFILE path$, data$
+                        52 => { // APPENDFILE path$, data (STRING or BYTES)
                             if argc != 2 { return Err(BasilError("APPENDFILE expects 2 arguments".into())); }
                             let path = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            let s = match &args[1] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            let mut f = OpenOptions::new().write(true).create(true).append(true).open(&path).map_err(|e| BasilError(format!("APPENDFILE {}: {}", path, e)))?; f.write_all(s.as_bytes()).map_err(|e| BasilError(format!("APPENDFILE {}: {}", path, e)))?; f.flush().ok(); self.stack.push(Value::Null);
+                            let data = bytes::data_of(&args[1]);
+                            self.fs.write(&path, &data, true).map_err(|e| BasilError(format!("APPENDFILE {}: {}", path, e)))?; self.stack.push(Value::Null);
                         }
                         53 => { // COPY src$, dst$
                             if argc != 2 { return Err(BasilError("COPY expects 2 arguments".into())); }
                             let src = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
                             let dst = match &args[1] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            let _ = fs::copy(&src, &dst).map_err(|e| BasilError(format!("COPY {} -> {}: {}", src, dst, e)))?; self.stack.push(Value::Null);
+                            self.fs.copy(&src, &dst).map_err(|e| BasilError(format!("COPY {} -> {}: {}", src, dst, e)))?; self.stack.push(Value::Null);
                         }
                         54 => { // MOVE src$, dst$
                             if argc != 2 { return Err(BasilError("MOVE expects 2 arguments".into())); }
                             let src = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
                             let dst = match &args[1] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            fs::rename(&src, &dst).map_err(|e| BasilError(format!("MOVE {} -> {}: {}", src, dst, e)))?; self.stack.push(Value::Null);
+                            self.fs.rename(&src, &dst).map_err(|e| BasilError(format!("MOVE {} -> {}: {}", src, dst, e)))?; self.stack.push(Value::Null);
                         }
                         55 => { // RENAME path$, newname$
                             if argc != 2 { return Err(BasilError("RENAME expects 2 arguments".into())); }
                             let src = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
                             let newname = match &args[1] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
                             let p = Path::new(&src); let dir = p.parent().unwrap_or(Path::new(".")); let dst = dir.join(newname);
-                            fs::rename(&src, &dst).map_err(|e| BasilError(format!("RENAME {} -> {}: {}", src, dst.display(), e)))?; self.stack.push(Value::Null);
+                            self.fs.rename(&src, &dst.to_string_lossy()).map_err(|e| BasilError(format!("RENAME {} -> {}: {}", src, dst.display(), e)))?; self.stack.push(Value::Null);
                         }
                         56 => { // DELETE path$
                             if argc != 1 { return Err(BasilError("DELETE expects 1 argument".into())); }
                             let path = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            fs::remove_file(&path).map_err(|e| BasilError(format!("DELETE {}: {}", path, e)))?; self.stack.push(Value::Null);
+                            self.fs.remove_file(&path).map_err(|e| BasilError(format!("DELETE {}: {}", path, e)))?; self.stack.push(Value::Null);
                         }
                         57 => { // DIR$(pattern$) -> STRING[]
                             if argc != 1 { return Err(BasilError("DIR$ expects 1 argument".into())); }
@@ -2263,11 +2906,7 @@ impl VM {
                                 (p.parent().unwrap_or(Path::new(".")).to_path_buf(), p.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string())
                             } else { (PathBuf::from("."), patt.clone()) };
                             let mut names: Vec<String> = Vec::new();
-                            for ent in fs::read_dir(&dir).map_err(|e| BasilError(format!("DIR$: {}: {}", dir.display(), e)))? {
-                                let ent = ent.map_err(|e| BasilError(format!("DIR$: {}", e)))?;
-                                let md = ent.metadata().map_err(|e| BasilError(format!("DIR$: {}", e)))?;
-                                if !md.is_file() { continue; }
-                                let name = ent.file_name().to_string_lossy().to_string();
+                            for name in self.fs.list_files(&dir.to_string_lossy()).map_err(|e| BasilError(format!("DIR$: {}: {}", dir.display(), e)))? {
                                 if self.glob_match_simple(&patstr, &name) { names.push(name); }
                             }
                             names.sort();
@@ -2276,7 +2915,7 @@ impl VM {
                         58 => { // ENV$(name$)
                             if argc != 1 { return Err(BasilError("ENV$ expects 1 argument".into())); }
                             let name = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            let val = env::var(&name).unwrap_or_default();
+                            let val = self.env.var(&name).unwrap_or_default();
                             self.stack.push(Value::Str(val));
                         }
                         59 => { // SETENV/EXPORTENV name$, value, exportFlag
@@ -2295,7 +2934,7 @@ impl VM {
                                 Value::Num(n) => *n != 0.0,
                                 _ => false,
                             };
-                            env::set_var(&name, &value_str);
+                            self.env.set_var(&name, &value_str);
                             let mut ok = true;
                             if export {
                                 #[cfg(windows)]
@@ -2328,12 +2967,14 @@ impl VM {
                         61 => { // EXIT(code)
                             if argc != 1 { return Err(BasilError("EXIT expects 1 argument".into())); }
                             let code = self.to_i64(&args[0])? as i32;
+                            self.output.end_captures();
+                            let _ = self.output.flush();
                             std::process::exit(code);
                         }
                         62 => { // MKDIRS%(path$) -> Int (1=ok,0=fail)
                             if argc != 1 { return Err(BasilError("MKDIRS% expects 1 argument".into())); }
                             let path = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            match fs::create_dir_all(&path) {
+                            match self.fs.create_dir_all(&path) {
                                 Ok(_) => self.stack.push(Value::Int(1)),
                                 Err(_e) => self.stack.push(Value::Int(0)),
                             }
@@ -2348,7 +2989,7 @@ impl VM {
                                 let t = s.trim();
                                 if t.is_empty() { ".env".to_string() } else { t.to_string() }
                             };
-                            match fs::read_to_string(&file) {
+                            match self.fs.read(&file).map(|b| String::from_utf8_lossy(&b).into_owned()) {
                                 Ok(contents) => {
                                     for (i, line) in contents.lines().enumerate() {
                                         let trimmed = line.trim();
@@ -2366,7 +3007,7 @@ impl VM {
                                                                 (val_raw.starts_with('\'') && val_raw.ends_with('\'') && val_raw.len() >= 2) {
                                                     val_raw[1..val_raw.len()-1].to_string()
                                                 } else { val_raw.to_string() };
-                                                env::set_var(key, unquoted);
+                                                self.env.set_var(key, &unquoted);
                                             }
                                             None => {
                                                 eprintln!("warning: LOADENV% {}:{}: invalid line (expected name=value or comment)", file, i + 1);
@@ -2471,111 +3112,348 @@ impl VM {
                             let resp = curl_utils::http_post(&url, &body, ct_opt.as_deref())?;
                             self.stack.push(Value::Str(resp));
                         }
-                        #[cfg(feature = "obj-json")]
-                        126 => { // JSON_PARSE$(text$)
+                        126 => { // JSON_PARSE$(text$) - the JSON normalized to compact text
                             if argc != 1 { return Err(BasilError("JSON_PARSE$ expects 1 argument".into())); }
-                            let s = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            let v: JValue = serde_json::from_str(&s)
-                                .map_err(|e| BasilError(format!("JSON_PARSE$: invalid JSON: {}", e)))?;
-                            let out = serde_json::to_string(&v)
-                                .map_err(|e| BasilError(format!("JSON_PARSE$: serialize failed: {}", e)))?;
-                            self.stack.push(Value::Str(out));
+                            let v = json::parse(&format!("{}", args[0]))?;
+                            self.stack.push(Value::Str(json::stringify(&v, 0)?));
+                        }
+                        127 => { // JSON_STRINGIFY$(value [, indent])
+                            if argc != 1 && argc != 2 { return Err(BasilError("JSON_STRINGIFY$ expects 1 or 2 arguments".into())); }
+                            let indent = if argc == 2 { self.to_i64(&args[1])?.clamp(0, 16) as usize } else { 0 };
+                            self.stack.push(Value::Str(json::stringify(&args[0], indent)?));
+                        }
+                        154 => { // JSON_PARSE(text$ [, "DECIMAL"]) - LIST/DICT/FLOAT/INTEGER/STRING/BOOL/NULL
+                            if argc != 1 && argc != 2 { return Err(BasilError("JSON_PARSE expects 1 or 2 arguments".into())); }
+                            let decimals = match args.get(1) {
+                                None => false,
+                                Some(Value::Str(m)) if m.eq_ignore_ascii_case("DECIMAL") => true,
+                                Some(other) => return Err(BasilError(format!("JSON_PARSE: unknown number mode \"{}\" (use DECIMAL)", other))),
+                            };
+                            let text = format!("{}", args[0]);
+                            let v = if decimals { json::parse_decimal(&text)? } else { json::parse(&text)? };
+                            self.stack.push(v);
                         }
-                        #[cfg(feature = "obj-json")]
-                        127 => { // JSON_STRINGIFY$(value)
-                            if argc != 1 { return Err(BasilError("JSON_STRINGIFY$ expects 1 argument".into())); }
-                            match &args[0] {
-                                Value::Str(s) => {
-                                    if let Ok(v) = serde_json::from_str::<JValue>(&s) {
-                                        let out = serde_json::to_string(&v)
-                                            .map_err(|e| BasilError(format!("JSON_STRINGIFY$: serialize failed: {}", e)))?;
-                                        self.stack.push(Value::Str(out));
-                                    } else {
-                                        let out = serde_json::to_string(&s)
-                                            .map_err(|e| BasilError(format!("JSON_STRINGIFY$: wrap failed: {}", e)))?;
-                                        self.stack.push(Value::Str(out));
-                                    }
+                        98 => { // NOW [(zone)] -> DateTime from the clock provider
+                            if argc > 1 { return Err(BasilError("NOW expects 0 or 1 argument".into())); }
+                            let offset = if argc == 1 { datetime::parse_zone(&args[0])? } else { 0 };
+                            self.stack.push(DateTime::from_system_time(self.clock.now(), offset).into_value());
+                        }
+                        99 => { // TIMER -> seconds since midnight UTC
+                            if argc != 0 { return Err(BasilError("TIMER expects no arguments".into())); }
+                            let now = DateTime::from_system_time(self.clock.now(), 0);
+                            self.stack.push(Value::Num(now.epoch().rem_euclid(86_400.0)));
+                        }
+                        100 | 101 => { // DATE$ -> "MM-DD-YYYY", TIME$ -> "HH:MM:SS" (UTC)
+                            if argc != 0 { return Err(BasilError(format!("{} expects no arguments", if bid == 100 { "DATE$" } else { "TIME$" }))); }
+                            let now = DateTime::from_system_time(self.clock.now(), 0);
+                            self.stack.push(Value::Str(now.format(if bid == 100 { "%m-%d-%Y" } else { "%H:%M:%S" })?));
+                        }
+                        102 => { // DATETIME(epoch [, zone]) or DATETIME(y, m, d [, h, mi, s [, zone]])
+                            let num = |v: &Value| match v { Value::Num(n) => Ok(*n), Value::Int(i) => Ok(*i as f64), other => Err(BasilError(format!("DATETIME expects numbers, got {}", self.type_of(other)))) };
+                            let dt = match argc {
+                                1 | 2 => {
+                                    let offset = if argc == 2 { datetime::parse_zone(&args[1])? } else { 0 };
+                                    DateTime::from_epoch(num(&args[0])?, offset)?
                                 }
-                                other => {
-                                    let v = value_to_jvalue(other)?;
-                                    let out = serde_json::to_string(&v)
-                                        .map_err(|e| BasilError(format!("JSON_STRINGIFY$: serialize failed: {}", e)))?;
-                                    self.stack.push(Value::Str(out));
+                                3..=7 => {
+                                    let field = |i: usize| if i < argc { self.to_i64(&args[i]) } else { Ok(0) };
+                                    let (mo, d, h, mi) = (field(1)?, field(2)?, field(3)?, field(4)?);
+                                    let sec = if argc > 5 { num(&args[5])? } else { 0.0 };
+                                    let offset = if argc == 7 { datetime::parse_zone(&args[6])? } else { 0 };
+                                    let small = |n: i64| u32::try_from(n).unwrap_or(u32::MAX);
+                                    DateTime::from_fields(field(0)?, small(mo), small(d), small(h), small(mi), sec, offset)
+                                        .map_err(|e| BasilError(format!("DATETIME: {}", e.0)))?
                                 }
+                                _ => return Err(BasilError("DATETIME expects 1 to 7 arguments".into())),
+                            };
+                            self.stack.push(dt.into_value());
+                        }
+                        103 => { // DATETIME_PARSE(text$ [, pattern$])
+                            if argc != 1 && argc != 2 { return Err(BasilError("DATETIME_PARSE expects 1 or 2 arguments".into())); }
+                            let text = format!("{}", args[0]);
+                            let dt = if argc == 2 { DateTime::parse_with(&text, &format!("{}", args[1]))? } else { DateTime::parse(&text)? };
+                            self.stack.push(dt.into_value());
+                        }
+                        92 => { // REGEX_MATCH%(text$, pattern$) -> 1 if the pattern matches anywhere
+                            if argc != 2 { return Err(BasilError("REGEX_MATCH% expects 2 arguments".into())); }
+                            let re = self.regex(&format!("{}", args[1]))?;
+                            self.stack.push(Value::Int(re.is_match(&format!("{}", args[0])) as i64));
+                        }
+                        93 => { // REGEX_FIND$(text$, pattern$) -> first match or ""
+                            if argc != 2 { return Err(BasilError("REGEX_FIND$ expects 2 arguments".into())); }
+                            let re = self.regex(&format!("{}", args[1]))?;
+                            self.stack.push(Value::Str(re.find(&format!("{}", args[0])).unwrap_or_default()));
+                        }
+                        94 => { // REGEX_FINDALL(text$, pattern$) -> LIST of matches
+                            if argc != 2 { return Err(BasilError("REGEX_FINDALL expects 2 arguments".into())); }
+                            let re = self.regex(&format!("{}", args[1]))?;
+                            let found = re.find_all(&format!("{}", args[0])).into_iter().map(Value::Str).collect();
+                            self.stack.push(Value::List(Rc::new(std::cell::RefCell::new(found))));
+                        }
+                        95 => { // REGEX_CAPTURES(text$, pattern$) -> LIST of groups, DICT of named groups, or NULL
+                            if argc != 2 { return Err(BasilError("REGEX_CAPTURES expects 2 arguments".into())); }
+                            let re = self.regex(&format!("{}", args[1]))?;
+                            self.stack.push(re.captures(&format!("{}", args[0])).unwrap_or(Value::Null));
+                        }
+                        96 => { // REGEX_REPLACE$(text$, pattern$, with$)
+                            if argc != 3 { return Err(BasilError("REGEX_REPLACE$ expects 3 arguments".into())); }
+                            let re = self.regex(&format!("{}", args[1]))?;
+                            self.stack.push(Value::Str(re.replace_all(&format!("{}", args[0]), &format!("{}", args[2]))));
+                        }
+                        97 => { // REGEX_SPLIT(text$, pattern$) -> LIST of pieces
+                            if argc != 2 { return Err(BasilError("REGEX_SPLIT expects 2 arguments".into())); }
+                            let re = self.regex(&format!("{}", args[1]))?;
+                            let pieces = re.split(&format!("{}", args[0])).into_iter().map(Value::Str).collect();
+                            self.stack.push(Value::List(Rc::new(std::cell::RefCell::new(pieces))));
+                        }
+                        104 => { // UCHR$(code) -> one-character string, error if not a Unicode scalar value
+                            if argc != 1 { return Err(BasilError("UCHR$ expects 1 argument".into())); }
+                            let code = self.to_i64(&args[0])?;
+                            self.stack.push(Value::Str(bytes::uchr(code)?));
+                        }
+                        105 => { // UASC%(s$ [, pos]) -> code point of the character at 1-based pos
+                            if argc != 1 && argc != 2 { return Err(BasilError("UASC% expects 1 or 2 arguments".into())); }
+                            let pos = if argc == 2 { self.to_i64(&args[1])? } else { 1 };
+                            self.stack.push(Value::Int(bytes::uasc(&format!("{}", args[0]), pos)?));
+                        }
+                        106 => { // GRAPHEMES(s$) -> LIST of user-perceived characters
+                            if argc != 1 { return Err(BasilError("GRAPHEMES expects 1 argument".into())); }
+                            let s = format!("{}", args[0]);
+                            let parts = bytes::graphemes(&s).into_iter().map(|g| Value::Str(g.to_string())).collect();
+                            self.stack.push(Value::List(Rc::new(std::cell::RefCell::new(parts))));
+                        }
+                        107 => { // GLEN%(s$) -> number of grapheme clusters
+                            if argc != 1 { return Err(BasilError("GLEN% expects 1 argument".into())); }
+                            self.stack.push(Value::Int(bytes::graphemes(&format!("{}", args[0])).len() as i64));
+                        }
+                        108 => { // BYTES(string$ | list | bytes | n)
+                            if argc != 1 { return Err(BasilError("BYTES expects 1 argument".into())); }
+                            let b = bytes::from_value(&args[0], &self.type_of(&args[0]))?;
+                            self.stack.push(bytes::value(b));
+                        }
+                        109 => { // BYTES_TEXT$(b) -> UTF-8 decoded string
+                            if argc != 1 { return Err(BasilError("BYTES_TEXT$ expects 1 argument".into())); }
+                            self.stack.push(Value::Str(bytes::text(&bytes::data_of(&args[0]))?));
+                        }
+                        110 => { // BYTES_HEX$(b) -> lowercase hex
+                            if argc != 1 { return Err(BasilError("BYTES_HEX$ expects 1 argument".into())); }
+                            self.stack.push(Value::Str(bytes::to_hex(&bytes::data_of(&args[0]))));
+                        }
+                        111 => { // HEX_BYTES(hex$)
+                            if argc != 1 { return Err(BasilError("HEX_BYTES expects 1 argument".into())); }
+                            self.stack.push(bytes::value(bytes::from_hex(&format!("{}", args[0]))?));
+                        }
+                        112 => { // BYTES_BASE64$(b) -> standard Base64 with padding
+                            if argc != 1 { return Err(BasilError("BYTES_BASE64$ expects 1 argument".into())); }
+                            self.stack.push(Value::Str(bytes::to_base64(&bytes::data_of(&args[0]))));
+                        }
+                        113 => { // BASE64_BYTES(text$)
+                            if argc != 1 { return Err(BasilError("BASE64_BYTES expects 1 argument".into())); }
+                            self.stack.push(bytes::value(bytes::from_base64(&format!("{}", args[0]))?));
+                        }
+                        114 => { // READFILE_BYTES(path$) -> whole file as BYTES
+                            if argc != 1 { return Err(BasilError("READFILE_BYTES expects 1 argument".into())); }
+                            let path = format!("{}", args[0]);
+                            let data = self.fs.read(&path).map_err(|e| BasilError(format!("READFILE_BYTES {}: {}", path, e)))?;
+                            self.stack.push(bytes::value(data));
+                        }
+                        115 => { // FREAD_BYTES(fh%, n) -> up to n bytes, empty at end of file
+                            if argc != 2 { return Err(BasilError("FREAD_BYTES expects 2 arguments".into())); }
+                            let h = self.to_i64(&args[0])?; let n = self.to_i64(&args[1])?.max(0) as usize;
+                            let e = self.fh_get_mut(h)?;
+                            if !e.readable { return Err(BasilError("FREAD_BYTES: handle not opened for reading".into())); }
+                            // grow with what the file actually holds rather than trusting n
+                            let mut buf = Vec::new();
+                            (&mut e.file).take(n as u64).read_to_end(&mut buf).map_err(|er| BasilError(format!("FREAD_BYTES: {}", er)))?;
+                            self.stack.push(bytes::value(buf));
+                        }
+                        165..=167 => { // SHA256$ / SHA1$ / MD5$(data [, format$]) -> digest as hex, base64 or base64url
+                            let name = ["SHA256$", "SHA1$", "MD5$"][(bid - 165) as usize];
+                            if argc != 1 && argc != 2 { return Err(BasilError(format!("{} expects 1 or 2 arguments", name))); }
+                            let data = bytes::data_of(&args[0]);
+                            let sum = match bid { 165 => crypto::sha256(&data), 166 => crypto::sha1(&data), _ => crypto::md5(&data) };
+                            let format = if argc == 2 { format!("{}", args[1]) } else { "hex".to_string() };
+                            self.stack.push(Value::Str(crypto::encode(name, &sum, &format)?));
+                        }
+                        168 => { // HMAC_SHA256$(key, data [, format$])
+                            if argc != 2 && argc != 3 { return Err(BasilError("HMAC_SHA256$ expects 2 or 3 arguments".into())); }
+                            let mac = crypto::hmac_sha256(&bytes::data_of(&args[0]), &bytes::data_of(&args[1]));
+                            let format = if argc == 3 { format!("{}", args[2]) } else { "hex".to_string() };
+                            self.stack.push(Value::Str(crypto::encode("HMAC_SHA256$", &mac, &format)?));
+                        }
+                        169 => { // SECURE_EQ%(a, b) -> 1 if equal, timing independent of where they differ
+                            if argc != 2 { return Err(BasilError("SECURE_EQ% expects 2 arguments".into())); }
+                            let eq = crypto::secure_eq(&bytes::data_of(&args[0]), &bytes::data_of(&args[1]));
+                            self.stack.push(Value::Int(eq as i64));
+                        }
+                        170 => { // RANDOM_BYTES$(n [, format$]) -> n bytes from the OS generator
+                            if argc != 1 && argc != 2 { return Err(BasilError("RANDOM_BYTES$ expects 1 or 2 arguments".into())); }
+                            let n = self.to_i64(&args[0])?;
+                            if !(0..=1 << 20).contains(&n) { return Err(BasilError(format!("RANDOM_BYTES$: count must be between 0 and 1048576, got {}", n))); }
+                            let data = crypto::random_bytes(n as usize)?;
+                            let format = if argc == 2 { format!("{}", args[1]) } else { "hex".to_string() };
+                            self.stack.push(Value::Str(crypto::encode("RANDOM_BYTES$", &data, &format)?));
+                        }
+                        171 => { // UUID$() -> random version 4 UUID
+                            if argc != 0 { return Err(BasilError("UUID$ expects 0 arguments".into())); }
+                            self.stack.push(Value::Str(crypto::uuid_v4()?));
+                        }
+                        172 => { // PASSWORD_HASH$(password$ [, iterations]) -> salted PBKDF2 hash string
+                            if argc != 1 && argc != 2 { return Err(BasilError("PASSWORD_HASH$ expects 1 or 2 arguments".into())); }
+                            let iterations = if argc == 2 { self.to_i64(&args[1])? } else { crypto::PASSWORD_ITERATIONS as i64 };
+                            let iterations = u32::try_from(iterations).unwrap_or(0);
+                            self.stack.push(Value::Str(crypto::password_hash(&format!("{}", args[0]), iterations)?));
+                        }
+                        116 => { // FORMAT$(n, pattern$ [, locale$]) -> "#,##0.00"-style formatting
+                            if argc != 2 && argc != 3 { return Err(BasilError("FORMAT$ expects 2 or 3 arguments".into())); }
+                            if !matches!(args[0], Value::Int(_) | Value::Num(_) | Value::Decimal(_)) {
+                                return Err(BasilError(format!("FORMAT$ expects a number, got {}", self.type_of(&args[0]))));
+                            }
+                            let locale = if argc == 3 { format!("{}", args[2]) } else { "en".to_string() };
+                            self.stack.push(Value::Str(using::format(&args[0], &format!("{}", args[1]), &locale)?));
+                        }
+                        117 => { // STR$(n) -> number as text, leading space when not negative
+                            if argc != 1 { return Err(BasilError("STR$ expects 1 argument".into())); }
+                            if !matches!(args[0], Value::Int(_) | Value::Num(_) | Value::Decimal(_)) { return Err(BasilError(format!("STR$ expects a number, got {}", self.type_of(&args[0])))); }
+                            self.stack.push(Value::Str(using::str_of(&args[0])));
+                        }
+                        118 => { // VAL(s$) -> leading number in s$, 0 if none
+                            if argc != 1 { return Err(BasilError("VAL expects 1 argument".into())); }
+                            let s = match &args[0] { Value::Str(s) => s.clone(), other => return Err(BasilError(format!("VAL expects a string, got {}", self.type_of(other)))) };
+                            self.stack.push(Value::Num(using::val(&s)));
+                        }
+                        119 => { // DEC(x [, scale [, mode$]]) -> DECIMAL from a string or number
+                            if !(1..=3).contains(&argc) { return Err(BasilError("DEC expects 1 to 3 arguments".into())); }
+                            let mut d = decimal::from_value(&args[0], "DEC")?;
+                            if argc >= 2 {
+                                let scale = decimal::scale_arg(self.to_i64(&args[1])?, "DEC")?;
+                                let mode = if argc == 3 { decimal::rounding_arg(&args[2], "DEC")? } else { self.dec_rounding };
+                                d = d.round(scale, mode);
                             }
+                            self.stack.push(decimal::value(d));
+                        }
+                        155 => { // DEC_DIV(a, b, scale [, mode$]) -> a / b with `scale` digits after the point
+                            if argc != 3 && argc != 4 { return Err(BasilError("DEC_DIV expects 3 or 4 arguments".into())); }
+                            let a = decimal::from_value(&args[0], "DEC_DIV")?;
+                            let b = decimal::from_value(&args[1], "DEC_DIV")?;
+                            let scale = decimal::scale_arg(self.to_i64(&args[2])?, "DEC_DIV")?;
+                            let mode = if argc == 4 { decimal::rounding_arg(&args[3], "DEC_DIV")? } else { self.dec_rounding };
+                            let q = a.quotient(&b, scale, mode).ok_or_else(|| BasilError("DEC_DIV: division by zero".into()))?;
+                            self.stack.push(decimal::value(q));
+                        }
+                        156 => { // DEC_ROUND(x, scale [, mode$]) -> exactly `scale` digits after the point
+                            if argc != 2 && argc != 3 { return Err(BasilError("DEC_ROUND expects 2 or 3 arguments".into())); }
+                            let d = decimal::from_value(&args[0], "DEC_ROUND")?;
+                            let scale = decimal::scale_arg(self.to_i64(&args[1])?, "DEC_ROUND")?;
+                            let mode = if argc == 3 { decimal::rounding_arg(&args[2], "DEC_ROUND")? } else { self.dec_rounding };
+                            self.stack.push(decimal::value(d.round(scale, mode)));
+                        }
+                        157 => { // DEC_SCALE%([n]) -> digits kept by DECIMAL `/`; setting returns the old value
+                            if argc > 1 { return Err(BasilError("DEC_SCALE% expects 0 or 1 argument".into())); }
+                            let old = self.dec_scale;
+                            if argc == 1 { self.dec_scale = decimal::scale_arg(self.to_i64(&args[0])?, "DEC_SCALE%")?; }
+                            self.stack.push(Value::Int(old as i64));
+                        }
+                        158 => { // DEC_ROUNDING$([mode$]) -> rounding of DECIMAL `/`; setting returns the old mode
+                            if argc > 1 { return Err(BasilError("DEC_ROUNDING$ expects 0 or 1 argument".into())); }
+                            let old = self.dec_rounding;
+                            if argc == 1 { self.dec_rounding = decimal::rounding_arg(&args[0], "DEC_ROUNDING$")?; }
+                            self.stack.push(Value::Str(old.name().to_string()));
+                        }
+                        159 | 136 => { // SQL_BIND$ / SQL_BIND_MYSQL$(sql$, values...) -> SQL text with ? / :name placeholders filled in
+                            let (who, dialect) = if bid == 159 { ("SQL_BIND$", sql::Dialect::Standard) } else { ("SQL_BIND_MYSQL$", sql::Dialect::MySql) };
+                            if argc < 1 { return Err(BasilError(format!("{} expects at least 1 argument", who))); }
+                            let sql = match &args[0] { Value::Str(s) => s.clone(), _ => return Err(BasilError(format!("{}: first argument must be the SQL text", who))) };
+                            self.stack.push(Value::Str(sql::bind(&sql, &args[1..], dialect)?));
+                        }
+                        173 => { // PASSWORD_VERIFY%(password$, hash$) -> 1 if the password matches
+                            if argc != 2 { return Err(BasilError("PASSWORD_VERIFY% expects 2 arguments".into())); }
+                            let ok = crypto::password_verify(&format!("{}", args[0]), &format!("{}", args[1]))?;
+                            self.stack.push(Value::Int(ok as i64));
+                        }
+                        83 | 84 => { // CINT(x) / CLNG(x) -> nearest INTEGER, halves to even
+                            let who = if bid == 83 { "CINT" } else { "CLNG" };
+                            if argc != 1 { return Err(BasilError(format!("{} expects 1 argument", who))); }
+                            self.stack.push(math::to_int(&args[0], who)?);
+                        }
+                        85 => { // CDBL(x) -> FLOAT
+                            if argc != 1 { return Err(BasilError("CDBL expects 1 argument".into())); }
+                            self.stack.push(math::to_float(&args[0])?);
+                        }
+                        86 | 87 => { // MIN(...) / MAX(...) of the arguments or of one LIST or array
+                            let (who, want) = if bid == 86 { ("MIN", std::cmp::Ordering::Less) } else { ("MAX", std::cmp::Ordering::Greater) };
+                            let v = self.min_max(args, want, who)?;
+                            self.stack.push(v);
                         }
-                        #[cfg(feature = "obj-csv")]
-                        128 => { // CSV_PARSE$(csv_text$)
+                        88 => { // PI
+                            if argc != 0 { return Err(BasilError("PI expects 0 arguments".into())); }
+                            self.stack.push(Value::Num(std::f64::consts::PI));
+                        }
+                        89 => { // HYPOT(x, y)
+                            if argc != 2 { return Err(BasilError("HYPOT expects 2 arguments".into())); }
+                            self.stack.push(math::hypot(&args[0], &args[1])?);
+                        }
+                        215 => { // ATAN2(y, x) -> angle in radians
+                            if argc != 2 { return Err(BasilError("ATAN2 expects 2 arguments".into())); }
+                            self.stack.push(math::atan2(&args[0], &args[1])?);
+                        }
+                        216 => { // LOG10(x)
+                            if argc != 1 { return Err(BasilError("LOG10 expects 1 argument".into())); }
+                            self.stack.push(math::log10(&args[0])?);
+                        }
+                        217 => { // POW(x, y) -> x^y
+                            if argc != 2 { return Err(BasilError("POW expects 2 arguments".into())); }
+                            self.stack.push(math::pow(&args[0], &args[1])?);
+                        }
+                        218 | 219 => { // GCD(...) / LCM(...)
+                            let vals = self.math_values(args)?;
+                            if vals.is_empty() { return Err(BasilError(format!("{} expects at least 1 argument", if bid == 218 { "GCD" } else { "LCM" }))); }
+                            self.stack.push(if bid == 218 { math::gcd(&vals)? } else { math::lcm(&vals)? });
+                        }
+                        225..=228 => { // SUM / AVG / MEDIAN / STDDEV of the arguments or of one LIST or array
+                            let vals = self.math_values(args)?;
+                            let v = match bid {
+                                225 => math::sum(&vals)?,
+                                226 => math::avg(&vals, self.dec_scale, self.dec_rounding)?,
+                                227 => math::median(&vals)?,
+                                _ => math::stddev(&vals)?,
+                            };
+                            self.stack.push(v);
+                        }
+                        128 => { // CSV_PARSE$(csv_text$) -> JSON array of objects keyed by the header row
                             if argc != 1 { return Err(BasilError("CSV_PARSE$ expects 1 argument".into())); }
                             let s = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            let mut rdr = ReaderBuilder::new()
-                                .has_headers(true)
-                                .from_reader(s.as_bytes());
-                            let headers = rdr.headers()
-                                .map_err(|e| BasilError(format!("CSV_PARSE$: read headers failed: {}", e)))?
-                                .clone();
-                            let mut rows: Vec<JValue> = Vec::new();
-                            for rec in rdr.records() {
-                                let rec = rec.map_err(|e| BasilError(format!("CSV_PARSE$: read record failed: {}", e)))?;
-                                let mut obj = serde_json::Map::new();
-                                for (i, field) in rec.iter().enumerate() {
-                                    let key = headers.get(i).unwrap_or("").to_string();
-                                    obj.insert(key, JValue::String(field.to_string()));
-                                }
-                                rows.push(JValue::Object(obj));
-                            }
-                            let out = serde_json::to_string(&rows)
-                                .map_err(|e| BasilError(format!("CSV_PARSE$: serialize failed: {}", e)))?;
-                            self.stack.push(Value::Str(out));
+                            self.stack.push(Value::Str(csv::parse_json(&s)?));
                         }
-                        #[cfg(feature = "obj-csv")]
-                        129 => { // CSV_WRITE$(rows_json$)
+                        129 => { // CSV_WRITE$(rows_json$) -> CSV text with a header row
                             if argc != 1 { return Err(BasilError("CSV_WRITE$ expects 1 argument".into())); }
                             let s = match &args[0] { Value::Str(s)=>s.clone(), other=>format!("{}", other) };
-                            let rows: JValue = serde_json::from_str(&s)
-                                .map_err(|e| BasilError(format!("CSV_WRITE$: invalid JSON: {}", e)))?;
-                            let arr = rows.as_array().ok_or_else(|| BasilError("CSV_WRITE$: expected JSON array of objects".into()))?;
-                            let mut headers: Vec<String> = Vec::new();
-                            let mut seen = std::collections::HashSet::new();
-                            if let Some(first) = arr.first().and_then(|v| v.as_object()) {
-                                for k in first.keys() {
-                                    headers.push(k.clone());
-                                    seen.insert(k.clone());
-                                }
-                            }
-                            for v in arr.iter() {
-                                if let Some(obj) = v.as_object() {
-                                    for k in obj.keys() {
-                                        if !seen.contains(k) {
-                                            headers.push(k.clone());
-                                            seen.insert(k.clone());
-                                        }
-                                    }
-                                }
-                            }
-                            let mut wtr = WriterBuilder::new().from_writer(vec![]);
-                            wtr.write_record(headers.iter())
-                                .map_err(|e| BasilError(format!("CSV_WRITE$: write headers failed: {}", e)))?;
-                            for v in arr.iter() {
-                                let obj = v.as_object().ok_or_else(|| BasilError("CSV_WRITE$: array items must be objects".into()))?;
-                                let mut row: Vec<String> = Vec::with_capacity(headers.len());
-                                for h in headers.iter() {
-                                    let cell = match obj.get(h) {
-                                        Some(JValue::String(s)) => s.clone(),
-                                        Some(JValue::Number(n)) => n.to_string(),
-                                        Some(JValue::Bool(b)) => if *b { "true".to_string() } else { "false".to_string() },
-                                        Some(JValue::Null) => String::new(),
-                                        Some(other) => serde_json::to_string(other).unwrap_or_default(),
-                                        None => String::new(),
-                                    };
-                                    row.push(cell);
-                                }
-                                wtr.write_record(&row)
-                                    .map_err(|e| BasilError(format!("CSV_WRITE$: write row failed: {}", e)))?;
-                            }
-                            let bytes = wtr.into_inner().map_err(|e| BasilError(format!("CSV_WRITE$: finalize failed: {}", e)))?;
-                            let out = String::from_utf8(bytes).map_err(|e| BasilError(format!("CSV_WRITE$: utf8 failed: {}", e)))?;
-                            self.stack.push(Value::Str(out));
+                            self.stack.push(Value::Str(csv::write_json(&s)?));
+                        }
+                        174 => { // CSV_READER(fh% [, opts]) -> reader object for FOR EACH / _NEXT
+                            if !(1..=2).contains(&argc) { return Err(BasilError("CSV_READER expects 1 or 2 arguments".into())); }
+                            let fh = self.to_i64(&args[0])?;
+                            let o = csv::Options::from_value(args.get(1), "CSV_READER")?;
+                            let r = self.csv_reader(fh, o)?;
+                            self.stack.push(r);
+                        }
+                        175 => { // CSV_READ_ALL(fh% | path$ [, opts]) -> LIST of rows
+                            if !(1..=2).contains(&argc) { return Err(BasilError("CSV_READ_ALL expects 1 or 2 arguments".into())); }
+                            let o = csv::Options::from_value(args.get(1), "CSV_READ_ALL")?;
+                            let rows = self.csv_read_all(&args[0], o)?;
+                            self.stack.push(rows);
+                        }
+                        176 => { // CSV_WRITE_ROW fh%, row [, opts]
+                            if !(2..=3).contains(&argc) { return Err(BasilError("CSV_WRITE_ROW expects 2 or 3 arguments".into())); }
+                            let fh = self.to_i64(&args[0])?;
+                            let o = csv::Options::from_value(args.get(2), "CSV_WRITE_ROW")?;
+                            self.csv_write_row(fh, &args[1], &o)?;
+                            self.stack.push(Value::Bool(true));
+                        }
+                        177 => { // CSV_FORMAT$(row [, opts]) -> one CSV line without the line end
+                            if !(1..=2).contains(&argc) { return Err(BasilError("CSV_FORMAT$ expects 1 or 2 arguments".into())); }
+                            let o = csv::Options::from_value(args.get(1), "CSV_FORMAT$")?;
+                            self.stack.push(Value::Str(csv::format_row(&args[0], &o, "CSV_FORMAT$")?));
                         }
                         #[cfg(feature = "obj-sqlite")]
                         130 => { // SQLITE_OPEN%(path$)
@@ -2937,6 +3815,12 @@ impl VM {
                                 other => return Err(BasilError(format!("builtin 138 expects StrArray2D, got {}", self.type_of(other)))),
                             }
                         }
+                        141 => { // SORT(list) -> sorted copy as a list; objects compare with _CMP
+                            if argc != 1 { return Err(BasilError("SORT expects 1 argument".into())); }
+                            let items = self.collect_values(args[0].clone())?;
+                            let sorted = self.sort_values(items)?;
+                            self.stack.push(Value::List(Rc::new(std::cell::RefCell::new(sorted))));
+                        }
                         139 => { // ARRAY_ROWS%(arr$())
                             if argc != 1 { return Err(BasilError("ARRAY_ROWS% expects 1 argument".into())); }
                             match &args[0] {
@@ -3086,10 +3970,10 @@ impl VM {
                         }
                         252 => { // MAKE_DICT({key: val, ...})
                             if argc % 2 != 0 { return Err(BasilError("MAKE_DICT expects an even number of arguments (key,value pairs)".into())); }
-                            let mut map: std::collections::HashMap<String, Value> = std::collections::HashMap::new();
+                            let mut map = Dict::new();
                             let mut i = 0usize;
                             while i < args.len() {
-                                let key = match &args[i] { Value::Str(s) => s.clone(), other => return Err(BasilError(format!("Dictionary key must be string, got {}", self.type_of(other)))) };
+                                let key = self.dict_key(&args[i])?;
                                 let val = args[i+1].clone();
                                 map.insert(key, val);
                                 i += 2;
@@ -3110,11 +3994,21 @@ impl VM {
                                     self.stack.push(v[idx0].clone());
                                 }
                                 Value::Dict(rc) => {
-                                    let key = match index { Value::Str(s) => s.clone(), other => return Err(BasilError(format!("Dictionary key must be string, got {}", self.type_of(other)))) };
+                                    let key = self.dict_key(index)?;
                                     let m = rc.borrow();
                                     if let Some(v) = m.get(&key) { self.stack.push(v.clone()); }
                                     else { return Err(BasilError(format!("Dictionary missing key: \"{}\"", key))); }
                                 }
+                                Value::Bytes(b) => {
+                                    let v = bytes::index(b, self.to_i64(index)?)?;
+                                    self.stack.push(v);
+                                }
+                                Value::Object(rc) => {
+                                    match self.call_protocol(rc, "_GETITEM", vec![index.clone()])? {
+                                        Some(v) => self.stack.push(v),
+                                        None => return Err(BasilError(format!("Attempted [] on {} which has no _GETITEM", self.type_of(target)))),
+                                    }
+                                }
                                 _ => { return Err(BasilError("Attempted [] on a non-list/dict value.".into())); }
                             }
                         }
@@ -3134,10 +4028,16 @@ impl VM {
                                     self.stack.push(Value::Null);
                                 }
                                 Value::Dict(rc) => {
-                                    let key = match index { Value::Str(s) => s.clone(), other => return Err(BasilError(format!("Dictionary key must be string, got {}", self.type_of(other)))) };
+                                    let key = self.dict_key(index)?;
                                     rc.borrow_mut().insert(key, value);
                                     self.stack.push(Value::Null);
                                 }
+                                Value::Object(rc) => {
+                                    if self.call_protocol(rc, "_SETITEM", vec![index.clone(), value])?.is_none() {
+                                        return Err(BasilError(format!("Attempted [] = on {} which has no _SETITEM", self.type_of(target))));
+                                    }
+                                    self.stack.push(Value::Null);
+                                }
                                 _ => { return Err(BasilError("Attempted [] on a non-list/dict value.".into())); }
                             }
                         }
@@ -3152,10 +4052,6 @@ impl VM {
                // other => { return Err(BasilError(format!("unhandled opcode {:?}", other))); }
             }
         }
-        if let Some(dbg) = &self.debugger { dbg.emit(debug::DebugEvent::Exited); }
-        if !self.gosub_stack.is_empty() {
-            eprintln!("warning: program terminated with {} pending GOSUB frames (missing RETURN?)", self.gosub_stack.len());
-        }
         Ok(())
     }
 
@@ -3163,7 +4059,7 @@ impl VM {
     pub fn set_debugger(&mut self, dbg: Arc<debug::Debugger>) { self.debugger = Some(dbg); }
     pub fn with_debugger(mut self, dbg: Arc<debug::Debugger>) -> Self { self.debugger = Some(dbg); self }
     pub fn get_call_stack(&self) -> Vec<debug::FrameInfo> {
-        let file = self.script_path.clone().unwrap_or_else(|| "<unknown>".into());
+        let file = self.current_file.clone().or_else(|| self.script_path.clone()).unwrap_or_else(|| "<unknown>".into());
         let line = self.current_line as usize;
         vec![debug::FrameInfo { function: "<top>".into(), file, line }]
     }
@@ -3182,6 +4078,8 @@ impl VM {
                 Value::Object(_) => "OBJECT".to_string(),
                 Value::List(_) => "LIST".to_string(),
                 Value::Dict(_) => "DICT".to_string(),
+                Value::Bytes(_) => "BYTES".to_string(),
+                Value::Decimal(_) => "DECIMAL".to_string(),
                 Value::StrArray2D { .. } => "STRARRAY2D".to_string(),
             };
             globals.push(debug::Variable { name: name.clone(), value: format!("{}", v), type_name: tn });
@@ -3202,10 +4100,11 @@ impl VM {
             20=>Op::Add, 21=>Op::Sub, 22=>Op::Mul, 23=>Op::Div, 24=>Op::Neg, 25=>Op::Mod,
             30=>Op::Eq, 31=>Op::Ne, 32=>Op::Lt, 33=>Op::Le, 34=>Op::Gt, 35=>Op::Ge,
             40=>Op::Jump, 41=>Op::JumpIfFalse, 42=>Op::JumpBack,
-            50=>Op::Call, 51=>Op::Ret,
+            50=>Op::Call, 51=>Op::Ret, 52=>Op::CallHost, 53=>Op::MakeGen, 54=>Op::Yield,
             60=>Op::Print, 61=>Op::Pop, 62=>Op::ToInt, 63=>Op::Builtin, 64=>Op::SetLine,
             70=>Op::ArrMake, 71=>Op::ArrGet, 72=>Op::ArrSet,
             80=>Op::NewObj, 81=>Op::GetProp, 82=>Op::SetProp, 83=>Op::CallMethod, 84=>Op::DescribeObj,
+            85=>Op::MakeClass, 86=>Op::NewInstance, 87=>Op::CallSuper,
             90=>Op::EnumNew, 91=>Op::EnumMoveNext, 92=>Op::EnumCurrent, 93=>Op::EnumDispose,
             100=>Op::NewClass, 101=>Op::GetMember, 102=>Op::SetMember, 103=>Op::CallMember, 104=>Op::DestroyInstance,
             105=>Op::ExecString, 106=>Op::EvalString,
@@ -3234,16 +4133,36 @@ impl VM {
             Value::Num(n) => Ok(n),
             Value::Int(i) => Ok(i as f64),
             Value::Bool(b) => Ok(if b { 1.0 } else { 0.0 }),
+            Value::Decimal(d) => Ok(d.to_f64()),
             _ => Err(BasilError("expected number".into())),
         }
     }
-    fn bin_num<F: Fn(f64,f64)->f64>(&mut self, f: F) -> Result<()> {
+    fn bin_num<F: Fn(f64,f64)->f64>(&mut self, op: decimal::Arith, f: F) -> Result<()> {
         let b = self.pop()?; let a = self.pop()?;
+        if decimal::involved(&a, &b) {
+            self.stack.push(decimal::arith(op, &a, &b, self.dec_scale, self.dec_rounding)?);
+            return Ok(());
+        }
+        if let (Value::Int(x), Value::Int(y)) = (&a, &b) {
+            self.stack.push(math::int_arith(op, *x, *y)?);
+            return Ok(());
+        }
         let b = self.as_num(b)?; let a = self.as_num(a)?;
         self.stack.push(Value::Num(f(a,b))); Ok(())
     }
     fn bin_num_cmp<F: Fn(f64,f64)->bool>(&mut self, f: F) -> Result<()> {
         let b = self.pop()?; let a = self.pop()?;
+        if matches!(a, Value::Object(_)) || matches!(b, Value::Object(_)) {
+            if let Some(o) = self.protocol_cmp(&a, &b)? {
+                self.stack.push(Value::Bool(f(o as i8 as f64, 0.0)));
+                return Ok(());
+            }
+        }
+        if decimal::involved(&a, &b) {
+            let o = decimal::operand(&a)?.cmp(&decimal::operand(&b)?);
+            self.stack.push(Value::Bool(f(o as i8 as f64, 0.0)));
+            return Ok(());
+        }
         let b = self.as_num(b)?; let a = self.as_num(a)?;
         self.stack.push(Value::Bool(f(a,b))); Ok(())
     }
@@ -3255,6 +4174,14 @@ impl VM {
             (Value::Num(_)|Value::Int(_)|Value::Bool(_), Value::Num(_)|Value::Int(_)|Value::Bool(_)) => {
                 let an = self.as_num(a)?; let bn = self.as_num(b)?; an == bn
             }
+            (Value::Decimal(_), Value::Num(_)|Value::Int(_)|Value::Bool(_)|Value::Decimal(_))
+            | (Value::Num(_)|Value::Int(_)|Value::Bool(_), Value::Decimal(_)) => {
+                decimal::operand(&a)? == decimal::operand(&b)?
+            }
+            (Value::Object(_), _) | (_, Value::Object(_)) => match self.protocol_eq(&a, &b)? {
+                Some(eq) => eq,
+                None => a == b,
+            },
             _ => a == b,
         };
         self.stack.push(Value::Bool(res));
@@ -3268,12 +4195,77 @@ impl VM {
             (Value::Num(_)|Value::Int(_)|Value::Bool(_), Value::Num(_)|Value::Int(_)|Value::Bool(_)) => {
                 let an = self.as_num(a)?; let bn = self.as_num(b)?; an != bn
             }
+            (Value::Decimal(_), Value::Num(_)|Value::Int(_)|Value::Bool(_)|Value::Decimal(_))
+            | (Value::Num(_)|Value::Int(_)|Value::Bool(_), Value::Decimal(_)) => {
+                decimal::operand(&a)? != decimal::operand(&b)?
+            }
+            (Value::Object(_), _) | (_, Value::Object(_)) => match self.protocol_eq(&a, &b)? {
+                Some(eq) => !eq,
+                None => a != b,
+            },
             _ => a != b,
         };
         self.stack.push(Value::Bool(res));
         Ok(())
     }
 
+    // Drop frames (e.g. a FUNC or method RAISEd from) down to the innermost live TRY and
+    // return its handler ip. Handlers left behind by a RETURN out of a TRY are skipped.
+    fn unwind_to_handler(&mut self) -> Option<usize> {
+        while let Some(h) = self._handlers.last() {
+            if h.depth <= self.frames.len() { break; }
+            self._handlers.pop();
+        }
+        let h = self._handlers.last()?;
+        let (ip, depth, sp) = (h.handler_ip, h.depth, h.sp);
+        self.frames.truncate(depth);
+        self.stack.truncate(sp);
+        Some(ip)
+    }
+
+    // Class of the method currently running, for PRIVATE checks.
+    fn caller_class(&self) -> Option<Rc<str>> {
+        self.frames.last().and_then(|f| f.owner.clone())
+    }
+
+    // Call a method of a file-based class instance on this VM, with the instance's
+    // globals in place of the program's.
+    fn push_class_frame(&mut self, env: &Rc<ClassEnv>, f: &Rc<basil_bytecode::Function>, args: Vec<Value>) -> Result<()> {
+        if f.arity as usize != args.len() {
+            return Err(BasilError(format!("arity mismatch: expected {}, got {}", f.arity, args.len())));
+        }
+        let base = self.stack.len();
+        self.stack.extend(args);
+        self.frames.push(Frame { chunk: f.chunk.clone(), ip: 0, base, owner: None, discard: false, env: Some(env.clone()), gen: None });
+        Ok(())
+    }
+
+    // Files opened by a class method or generator stay open across calls (until FCLOSE
+    // or the end of the program); others are closed when the opening frame returns.
+    fn fh_owner_depth(&self) -> usize {
+        if self.frames.last().is_some_and(|f| f.env.is_some() || f.gen.is_some()) { 0 } else { self.frames.len() }
+    }
+
+    // Call a CLASS method on this VM: ME and the arguments become the frame's first locals.
+    fn push_method_frame(&mut self, class_name: &str, name: &str, m: &classes::Method, me: Value, args: Vec<Value>, discard: bool) -> Result<()> {
+        if m.func.arity as usize != args.len() + 1 {
+            return Err(BasilError(format!("{}.{} expects {} argument(s), got {}", class_name, name, m.func.arity.saturating_sub(1), args.len())));
+        }
+        let base = self.stack.len();
+        self.stack.push(me);
+        self.stack.extend(args);
+        self.frames.push(Frame { chunk: m.func.chunk.clone(), ip: 0, base, owner: Some(m.owner.clone()), discard, env: None, gen: None });
+        Ok(())
+    }
+
+    // DICT keys are strings; numbers and other values are not converted
+    fn dict_key(&self, k: &Value) -> Result<String> {
+        match k {
+            Value::Str(s) => Ok(s.clone()),
+            other => Err(BasilError(format!("Dictionary key must be string, got {}", self.type_of(other)))),
+        }
+    }
+
     fn type_of(&self, v: &Value) -> String {
         match v {
             Value::Null => "NULL".to_string(),
@@ -3296,6 +4288,8 @@ impl VM {
             Value::Object(rc) => rc.borrow().type_name().to_string(),
             Value::List(_) => "LIST".to_string(),
             Value::Dict(_) => "DICT".to_string(),
+            Value::Bytes(_) => "BYTES".to_string(),
+            Value::Decimal(_) => "DECIMAL".to_string(),
             Value::StrArray2D { .. } => "STRING[][]".to_string(),
         }
     }
@@ -3311,6 +4305,16 @@ impl VM {
         self.rng_state = x;
         x
     }
+    // RANDOMIZE: the same seed gives the same RND sequence; None reseeds from the clock
+    fn randomize(&mut self, seed: Option<u64>) {
+        let Some(seed) = seed else { self.rng_state = time_seed(); return; };
+        // splitmix64, so nearby seeds start far apart
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        self.rng_state = if z == 0 { 0xA5A5_5A5A_DEAD_BEEFu64 } else { z };
+    }
     fn rnd_f64(&mut self) -> f64 {
         // Use upper 53 bits to create a uniform in [0,1)
         let u = self.rnd_u64() >> 11; // 53 bits
@@ -3410,28 +4414,81 @@ impl VM {
         out.into_iter().filter(|pb| seen.insert(pb.clone())).collect()
     }
 
-    fn load_class_program(&self, fname: &str) -> Result<(BCProgram, String)> {
-        use std::fs;
+    // Compiled program of a .bas (or .basx) file found like CLASS files, with its path.
+    fn load_program(&self, fname: &str) -> Result<Option<(BCProgram, String)>> {
         for cand in self.resolve_class_candidates(fname) {
-            let exists = fs::metadata(&cand).is_ok();
-            if !exists { continue; }
+            let path = cand.to_string_lossy().to_string();
+            let bytes = match self.fs.read(&path) {
+                Ok(b) => b,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(BasilError(format!("Failed to read {}: {}", path, e))),
+            };
             let ext = cand.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
             if ext == "basx" {
-                let bytes = fs::read(&cand).map_err(|e| BasilError(format!("Failed to read {}: {}", cand.display(), e)))?;
                 let prog = deserialize_program(&bytes).map_err(|_| BasilError("Bad .basx file".into()))?;
-                return Ok((prog, cand.to_string_lossy().to_string()));
+                return Ok(Some((prog, path)));
             } else {
                 // Treat others as .bas source
-                let src = fs::read_to_string(&cand).map_err(|e| BasilError(format!("Failed to read {}: {}", cand.display(), e)))?;
+                let src = String::from_utf8(bytes).map_err(|_| BasilError(format!("Failed to read {}: not valid UTF-8", path)))?;
                 let ast = parse_basil(&src)?;
-                let prog = compile_basil(&ast)?;
-                return Ok((prog, cand.to_string_lossy().to_string()));
+                let prog = compile_basil(&ast, &self.host)?;
+                return Ok(Some((prog, path)));
             }
         }
-        Err(BasilError("Class file not found.".into()))
+        Ok(None)
+    }
+}
+
+// The instance globals a FUNC value runs against: its CLASS instance's, or None for the
+// program's own FUNCs.
+fn class_env_of(f: &basil_bytecode::Function) -> Result<Option<Rc<ClassEnv>>> {
+    let Some(weak) = &f.bound else { return Ok(None) };
+    weak.upgrade().and_then(|env| env.downcast::<ClassEnv>().ok()).map(Some)
+        .ok_or_else(|| BasilError(format!("{}: its CLASS instance no longer exists", f.name.as_deref().unwrap_or("FUNC"))))
+}
+
+// Environment and function for a method call on a file-based class instance.
+fn class_file_method(rc: &basil_bytecode::ObjectRef, method: &str) -> Result<Option<(Rc<ClassEnv>, Rc<basil_bytecode::Function>)>> {
+    let obj = rc.borrow();
+    match obj.as_any().and_then(|a| a.downcast_ref::<ClassInstance>()) {
+        Some(inst) => Ok(Some((inst.env.clone(), inst.method(method)?))),
+        None => Ok(None),
     }
 }
 
+// INKEY% code of a key pressed on the terminal, or 0 if none is waiting.
+fn terminal_key_code() -> Result<i64> {
+    enable_raw_mode().map_err(|e| BasilError(format!("enable_raw_mode: {}", e)))?;
+    let code_i: i64 = if poll(Duration::from_millis(0)).map_err(|e| BasilError(format!("poll: {}", e)))? {
+        match read().map_err(|e| BasilError(format!("read key: {}", e)))? {
+            Event::Key(KeyEvent { code, .. }) => {
+                match code {
+                    KeyCode::Char(c) => c as i64,
+                    KeyCode::Enter => 13,
+                    KeyCode::Backspace => 8,
+                    KeyCode::Tab => 9,
+                    KeyCode::Esc => 27,
+                    KeyCode::Up => 1000,
+                    KeyCode::Down => 1001,
+                    KeyCode::Left => 1002,
+                    KeyCode::Right => 1003,
+                    KeyCode::Home => 1004,
+                    KeyCode::End => 1005,
+                    KeyCode::PageUp => 1006,
+                    KeyCode::PageDown => 1007,
+                    KeyCode::Insert => 1008,
+                    KeyCode::Delete => 1009,
+                    KeyCode::F(n) => 1100 + n as i64,
+                    _ => 0,
+                }
+            }
+            _ => 0,
+        }
+    } else { 0 };
+    let _ = disable_raw_mode();
+    Ok(code_i)
+}
+
 fn is_truthy(v: &Value) -> bool {
     match v {
         Value::Null => false,
@@ -3445,9 +4502,22 @@ fn is_truthy(v: &Value) -> bool {
         Value::StrArray2D { rows, cols, data } => (*rows > 0) && (*cols > 0) && (!data.is_empty()),
         Value::List(rc) => !rc.borrow().is_empty(),
         Value::Dict(rc) => !rc.borrow().is_empty(),
+        Value::Bytes(data) => !data.is_empty(),
+        Value::Decimal(d) => !d.is_zero(),
     }
 }
 
+impl VmCtx for VM {
+    fn print(&mut self, s: &str) { self.output.write_str(s); }
+
+    fn get_global(&self, name: &str) -> Option<Value> { VM::get_global(self, name) }
+
+    fn set_global(&mut self, name: &str, v: Value) -> bool { self.set_global_by_name(name, v) }
+
+    fn call(&mut self, f: &Value, args: Vec<Value>) -> Result<Value> { self.call_value(f, args) }
+
+    fn script_path(&self) -> Option<String> { self.script_path.clone() }
+}
 
 impl Drop for VM {
     fn drop(&mut self) {
@@ -0,0 +1,356 @@
+// The math library beyond ABS/ATN/COS/EXP/INT/LOG/RND/SIN/SQR/TAN.
+//
+//   RANDOMIZE [seed]                 a fixed seed repeats the RND sequence; none reseeds
+//                                    from the clock
+//   RND() / RND(n) / RND(lo, hi)     a FLOAT in [0, 1), or an INTEGER from 1 to n / lo to hi
+//   ROUND(x [, digits [, mode$]])    digits after the point (negative: tens, hundreds ...),
+//                                    HALF_EVEN (banker's) unless a DEC_ROUND mode is given
+//   FIX(x) SGN(x)                    toward zero; -1, 0 or 1
+//   CINT(x) CLNG(x) CDBL(x)          to INTEGER rounding half to even (both are 64-bit
+//                                    here), to FLOAT; strings are read as numbers
+//   MIN(...) MAX(...)                of the arguments, or of one LIST or array
+//   HYPOT(x, y) ATAN2(y, x) LOG10(x) POW(x, y) PI
+//   GCD(...) LCM(...)                of whole numbers
+//   SUM AVG MEDIAN STDDEV (...)      likewise of numbers; STDDEV is the sample deviation
+//
+// x ROUNDs the way it is written, so ROUND(2.675, 2, "HALF_UP") is 2.68 even though the
+// FLOAT is a little below. Results keep the kind of number they were given: INTEGERs stay
+// INTEGERs (+, -, * and MOD of two, SUM, POW to a whole power) and DECIMALs stay exact.
+// INTEGER arithmetic that does not fit in 64 bits is an error instead of wrapping around.
+//
+// Number literals are FLOATs, except whole ones past 2^53 that a FLOAT would round: those
+// are INTEGERs, so LET a% = 9223372036854775807 stores exactly. An INTEGER mixed with a
+// FLOAT, literal or not, gives a FLOAT and so is exact only up to 2^53: for a% past that,
+// a% + 1 rounds while a% + CLNG(1) (or an INTEGER variable) does not.
+
+use std::cmp::Ordering;
+
+use basil_bytecode::decimal::MAX_SCALE;
+use basil_bytecode::{Decimal, Rounding, Value};
+use basil_common::{BasilError, Result};
+
+use crate::decimal::{self, Arith};
+use crate::VM;
+
+// a op b for two INTEGERs; `/` is still a FLOAT division
+pub fn int_arith(op: Arith, a: i64, b: i64) -> Result<Value> {
+    let (r, sym) = match op {
+        Arith::Add => (a.checked_add(b), "+"),
+        Arith::Sub => (a.checked_sub(b), "-"),
+        Arith::Mul => (a.checked_mul(b), "*"),
+        Arith::Div => return Ok(Value::Num(a as f64 / b as f64)),
+        Arith::Mod if b == 0 => return Err(BasilError("MOD by zero".into())),
+        // only MIN MOD -1 overflows in checked_rem, and its remainder is 0
+        Arith::Mod => (Some(a.wrapping_rem(b)), "MOD"),
+    };
+    r.map(Value::Int).ok_or_else(|| BasilError(format!("INTEGER overflow: {} {} {}", a, sym, b)))
+}
+
+pub fn int_neg(a: i64) -> Result<Value> {
+    a.checked_neg().map(Value::Int).ok_or_else(|| BasilError(format!("INTEGER overflow: -({})", a)))
+}
+
+// A whole FLOAT as an INTEGER; None when it is out of range (or NaN)
+pub fn checked_int(x: f64) -> Option<i64> {
+    (x.is_finite() && (-9.223_372_036_854_776e18..9.223_372_036_854_776e18).contains(&x)).then_some(x as i64)
+}
+
+pub fn int_of_f64(x: f64, who: &str) -> Result<i64> {
+    checked_int(x).ok_or_else(|| BasilError(format!("{}: {} is out of range for an INTEGER", who, x)))
+}
+
+fn int_of_decimal(d: &Decimal, who: &str) -> Result<i64> {
+    d.to_i64().ok_or_else(|| BasilError(format!("{}: {} is out of range for an INTEGER", who, d)))
+}
+
+// An argument as a number; strings are read as one, as CINT("42") expects
+fn number(v: &Value, who: &str) -> Result<Value> {
+    match v {
+        Value::Int(_) | Value::Num(_) | Value::Decimal(_) => Ok(v.clone()),
+        Value::Bool(b) => Ok(Value::Int(*b as i64)),
+        Value::Str(s) => match s.trim().parse::<i64>() {
+            Ok(i) => Ok(Value::Int(i)),
+            Err(_) => s.trim().parse::<f64>().map(Value::Num)
+                .map_err(|_| BasilError(format!("{}: \"{}\" is not a number", who, s))),
+        },
+        other => Err(BasilError(format!("{}: expected a number, got {}", who, type_name(other)))),
+    }
+}
+
+fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "NULL",
+        Value::Str(_) => "STRING",
+        Value::Bool(_) => "BOOL",
+        Value::List(_) => "LIST",
+        Value::Dict(_) => "DICT",
+        Value::Bytes(_) => "BYTES",
+        _ => "OBJECT",
+    }
+}
+
+fn float(v: &Value, who: &str) -> Result<f64> {
+    match number(v, who)? {
+        Value::Int(i) => Ok(i as f64),
+        Value::Num(n) => Ok(n),
+        Value::Decimal(d) => Ok(d.to_f64()),
+        _ => unreachable!(),
+    }
+}
+
+fn pow10(k: u32) -> Decimal { Decimal::parse(&format!("1e{}", k)).unwrap_or_else(|| Decimal::from_i64(1)) }
+
+// d to `digits` places; negative digits round to a multiple of 10^-digits
+fn round_decimal(d: &Decimal, digits: i64, mode: Rounding) -> Decimal {
+    if digits >= 0 {
+        return if (digits as u32) < d.scale() { d.round(digits as u32, mode) } else { d.clone() };
+    }
+    let unit = pow10((-digits) as u32);
+    let whole = d.quotient(&unit, 0, mode).unwrap_or_else(|| d.clone());
+    &whole * &unit
+}
+
+pub fn round(v: &Value, digits: i64, mode: Rounding) -> Result<Value> {
+    if !(-(MAX_SCALE as i64)..=MAX_SCALE as i64).contains(&digits) {
+        return Err(BasilError(format!("ROUND: digits must be from -{} to {}", MAX_SCALE, MAX_SCALE)));
+    }
+    match number(v, "ROUND")? {
+        Value::Int(i) if digits >= 0 => Ok(Value::Int(i)),
+        Value::Int(i) => Ok(Value::Int(int_of_decimal(&round_decimal(&Decimal::from_i64(i), digits, mode), "ROUND")?)),
+        Value::Decimal(d) => Ok(decimal::value(round_decimal(&d, digits, mode))),
+        Value::Num(n) => match Decimal::from_f64(n) {
+            Some(d) => Ok(Value::Num(round_decimal(&d, digits, mode).to_f64())),
+            None => Ok(Value::Num(n)),
+        },
+        _ => unreachable!(),
+    }
+}
+
+pub fn fix(v: &Value) -> Result<Value> {
+    match number(v, "FIX")? {
+        Value::Int(i) => Ok(Value::Int(i)),
+        Value::Decimal(d) => Ok(Value::Int(int_of_decimal(&d, "FIX")?)),
+        Value::Num(n) => Ok(Value::Int(int_of_f64(n.trunc(), "FIX")?)),
+        _ => unreachable!(),
+    }
+}
+
+pub fn sgn(v: &Value) -> Result<Value> {
+    let s = match number(v, "SGN")? {
+        Value::Int(i) => i.signum(),
+        Value::Decimal(d) => if d.is_zero() { 0 } else if d.is_negative() { -1 } else { 1 },
+        Value::Num(n) if n.is_nan() => return Err(BasilError("SGN: NaN has no sign".into())),
+        Value::Num(n) => if n > 0.0 { 1 } else if n < 0.0 { -1 } else { 0 },
+        _ => unreachable!(),
+    };
+    Ok(Value::Int(s))
+}
+
+// CINT and CLNG: nearest INTEGER, halves to even
+pub fn to_int(v: &Value, who: &str) -> Result<Value> {
+    match number(v, who)? {
+        Value::Int(i) => Ok(Value::Int(i)),
+        Value::Decimal(d) => Ok(Value::Int(int_of_decimal(&d.round(0, Rounding::HalfEven), who)?)),
+        Value::Num(n) => Ok(Value::Int(int_of_f64(n.round_ties_even(), who)?)),
+        _ => unreachable!(),
+    }
+}
+
+pub fn to_float(v: &Value) -> Result<Value> { float(v, "CDBL").map(Value::Num) }
+
+pub fn hypot(x: &Value, y: &Value) -> Result<Value> { Ok(Value::Num(float(x, "HYPOT")?.hypot(float(y, "HYPOT")?))) }
+
+pub fn atan2(y: &Value, x: &Value) -> Result<Value> { Ok(Value::Num(float(y, "ATAN2")?.atan2(float(x, "ATAN2")?))) }
+
+pub fn log10(x: &Value) -> Result<Value> {
+    let x = float(x, "LOG10")?;
+    if x <= 0.0 { return Err(BasilError("LOG10 domain error: x must be > 0".into())); }
+    Ok(Value::Num(x.log10()))
+}
+
+pub fn pow(x: &Value, y: &Value) -> Result<Value> {
+    let (x, y) = (number(x, "POW")?, number(y, "POW")?);
+    let whole_exp = match &y {
+        Value::Int(e) => Some(*e),
+        Value::Num(e) if e.fract() == 0.0 && e.abs() < 1e9 => Some(*e as i64),
+        _ => None,
+    };
+    match (&x, whole_exp) {
+        (Value::Int(b), Some(e)) if e >= 0 => {
+            let r = u32::try_from(e).ok().and_then(|e| b.checked_pow(e));
+            r.map(Value::Int).ok_or_else(|| BasilError(format!("INTEGER overflow: POW({}, {})", b, e)))
+        }
+        (Value::Decimal(b), Some(e)) if e >= 0 => {
+            if b.scale() as i64 * e > MAX_SCALE as i64 {
+                return Err(BasilError(format!("POW: the result needs more than {} decimal places", MAX_SCALE)));
+            }
+            let (mut base, mut e, mut r) = ((**b).clone(), e, Decimal::from_i64(1));
+            while e > 0 {
+                if e & 1 == 1 { r = &r * &base; }
+                base = &base * &base;
+                e >>= 1;
+            }
+            Ok(decimal::value(r))
+        }
+        _ => {
+            let (b, e) = (float(&x, "POW")?, float(&y, "POW")?);
+            let r = b.powf(e);
+            if r.is_nan() && !b.is_nan() && !e.is_nan() {
+                return Err(BasilError("POW domain error: a negative base needs a whole exponent".into()));
+            }
+            Ok(Value::Num(r))
+        }
+    }
+}
+
+fn whole(v: &Value, who: &str) -> Result<i64> {
+    match number(v, who)? {
+        Value::Int(i) => Ok(i),
+        Value::Num(n) if n.fract() == 0.0 => int_of_f64(n, who),
+        Value::Decimal(d) if d.round(0, Rounding::Down) == *d => int_of_decimal(&d, who),
+        other => Err(BasilError(format!("{}: {} is not a whole number", who, other))),
+    }
+}
+
+fn gcd2(a: i64, b: i64) -> u64 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 { (a, b) = (b, a % b); }
+    a
+}
+
+pub fn gcd(vals: &[Value]) -> Result<Value> {
+    let mut g = 0u64;
+    for v in vals { g = gcd2(g as i64, whole(v, "GCD")?); }
+    i64::try_from(g).map(Value::Int).map_err(|_| BasilError("INTEGER overflow: GCD".into()))
+}
+
+pub fn lcm(vals: &[Value]) -> Result<Value> {
+    let mut l: i64 = 1;
+    for v in vals {
+        let n = whole(v, "LCM")?;
+        if n == 0 { return Ok(Value::Int(0)); }
+        let step = (n.unsigned_abs() / gcd2(l, n)) as i64;
+        l = l.checked_mul(step).ok_or_else(|| BasilError("INTEGER overflow: LCM".into()))?;
+    }
+    Ok(Value::Int(l))
+}
+
+// The numbers of an aggregate: all DECIMAL-exact when one is a DECIMAL, INTEGER when all are
+enum Numbers {
+    Ints(Vec<i64>),
+    Floats(Vec<f64>),
+    Decimals(Vec<Decimal>),
+}
+
+fn numbers(vals: &[Value], who: &str) -> Result<Numbers> {
+    for (i, v) in vals.iter().enumerate() {
+        if !matches!(v, Value::Int(_) | Value::Num(_) | Value::Decimal(_) | Value::Bool(_)) {
+            return Err(BasilError(format!("{}: element {} is not a number ({})", who, i + 1, type_name(v))));
+        }
+    }
+    Ok(if vals.iter().any(|v| matches!(v, Value::Decimal(_))) {
+        Numbers::Decimals(vals.iter().map(decimal::operand).collect::<Result<_>>().map_err(|e| BasilError(format!("{}: {}", who, e.0)))?)
+    } else if vals.iter().all(|v| matches!(v, Value::Int(_))) {
+        Numbers::Ints(vals.iter().map(|v| if let Value::Int(i) = v { *i } else { 0 }).collect())
+    } else {
+        Numbers::Floats(vals.iter().map(|v| float(v, who)).collect::<Result<_>>()?)
+    })
+}
+
+// Compensated (Neumaier) sum, so SUM of ten 0.1s is 1
+fn fsum(xs: &[f64]) -> f64 {
+    let (mut s, mut c) = (0.0f64, 0.0f64);
+    for &x in xs {
+        let t = s + x;
+        c += if s.abs() >= x.abs() { (s - t) + x } else { (x - t) + s };
+        s = t;
+    }
+    s + c
+}
+
+pub fn sum(vals: &[Value]) -> Result<Value> {
+    match numbers(vals, "SUM")? {
+        Numbers::Ints(xs) => xs.iter().try_fold(0i64, |a, &x| a.checked_add(x)).map(Value::Int)
+            .ok_or_else(|| BasilError("INTEGER overflow: SUM".into())),
+        Numbers::Floats(xs) => Ok(Value::Num(fsum(&xs))),
+        Numbers::Decimals(xs) => Ok(decimal::value(xs.iter().fold(Decimal::from_i64(0), |a, x| &a + x))),
+    }
+}
+
+fn nonempty(vals: &[Value], who: &str) -> Result<()> {
+    if vals.is_empty() { return Err(BasilError(format!("{}: no values", who))); }
+    Ok(())
+}
+
+pub fn avg(vals: &[Value], scale: u32, mode: Rounding) -> Result<Value> {
+    nonempty(vals, "AVG")?;
+    let n = vals.len() as i64;
+    match numbers(vals, "AVG")? {
+        Numbers::Decimals(_) => decimal::arith(Arith::Div, &sum(vals)?, &Value::Int(n), scale, mode),
+        Numbers::Ints(xs) => Ok(Value::Num(fsum(&xs.iter().map(|&x| x as f64).collect::<Vec<_>>()) / n as f64)),
+        Numbers::Floats(xs) => Ok(Value::Num(fsum(&xs) / n as f64)),
+    }
+}
+
+pub fn median(vals: &[Value]) -> Result<Value> {
+    nonempty(vals, "MEDIAN")?;
+    let mid = vals.len() / 2;
+    let even = vals.len().is_multiple_of(2);
+    match numbers(vals, "MEDIAN")? {
+        Numbers::Decimals(mut xs) => {
+            xs.sort();
+            if !even { return Ok(decimal::value(xs.swap_remove(mid))); }
+            let two = Decimal::from_i64(2);
+            let both = &xs[mid - 1] + &xs[mid];
+            Ok(decimal::value(both.quotient(&two, both.scale() + 1, Rounding::HalfEven).unwrap_or(both)))
+        }
+        Numbers::Ints(mut xs) => {
+            xs.sort_unstable();
+            Ok(if even { Value::Num((xs[mid - 1] as f64 + xs[mid] as f64) / 2.0) } else { Value::Int(xs[mid]) })
+        }
+        Numbers::Floats(mut xs) => {
+            if xs.iter().any(|x| x.is_nan()) { return Err(BasilError("MEDIAN: NaN has no place in the order".into())); }
+            xs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            Ok(Value::Num(if even { (xs[mid - 1] + xs[mid]) / 2.0 } else { xs[mid] }))
+        }
+    }
+}
+
+pub fn stddev(vals: &[Value]) -> Result<Value> {
+    if vals.len() < 2 { return Err(BasilError("STDDEV: needs at least 2 values".into())); }
+    let xs: Vec<f64> = match numbers(vals, "STDDEV")? {
+        Numbers::Ints(xs) => xs.into_iter().map(|x| x as f64).collect(),
+        Numbers::Floats(xs) => xs,
+        Numbers::Decimals(xs) => xs.iter().map(Decimal::to_f64).collect(),
+    };
+    // Welford's running mean and sum of squared deviations
+    let (mut mean, mut m2) = (0.0, 0.0);
+    for (i, x) in xs.iter().enumerate() {
+        let d = x - mean;
+        mean += d / (i + 1) as f64;
+        m2 += d * (x - mean);
+    }
+    Ok(Value::Num((m2 / (xs.len() - 1) as f64).sqrt()))
+}
+
+impl VM {
+    // The values of a variadic math builtin: its arguments, or the elements of a lone
+    // LIST, array or iterable
+    pub(crate) fn math_values(&mut self, args: Vec<Value>) -> Result<Vec<Value>> {
+        match args.as_slice() {
+            [Value::List(_) | Value::Array(_) | Value::Object(_)] => self.collect_values(args.into_iter().next().unwrap_or(Value::Null)),
+            _ => Ok(args),
+        }
+    }
+
+    // MIN and MAX keep the winning value as it is (INTEGER, DECIMAL, STRING ...)
+    pub(crate) fn min_max(&mut self, args: Vec<Value>, want: Ordering, who: &str) -> Result<Value> {
+        let vals = self.math_values(args)?;
+        let mut it = vals.into_iter();
+        let mut best = it.next().ok_or_else(|| BasilError(format!("{}: no values", who)))?;
+        for v in it {
+            if self.compare_values(&v, &best).map_err(|e| BasilError(e.0.replacen("SORT", who, 1)))? == want { best = v; }
+        }
+        Ok(best)
+    }
+}
@@ -0,0 +1,123 @@
+// Output path of PRINT and friends: an in-VM buffer in front of a pluggable sink.
+//
+// Output is buffered and reaches the sink on FLUSH, when the buffer fills up, before
+// INPUT/terminal/SHELL builtins and when run() returns. On a terminal every write is
+// flushed right away so prompts and progress output appear immediately.
+// OB_START ... OB_GET$() captures output into a string instead.
+
+use std::io::{self, IsTerminal, Write};
+
+const FLUSH_AT: usize = 32 * 1024;
+
+pub struct Output {
+    sink: Box<dyn Write>,
+    pending: Vec<u8>,
+    captures: Vec<String>,
+    // Anything reached the sink (after which the response can no longer be replaced)
+    sent: bool,
+    auto_flush: bool,
+}
+
+impl Output {
+    pub fn stdout() -> Self {
+        let tty = io::stdout().is_terminal();
+        Output::new(Box::new(io::stdout()), tty)
+    }
+
+    pub fn new(sink: Box<dyn Write>, auto_flush: bool) -> Self {
+        Output { sink, pending: Vec::new(), captures: Vec::new(), sent: false, auto_flush }
+    }
+
+    pub fn write_str(&mut self, s: &str) {
+        if let Some(c) = self.captures.last_mut() {
+            c.push_str(s);
+            return;
+        }
+        self.write_bytes(s.as_bytes());
+    }
+
+    // Bytes bypass OB captures (used by hosts for binary bodies)
+    pub fn write_bytes(&mut self, b: &[u8]) {
+        self.pending.extend_from_slice(b);
+        if self.auto_flush || self.pending.len() >= FLUSH_AT {
+            let _ = self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.sent = true;
+            let res = self.sink.write_all(&self.pending);
+            self.pending.clear();
+            res?;
+        }
+        self.sink.flush()
+    }
+
+    // Drop everything not yet flushed. False if part of the output was already sent.
+    pub fn discard(&mut self) -> bool {
+        self.pending.clear();
+        self.captures.clear();
+        !self.sent
+    }
+
+    pub fn ob_start(&mut self) { self.captures.push(String::new()); }
+
+    // End the innermost capture and return what it collected.
+    pub fn ob_get(&mut self) -> Option<String> { self.captures.pop() }
+
+    // End the innermost capture, passing its text on to the enclosing output.
+    pub fn ob_end(&mut self) -> bool {
+        match self.captures.pop() {
+            Some(s) => { self.write_str(&s); true }
+            None => false,
+        }
+    }
+
+    // Unclosed captures at program end are written out, innermost last.
+    pub fn end_captures(&mut self) { self.end_captures_to(0); }
+
+    // The same, keeping the outermost `depth` captures (those of a VM that lent us its output).
+    pub fn end_captures_to(&mut self, depth: usize) {
+        while self.captures.len() > depth && self.ob_end() {}
+    }
+
+    pub fn capture_depth(&self) -> usize { self.captures.len() }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) { let _ = self.flush(); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, b: &[u8]) -> io::Result<usize> { self.0.borrow_mut().extend_from_slice(b); Ok(b.len()) }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn buffering_captures_and_discard() {
+        let data = Rc::new(RefCell::new(Vec::new()));
+        let mut out = Output::new(Box::new(Shared(data.clone())), false);
+        out.write_str("a");
+        out.ob_start();
+        out.write_str("inner");
+        assert_eq!(out.ob_get().as_deref(), Some("inner"));
+        out.ob_start();
+        out.write_str("b");
+        assert!(out.ob_end());
+        assert!(data.borrow().is_empty());
+        out.flush().unwrap();
+        assert_eq!(&*data.borrow(), b"ab");
+        out.write_str("late");
+        assert!(!out.discard());
+        out.flush().unwrap();
+        assert_eq!(&*data.borrow(), b"ab");
+    }
+}
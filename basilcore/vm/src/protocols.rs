@@ -0,0 +1,187 @@
+// Protocols: objects behave like built-in values by defining methods with these names.
+// Basil classes (inline or CLASS("file")) define them as FUNCs; Rust BasicObjects list
+// them in their descriptor and answer them in call().
+//
+//   _ITER()         FOR EACH and SORT: a list, array or dict to walk, or an iterator object
+//   _NEXT()         iterator: the next element, or NULL when there are no more
+//   _GETITEM(i)     obj[i]
+//   _SETITEM(i, v)  obj[i] = v
+//   _STR$()         PRINT and string concatenation
+//   _EQ(other)      = and <> (falls back to _CMP)
+//   _CMP(other)     <, <=, >, >= and SORT: negative, zero or positive
+//
+// Basil methods run as nested calls on this VM and their result is used in place.
+
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use basil_bytecode::{Function, ObjectRef, Value};
+use basil_common::{BasilError, Result};
+
+use crate::csv::CsvReader;
+use crate::decimal;
+use crate::generators::{self, Generator};
+use crate::{classes, is_truthy, ClassEnv, ClassInstance, Instance, VM};
+
+enum Target {
+    Method(String, classes::Method),
+    ClassFile(Rc<ClassEnv>, Rc<Function>),
+    Generator,
+    CsvReader,
+    Host,
+}
+
+// How `rc` answers the protocol method `name`, if it defines it.
+fn target(rc: &ObjectRef, name: &str) -> Option<Target> {
+    let obj = rc.borrow();
+    if let Some(any) = obj.as_any() {
+        if let Some(inst) = any.downcast_ref::<Instance>() {
+            return inst.class.method(name, None).ok().map(|m| Target::Method(inst.class.name.clone(), m.clone()));
+        }
+        if let Some(inst) = any.downcast_ref::<ClassInstance>() {
+            return inst.method(name).ok().map(|f| Target::ClassFile(inst.env.clone(), f));
+        }
+        if any.is::<Generator>() {
+            return name.eq_ignore_ascii_case("_NEXT").then_some(Target::Generator);
+        }
+        if any.is::<CsvReader>() {
+            return name.eq_ignore_ascii_case("_NEXT").then_some(Target::CsvReader);
+        }
+    }
+    obj.descriptor().methods.iter().any(|m| m.name.eq_ignore_ascii_case(name)).then_some(Target::Host)
+}
+
+pub(crate) fn has_protocol(rc: &ObjectRef, name: &str) -> bool {
+    target(rc, name).is_some()
+}
+
+impl VM {
+    // Call protocol method `name` of `rc`; None if the object does not define it.
+    pub(crate) fn call_protocol(&mut self, rc: &ObjectRef, name: &str, args: Vec<Value>) -> Result<Option<Value>> {
+        let (depth, base) = (self.frames.len(), self.stack.len());
+        match target(rc, name) {
+            None => return Ok(None),
+            Some(Target::Host) => return rc.borrow_mut().call(name, &args).map(Some),
+            Some(Target::Generator) => return Ok(Some(self.generator_next(&Value::Object(rc.clone()))?.unwrap_or(Value::Null))),
+            Some(Target::CsvReader) => return self.csv_next(rc).map(Some),
+            Some(Target::Method(class, m)) => self.push_method_frame(&class, name, &m, Value::Object(rc.clone()), args, false)?,
+            Some(Target::ClassFile(env, f)) => self.push_class_frame(&env, &f, args)?,
+        }
+        self.finish_call(depth, base).map(Some)
+    }
+
+    // String form for PRINT and string concatenation, using _STR$ of objects (also
+    // inside lists and dicts).
+    pub(crate) fn display_string(&mut self, v: &Value) -> Result<String> {
+        match v {
+            Value::Object(rc) => match self.call_protocol(rc, "_STR$", Vec::new())? {
+                Some(s) => Ok(format!("{}", s)),
+                None => Ok(format!("{}", v)),
+            },
+            Value::List(items) if items.borrow().iter().any(is_container) => {
+                let items = items.borrow().clone();
+                let mut parts = Vec::with_capacity(items.len());
+                for it in &items { parts.push(self.display_string(it)?); }
+                Ok(format!("[{}]", parts.join(", ")))
+            }
+            Value::Dict(map) if map.borrow().values().any(is_container) => {
+                let entries: Vec<(String, Value)> = map.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                let mut parts = Vec::with_capacity(entries.len());
+                for (k, v) in &entries { parts.push(format!("\"{}\": {}", k, self.display_string(v)?)); }
+                Ok(format!("{{{}}}", parts.join(", ")))
+            }
+            _ => Ok(format!("{}", v)),
+        }
+    }
+
+    // a = b for objects defining _EQ or _CMP (on either side).
+    pub(crate) fn protocol_eq(&mut self, a: &Value, b: &Value) -> Result<Option<bool>> {
+        if let Value::Object(rc) = a {
+            if let Some(r) = self.call_protocol(rc, "_EQ", vec![b.clone()])? { return Ok(Some(is_truthy(&r))); }
+        }
+        if let Value::Object(rc) = b {
+            if let Some(r) = self.call_protocol(rc, "_EQ", vec![a.clone()])? { return Ok(Some(is_truthy(&r))); }
+        }
+        Ok(self.protocol_cmp(a, b)?.map(|o| o == Ordering::Equal))
+    }
+
+    // Ordering of a and b for objects defining _CMP (on either side).
+    pub(crate) fn protocol_cmp(&mut self, a: &Value, b: &Value) -> Result<Option<Ordering>> {
+        if let Value::Object(rc) = a {
+            if let Some(r) = self.call_protocol(rc, "_CMP", vec![b.clone()])? { return Ok(Some(self.sign(r)?)); }
+        }
+        if let Value::Object(rc) = b {
+            if let Some(r) = self.call_protocol(rc, "_CMP", vec![a.clone()])? { return Ok(Some(self.sign(r)?.reverse())); }
+        }
+        Ok(None)
+    }
+
+    fn sign(&self, r: Value) -> Result<Ordering> {
+        let n = self.as_num(r)?;
+        Ok(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal))
+    }
+
+    // Ordering used by SORT: numbers, strings, and objects through _CMP.
+    pub(crate) fn compare_values(&mut self, a: &Value, b: &Value) -> Result<Ordering> {
+        if let Some(o) = self.protocol_cmp(a, b)? { return Ok(o); }
+        match (a, b) {
+            (Value::Str(x), Value::Str(y)) => Ok(x.cmp(y)),
+            (Value::Decimal(_), Value::Num(_) | Value::Int(_) | Value::Bool(_) | Value::Decimal(_))
+            | (Value::Num(_) | Value::Int(_) | Value::Bool(_), Value::Decimal(_)) => {
+                Ok(decimal::operand(a)?.cmp(&decimal::operand(b)?))
+            }
+            (Value::Num(_) | Value::Int(_) | Value::Bool(_), Value::Num(_) | Value::Int(_) | Value::Bool(_)) => {
+                let (x, y) = (self.as_num(a.clone())?, self.as_num(b.clone())?);
+                Ok(x.partial_cmp(&y).unwrap_or(Ordering::Equal))
+            }
+            _ => Err(BasilError(format!("SORT: cannot compare {} with {}", self.type_of(a), self.type_of(b)))),
+        }
+    }
+
+    // Stable merge sort; comparisons may run Basil code and fail.
+    pub(crate) fn sort_values(&mut self, mut v: Vec<Value>) -> Result<Vec<Value>> {
+        if v.len() < 2 { return Ok(v); }
+        let right = v.split_off(v.len() / 2);
+        let (left, right) = (self.sort_values(v)?, self.sort_values(right)?);
+        let mut out = Vec::with_capacity(left.len() + right.len());
+        let (mut l, mut r) = (left.into_iter().peekable(), right.into_iter().peekable());
+        while let (Some(a), Some(b)) = (l.peek(), r.peek()) {
+            if self.compare_values(b, a)? == Ordering::Less { out.extend(r.next()); } else { out.extend(l.next()); }
+        }
+        out.extend(l);
+        out.extend(r);
+        Ok(out)
+    }
+
+    // Next element of an iterable object for FOR EACH and SORT, None at the end. A
+    // generator ends when it finishes, so it may YIELD NULL; other iterators end on NULL.
+    pub(crate) fn iter_next(&mut self, rc: &ObjectRef) -> Result<Option<Value>> {
+        if generators::is_generator(rc) {
+            return self.generator_next(&Value::Object(rc.clone()));
+        }
+        Ok(self.call_protocol(rc, "_NEXT", Vec::new())?.filter(|v| !matches!(v, Value::Null)))
+    }
+
+    // Elements FOR EACH would visit, for SORT.
+    pub(crate) fn collect_values(&mut self, v: Value) -> Result<Vec<Value>> {
+        let v = match &v {
+            Value::Object(rc) => self.call_protocol(rc, "_ITER", Vec::new())?.unwrap_or(v),
+            _ => v,
+        };
+        match v {
+            Value::List(items) => Ok(items.borrow().clone()),
+            Value::Array(arr) => Ok(arr.data.borrow().clone()),
+            Value::Dict(map) => Ok(map.borrow().keys().map(|k| Value::Str(k.clone())).collect()),
+            Value::Object(rc) if has_protocol(&rc, "_NEXT") => {
+                let mut out = Vec::new();
+                while let Some(x) = self.iter_next(&rc)? { out.push(x); }
+                Ok(out)
+            }
+            other => Err(BasilError(format!("SORT expects a list, array or iterable object (got TYPE={})", self.type_of(&other)))),
+        }
+    }
+}
+
+fn is_container(v: &Value) -> bool {
+    matches!(v, Value::Object(_) | Value::List(_) | Value::Dict(_))
+}
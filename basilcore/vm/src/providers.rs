@@ -0,0 +1,292 @@
+// Pluggable services used by the VM builtins, so embedding hosts can sandbox or fake them:
+//
+//   FileSystem   FOPEN/F*, READFILE$, WRITEFILE, APPENDFILE, COPY, MOVE, RENAME, DELETE,
+//                DIR$, MKDIRS%, LOADENV%
+//   Environment  ENV$, SETENV/EXPORTENV, LOADENV% and the CGI variables (QUERY_STRING, ...)
+//   Clock        SLEEP, the current time and the event loop's timers (AWAIT, SETTIMEOUT, ...)
+//
+// Output is replaced with VM::set_output and keyboard input with VM::set_input
+// (InputProvider). The defaults talk to the OS; MemoryFileSystem, MapEnvironment and
+// ManualClock are in-process stand-ins. Providers are shared (Rc) so the host can keep
+// a handle to inspect files or advance time while the VM holds another.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+pub trait FileStream: Read + Write + Seek {}
+impl<T: Read + Write + Seek> FileStream for T {}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenMode {
+    pub read: bool,
+    pub write: bool,
+    pub append: bool,
+    pub create: bool,
+    pub truncate: bool,
+}
+
+pub trait FileSystem {
+    fn open(&self, path: &str, mode: OpenMode) -> io::Result<Box<dyn FileStream>>;
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+    fn write(&self, path: &str, data: &[u8], append: bool) -> io::Result<()>;
+    fn copy(&self, src: &str, dst: &str) -> io::Result<()>;
+    fn rename(&self, src: &str, dst: &str) -> io::Result<()>;
+    fn remove_file(&self, path: &str) -> io::Result<()>;
+    fn create_dir_all(&self, path: &str) -> io::Result<()>;
+    // Names of the regular files in `dir`.
+    fn list_files(&self, dir: &str) -> io::Result<Vec<String>>;
+}
+
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn open(&self, path: &str, mode: OpenMode) -> io::Result<Box<dyn FileStream>> {
+        let f = fs::OpenOptions::new()
+            .read(mode.read).write(mode.write).append(mode.append)
+            .create(mode.create).truncate(mode.truncate)
+            .open(path)?;
+        Ok(Box::new(f))
+    }
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> { fs::read(path) }
+    fn write(&self, path: &str, data: &[u8], append: bool) -> io::Result<()> {
+        let mut f = fs::OpenOptions::new().write(true).create(true).append(append).truncate(!append).open(path)?;
+        f.write_all(data)?;
+        f.flush()
+    }
+    fn copy(&self, src: &str, dst: &str) -> io::Result<()> { fs::copy(src, dst).map(|_| ()) }
+    fn rename(&self, src: &str, dst: &str) -> io::Result<()> { fs::rename(src, dst) }
+    fn remove_file(&self, path: &str) -> io::Result<()> { fs::remove_file(path) }
+    fn create_dir_all(&self, path: &str) -> io::Result<()> { fs::create_dir_all(path) }
+    fn list_files(&self, dir: &str) -> io::Result<Vec<String>> {
+        let mut out = Vec::new();
+        for ent in fs::read_dir(dir)? {
+            let ent = ent?;
+            if ent.metadata()?.is_file() { out.push(ent.file_name().to_string_lossy().into_owned()); }
+        }
+        Ok(out)
+    }
+}
+
+type FileData = Rc<RefCell<Vec<u8>>>;
+
+// Files kept in memory, keyed by normalized relative path ("./a/../b.txt" == "b.txt").
+// Directories exist implicitly.
+#[derive(Default)]
+pub struct MemoryFileSystem {
+    files: RefCell<BTreeMap<PathBuf, FileData>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self { MemoryFileSystem::default() }
+
+    pub fn insert(&self, path: &str, data: impl Into<Vec<u8>>) {
+        self.files.borrow_mut().insert(norm(path), Rc::new(RefCell::new(data.into())));
+    }
+
+    pub fn contents(&self, path: &str) -> Option<Vec<u8>> {
+        self.files.borrow().get(&norm(path)).map(|d| d.borrow().clone())
+    }
+
+    fn get(&self, path: &str) -> io::Result<FileData> {
+        self.files.borrow().get(&norm(path)).cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{}: no such file", path)))
+    }
+}
+
+fn norm(path: &str) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in Path::new(path).components() {
+        match c {
+            Component::Normal(s) => out.push(s),
+            Component::ParentDir => { out.pop(); }
+            _ => {}
+        }
+    }
+    out
+}
+
+// Open file of a MemoryFileSystem; writes go straight to the shared buffer.
+struct MemFile {
+    data: FileData,
+    pos: u64,
+    append: bool,
+}
+
+impl Read for MemFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.data.borrow();
+        let start = (self.pos as usize).min(data.len());
+        let n = buf.len().min(data.len() - start);
+        buf[..n].copy_from_slice(&data[start..start + n]);
+        self.pos = (start + n) as u64;
+        Ok(n)
+    }
+}
+
+impl Write for MemFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = self.data.borrow_mut();
+        if self.append { self.pos = data.len() as u64; }
+        let start = self.pos as usize;
+        if data.len() < start { data.resize(start, 0); }
+        let overlap = buf.len().min(data.len() - start);
+        data[start..start + overlap].copy_from_slice(&buf[..overlap]);
+        data.extend_from_slice(&buf[overlap..]);
+        self.pos = (start + buf.len()) as u64;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+impl Seek for MemFile {
+    fn seek(&mut self, to: SeekFrom) -> io::Result<u64> {
+        let len = self.data.borrow().len() as i64;
+        let new = match to {
+            SeekFrom::Start(n) => n as i64,
+            SeekFrom::End(off) => len + off,
+            SeekFrom::Current(off) => self.pos as i64 + off,
+        };
+        if new < 0 { return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before start of file")); }
+        self.pos = new as u64;
+        Ok(new as u64)
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn open(&self, path: &str, mode: OpenMode) -> io::Result<Box<dyn FileStream>> {
+        let data = match self.get(path) {
+            Ok(d) => d,
+            Err(_) if mode.create => {
+                let d: FileData = Rc::new(RefCell::new(Vec::new()));
+                self.files.borrow_mut().insert(norm(path), d.clone());
+                d
+            }
+            Err(e) => return Err(e),
+        };
+        if mode.truncate { data.borrow_mut().clear(); }
+        Ok(Box::new(MemFile { data, pos: 0, append: mode.append }))
+    }
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> { Ok(self.get(path)?.borrow().clone()) }
+    fn write(&self, path: &str, data: &[u8], append: bool) -> io::Result<()> {
+        let mut files = self.files.borrow_mut();
+        let entry = files.entry(norm(path)).or_default();
+        if !append { entry.borrow_mut().clear(); }
+        entry.borrow_mut().extend_from_slice(data);
+        Ok(())
+    }
+    fn copy(&self, src: &str, dst: &str) -> io::Result<()> {
+        let data = self.read(src)?;
+        self.write(dst, &data, false)
+    }
+    fn rename(&self, src: &str, dst: &str) -> io::Result<()> {
+        let data = self.get(src)?;
+        let mut files = self.files.borrow_mut();
+        files.remove(&norm(src));
+        files.insert(norm(dst), data);
+        Ok(())
+    }
+    fn remove_file(&self, path: &str) -> io::Result<()> {
+        self.files.borrow_mut().remove(&norm(path)).map(|_| ())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{}: no such file", path)))
+    }
+    fn create_dir_all(&self, _path: &str) -> io::Result<()> { Ok(()) }
+    fn list_files(&self, dir: &str) -> io::Result<Vec<String>> {
+        let dir = norm(dir);
+        Ok(self.files.borrow().keys()
+            .filter(|p| p.parent() == Some(dir.as_path()))
+            .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .collect())
+    }
+}
+
+pub trait Environment {
+    fn var(&self, name: &str) -> Option<String>;
+    fn set_var(&self, name: &str, value: &str);
+}
+
+// The process environment.
+pub struct OsEnvironment;
+
+impl Environment for OsEnvironment {
+    fn var(&self, name: &str) -> Option<String> { env::var(name).ok() }
+    fn set_var(&self, name: &str, value: &str) { env::set_var(name, value); }
+}
+
+// An environment private to the VM(s) it is installed in.
+#[derive(Default)]
+pub struct MapEnvironment {
+    vars: RefCell<HashMap<String, String>>,
+}
+
+impl MapEnvironment {
+    pub fn new() -> Self { MapEnvironment::default() }
+
+    // Start from a copy of the process environment.
+    pub fn from_process() -> Self {
+        MapEnvironment { vars: RefCell::new(env::vars().collect()) }
+    }
+}
+
+impl Environment for MapEnvironment {
+    fn var(&self, name: &str) -> Option<String> { self.vars.borrow().get(name).cloned() }
+    fn set_var(&self, name: &str, value: &str) { self.vars.borrow_mut().insert(name.to_string(), value.to_string()); }
+}
+
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+    fn sleep(&self, d: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime { SystemTime::now() }
+    fn sleep(&self, d: Duration) { std::thread::sleep(d); }
+}
+
+// A clock that only moves when told to; SLEEP advances it instantly.
+pub struct ManualClock {
+    now: Cell<SystemTime>,
+}
+
+impl ManualClock {
+    pub fn new(start: SystemTime) -> Self { ManualClock { now: Cell::new(start) } }
+    pub fn advance(&self, d: Duration) { self.now.set(self.now.get() + d); }
+    pub fn set(&self, t: SystemTime) { self.now.set(t); }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime { self.now.get() }
+    fn sleep(&self, d: Duration) { self.advance(d); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_files() {
+        let fs = MemoryFileSystem::new();
+        fs.write("dir/a.txt", b"hello", false).unwrap();
+        fs.write("./dir/../dir/a.txt", b" world", true).unwrap();
+        assert_eq!(fs.read("dir/a.txt").unwrap(), b"hello world");
+
+        let mut f = fs.open("dir/a.txt", OpenMode { read: true, write: true, ..Default::default() }).unwrap();
+        f.seek(SeekFrom::Start(6)).unwrap();
+        f.write_all(b"W").unwrap();
+        let mut s = String::new();
+        f.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "orld");
+        assert_eq!(fs.contents("dir/a.txt").unwrap(), b"hello World");
+
+        fs.rename("dir/a.txt", "dir/b.txt").unwrap();
+        assert_eq!(fs.list_files("dir").unwrap(), vec!["b.txt".to_string()]);
+        assert!(fs.open("missing", OpenMode { read: true, ..Default::default() }).is_err());
+    }
+}
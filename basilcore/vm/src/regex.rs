@@ -0,0 +1,609 @@
+// Regular expressions for the REGEX_* builtins, matched by a small in-tree engine.
+//
+//   REGEX_MATCH%(text$, pattern$)           1 if the pattern matches anywhere in text$, else 0
+//   REGEX_FIND$(text$, pattern$)            the first match, "" if there is none
+//   REGEX_FINDALL(text$, pattern$)          LIST of every match
+//   REGEX_CAPTURES(text$, pattern$)         groups of the first match, NULL if none: a LIST
+//                                           (whole match, then groups 1..n) or, when the
+//                                           pattern names its groups, a DICT by name
+//   REGEX_REPLACE$(text$, pattern$, with$)  every match replaced; $1, ${name}, $0, $$ in with$
+//   REGEX_SPLIT(text$, pattern$)            LIST of the pieces between matches
+//
+// Syntax: literals, ., [...] classes with ranges and ^, \d \w \s and \D \W \S, \b \B, ^ $
+// (\A \z), (...), (?:...), (?P<name>...) or (?<name>...), |, * + ? {n} {n,} {n,m} and their
+// lazy forms, and the flags (?i) (?m) (?s), also scoped as (?i:...). Matches are
+// leftmost-first as in Perl; there are no backreferences or lookaround. Each (branch,
+// position) pair is tried at most once, so no pattern can take exponential time.
+// Groups that took no part in a match are NULL. Compiled patterns are cached per VM.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use basil_bytecode::Value;
+use basil_common::{BasilError, Result};
+
+use crate::VM;
+
+const MAX_REPEAT: u32 = 1000;
+const MAX_PROGRAM: usize = 100_000;
+const MAX_NESTING: usize = 200;
+// Patterns kept by one VM before the cache starts over
+const CACHE_LIMIT: usize = 256;
+
+#[derive(Clone, Copy)]
+enum Perl { Digit, Word, Space }
+
+impl Perl {
+    fn matches(self, c: char) -> bool {
+        match self {
+            Perl::Digit => c.is_ascii_digit(),
+            Perl::Word => is_word(c),
+            Perl::Space => c.is_whitespace(),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum ClassItem {
+    Range(char, char),
+    Perl(Perl, bool),
+}
+
+#[derive(Clone)]
+struct Class {
+    items: Vec<ClassItem>,
+    negated: bool,
+    fold: bool,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let hit = |c: char| self.items.iter().any(|it| match *it {
+            ClassItem::Range(lo, hi) => lo <= c && c <= hi,
+            ClassItem::Perl(p, negated) => p.matches(c) != negated,
+        });
+        let found = hit(c) || (self.fold && (hit(lower(c)) || hit(upper(c))));
+        found != self.negated
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Look { Start, End, LineStart, LineEnd, WordBoundary, NotWordBoundary }
+
+fn look_at(look: Look, text: &[char], pos: usize) -> bool {
+    let word_before = pos > 0 && is_word(text[pos - 1]);
+    let word_after = pos < text.len() && is_word(text[pos]);
+    match look {
+        Look::Start => pos == 0,
+        Look::End => pos == text.len(),
+        Look::LineStart => pos == 0 || text[pos - 1] == '\n',
+        Look::LineEnd => pos == text.len() || text[pos] == '\n',
+        Look::WordBoundary => word_before != word_after,
+        Look::NotWordBoundary => word_before == word_after,
+    }
+}
+
+fn is_word(c: char) -> bool { c.is_alphanumeric() || c == '_' }
+fn lower(c: char) -> char { c.to_lowercase().next().unwrap_or(c) }
+fn upper(c: char) -> char { c.to_uppercase().next().unwrap_or(c) }
+
+enum Node {
+    Empty,
+    Char(char, bool),
+    Any(bool),
+    Class(Class),
+    Look(Look),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat { node: Box<Node>, min: u32, max: Option<u32>, greedy: bool },
+}
+
+enum Escaped {
+    Char(char),
+    Perl(Perl, bool),
+    Look(Look),
+}
+
+#[derive(Clone, Copy, Default)]
+struct Flags {
+    fold: bool,
+    multi_line: bool,
+    dot_all: bool,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+    groups: usize,
+    names: Vec<(String, usize)>,
+}
+
+type Parsed<T> = std::result::Result<T, String>;
+
+impl Parser {
+    fn peek(&self) -> Option<char> { self.chars.get(self.pos).copied() }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() { self.pos += 1; }
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) { self.pos += 1; true } else { false }
+    }
+
+    fn alternation(&mut self, flags: &mut Flags) -> Parsed<Node> {
+        let mut alts = vec![self.concat(flags)?];
+        while self.eat('|') { alts.push(self.concat(flags)?); }
+        Ok(if alts.len() == 1 { alts.remove(0) } else { Node::Alt(alts) })
+    }
+
+    fn concat(&mut self, flags: &mut Flags) -> Parsed<Node> {
+        let mut items = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' { break; }
+            // A bare flag group like (?i) changes `flags` and matches nothing
+            if let Some(atom) = self.atom(flags)? { items.push(self.quantified(atom)?); }
+        }
+        Ok(match items.len() {
+            0 => Node::Empty,
+            1 => items.remove(0),
+            _ => Node::Concat(items),
+        })
+    }
+
+    fn atom(&mut self, flags: &mut Flags) -> Parsed<Option<Node>> {
+        let node = match self.next() {
+            Some('.') => Node::Any(flags.dot_all),
+            Some('^') => Node::Look(if flags.multi_line { Look::LineStart } else { Look::Start }),
+            Some('$') => Node::Look(if flags.multi_line { Look::LineEnd } else { Look::End }),
+            Some('[') => Node::Class(self.class(flags)?),
+            Some('(') => return self.group(flags),
+            Some('\\') => match self.escape()? {
+                Escaped::Char(c) => Node::Char(c, flags.fold),
+                Escaped::Perl(p, negated) => Node::Class(Class { items: vec![ClassItem::Perl(p, negated)], negated: false, fold: false }),
+                Escaped::Look(l) => Node::Look(l),
+            },
+            Some(c @ ('*' | '+' | '?')) => return Err(format!("nothing to repeat before '{}'", c)),
+            Some(c) => Node::Char(c, flags.fold),
+            None => Node::Empty,
+        };
+        Ok(Some(node))
+    }
+
+    fn group(&mut self, flags: &mut Flags) -> Parsed<Option<Node>> {
+        let mut inner = *flags;
+        let mut index = None;
+        if self.eat('?') {
+            let python_style = self.peek() == Some('P') && self.chars.get(self.pos + 1) == Some(&'<');
+            if python_style { self.pos += 1; }
+            let named = self.eat('<');
+            if named {
+                if matches!(self.peek(), Some('=' | '!')) { return Err("lookbehind is not supported".into()); }
+                let mut name = String::new();
+                loop {
+                    match self.next() {
+                        Some('>') => break,
+                        Some(c) if is_word(c) => name.push(c),
+                        _ => return Err("group names must be letters, digits or _ closed by '>'".into()),
+                    }
+                }
+                if name.is_empty() { return Err("empty group name".into()); }
+                if self.names.iter().any(|(n, _)| *n == name) { return Err(format!("duplicate group name '{}'", name)); }
+                self.groups += 1;
+                index = Some(self.groups);
+                self.names.push((name, self.groups));
+            } else if !self.eat(':') {
+                let mut on = true;
+                loop {
+                    match self.next() {
+                        Some('i') => inner.fold = on,
+                        Some('m') => inner.multi_line = on,
+                        Some('s') => inner.dot_all = on,
+                        Some('-') => on = false,
+                        Some(')') => { *flags = inner; return Ok(None); }
+                        Some(':') => break,
+                        Some(c) => return Err(format!("unsupported group (?{}", c)),
+                        None => return Err("unclosed group".into()),
+                    }
+                }
+            }
+        } else {
+            self.groups += 1;
+            index = Some(self.groups);
+        }
+        self.depth += 1;
+        if self.depth > MAX_NESTING { return Err("groups nested too deeply".into()); }
+        let node = self.alternation(&mut inner)?;
+        self.depth -= 1;
+        if !self.eat(')') { return Err("unclosed group".into()); }
+        Ok(Some(Node::Group(Box::new(node), index)))
+    }
+
+    fn escape(&mut self) -> Parsed<Escaped> {
+        let c = self.next().ok_or("pattern ends with \\")?;
+        Ok(match c {
+            'd' => Escaped::Perl(Perl::Digit, false),
+            'D' => Escaped::Perl(Perl::Digit, true),
+            'w' => Escaped::Perl(Perl::Word, false),
+            'W' => Escaped::Perl(Perl::Word, true),
+            's' => Escaped::Perl(Perl::Space, false),
+            'S' => Escaped::Perl(Perl::Space, true),
+            'b' => Escaped::Look(Look::WordBoundary),
+            'B' => Escaped::Look(Look::NotWordBoundary),
+            'A' => Escaped::Look(Look::Start),
+            'z' => Escaped::Look(Look::End),
+            'n' => Escaped::Char('\n'),
+            'r' => Escaped::Char('\r'),
+            't' => Escaped::Char('\t'),
+            'f' => Escaped::Char('\x0c'),
+            'v' => Escaped::Char('\x0b'),
+            '0' => Escaped::Char('\0'),
+            'x' => {
+                let digits: String = if self.eat('{') {
+                    let mut d = String::new();
+                    loop {
+                        match self.next() {
+                            Some('}') => break,
+                            Some(c) => d.push(c),
+                            None => return Err("unclosed \\x{...}".into()),
+                        }
+                    }
+                    d
+                } else {
+                    (0..2).filter_map(|_| self.next()).collect()
+                };
+                let code = u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32);
+                Escaped::Char(code.ok_or_else(|| format!("invalid escape \\x{}", digits))?)
+            }
+            c if c.is_ascii_alphanumeric() => return Err(format!("unknown escape \\{}", c)),
+            c => Escaped::Char(c),
+        })
+    }
+
+    fn class(&mut self, flags: &Flags) -> Parsed<Class> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or("unclosed character class")?;
+            if c == ']' && !first { break; }
+            first = false;
+            let lo = match c {
+                '\\' => match self.escape()? {
+                    Escaped::Char(c) => c,
+                    Escaped::Perl(p, negated) => { items.push(ClassItem::Perl(p, negated)); continue; }
+                    Escaped::Look(_) => return Err("\\b, \\B, \\A and \\z cannot appear in a class".into()),
+                },
+                c => c,
+            };
+            let is_range = self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']');
+            if !is_range {
+                items.push(ClassItem::Range(lo, lo));
+                continue;
+            }
+            self.pos += 1;
+            let hi = match self.next() {
+                Some('\\') => match self.escape()? {
+                    Escaped::Char(c) => c,
+                    _ => return Err("a class range must end in a character".into()),
+                },
+                Some(c) => c,
+                None => return Err("unclosed character class".into()),
+            };
+            if hi < lo { return Err(format!("invalid class range {}-{}", lo, hi)); }
+            items.push(ClassItem::Range(lo, hi));
+        }
+        Ok(Class { items, negated, fold: flags.fold })
+    }
+
+    fn quantified(&mut self, atom: Node) -> Parsed<Node> {
+        let (min, max) = match self.peek() {
+            Some('*') => { self.pos += 1; (0, None) }
+            Some('+') => { self.pos += 1; (1, None) }
+            Some('?') => { self.pos += 1; (0, Some(1)) }
+            Some('{') => match self.braces()? {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+        let greedy = !self.eat('?');
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    // {n}, {n,} or {n,m}; None, with nothing consumed, when the brace is just a character
+    fn braces(&mut self) -> Parsed<Option<(u32, Option<u32>)>> {
+        let Some(len) = self.chars[self.pos..].iter().position(|&c| c == '}') else { return Ok(None) };
+        let body: String = self.chars[self.pos + 1..self.pos + len].iter().collect();
+        let number = |s: &str| if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) { s.parse::<u32>().ok() } else { None };
+        let (min, max) = match body.split_once(',') {
+            None => match number(&body) { Some(n) => (n, Some(n)), None => return Ok(None) },
+            Some((lo, "")) => match number(lo) { Some(n) => (n, None), None => return Ok(None) },
+            Some((lo, hi)) => match (number(lo), number(hi)) { (Some(a), Some(b)) => (a, Some(b)), _ => return Ok(None) },
+        };
+        if max.is_some_and(|m| m < min) { return Err(format!("invalid repetition {{{}}}", body)); }
+        if min.max(max.unwrap_or(0)) > MAX_REPEAT { return Err(format!("repetition count above {}", MAX_REPEAT)); }
+        self.pos += len + 1;
+        Ok(Some((min, max)))
+    }
+}
+
+enum Inst {
+    Char(char, bool),
+    Any(bool),
+    Class(Class),
+    Look(Look),
+    Split(usize, usize),
+    Jmp(usize),
+    Save(usize),
+    Match,
+}
+
+struct Compiler {
+    prog: Vec<Inst>,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> Parsed<usize> {
+        if self.prog.len() >= MAX_PROGRAM { return Err("pattern is too large".into()); }
+        self.prog.push(inst);
+        Ok(self.prog.len() - 1)
+    }
+
+    // Patch a placeholder Split so the preferred branch is `first` when greedy
+    fn patch_split(&mut self, at: usize, first: usize, second: usize, greedy: bool) {
+        self.prog[at] = if greedy { Inst::Split(first, second) } else { Inst::Split(second, first) };
+    }
+
+    fn node(&mut self, node: &Node) -> Parsed<()> {
+        match node {
+            Node::Empty => {}
+            Node::Char(c, fold) => { self.emit(Inst::Char(*c, *fold))?; }
+            Node::Any(newline) => { self.emit(Inst::Any(*newline))?; }
+            Node::Class(class) => { self.emit(Inst::Class(class.clone()))?; }
+            Node::Look(look) => { self.emit(Inst::Look(*look))?; }
+            Node::Group(inner, index) => {
+                if let Some(k) = index { self.emit(Inst::Save(2 * k))?; }
+                self.node(inner)?;
+                if let Some(k) = index { self.emit(Inst::Save(2 * k + 1))?; }
+            }
+            Node::Concat(items) => for it in items { self.node(it)?; },
+            Node::Alt(alts) => {
+                let mut jumps = Vec::new();
+                for (i, alt) in alts.iter().enumerate() {
+                    if i + 1 == alts.len() {
+                        self.node(alt)?;
+                        break;
+                    }
+                    let split = self.emit(Inst::Split(0, 0))?;
+                    self.node(alt)?;
+                    jumps.push(self.emit(Inst::Jmp(0))?);
+                    self.patch_split(split, split + 1, self.prog.len(), true);
+                }
+                let end = self.prog.len();
+                for j in jumps { self.prog[j] = Inst::Jmp(end); }
+            }
+            Node::Repeat { node, min, max, greedy } => {
+                for _ in 0..*min { self.node(node)?; }
+                match max {
+                    None => {
+                        let split = self.emit(Inst::Split(0, 0))?;
+                        self.node(node)?;
+                        self.emit(Inst::Jmp(split))?;
+                        self.patch_split(split, split + 1, self.prog.len(), *greedy);
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit(Inst::Split(0, 0))?);
+                            self.node(node)?;
+                        }
+                        let out = self.prog.len();
+                        for s in splits { self.patch_split(s, s + 1, out, *greedy); }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Job {
+    Try(usize, usize),
+    Restore(usize, Option<usize>),
+}
+
+// Group k of a match spans slots 2k..2k+1 (group 0 is the whole match)
+type Slots = Vec<Option<usize>>;
+
+pub struct Regex {
+    prog: Vec<Inst>,
+    groups: usize,
+    names: Vec<(String, usize)>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex> {
+        Regex::compile(pattern).map_err(|msg| BasilError(format!("invalid regex {:?}: {}", pattern, msg)))
+    }
+
+    fn compile(pattern: &str) -> Parsed<Regex> {
+        let mut p = Parser { chars: pattern.chars().collect(), pos: 0, depth: 0, groups: 0, names: Vec::new() };
+        let node = p.alternation(&mut Flags::default())?;
+        if p.pos < p.chars.len() { return Err("unmatched ')'".into()); }
+        let mut c = Compiler { prog: Vec::new() };
+        c.emit(Inst::Save(0))?;
+        c.node(&node)?;
+        c.emit(Inst::Save(1))?;
+        c.emit(Inst::Match)?;
+        Ok(Regex { prog: c.prog, groups: p.groups + 1, names: p.names })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        self.search(&chars, 0).is_some()
+    }
+
+    pub fn find(&self, text: &str) -> Option<String> {
+        let chars: Vec<char> = text.chars().collect();
+        self.search(&chars, 0).map(|m| group_text(&chars, &m, 0).unwrap_or_default())
+    }
+
+    pub fn find_all(&self, text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        self.matches(&chars).iter().map(|m| group_text(&chars, m, 0).unwrap_or_default()).collect()
+    }
+
+    // The first match's groups as a LIST, or a DICT of the named groups; None if no match
+    pub fn captures(&self, text: &str) -> Option<Value> {
+        let chars: Vec<char> = text.chars().collect();
+        let m = self.search(&chars, 0)?;
+        let group = |k: usize| group_text(&chars, &m, k).map(Value::Str).unwrap_or(Value::Null);
+        Some(if self.names.is_empty() {
+            Value::List(Rc::new(RefCell::new((0..self.groups).map(group).collect())))
+        } else {
+            Value::Dict(Rc::new(RefCell::new(self.names.iter().map(|(n, k)| (n.clone(), group(*k))).collect())))
+        })
+    }
+
+    pub fn replace_all(&self, text: &str, with: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::new();
+        let mut last = 0;
+        for m in self.matches(&chars) {
+            let (start, end) = (m[0].unwrap_or(last), m[1].unwrap_or(last));
+            out.extend(&chars[last..start]);
+            self.expand(with, &chars, &m, &mut out);
+            last = end;
+        }
+        out.extend(&chars[last..]);
+        out
+    }
+
+    pub fn split(&self, text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut pieces = Vec::new();
+        let mut last = 0;
+        for m in self.matches(&chars) {
+            let (start, end) = (m[0].unwrap_or(last), m[1].unwrap_or(last));
+            pieces.push(chars[last..start].iter().collect());
+            last = end;
+        }
+        pieces.push(chars[last..].iter().collect());
+        pieces
+    }
+
+    // $n, ${n}, ${name} and $$ in a replacement; unknown or unmatched groups give ""
+    fn expand(&self, with: &str, chars: &[char], m: &Slots, out: &mut String) {
+        let mut it = with.chars().peekable();
+        while let Some(c) = it.next() {
+            if c != '$' {
+                out.push(c);
+                continue;
+            }
+            let group: String = match it.peek() {
+                Some('$') => { it.next(); out.push('$'); continue; }
+                Some('{') => { it.next(); it.by_ref().take_while(|&c| c != '}').collect() }
+                Some(c) if c.is_ascii_digit() => {
+                    let mut digits = String::new();
+                    while let Some(d) = it.next_if(|c| c.is_ascii_digit()) { digits.push(d); }
+                    digits
+                }
+                _ => { out.push('$'); continue; }
+            };
+            let index = group.parse::<usize>().ok().filter(|&k| k < self.groups)
+                .or_else(|| self.names.iter().find(|(n, _)| *n == group).map(|(_, k)| *k));
+            if let Some(text) = index.and_then(|k| group_text(chars, m, k)) { out.push_str(&text); }
+        }
+    }
+
+    // Every match from left to right; an empty match right where the previous one ended is skipped
+    fn matches(&self, text: &[char]) -> Vec<Slots> {
+        let mut found = Vec::new();
+        let mut start = 0;
+        let mut last_end = None;
+        while start <= text.len() {
+            let Some(m) = self.search(text, start) else { break };
+            let (s, e) = (m[0].unwrap_or(start), m[1].unwrap_or(start));
+            start = if s == e { e + 1 } else { e };
+            if s == e && last_end == Some(e) { continue; }
+            last_end = Some(e);
+            found.push(m);
+        }
+        found
+    }
+
+    // The leftmost match starting at or after `start`
+    fn search(&self, text: &[char], start: usize) -> Option<Slots> {
+        // Reaching a Split at a position already tried cannot succeed where it failed before
+        let mut tried = HashSet::new();
+        (start..=text.len()).find_map(|at| self.run(text, at, &mut tried))
+    }
+
+    fn run(&self, text: &[char], at: usize, tried: &mut HashSet<(usize, usize)>) -> Option<Slots> {
+        let mut slots: Slots = vec![None; 2 * self.groups];
+        let mut stack = vec![Job::Try(0, at)];
+        while let Some(job) = stack.pop() {
+            let (mut pc, mut pos) = match job {
+                Job::Try(pc, pos) => (pc, pos),
+                Job::Restore(slot, old) => { slots[slot] = old; continue; }
+            };
+            loop {
+                match &self.prog[pc] {
+                    Inst::Char(c, fold) => match text.get(pos) {
+                        Some(&t) if t == *c || (*fold && lower(t) == lower(*c)) => { pc += 1; pos += 1; }
+                        _ => break,
+                    },
+                    Inst::Any(newline) => match text.get(pos) {
+                        Some(&t) if *newline || t != '\n' => { pc += 1; pos += 1; }
+                        _ => break,
+                    },
+                    Inst::Class(class) => match text.get(pos) {
+                        Some(&t) if class.matches(t) => { pc += 1; pos += 1; }
+                        _ => break,
+                    },
+                    Inst::Look(look) => {
+                        if !look_at(*look, text, pos) { break; }
+                        pc += 1;
+                    }
+                    Inst::Split(first, second) => {
+                        if !tried.insert((pc, pos)) { break; }
+                        stack.push(Job::Try(*second, pos));
+                        pc = *first;
+                    }
+                    Inst::Jmp(to) => pc = *to,
+                    Inst::Save(slot) => {
+                        stack.push(Job::Restore(*slot, slots[*slot]));
+                        slots[*slot] = Some(pos);
+                        pc += 1;
+                    }
+                    Inst::Match => return Some(slots),
+                }
+            }
+        }
+        None
+    }
+}
+
+fn group_text(chars: &[char], m: &Slots, k: usize) -> Option<String> {
+    match (m.get(2 * k).copied().flatten(), m.get(2 * k + 1).copied().flatten()) {
+        (Some(s), Some(e)) if s <= e => Some(chars[s..e].iter().collect()),
+        _ => None,
+    }
+}
+
+impl VM {
+    // The compiled form of `pattern`, from this VM's cache when it has been seen before
+    pub(crate) fn regex(&mut self, pattern: &str) -> Result<Rc<Regex>> {
+        if let Some(re) = self.regexes.get(pattern) { return Ok(re.clone()); }
+        let re = Rc::new(Regex::new(pattern)?);
+        if self.regexes.len() >= CACHE_LIMIT { self.regexes.clear(); }
+        self.regexes.insert(pattern.to_string(), re.clone());
+        Ok(re)
+    }
+}
@@ -0,0 +1,204 @@
+// Route table filled by ROUTE statements. The host (basilc) drives dispatch after the
+// script's top level has run: it matches the request, sets the path params on the VM and
+// calls the handler through VM::call_value.
+
+use basil_bytecode::Value;
+use basil_common::{BasilError, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Lit(String),
+    Param(String),
+    // Trailing `*` or `*name`: captures the rest of the path (possibly empty)
+    Rest(String),
+}
+
+#[derive(Clone)]
+pub struct Route {
+    pub method: String,
+    pub pattern: String,
+    segments: Vec<Segment>,
+    pub handler: Value,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HookKind { Before, After, NotFound, Error }
+
+impl HookKind {
+    pub fn parse(s: &str) -> Option<HookKind> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "BEFORE" => Some(HookKind::Before),
+            "AFTER" => Some(HookKind::After),
+            "NOT_FOUND" | "NOTFOUND" | "404" => Some(HookKind::NotFound),
+            "ERROR" | "500" => Some(HookKind::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Routes {
+    pub routes: Vec<Route>,
+    pub before: Vec<Value>,
+    pub after: Vec<Value>,
+    pub not_found: Option<Value>,
+    pub on_error: Option<Value>,
+}
+
+impl Routes {
+    pub fn is_empty(&self) -> bool { self.routes.is_empty() }
+
+    pub fn add(&mut self, method: &str, pattern: &str, handler: Value) -> Result<()> {
+        let method = method.trim().to_ascii_uppercase();
+        if method.is_empty() || !method.chars().all(|c| c.is_ascii_alphabetic() || c == '*') {
+            return Err(BasilError(format!("ROUTE: invalid method \"{}\"", method)));
+        }
+        let segments = parse_pattern(pattern)?;
+        self.routes.push(Route { method, pattern: pattern.to_string(), segments, handler });
+        Ok(())
+    }
+
+    pub fn add_hook(&mut self, kind: HookKind, handler: Value) {
+        match kind {
+            HookKind::Before => self.before.push(handler),
+            HookKind::After => self.after.push(handler),
+            HookKind::NotFound => self.not_found = Some(handler),
+            HookKind::Error => self.on_error = Some(handler),
+        }
+    }
+
+    // First route (in registration order) whose method and pattern match.
+    // HEAD requests fall back to GET routes; "*" or "ANY" routes match every method.
+    pub fn find(&self, method: &str, path: &str) -> Option<(Value, Vec<(String, String)>)> {
+        let method = method.trim().to_ascii_uppercase();
+        let parts = split_path(path);
+        for r in &self.routes {
+            let method_ok = r.method == method
+                || r.method == "*"
+                || r.method == "ANY"
+                || (method == "HEAD" && r.method == "GET");
+            if !method_ok { continue; }
+            if let Some(params) = match_segments(&r.segments, &parts) {
+                return Some((r.handler.clone(), params));
+            }
+        }
+        None
+    }
+
+    // Methods that have some route matching `path` (used for 405-style diagnostics).
+    pub fn methods_for(&self, path: &str) -> Vec<String> {
+        let parts = split_path(path);
+        let mut out: Vec<String> = Vec::new();
+        for r in &self.routes {
+            if match_segments(&r.segments, &parts).is_some() && !out.contains(&r.method) {
+                out.push(r.method.clone());
+            }
+        }
+        out
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<Vec<Segment>> {
+    if !pattern.starts_with('/') {
+        return Err(BasilError(format!("ROUTE: pattern must start with '/': \"{}\"", pattern)));
+    }
+    let parts = split_path(pattern);
+    let mut segs = Vec::with_capacity(parts.len());
+    for (i, p) in parts.iter().enumerate() {
+        if let Some(name) = p.strip_prefix(':') {
+            if name.is_empty() {
+                return Err(BasilError(format!("ROUTE: empty parameter name in \"{}\"", pattern)));
+            }
+            segs.push(Segment::Param(name.to_string()));
+        } else if let Some(name) = p.strip_prefix('*') {
+            if i + 1 != parts.len() {
+                return Err(BasilError(format!("ROUTE: '*' must be the last segment in \"{}\"", pattern)));
+            }
+            segs.push(Segment::Rest(name.to_string()));
+        } else {
+            segs.push(Segment::Lit(p.to_string()));
+        }
+    }
+    Ok(segs)
+}
+
+// "/a//b/" -> ["a", "b"]; trailing and repeated slashes are ignored.
+fn split_path(path: &str) -> Vec<&str> {
+    let path = path.split(['?', '#']).next().unwrap_or("");
+    path.split('/').filter(|s| !s.is_empty()).collect()
+}
+
+fn match_segments(segs: &[Segment], parts: &[&str]) -> Option<Vec<(String, String)>> {
+    let mut params = Vec::new();
+    for (i, seg) in segs.iter().enumerate() {
+        match seg {
+            Segment::Rest(name) => {
+                let rest: Vec<String> = parts[i.min(parts.len())..].iter().map(|p| percent_decode(p)).collect();
+                let key = if name.is_empty() { "*".to_string() } else { name.clone() };
+                params.push((key, rest.join("/")));
+                return Some(params);
+            }
+            Segment::Lit(lit) => {
+                let p = parts.get(i)?;
+                if percent_decode(p) != *lit { return None; }
+            }
+            Segment::Param(name) => {
+                let p = parts.get(i)?;
+                params.push((name.clone(), percent_decode(p)));
+            }
+        }
+    }
+    if parts.len() == segs.len() { Some(params) } else { None }
+}
+
+// Path-style percent decoding ('+' is kept literally, unlike form encoding).
+pub fn percent_decode(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%' && i + 2 < b.len() {
+            if let Some(v) = std::str::from_utf8(&b[i + 1..i + 3]).ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(v);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(b[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h() -> Value { Value::Null }
+
+    #[test]
+    fn params_and_rest() {
+        let mut r = Routes::default();
+        r.add("GET", "/users/:id", h()).unwrap();
+        r.add("GET", "/files/*path", h()).unwrap();
+        let (_, p) = r.find("GET", "/users/42/").unwrap();
+        assert_eq!(p, vec![("id".to_string(), "42".to_string())]);
+        let (_, p) = r.find("GET", "/files/a/b%20c.txt?x=1").unwrap();
+        assert_eq!(p, vec![("path".to_string(), "a/b c.txt".to_string())]);
+        assert!(r.find("GET", "/users").is_none());
+        assert!(r.find("GET", "/users/1/2").is_none());
+    }
+
+    #[test]
+    fn methods() {
+        let mut r = Routes::default();
+        r.add("get", "/", h()).unwrap();
+        r.add("*", "/any", h()).unwrap();
+        assert!(r.find("HEAD", "/").is_some());
+        assert!(r.find("POST", "/").is_none());
+        assert!(r.find("DELETE", "/any").is_some());
+        assert_eq!(r.methods_for("/"), vec!["GET".to_string()]);
+        assert!(r.add("GET", "users", h()).is_err());
+        assert!(r.add("GET", "/*x/y", h()).is_err());
+    }
+}
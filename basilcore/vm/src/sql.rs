@@ -0,0 +1,152 @@
+// SQL parameter binding for the statement-string database builtins (SQLITE_EXEC% and
+// friends take finished SQL text).
+//
+//   SQL_BIND$(sql$, v1, v2 ...)      each ? takes the next value
+//   SQL_BIND$(sql$, [v1, v2 ...])    the same from a LIST
+//   SQL_BIND$(sql$, {"id": v ...})   :id (also @id, $id) takes the value with that key
+//   SQL_BIND_MYSQL$(sql$, ...)       the same for MySQL/MariaDB
+//
+// Values become literals: strings quoted, BYTES as X'...', DECIMALs with all their digits
+// (so NUMERIC columns get the exact amount), booleans as 1 and 0, NULL as NULL, negative
+// numbers in parentheses. Placeholders inside quotes and comments are left alone, as is
+// Postgres' ::type cast.
+//
+// Quoting follows the dialect. SQL_BIND$ writes standard SQL strings, where only ' is
+// special and is doubled: right for SQLite and for Postgres (standard_conforming_strings,
+// the default since 9.1). MySQL also treats backslash as an escape inside quotes, so there
+// a value ending in \ would swallow the closing quote; SQL_BIND_MYSQL$ escapes
+// backslashes too, and reads the statement's own quoted text and # comments the MySQL way.
+
+use basil_bytecode::Value;
+use basil_common::{BasilError, Result};
+
+use crate::bytes;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Dialect {
+    Standard,
+    MySql,
+}
+
+impl Dialect {
+    fn name(self) -> &'static str {
+        match self {
+            Dialect::Standard => "SQL_BIND$",
+            Dialect::MySql => "SQL_BIND_MYSQL$",
+        }
+    }
+}
+
+pub fn bind(sql: &str, params: &[Value], dialect: Dialect) -> Result<String> {
+    let who = dialect.name();
+    let named = match params {
+        [Value::Dict(d)] => Some(d.borrow().clone()),
+        _ => None,
+    };
+    let list: Vec<Value> = match params {
+        [Value::List(items)] => items.borrow().clone(),
+        _ => params.to_vec(),
+    };
+    let c: Vec<char> = sql.chars().collect();
+    let mut out = String::with_capacity(sql.len());
+    let mut next = 0;
+    let mut i = 0;
+    while i < c.len() {
+        let ch = c[i];
+        // Quoted text, quoted names and comments are copied as they are
+        let skip_to = match ch {
+            '\'' | '"' if dialect == Dialect::MySql => Some(mysql_quote_end(&c, i)),
+            '\'' | '"' | '`' => Some(c[i + 1..].iter().position(|&q| q == ch).map(|p| i + p + 2).unwrap_or(c.len())),
+            '-' if c.get(i + 1) == Some(&'-') => Some(c[i..].iter().position(|&q| q == '\n').map(|p| i + p).unwrap_or(c.len())),
+            '#' if dialect == Dialect::MySql => Some(c[i..].iter().position(|&q| q == '\n').map(|p| i + p).unwrap_or(c.len())),
+            '/' if c.get(i + 1) == Some(&'*') => Some(
+                (i + 2..c.len().saturating_sub(1)).find(|&j| c[j] == '*' && c[j + 1] == '/').map(|j| j + 2).unwrap_or(c.len()),
+            ),
+            _ => None,
+        };
+        if let Some(end) = skip_to {
+            out.extend(&c[i..end]);
+            i = end;
+            continue;
+        }
+        if ch == '?' && named.is_none() {
+            let v = list.get(next).ok_or_else(|| BasilError(format!(
+                "{}: the statement has more ? placeholders than the {} value(s) given", who, list.len()
+            )))?;
+            next += 1;
+            out.push_str(&literal(v, &format!("value {}", next), dialect)?);
+            i += 1;
+            continue;
+        }
+        let starts_name = matches!(c.get(i + 1), Some(n) if n.is_alphabetic() || *n == '_');
+        if let (Some(map), ':' | '@' | '$', true) = (&named, ch, starts_name) {
+            if !(ch == ':' && i > 0 && c[i - 1] == ':') {
+                let end = (i + 1..c.len()).find(|&j| !(c[j].is_alphanumeric() || c[j] == '_')).unwrap_or(c.len());
+                let name: String = c[i + 1..end].iter().collect();
+                let v = map.get(&name).ok_or_else(|| BasilError(format!("{}: no value for {}{}", who, ch, name)))?;
+                out.push_str(&literal(v, &name, dialect)?);
+                i = end;
+                continue;
+            }
+        }
+        out.push(ch);
+        i += 1;
+    }
+    if named.is_none() && next < list.len() {
+        return Err(BasilError(format!("{}: {} value(s) given but the statement has {} ? placeholder(s)", who, list.len(), next)));
+    }
+    Ok(out)
+}
+
+// End (past the closing quote) of the MySQL string starting at c[i]: a backslash escapes
+// the next character and a doubled quote stands for itself.
+fn mysql_quote_end(c: &[char], i: usize) -> usize {
+    let q = c[i];
+    let mut j = i + 1;
+    while j < c.len() {
+        if c[j] == '\\' { j += 2; continue; }
+        if c[j] == q {
+            if c.get(j + 1) == Some(&q) { j += 2; continue; }
+            return j + 1;
+        }
+        j += 1;
+    }
+    c.len()
+}
+
+// A MySQL string literal; the escapes are those of mysql_real_escape_string
+fn mysql_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('\'');
+    for ch in s.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("\\'"),
+            '"' => out.push_str("\\\""),
+            '\0' => out.push_str("\\0"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\x1a' => out.push_str("\\Z"),
+            _ => out.push(ch),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+fn literal(v: &Value, which: &str, dialect: Dialect) -> Result<String> {
+    let who = dialect.name();
+    let signed = |text: String| if text.starts_with('-') { format!("({})", text) } else { text };
+    Ok(match v {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) => (if *b { "1" } else { "0" }).to_string(),
+        Value::Int(i) => signed(i.to_string()),
+        Value::Num(n) if n.is_finite() => signed(n.to_string()),
+        Value::Decimal(d) => signed(d.to_string()),
+        Value::Str(s) if dialect == Dialect::MySql => mysql_string(s),
+        Value::Str(s) => format!("'{}'", s.replace('\'', "''")),
+        Value::Bytes(b) => format!("X'{}'", bytes::to_hex(b)),
+        Value::Num(n) => return Err(BasilError(format!("{}: {} is {}, which SQL cannot store", who, which, n))),
+        _ => return Err(BasilError(format!("{}: {} is not a number, string, BYTES or NULL", who, which))),
+    })
+}
@@ -0,0 +1,502 @@
+// Classic BASIC number formatting and conversion.
+//
+//   PRINT USING mask$; v1, v2 ...    USING$(mask$, v1, v2 ...)
+//   FORMAT$(n, pattern$ [, locale$])
+//   STR$(n)    VAL(s$)
+//
+// PRINT USING masks follow GW-BASIC and QBasic:
+//   #  digit    .  decimal point    ,  thousands (left of the point)    ^^^^ or ^^^^^  exponent
+//   +  sign, first or last    -  trailing minus    **  fill with *    $$  floating $    **$  both
+//   !  first character    \  \  as many characters as the field is wide    &  whole string
+//   _  next character as is
+// Everything else is copied. Values fill the fields in order and the mask starts over while
+// values remain; a number too wide for its field, or infinite or NaN, is printed in full
+// after a %. In PRINT USING the mask is read as written, so "\  \" needs no escaping; the
+// USING$ function takes an ordinary string, where each backslash is written \\. Masks in the
+// older C style (%d, %6.2f, %s) are still handled by the VM's own formatter.
+//
+// FORMAT$ patterns are spreadsheet style: 0 and # digits, , grouping, . decimals, % percent,
+// E+00 exponent, quoted or \-escaped text, and positive;negative;zero sections. The locale
+// ("en", "de-DE", "fr", "de-CH", ...) only chooses the grouping and decimal marks.
+//
+// DECIMALs are laid out from their exact digits, so 1.005@ rounds to 1.01 where the FLOAT
+// 1.005 (really 1.00499...) gives 1.00. Exponent fields still go through a FLOAT.
+
+use basil_bytecode::{Decimal, Rounding, Value};
+use basil_common::{BasilError, Result};
+
+enum Piece {
+    Lit(String),
+    Num(NumField),
+    First,
+    Whole,
+    Width(usize),
+}
+
+#[derive(Default)]
+struct NumField {
+    // Positions left of the point, counting ** / $$ / **$ and commas
+    left: usize,
+    // Digits after the point, None without a point
+    right: Option<usize>,
+    comma: bool,
+    star: bool,
+    dollar: bool,
+    lead_plus: bool,
+    trail: Option<char>,
+    // Width of the ^^^^ part, 0 for fixed point
+    exp: usize,
+}
+
+// True for masks meant for the older %d / %f / %s USING$
+pub fn is_printf_style(mask: &str) -> bool {
+    let b = mask.as_bytes();
+    (0..b.len()).any(|i| {
+        if b[i] != b'%' { return false; }
+        let mut j = i + 1;
+        while j < b.len() && (b[j].is_ascii_digit() || b[j] == b'.') { j += 1; }
+        matches!(b.get(j), Some(b'd' | b'i' | b'f' | b'F' | b's' | b'S' | b'%'))
+    })
+}
+
+pub fn using(mask: &str, args: &[Value]) -> Result<String> {
+    let pieces = parse_mask(mask);
+    let mut out = String::new();
+    if pieces.iter().all(|p| matches!(p, Piece::Lit(_))) {
+        if !args.is_empty() { return Err(BasilError("USING$: the format has no fields for its values".into())); }
+        out.push_str(mask);
+        return Ok(out);
+    }
+    let mut next = 0;
+    'mask: loop {
+        for p in &pieces {
+            if let Piece::Lit(s) = p { out.push_str(s); continue; }
+            let Some(v) = args.get(next) else { break 'mask; };
+            next += 1;
+            match (p, v) {
+                (Piece::Num(f), Value::Int(_) | Value::Num(_) | Value::Bool(_) | Value::Decimal(_)) => out.push_str(&number(f, v)),
+                (Piece::Num(_), _) => return Err(BasilError(format!("USING$: value {} is not a number but its field is numeric", next))),
+                (_, Value::Str(s)) => match p {
+                    Piece::First => out.push(s.chars().next().unwrap_or(' ')),
+                    Piece::Width(w) => out.push_str(&format!("{:<w$}", s.chars().take(*w).collect::<String>())),
+                    _ => out.push_str(s),
+                },
+                _ => return Err(BasilError(format!("USING$: value {} is not a string but its field is", next))),
+            }
+        }
+        if next >= args.len() { break; }
+    }
+    Ok(out)
+}
+
+fn num_of(v: &Value) -> f64 {
+    match v {
+        Value::Int(i) => *i as f64,
+        Value::Num(n) => *n,
+        Value::Bool(b) => *b as i64 as f64,
+        Value::Decimal(d) => d.to_f64(),
+        _ => 0.0,
+    }
+}
+
+fn at(m: &[char], i: usize, s: &str) -> bool {
+    s.chars().enumerate().all(|(k, c)| m.get(i + k) == Some(&c))
+}
+
+fn starts_number(m: &[char], i: usize) -> bool {
+    at(m, i, "#") || at(m, i, ".#") || at(m, i, "**") || at(m, i, "$$")
+}
+
+fn parse_mask(mask: &str) -> Vec<Piece> {
+    let m: Vec<char> = mask.chars().collect();
+    let mut out = Vec::new();
+    let mut lit = String::new();
+    let mut i = 0;
+    while i < m.len() {
+        let field = match m[i] {
+            '_' if i + 1 < m.len() => {
+                lit.push(m[i + 1]);
+                i += 2;
+                continue;
+            }
+            '!' => Some((Piece::First, i + 1)),
+            '&' => Some((Piece::Whole, i + 1)),
+            '\\' => {
+                let end = (i + 1..m.len()).find(|&j| m[j] != ' ');
+                end.filter(|&j| m[j] == '\\').map(|j| (Piece::Width(j - i + 1), j + 1))
+            }
+            _ => numeric_field(&m, i),
+        };
+        match field {
+            Some((p, next)) => {
+                if !lit.is_empty() { out.push(Piece::Lit(std::mem::take(&mut lit))); }
+                out.push(p);
+                i = next;
+            }
+            None => {
+                lit.push(m[i]);
+                i += 1;
+            }
+        }
+    }
+    if !lit.is_empty() { out.push(Piece::Lit(lit)); }
+    out
+}
+
+fn numeric_field(m: &[char], i: usize) -> Option<(Piece, usize)> {
+    let mut f = NumField::default();
+    let mut j = i;
+    if m[j] == '+' && starts_number(m, j + 1) {
+        f.lead_plus = true;
+        j += 1;
+    }
+    if !starts_number(m, j) { return None; }
+    if at(m, j, "**$") {
+        (f.star, f.dollar, f.left) = (true, true, 3);
+    } else if at(m, j, "**") {
+        (f.star, f.left) = (true, 2);
+    } else if at(m, j, "$$") {
+        (f.dollar, f.left) = (true, 2);
+    }
+    j += f.left;
+    while at(m, j, "#") || (at(m, j, ",") && (at(m, j + 1, "#") || at(m, j + 1, ",") || at(m, j + 1, "."))) {
+        f.comma |= m[j] == ',';
+        f.left += 1;
+        j += 1;
+    }
+    if at(m, j, ".") {
+        j += 1;
+        let digits = m[j..].iter().take_while(|&&c| c == '#').count();
+        f.right = Some(digits);
+        j += digits;
+    }
+    if at(m, j, "^^^^^") {
+        f.exp = 5;
+    } else if at(m, j, "^^^^") {
+        f.exp = 4;
+    }
+    j += f.exp;
+    if !f.lead_plus && (at(m, j, "+") || at(m, j, "-")) {
+        f.trail = Some(m[j]);
+        j += 1;
+    }
+    Some((Piece::Num(f), j))
+}
+
+// Fixed-point text of |x| with `places` decimals, halves rounded away from zero
+fn fixed(x: f64, places: usize) -> (String, String) {
+    let scale = 10f64.powi(places as i32);
+    let r = if (x * scale).is_finite() { (x.abs() * scale).round() / scale } else { x.abs() };
+    split_point(format!("{:.*}", places, r))
+}
+
+// fixed() for a DECIMAL, from its own digits
+fn fixed_exact(d: &Decimal, places: usize) -> (String, String) {
+    split_point(d.abs().round(places as u32, Rounding::HalfUp).to_string())
+}
+
+fn split_point(s: String) -> (String, String) {
+    match s.split_once('.') {
+        Some((a, b)) => (a.to_string(), b.to_string()),
+        None => (s, String::new()),
+    }
+}
+
+fn group(digits: &str, sep: &str) -> String {
+    let n = digits.chars().count();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (n - i).is_multiple_of(3) { out.push_str(sep); }
+        out.push(c);
+    }
+    out
+}
+
+fn number(f: &NumField, v: &Value) -> String {
+    let x = num_of(v);
+    // No field is wide enough for an infinity or NaN: the overflow marker and the value
+    if !x.is_finite() { return format!("%{}", x); }
+    let right = f.right.unwrap_or(0);
+    let (int_part, frac, exp_text) = if f.exp > 0 {
+        let (m, e) = scientific(f, x);
+        (m.0, m.1, Some(e))
+    } else {
+        let (a, b) = match v {
+            Value::Decimal(d) => fixed_exact(d, right),
+            _ => fixed(x, right),
+        };
+        (if a == "0" { String::new() } else { a }, b, None)
+    };
+    let neg = x < 0.0 && (int_part.chars().chain(frac.chars()).any(|c| c != '0'));
+    let int_part = if f.comma { group(&int_part, ",") } else { int_part };
+
+    let mut body = String::new();
+    if f.lead_plus { body.push(if neg { '-' } else { '+' }); }
+    else if neg && f.trail.is_none() { body.push('-'); }
+    if f.dollar { body.push('$'); }
+    let width = f.left + f.lead_plus as usize;
+    // A lone zero before the point when the field has room for it; exponent fields keep
+    // that position for the sign
+    let zero = int_part.is_empty() && f.exp == 0 && (body.chars().count() < width || f.right.is_none());
+    let int_part = if zero { "0".to_string() } else { int_part };
+    body.push_str(&int_part);
+
+    let mut out = String::new();
+    let used = body.chars().count();
+    if used > width {
+        out.push('%');
+    } else {
+        let pad = if f.star { '*' } else { ' ' };
+        out.extend(std::iter::repeat_n(pad, width - used));
+    }
+    out.push_str(&body);
+    if f.right.is_some() {
+        out.push('.');
+        out.push_str(&frac);
+    }
+    if let Some(e) = exp_text { out.push_str(&e); }
+    match f.trail {
+        Some('+') => out.push(if neg { '-' } else { '+' }),
+        Some(_) => out.push(if neg { '-' } else { ' ' }),
+        None => {}
+    }
+    out
+}
+
+// Mantissa (integer digits, fraction digits) and exponent text for a ^^^^ field. One
+// leading position stays free for the sign unless the mask has a + or trailing -.
+fn scientific(f: &NumField, x: f64) -> ((String, String), String) {
+    let right = f.right.unwrap_or(0);
+    let free_sign = !f.lead_plus && f.trail.is_none();
+    let mut ints = if free_sign { f.left.saturating_sub(1) } else { f.left };
+    if ints == 0 && right == 0 { ints = 1; }
+    let sig = ints + right;
+    let (digits, lead_exp) = if x == 0.0 {
+        ("0".repeat(sig), ints as i32 - 1)
+    } else {
+        let s = format!("{:.*e}", sig - 1, x.abs());
+        let (m, e) = s.split_once('e').unwrap_or((&s, "0"));
+        (m.replace('.', ""), e.parse::<i32>().unwrap_or(0))
+    };
+    let e = lead_exp + 1 - ints as i32;
+    let e_digits = f.exp - 2;
+    let exp_text = format!("E{}{:0w$}", if e < 0 { '-' } else { '+' }, e.unsigned_abs(), w = e_digits);
+    ((digits[..ints].to_string(), digits[ints..].to_string()), exp_text)
+}
+
+// STR$(n): the number as PRINT shows it, with a leading space in place of a plus sign
+pub fn str_of(v: &Value) -> String {
+    let shown = v.to_string();
+    if shown.starts_with('-') { shown } else { format!(" {}", shown) }
+}
+
+// VAL(s$): the number at the start of s$, 0 if there is none. Blanks are ignored, D works
+// like E, and &H / &O / &B prefixes read hex, octal and binary.
+pub fn val(s: &str) -> f64 {
+    let t: String = s.chars().filter(|c| !matches!(c, ' ' | '\t' | '\r' | '\n')).collect();
+    let upper = t.to_ascii_uppercase();
+    let radix = [("&H", 16), ("&O", 8), ("&B", 2), ("&", 8)].into_iter().find(|(p, _)| upper.starts_with(p));
+    if let Some((prefix, radix)) = radix {
+        let digits: String = upper[prefix.len()..].chars().take_while(|c| c.is_digit(radix)).collect();
+        return u64::from_str_radix(&digits, radix).map(|n| n as f64).unwrap_or(0.0);
+    }
+    let b = upper.as_bytes();
+    let mut end = 0;
+    if matches!(b.first(), Some(b'+' | b'-')) { end = 1; }
+    let int_digits = b[end..].iter().take_while(|c| c.is_ascii_digit()).count();
+    end += int_digits;
+    let mut frac_digits = 0;
+    if b.get(end) == Some(&b'.') {
+        frac_digits = b[end + 1..].iter().take_while(|c| c.is_ascii_digit()).count();
+        end += 1 + frac_digits;
+    }
+    if int_digits + frac_digits == 0 { return 0.0; }
+    if matches!(b.get(end), Some(b'E' | b'D')) {
+        let mut e = end + 1;
+        if matches!(b.get(e), Some(b'+' | b'-')) { e += 1; }
+        let exp_digits = b[e..].iter().take_while(|c| c.is_ascii_digit()).count();
+        if exp_digits > 0 { end = e + exp_digits; }
+    }
+    upper[..end].replace('D', "E").parse().unwrap_or(0.0)
+}
+
+// Grouping and decimal marks for FORMAT$
+fn separators(locale: &str) -> Result<(&'static str, char)> {
+    let tag = locale.to_ascii_lowercase().replace('_', "-");
+    let mut parts = tag.split('-');
+    let lang = parts.next().unwrap_or("");
+    let region = parts.next().unwrap_or("");
+    Ok(match (lang, region) {
+        ("" | "c" | "posix", _) => (",", '.'),
+        ("de" | "fr" | "it" | "rm", "ch" | "li") => ("'", '.'),
+        ("es", "mx" | "us") => (",", '.'),
+        ("en" | "ja" | "zh" | "ko" | "he" | "th" | "ms" | "fil" | "hi", _) => (",", '.'),
+        ("de" | "es" | "it" | "nl" | "pt" | "id" | "tr" | "da" | "el" | "ro" | "hr" | "sl" | "sr" | "vi", _) => (".", ','),
+        ("fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "nn" | "uk" | "hu" | "bg" | "lt" | "lv" | "et", _) => ("\u{a0}", ','),
+        _ => return Err(BasilError(format!("FORMAT$: unknown locale \"{}\"", locale))),
+    })
+}
+
+enum Tok {
+    Digit(bool),
+    Point,
+    Lit(String),
+    Exp { plus: bool, digits: usize },
+}
+
+pub fn format(v: &Value, pattern: &str, locale: &str) -> Result<String> {
+    let (sep, point) = separators(locale)?;
+    if pattern.is_empty() { return Ok(v.to_string()); }
+    let x = num_of(v);
+    let sections = split_sections(pattern);
+    let (section, value, auto_minus) = match sections.len() {
+        n if x < 0.0 && n >= 2 && !sections[1].is_empty() => (sections[1].as_str(), -x, false),
+        n if x == 0.0 && n >= 3 => (sections[2].as_str(), x, false),
+        _ => (sections[0].as_str(), x, true),
+    };
+    let (toks, grouping, percent) = tokenize(section);
+    let value = if percent { value * 100.0 } else { value };
+
+    let point_at = toks.iter().position(|t| matches!(t, Tok::Point));
+    let exp = toks.iter().find_map(|t| if let Tok::Exp { plus, digits } = t { Some((*plus, *digits)) } else { None });
+    let int_end = point_at.or_else(|| toks.iter().position(|t| matches!(t, Tok::Exp { .. }))).unwrap_or(toks.len());
+    let int_slots: Vec<bool> = toks[..int_end].iter().filter_map(|t| if let Tok::Digit(z) = t { Some(*z) } else { None }).collect();
+    let frac_slots: Vec<bool> = toks[int_end..].iter()
+        .take_while(|t| !matches!(t, Tok::Exp { .. }))
+        .filter_map(|t| if let Tok::Digit(z) = t { Some(*z) } else { None }).collect();
+    let min_frac = frac_slots.iter().rposition(|z| *z).map(|i| i + 1).unwrap_or(0);
+
+    // Scale for the exponent so the mantissa fills the integer digits
+    let mut exponent = 0i32;
+    let mut mant = value.abs();
+    if exp.is_some() && mant != 0.0 {
+        let lead = int_slots.len().max(1) as i32;
+        exponent = mant.log10().floor() as i32 - (lead - 1);
+        mant /= 10f64.powi(exponent);
+        // Rounding can carry into a new digit: 9.99 -> 10.0
+        let (a, _) = fixed(mant, frac_slots.len());
+        if a.len() as i32 > lead {
+            exponent += 1;
+            mant /= 10.0;
+        }
+    }
+    let (int_digits, mut frac_digits) = match v {
+        Value::Decimal(d) if exp.is_none() => fixed_exact(&if percent { &**d * &Decimal::from_i64(100) } else { (**d).clone() }, frac_slots.len()),
+        _ => fixed(mant, frac_slots.len()),
+    };
+    while frac_digits.len() > min_frac && frac_digits.ends_with('0') { frac_digits.pop(); }
+    let int_digits = if int_digits == "0" { String::new() } else { int_digits };
+    let neg = auto_minus && value < 0.0 && (int_digits.chars().chain(frac_digits.chars()).any(|c| c != '0'));
+
+    // Integer digits fill the slots from the right; the first slot takes any extra digits
+    let first_zero = int_slots.iter().position(|z| *z);
+    let mut int_out: Vec<String> = Vec::new();
+    let mut pending: Vec<char> = int_digits.chars().collect();
+    let mut slot = int_slots.len();
+    let mut emitted = 0;
+    let push_digit = |out: &mut Vec<String>, c: char, emitted: &mut usize| {
+        if grouping && *emitted > 0 && emitted.is_multiple_of(3) { out.push(sep.to_string()); }
+        out.push(c.to_string());
+        *emitted += 1;
+    };
+    for t in toks[..int_end].iter().rev() {
+        match t {
+            Tok::Digit(_) => {
+                slot -= 1;
+                if let Some(c) = pending.pop() {
+                    push_digit(&mut int_out, c, &mut emitted);
+                } else if first_zero.is_some_and(|z| slot >= z) {
+                    push_digit(&mut int_out, '0', &mut emitted);
+                }
+                if slot == 0 {
+                    while let Some(c) = pending.pop() { push_digit(&mut int_out, c, &mut emitted); }
+                }
+            }
+            Tok::Lit(s) => int_out.push(s.clone()),
+            _ => {}
+        }
+    }
+    // Without any digit slot, the integer part still shows
+    if int_slots.is_empty() && !pending.is_empty() {
+        let lead = group(&pending.iter().collect::<String>(), if grouping { sep } else { "" });
+        int_out.push(lead);
+    }
+    let mut out = String::new();
+    if neg { out.push('-'); }
+    out.extend(int_out.into_iter().rev());
+
+    let mut frac = frac_digits.chars();
+    for t in &toks[int_end..] {
+        match t {
+            Tok::Point => if !frac_digits.is_empty() { out.push(point); },
+            Tok::Digit(_) => if let Some(c) = frac.next() { out.push(c); },
+            Tok::Lit(s) => out.push_str(s),
+            Tok::Exp { plus, digits } => {
+                let sign = if exponent < 0 { "-" } else if *plus { "+" } else { "" };
+                out.push_str(&format!("E{}{:0w$}", sign, exponent.unsigned_abs(), w = *digits));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn split_sections(pattern: &str) -> Vec<String> {
+    let mut out = vec![String::new()];
+    let mut quote: Option<char> = None;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        let cur = out.last_mut().expect("at least one section");
+        match (quote, c) {
+            (Some(q), c) if c == q => { quote = None; cur.push(c); }
+            (None, '\'' | '"') => { quote = Some(c); cur.push(c); }
+            (None, '\\') => { cur.push(c); if let Some(n) = chars.next() { cur.push(n); } }
+            (None, ';') => out.push(String::new()),
+            _ => cur.push(c),
+        }
+    }
+    out
+}
+
+// Pattern tokens, whether , grouping is on, and whether % scales by 100
+fn tokenize(section: &str) -> (Vec<Tok>, bool, bool) {
+    let c: Vec<char> = section.chars().collect();
+    let mut toks: Vec<Tok> = Vec::new();
+    let (mut grouping, mut percent, mut seen_point) = (false, false, false);
+    let lit = |toks: &mut Vec<Tok>, s: &str| match toks.last_mut() {
+        Some(Tok::Lit(l)) => l.push_str(s),
+        _ => toks.push(Tok::Lit(s.to_string())),
+    };
+    let mut i = 0;
+    while i < c.len() {
+        match c[i] {
+            '0' | '#' => toks.push(Tok::Digit(c[i] == '0')),
+            '.' if !seen_point => { seen_point = true; toks.push(Tok::Point); }
+            ',' if !seen_point => grouping = true,
+            '%' => { percent = true; lit(&mut toks, "%"); }
+            'E' | 'e' if matches!(c.get(i + 1), Some('0' | '+' | '-')) => {
+                let plus = c[i + 1] == '+';
+                let mut j = i + 1 + matches!(c[i + 1], '+' | '-') as usize;
+                let start = j;
+                while c.get(j) == Some(&'0') { j += 1; }
+                toks.push(Tok::Exp { plus, digits: (j - start).max(1) });
+                i = j;
+                continue;
+            }
+            '\'' | '"' => {
+                let q = c[i];
+                let end = (i + 1..c.len()).find(|&j| c[j] == q).unwrap_or(c.len());
+                lit(&mut toks, &c[i + 1..end].iter().collect::<String>());
+                i = end + 1;
+                continue;
+            }
+            '\\' => {
+                if let Some(n) = c.get(i + 1) { lit(&mut toks, &n.to_string()); }
+                i += 2;
+                continue;
+            }
+            other => lit(&mut toks, &other.to_string()),
+        }
+        i += 1;
+    }
+    (toks, grouping, percent)
+}
@@ -0,0 +1,213 @@
+// Web helpers shared by the VM builtins (COOKIE$, CSRF_TOKEN$, CSRF_FIELD$, CSRF_FORMS$)
+// and by the CGI front end in basilc, which verifies CSRF tokens before a script runs.
+
+use basil_common::{BasilError, Result};
+
+use crate::providers::Environment;
+use crate::{bytes, crypto};
+
+pub const CSRF_COOKIE: &str = "basil_csrf";
+pub const CSRF_FIELD: &str = "csrf_token";
+// Set by the CGI parent for the child run; falls back to the cookie when absent.
+pub const CSRF_ENV: &str = "BASIL_CSRF_TOKEN";
+
+// Build a Set-Cookie header line. HttpOnly and Secure are always present and SameSite
+// defaults to Lax; `attrs` may add Path, Domain, Max-Age, Expires and SameSite=Strict|Lax|None.
+pub fn set_cookie_header(name: &str, value: &str, attrs: &str) -> Result<String> {
+    if name.is_empty() || !name.bytes().all(is_token_byte) {
+        return Err(BasilError(format!("COOKIE$: invalid cookie name \"{}\"", name)));
+    }
+    let mut path = "/".to_string();
+    let mut same_site = "Lax".to_string();
+    let mut extra: Vec<String> = Vec::new();
+    for part in attrs.split(';') {
+        let part = part.trim();
+        if part.is_empty() { continue; }
+        let (k, v) = match part.split_once('=') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => (part, ""),
+        };
+        if v.bytes().any(|b| b == b';' || b < 0x20 || b == 0x7f) {
+            return Err(BasilError(format!("COOKIE$: invalid value for {}", k)));
+        }
+        match k.to_ascii_lowercase().as_str() {
+            "path" => path = v.to_string(),
+            "domain" => extra.push(format!("Domain={}", v)),
+            "max-age" => {
+                let n: i64 = v.parse().map_err(|_| BasilError(format!("COOKIE$: Max-Age must be a number, got \"{}\"", v)))?;
+                extra.push(format!("Max-Age={}", n));
+            }
+            "expires" => extra.push(format!("Expires={}", v)),
+            "samesite" => {
+                same_site = match v.to_ascii_lowercase().as_str() {
+                    "strict" => "Strict".into(),
+                    "lax" => "Lax".into(),
+                    "none" => "None".into(),
+                    _ => return Err(BasilError(format!("COOKIE$: SameSite must be Strict, Lax or None, got \"{}\"", v))),
+                };
+            }
+            // Always set; accepted so callers can spell them out
+            "httponly" | "secure" => {}
+            _ => return Err(BasilError(format!("COOKIE$: unknown attribute \"{}\"", k))),
+        }
+    }
+    let mut out = format!("Set-Cookie: {}={}; Path={}", name, encode_cookie_value(value), path);
+    for e in extra { out.push_str("; "); out.push_str(&e); }
+    out.push_str("; HttpOnly; Secure; SameSite=");
+    out.push_str(&same_site);
+    Ok(out)
+}
+
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+// Percent-encode bytes that are not allowed in a cookie-octet (RFC 6265).
+fn encode_cookie_value(v: &str) -> String {
+    let mut out = String::with_capacity(v.len());
+    for &b in v.as_bytes() {
+        let ok = b == 0x21 || (0x23..=0x2B).contains(&b) || (0x2D..=0x3A).contains(&b)
+            || (0x3C..=0x5B).contains(&b) || (0x5D..=0x7E).contains(&b);
+        if ok && b != b'%' { out.push(b as char); } else { out.push_str(&format!("%{:02X}", b)); }
+    }
+    out
+}
+
+// Value of cookie `name` in an HTTP Cookie header ("a=1; b=2").
+pub fn cookie_value(header: &str, name: &str) -> Option<String> {
+    header.split(';').find_map(|kv| {
+        let (k, v) = kv.trim().split_once('=')?;
+        if k.trim() == name { Some(v.trim().trim_matches('"').to_string()) } else { None }
+    })
+}
+
+// A new random token: 32 bytes from the OS, hex encoded.
+pub fn new_token() -> Result<String> {
+    Ok(bytes::to_hex(&crypto::random_bytes(32)?))
+}
+
+// Tokens we issue are 64 lowercase hex digits; anything else from a client is ignored.
+pub fn is_valid_token(t: &str) -> bool {
+    t.len() == 64 && t.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+// Token of the current request: issued by the CGI parent, else the browser's cookie.
+pub fn current_csrf_token(env: &dyn Environment) -> String {
+    if let Some(t) = env.var(CSRF_ENV) {
+        if is_valid_token(&t) { return t; }
+    }
+    env.var("HTTP_COOKIE")
+        .and_then(|h| cookie_value(&h, CSRF_COOKIE))
+        .filter(|t| is_valid_token(t))
+        .unwrap_or_default()
+}
+
+// Hidden <input> carrying `token`, for forms checked by #CGI_CSRF.
+pub fn csrf_field(token: &str) -> String {
+    format!("<input type=\"hidden\" name=\"{}\" value=\"{}\">", CSRF_FIELD, token)
+}
+
+// `html` with `field` added right after the opening tag of every <form> that posts. GET
+// forms are left alone: their fields end up in the URL, and #CGI_CSRF does not check them.
+pub fn add_csrf_fields(html: &str, field: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let mut out = String::with_capacity(html.len() + field.len());
+    let mut copied = 0;
+    let mut from = 0;
+    while let Some(at) = lower[from..].find("<form").map(|n| from + n) {
+        from = at + 5;
+        if !matches!(lower.as_bytes().get(from), Some(b) if b.is_ascii_whitespace() || *b == b'>') { continue; }
+        let Some(end) = tag_end(&lower, from) else { break };
+        from = end;
+        if !matches!(attr_value(&lower[at + 5..end - 1], "method").as_deref(), Some("post" | "put" | "patch" | "delete")) { continue; }
+        out.push_str(&html[copied..end]);
+        out.push_str(field);
+        copied = end;
+    }
+    out.push_str(&html[copied..]);
+    out
+}
+
+// Index just past the '>' closing the tag whose attributes start at `from`
+fn tag_end(s: &str, from: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, b) in s.bytes().enumerate().skip(from) {
+        match (quote, b) {
+            (Some(q), _) if b == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'"' | b'\'') => quote = Some(b),
+            (None, b'>') => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Value of attribute `name` in the (lowercased) attribute text of a tag
+fn attr_value(attrs: &str, name: &str) -> Option<String> {
+    let b = attrs.as_bytes();
+    let mut i = 0;
+    while i < b.len() {
+        while i < b.len() && (b[i].is_ascii_whitespace() || b[i] == b'/') { i += 1; }
+        let start = i;
+        while i < b.len() && !b[i].is_ascii_whitespace() && b[i] != b'=' && b[i] != b'/' { i += 1; }
+        let key = &attrs[start..i];
+        while i < b.len() && b[i].is_ascii_whitespace() { i += 1; }
+        let mut value = String::new();
+        if i < b.len() && b[i] == b'=' {
+            i += 1;
+            while i < b.len() && b[i].is_ascii_whitespace() { i += 1; }
+            match b.get(i) {
+                Some(&q) if q == b'"' || q == b'\'' => {
+                    let end = attrs[i + 1..].find(q as char).map(|n| i + 1 + n).unwrap_or(b.len());
+                    value = attrs[i + 1..end].to_string();
+                    i = end + 1;
+                }
+                _ => {
+                    let start = i;
+                    while i < b.len() && !b[i].is_ascii_whitespace() { i += 1; }
+                    value = attrs[start..i].to_string();
+                }
+            }
+        }
+        if key == name { return Some(value.trim().to_string()); }
+        if key.is_empty() { i += 1; }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookie_builder_enforces_flags() {
+        let h = set_cookie_header("user", "a b;c", "").unwrap();
+        assert_eq!(h, "Set-Cookie: user=a%20b%3Bc; Path=/; HttpOnly; Secure; SameSite=Lax");
+        let h = set_cookie_header("sid", "x", "Max-Age=0; SameSite=strict; Path=/app").unwrap();
+        assert_eq!(h, "Set-Cookie: sid=x; Path=/app; Max-Age=0; HttpOnly; Secure; SameSite=Strict");
+        assert!(set_cookie_header("bad name", "x", "").is_err());
+        assert!(set_cookie_header("a", "x", "SameSite=Loose").is_err());
+        assert!(set_cookie_header("a", "x", "Path=/\r\nX-Evil: 1").is_err());
+    }
+
+    #[test]
+    fn tokens() {
+        let t = new_token().unwrap();
+        assert!(is_valid_token(&t));
+        assert_ne!(t, new_token().unwrap());
+        assert_eq!(cookie_value("a=1; basil_csrf=zz", CSRF_COOKIE).as_deref(), Some("zz"));
+    }
+
+    #[test]
+    fn csrf_fields_go_into_posting_forms() {
+        let html = "<FORM METHOD=POST action=\"/a?x=>\"><b></b></FORM>\n<form method='get'></form><formula>\
+                    <form class=\"c\" method=\"Post\">\n</form>";
+        assert_eq!(
+            add_csrf_fields(html, "[T]"),
+            "<FORM METHOD=POST action=\"/a?x=>\">[T]<b></b></FORM>\n<form method='get'></form><formula>\
+             <form class=\"c\" method=\"Post\">[T]\n</form>"
+        );
+        assert_eq!(add_csrf_fields("<form", "[T]"), "<form");
+    }
+}
@@ -0,0 +1,229 @@
+// Workers: scripts running in parallel, each on its own thread with its own VM.
+//
+//   w% = SPAWN("worker.bas")    compile worker.bas (next to the script) and start it
+//   SEND(w%, value)             copy value to worker w%; 0 sends to the parent from a worker
+//   RECEIVE(w% [, ms])          next value from w% (0 = parent); NULL after ms, or once the
+//                               other side has finished and nothing is left
+//   JOIN(w%)                    wait for w% to finish; an error in the worker is raised here
+//
+// VMs share nothing: values are deep-copied (SendValue) and objects cannot be sent.
+// Workers get no host functions. Their providers and output are the defaults (the real
+// disk, environment, clock and stdout) only while the spawning VM uses the defaults too:
+// once the host has replaced any of them, SPAWN fails unless the host has given the VM a
+// WorkerSetup (VM::set_worker_setup) that prepares each worker on its own thread. Workers
+// pass the setup on to their own workers. When the spawning script's run() ends, its
+// workers see RECEIVE(0) return NULL and are waited for.
+//
+// WorkerPool is the host side of the same idea: a fixed set of threads, each with a
+// VM of one SharedProgram, that jobs (e.g. web requests) are handed to. Before each job
+// the VM's globals are put back to what setup left, so no job sees another's data
+// whichever thread it lands on. Objects and generators cannot be copied; a global
+// holding one keeps the same instance, with whatever earlier jobs did to it.
+
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use basil_bytecode::{SendValue, SharedProgram, Value};
+use basil_common::{BasilError, Result};
+
+use crate::VM;
+
+// One end of the pair of channels between two VMs
+struct Link {
+    tx: Sender<SendValue>,
+    rx: Receiver<SendValue>,
+}
+
+fn link_pair() -> (Link, Link) {
+    let (tx_a, rx_b) = mpsc::channel();
+    let (tx_b, rx_a) = mpsc::channel();
+    (Link { tx: tx_a, rx: rx_a }, Link { tx: tx_b, rx: rx_b })
+}
+
+struct Worker {
+    link: Link,
+    thread: JoinHandle<Result<()>>,
+}
+
+// Prepares a SPAWNed worker's VM (providers, output) on the worker's thread
+pub type WorkerSetup = Arc<dyn Fn(&mut VM) -> Result<()> + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct Workers {
+    parent: Option<Link>,
+    children: HashMap<i64, Worker>,
+    next_id: i64,
+    pub(crate) setup: Option<WorkerSetup>,
+    // The host replaced a provider or the output of this VM
+    pub(crate) custom_providers: bool,
+}
+
+impl VM {
+    fn link(&self, id: i64) -> Result<&Link> {
+        let link = if id == 0 { self.workers.parent.as_ref() } else { self.workers.children.get(&id).map(|w| &w.link) };
+        link.ok_or_else(|| if id == 0 {
+            BasilError("worker 0 (the parent) is only available inside a SPAWNed script".into())
+        } else {
+            BasilError(format!("unknown worker id {}", id))
+        })
+    }
+
+    // SPAWN: the id of the new worker
+    pub(crate) fn spawn_worker(&mut self, fname: &str) -> Result<i64> {
+        let setup = self.workers.setup.clone();
+        if setup.is_none() && self.workers.custom_providers {
+            return Err(BasilError("SPAWN: this VM runs on host-supplied providers and the host has not set up workers for them".into()));
+        }
+        let (prog, path) = self.load_program(fname)?
+            .ok_or_else(|| BasilError(format!("SPAWN: file not found: {}", fname)))?;
+        let program = SharedProgram::new(prog)?;
+        self.workers.next_id += 1;
+        let id = self.workers.next_id;
+        let (ours, theirs) = link_pair();
+        let thread = thread::Builder::new()
+            .name(format!("basil-worker-{}", id))
+            .spawn(move || {
+                let mut vm = VM::new(program.program());
+                vm.set_script_path(path);
+                if let Some(setup) = &setup { setup(&mut vm)?; }
+                vm.workers.setup = setup;
+                vm.workers.parent = Some(theirs);
+                vm.run()
+            })
+            .map_err(|e| BasilError(format!("SPAWN: {}", e)))?;
+        self.workers.children.insert(id, Worker { link: ours, thread });
+        Ok(id)
+    }
+
+    // SEND: false if the receiving VM has already finished
+    pub(crate) fn send_to_worker(&self, id: i64, v: &Value) -> Result<bool> {
+        let msg = SendValue::from_value(v)?;
+        Ok(self.link(id)?.tx.send(msg).is_ok())
+    }
+
+    pub(crate) fn receive_from_worker(&self, id: i64, timeout: Option<Duration>) -> Result<Value> {
+        let rx = &self.link(id)?.rx;
+        let msg = match timeout {
+            None => rx.recv().ok(),
+            Some(t) => rx.recv_timeout(t).ok(),
+        };
+        Ok(msg.map(|m| m.to_value()).unwrap_or(Value::Null))
+    }
+
+    pub(crate) fn join_worker(&mut self, id: i64) -> Result<()> {
+        let w = self.workers.children.remove(&id).ok_or_else(|| BasilError(format!("unknown worker id {}", id)))?;
+        drop(w.link);
+        match w.thread.join() {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(BasilError(format!("worker {}: {}", id, e.0))),
+            Err(_) => Err(BasilError(format!("worker {} panicked", id))),
+        }
+    }
+
+    // Wait for the workers the script did not JOIN; the first error among them is returned.
+    pub(crate) fn join_all_workers(&mut self) -> Result<()> {
+        let mut ids: Vec<i64> = self.workers.children.keys().copied().collect();
+        ids.sort_unstable();
+        let mut first = Ok(());
+        for id in ids {
+            let res = self.join_worker(id);
+            if first.is_ok() { first = res; }
+        }
+        first
+    }
+}
+
+type Job = Box<dyn FnOnce(&mut VM) + Send>;
+
+// The globals as setup left them: copies where possible, otherwise the value itself
+struct Baseline(Vec<std::result::Result<SendValue, Value>>);
+
+impl Baseline {
+    fn take(vm: &VM) -> Baseline {
+        Baseline(vm.globals.iter().map(|v| SendValue::from_value(v).map_err(|_| v.clone())).collect())
+    }
+
+    fn restore(&self, vm: &mut VM) {
+        for (g, b) in vm.globals.iter_mut().zip(&self.0) {
+            *g = match b { Ok(copy) => copy.to_value(), Err(v) => v.clone() };
+        }
+    }
+}
+
+pub struct WorkerPool {
+    jobs: Option<Sender<Job>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    // `size` threads, each with a VM of `program` prepared by `setup` (set providers, run()
+    // the top level so FUNCs and ROUTEs exist, ...). Fails if setup fails on any of them.
+    pub fn new<F>(program: SharedProgram, size: usize, setup: F) -> Result<WorkerPool>
+    where
+        F: Fn(&mut VM) -> Result<()> + Send + Sync + 'static,
+    {
+        let (jobs, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        let setup = Arc::new(setup);
+        let (ready_tx, ready_rx) = mpsc::channel::<Result<()>>();
+        let mut pool = WorkerPool { jobs: Some(jobs), threads: Vec::new() };
+        for n in 0..size.max(1) {
+            let (program, setup, rx, ready) = (program.clone(), setup.clone(), rx.clone(), ready_tx.clone());
+            let t = thread::Builder::new()
+                .name(format!("basil-pool-{}", n))
+                .spawn(move || {
+                    let make_vm = || -> Result<VM> {
+                        let mut vm = VM::new(program.program());
+                        setup(&mut vm)?;
+                        Ok(vm)
+                    };
+                    let mut vm = match make_vm() {
+                        Ok(vm) => { let _ = ready.send(Ok(())); vm }
+                        Err(e) => { let _ = ready.send(Err(e)); return; }
+                    };
+                    let baseline = Baseline::take(&vm);
+                    loop {
+                        let job = match rx.lock().ok().and_then(|r| r.recv().ok()) { Some(j) => j, None => return };
+                        baseline.restore(&mut vm);
+                        // A job that panics may leave its VM half-way through; start over
+                        if catch_unwind(AssertUnwindSafe(|| job(&mut vm))).is_err() {
+                            vm = match make_vm() { Ok(vm) => vm, Err(_) => return };
+                        }
+                    }
+                })
+                .map_err(|e| BasilError(format!("worker pool: {}", e)))?;
+            pool.threads.push(t);
+        }
+        for _ in 0..pool.threads.len() {
+            ready_rx.recv().map_err(|_| BasilError("worker pool: a worker stopped during setup".into()))??;
+        }
+        Ok(pool)
+    }
+
+    pub fn size(&self) -> usize { self.threads.len() }
+
+    // Run `job` on the next free worker's VM; its result arrives on the returned channel.
+    pub fn execute<R, F>(&self, job: F) -> Receiver<R>
+    where
+        F: FnOnce(&mut VM) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send(Box::new(move |vm: &mut VM| { let _ = tx.send(job(vm)); }));
+        }
+        rx
+    }
+}
+
+impl Drop for WorkerPool {
+    // Finish queued jobs, then stop the threads
+    fn drop(&mut self) {
+        self.jobs = None;
+        for t in self.threads.drain(..) { let _ = t.join(); }
+    }
+}
@@ -0,0 +1,109 @@
+use std::rc::Rc;
+
+use basil_common::Result;
+use basil_vm::providers::MemoryFileSystem;
+use basil_vm::bytes;
+
+mod common;
+
+// Scripts run against an in-memory filesystem
+fn run_with(src: &str, fs: Rc<MemoryFileSystem>) -> Result<String> { common::run_with(src, |vm| vm.set_filesystem(fs)) }
+
+fn run(src: &str) -> Result<String> { run_with(src, Rc::new(MemoryFileSystem::new())) }
+
+#[test]
+fn strings_count_characters_and_graphemes() {
+    let src = r#"
+LET s$ = "na" + UCHR$(239) + "ve caf" + UCHR$(233)
+PRINTLN LEN(s$), MID$(s$, 3, 3), LEFT$(s$, 3), RIGHT$(s$, 2), INSTR(s$, "caf")
+PRINTLN UASC%(s$, 3), UASC%(s$), UASC%(s$, 99), UCHR$(8364), LEN(UCHR$(128512))
+LET e$ = "e" + UCHR$(769)
+LET flags$ = UCHR$(127482) + UCHR$(127480) + UCHR$(127467) + UCHR$(127479)
+LET family$ = UCHR$(128104) + UCHR$(8205) + UCHR$(128105) + UCHR$(8205) + UCHR$(128103)
+LET wave$ = UCHR$(128075) + UCHR$(127997)
+PRINTLN LEN(e$), GLEN%(e$), LEN(flags$), GLEN%(flags$), GLEN%(family$), GLEN%(wave$ + "!")
+PRINTLN GLEN%("a\r\nb"), GLEN%(UCHR$(4352) + UCHR$(4449) + UCHR$(4520)), GLEN%("")
+LET g = GRAPHEMES("x" + e$ + flags$)
+PRINTLN LEN(g), g[2] = e$, LEN(g[3])
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "10\tïve\tnaï\tfé\t6\n239\t110\t0\t€\t1\n2\t1\t4\t2\t1\t2\n3\t1\t0\n4\ttrue\t2\n"
+    );
+    let err = run("PRINTLN UCHR$(55296)\n").unwrap_err();
+    assert_eq!(err.0, "UCHR$: 55296 is not a Unicode scalar value");
+}
+
+#[test]
+fn bytes_slice_index_and_encode() {
+    let src = r#"
+LET b = BYTES("h" + UCHR$(233) + "llo")
+PRINTLN TYPE$(b), LEN(b), BYTES_HEX$(b), b[1], b[3]
+PRINTLN BYTES_HEX$(MID$(b, 2, 2)), BYTES_TEXT$(MID$(b, 2, 2)), BYTES_HEX$(LEFT$(b, 1)), BYTES_TEXT$(RIGHT$(b, 3))
+PRINTLN INSTR(b, "llo"), INSTR(b, BYTES([108])), INSTR(b, "zz"), LEN(MID$(b, 50))
+LET total% = 0
+FOR EACH x IN BYTES([1, 2, 255])
+  total% = total% + x
+NEXT
+PRINTLN total%, BYTES_HEX$(BYTES([1, 2]) + BYTES(2)), BYTES("ab") = HEX_BYTES("61 62")
+PRINTLN BYTES_BASE64$(BYTES("Man")), BYTES_BASE64$(BYTES("Ma")), BYTES_TEXT$(BASE64_BYTES("TWE")), BYTES_HEX$(BASE64_BYTES("_-8="))
+PRINTLN BYTES([0, 65, 255])
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "BYTES\t6\t68c3a96c6c6f\t104\t169\nc3a9\té\t68\tllo\n3\t3\t0\t0\n258\t01020000\ttrue\nTWFu\tTWE=\tMa\tffef\nb\"\\x00A\\xff\"\n"
+    );
+    let err = run("PRINTLN BYTES_TEXT$(MID$(BYTES(UCHR$(233)), 1, 1))\n").unwrap_err();
+    assert_eq!(err.0, "BYTES_TEXT$: invalid UTF-8 at byte 1");
+    let err = run("PRINTLN BYTES([1, 256])\n").unwrap_err();
+    assert_eq!(err.0, "BYTES: list item 2 is not a byte (0 to 255)");
+    let err = run("LET b = BYTES(2)\nPRINTLN b[3]\n").unwrap_err();
+    assert_eq!(err.0, "Bytes index out of range: 3");
+    assert_eq!(run("PRINTLN HEX_BYTES(\"abc\")\n").unwrap_err().0, "HEX_BYTES: odd number of hex digits");
+    assert_eq!(
+        run("PRINTLN BYTES(VAL(\"1e15\"))\n").unwrap_err().0,
+        "BYTES: 1000000000000000 bytes is more than the limit of 1073741824"
+    );
+    assert_eq!(run("PRINTLN STRING$(3, 9731), STRING$(2, 65)\n").unwrap(), "\u{2603}\u{2603}\u{2603}\tAA\n");
+    assert_eq!(run("PRINTLN STRING$(3, 55296)\n").unwrap_err().0, "STRING$: 55296 is not a Unicode scalar value");
+    assert_eq!(run("PRINTLN STRING$(2, -1)\n").unwrap_err().0, "STRING$: -1 is not a Unicode scalar value");
+
+    assert_eq!(bytes::from_base64(&bytes::to_base64(&[0, 1, 2, 250, 251])).unwrap(), vec![0, 1, 2, 250, 251]);
+    assert!(bytes::from_base64("A").is_err());
+}
+
+#[test]
+fn binary_files_and_struct_packing() {
+    let fs = Rc::new(MemoryFileSystem::new());
+    fs.insert("in.bin", vec![0u8, 159, 146, 150, 255]);
+    let src = r#"
+LET data = READFILE_BYTES("in.bin")
+PRINTLN LEN(data), BYTES_HEX$(data)
+WRITEFILE("copy.bin", data)
+APPENDFILE("copy.bin", BYTES([7]))
+LET fh% = FOPEN("in.bin", "rb")
+LET head = FREAD_BYTES(fh%, 2)
+LET rest = FREAD_BYTES(fh%, VAL("1e15"))
+PRINTLN BYTES_HEX$(head), BYTES_HEX$(rest), LEN(FREAD_BYTES(fh%, 4))
+FCLOSE(fh%)
+TYPE PT
+  DIM X%
+  DIM Y%
+END TYPE
+DIM p AS PT
+p.X% = 1
+p.Y% = -2
+LET packed$ = p
+PRINTLN TYPE$(packed$), BYTES_HEX$(packed$)
+WRITEFILE("pt.bin", packed$)
+DIM q AS PT
+LET loaded$ = READFILE_BYTES("pt.bin")
+LET q = loaded$
+PRINTLN q.X%, q.Y%
+"#;
+    assert_eq!(
+        run_with(src, fs.clone()).unwrap(),
+        "5\t009f9296ff\n009f\t9296ff\t0\nBYTES\t01000000feffffff\n1\t-2\n"
+    );
+    assert_eq!(fs.contents("copy.bin").unwrap(), vec![0u8, 159, 146, 150, 255, 7]);
+}
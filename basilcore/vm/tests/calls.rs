@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use basil_bytecode::{FromValue, HostFunctions, IntoValue, Value};
+use basil_vm::VM;
+
+fn vm_for(src: &str, host: &HostFunctions) -> VM {
+    let ast = basil_parser::parse(src).expect("parse");
+    let prog = basil_compiler::compile_with_host(&ast, host).expect("compile");
+    let mut vm = VM::new(prog);
+    vm.set_host_functions(Rc::new(host.clone()));
+    vm
+}
+
+#[test]
+fn call_functions_after_run() {
+    let src = r#"
+LET calls% = 0
+FUNC Greet$(name$)
+  calls% = calls% + 1
+  RETURN "Hello, " + name$
+END FUNC
+FUNC Sum(xs)
+  LET t = 0
+  FOR EACH x IN xs
+    t = t + x
+  NEXT
+  RETURN t
+END FUNC
+FUNC Lookup$(d, k$)
+  RETURN d[k$]
+END FUNC
+"#;
+    let mut vm = vm_for(src, &HostFunctions::new());
+    assert!(vm.call_function("Greet$", &["x".into_value()]).unwrap_err().0.contains("run() the program first"));
+    vm.run().expect("run");
+
+    let r = vm.call_function("greet$", &["Ann".into_value()]).expect("call");
+    assert_eq!(String::from_value(&r).unwrap(), "Hello, Ann");
+    vm.call_function("GREET$", &["Bob".into_value()]).expect("call again");
+    assert_eq!(i64::from_value(&vm.get_global("calls%").unwrap()).unwrap(), 2);
+
+    let r = vm.call_function("Sum", &[vec![1i64, 2, 3].into_value()]).expect("sum");
+    assert_eq!(f64::from_value(&r).unwrap(), 6.0);
+
+    let mut d = HashMap::new();
+    d.insert("k".to_string(), "v".to_string());
+    let r = vm.call_function("Lookup$", &[d.into_value(), "k".into_value()]).expect("lookup");
+    assert_eq!(String::from_value(&r).unwrap(), "v");
+
+    assert_eq!(vm.call_function("Sum", &[]).unwrap_err().0, "arity mismatch: expected 1, got 0");
+    assert_eq!(vm.call_function("Nope", &[]).unwrap_err().0, "Unknown function: Nope");
+    // The VM stays usable after a failed call
+    assert!(vm.call_function("Greet$", &["Cy".into_value()]).is_ok());
+}
+
+#[test]
+fn raise_in_callback_does_not_reach_outer_try() {
+    let mut h = HostFunctions::new();
+    h.register("TRY_CALL$", 1..=1, |ctx, args| {
+        Ok(match ctx.call(&args[0], vec![]) {
+            Ok(v) => Value::Str(format!("ok {}", v)),
+            Err(e) => Value::Str(format!("failed {}", e)),
+        })
+    }).unwrap();
+    let src = r#"
+FUNC Boom()
+  RAISE "bad"
+END FUNC
+LET r$ = ""
+TRY
+  r$ = TRY_CALL$(Boom)
+CATCH e$
+  r$ = "outer caught " + e$
+END TRY
+"#;
+    let mut vm = vm_for(src, &h);
+    vm.run().expect("run");
+    assert_eq!(String::from_value(&vm.get_global("r$").unwrap()).unwrap(), "failed bad");
+}
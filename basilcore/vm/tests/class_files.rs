@@ -0,0 +1,148 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use basil_bytecode::{FromValue, Value};
+use basil_vm::debug::{DebugEvent, Debugger};
+use basil_vm::VM;
+
+mod common;
+
+const COUNTER: &str = r#"count = 0
+fh% = 0
+FUNC Inc(n)
+  count = count + Twice(n)
+  RETURN count
+END FUNC
+FUNC Twice(n)
+  RETURN n * 2
+END FUNC
+FUNC OpenLog(p$)
+  IF fh% = 0 THEN fh% = FOPEN(p$, "w")
+  RETURN fh%
+END FUNC
+FUNC Log(s$)
+  RETURN FWRITELN(fh%, s$)
+END FUNC
+FUNC CloseLog()
+  RETURN FCLOSE(fh%)
+END FUNC
+"#;
+
+// A scratch directory holding Counter.bas
+fn class_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("basil_class_files_{}_{}", test, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("Counter.bas"), COUNTER).unwrap();
+    dir
+}
+
+fn vm_for(dir: &Path, src: &str) -> VM {
+    let mut vm = common::vm_for(src);
+    vm.set_script_path(dir.join("main.bas").to_string_lossy().into_owned());
+    vm
+}
+
+#[test]
+fn methods_share_instance_state_and_file_handles() {
+    let dir = class_dir("state");
+    let log = dir.join("log.txt");
+    let src = format!(r#"
+LET a@ = CLASS("Counter.bas")
+LET b@ = CLASS("Counter.bas")
+FOR i = 1 TO 1000
+  LET t = a@.Inc(1)
+NEXT i
+LET u = b@.Inc(5)
+LET ca = a@.count
+LET cb = b@.count
+LET r = a@.OpenLog("{}")
+r = a@.Log("one")
+r = a@.Log("two")
+r = a@.CloseLog()
+"#, log.display());
+    let mut vm = vm_for(&dir, &src);
+    vm.run().expect("run");
+    assert_eq!(f64::from_value(&vm.get_global("ca").unwrap()).unwrap(), 2000.0);
+    assert_eq!(f64::from_value(&vm.get_global("cb").unwrap()).unwrap(), 10.0);
+    // The handle opened by OpenLog stays open for the later calls
+    assert_eq!(std::fs::read_to_string(&log).unwrap().lines().collect::<Vec<_>>(), ["one", "two"]);
+
+    // Host code can still call methods directly
+    let Some(Value::Object(a)) = vm.get_global("a@") else { panic!("a@ is not an object") };
+    let v = a.borrow_mut().call("Inc", &[Value::Num(1.0)]).unwrap();
+    assert_eq!(f64::from_value(&v).unwrap(), 2002.0);
+    assert_eq!(a.borrow_mut().call("Inc", &[]).unwrap_err().0, "arity mismatch: expected 1, got 0");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn breakpoint_inside_class_method() {
+    let dir = class_dir("debug");
+    let class_file = dir.join("Counter.bas").to_string_lossy().into_owned();
+    let mut vm = vm_for(&dir, "LET c@ = CLASS(\"Counter.bas\")\nLET t = c@.Inc(1)\n");
+
+    let dbg = Debugger::new();
+    let rx = dbg.subscribe();
+    let stops: Arc<Mutex<Vec<(String, usize)>>> = Arc::new(Mutex::new(Vec::new()));
+    let (stops_clone, dbg_for_thread) = (stops.clone(), dbg.clone());
+    let handle = thread::spawn(move || {
+        while let Ok(ev) = rx.recv() {
+            match ev {
+                DebugEvent::StoppedBreakpoint { file, line } => { stops_clone.lock().unwrap().push((file, line)); dbg_for_thread.resume(); }
+                DebugEvent::Exited => break,
+                _ => {}
+            }
+        }
+    });
+    // Line 4 of Counter.bas is inside FUNC Inc
+    dbg.set_breakpoint(class_file.clone(), 4);
+    vm.set_debugger(dbg.clone());
+    vm.run().expect("run");
+    handle.join().unwrap();
+
+    assert_eq!(*stops.lock().unwrap(), [(class_file, 4)]);
+    assert_eq!(f64::from_value(&vm.get_global("t").unwrap()).unwrap(), 2.0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn class_funcs_passed_as_values_keep_their_instance() {
+    let dir = class_dir("bound");
+    // More globals than the main program, so a wrong environment would index past its end
+    std::fs::write(dir.join("Ticker.bas"), "a = 0\nb = 0\nticks = 0\nFUNC Tick()\n  ticks = ticks + 1\n  RETURN 0\nEND FUNC\nFUNC Start()\n  RETURN SETTIMEOUT(Tick, 10)\nEND FUNC\n").unwrap();
+    let src = "LET t@ = CLASS(\"Ticker.bas\")\nLET id% = t@.Start()\nLET r = t@.Start()\n";
+    let mut vm = vm_for(&dir, src);
+    vm.run().expect("run");
+    // Both timers ran after the top level ended, against the instance globals
+    let Some(Value::Object(t)) = vm.get_global("t@") else { panic!("t@ is not an object") };
+    assert_eq!(f64::from_value(&t.borrow().get_prop("ticks").unwrap()).unwrap(), 2.0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn many_method_calls_run_on_the_callers_frames() {
+    let dir = class_dir("many");
+    std::fs::write(dir.join("Fib.bas"), "calls = 0\nFUNC Fib(n)\n  calls = calls + 1\n  IF n < 2 THEN RETURN n\n  RETURN Fib(n - 1) + Fib(n - 2)\nEND FUNC\n").unwrap();
+    let src = r#"
+LET c@ = CLASS("Counter.bas")
+LET f@ = CLASS("Fib.bas")
+FUNC Bump(o@, n)
+  RETURN o@.Inc(n)
+END FUNC
+LET t = 0
+FOR i = 1 TO 20000
+  t = Bump(c@, 1)
+NEXT i
+LET fib = f@.Fib(20)
+LET calls = f@.calls
+"#;
+    let mut vm = vm_for(&dir, src);
+    vm.run().expect("run");
+    // 20000 calls through a program FUNC, each calling Inc and Twice in the instance
+    assert_eq!(f64::from_value(&vm.get_global("t").unwrap()).unwrap(), 40000.0);
+    // Fib(20) recurses 21891 times inside the instance
+    assert_eq!(f64::from_value(&vm.get_global("fib").unwrap()).unwrap(), 6765.0);
+    assert_eq!(f64::from_value(&vm.get_global("calls").unwrap()).unwrap(), 21891.0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,111 @@
+use basil_bytecode::{FromValue, Value};
+use basil_vm::VM;
+
+mod common;
+use common::{capture, text, vm_for};
+
+const ANIMALS: &str = r#"
+CLASS Animal
+  PUBLIC name$
+  PRIVATE secret$ = "hidden"
+  legs% = 4
+  SUB NEW(n$)
+    ME.name$ = n$
+  END SUB
+  FUNC Speak$()
+    RETURN ME.name$ + " makes a sound"
+  END FUNC
+  FUNC Info$()
+    RETURN ME.Speak$() + " on " + ME.legs% + " legs (" + ME.Hidden$() + ")"
+  END FUNC
+  PRIVATE FUNC Hidden$()
+    RETURN SELF.secret$
+  END FUNC
+END CLASS
+
+CLASS Bird EXTENDS Animal
+  SUB NEW(n$)
+    SUPER.NEW(n$)
+    ME.legs% = 2
+  END SUB
+END CLASS
+
+CLASS Dog EXTENDS Animal
+  tricks = {}
+  FUNC Speak$()
+    RETURN ME.name$ + " barks; " + SUPER.Speak$()
+  END FUNC
+END CLASS
+"#;
+
+fn run(src: &str) -> (VM, String) {
+    let mut vm = vm_for(src);
+    let out = capture(&mut vm);
+    vm.run().expect("run");
+    (vm, text(&out))
+}
+
+fn run_err(src: &str) -> String { vm_for(src).run().unwrap_err().0 }
+
+#[test]
+fn constructors_inheritance_and_overrides() {
+    let src = format!("{}{}", ANIMALS, r#"
+LET d@ = NEW Dog("Rex")
+DIM b@ AS Bird("Tweety")
+LET d2@ = NEW Dog("Fido")
+d@.tricks["sit"] = 1
+PRINTLN d@.Info$()
+PRINTLN b@.Info$()
+PRINTLN d@ INSTANCEOF Animal, d@ INSTANCEOF Bird, b@ INSTANCEOF Animal, 5 INSTANCEOF Animal
+LET kind$ = TYPE$(d@)
+"#);
+    let (vm, out) = run(&src);
+    assert_eq!(out, "Rex barks; Rex makes a sound on 4 legs (hidden)\nTweety makes a sound on 2 legs (hidden)\ntrue\tfalse\ttrue\tfalse\n");
+    assert_eq!(String::from_value(&vm.get_global("kind$").unwrap()).unwrap(), "Dog");
+    // Field initializers run per instance: d2@ gets its own empty dict
+    let Some(Value::Object(d2)) = vm.get_global("d2@") else { panic!("d2@ is not an object") };
+    assert_eq!(format!("{}", d2.borrow().get_prop("tricks").unwrap()), "{}");
+    assert!(d2.borrow().get_prop("secret$").is_err());
+}
+
+#[test]
+fn visibility_and_arity_errors() {
+    let err = run_err(&format!("{}{}", ANIMALS, "LET d@ = NEW Dog(\"Rex\")\nPRINTLN d@.secret$\n"));
+    assert_eq!(err, "Dog.secret$ is private");
+    let err = run_err(&format!("{}{}", ANIMALS, "LET d@ = NEW Dog(\"Rex\")\nPRINTLN d@.Hidden$()\n"));
+    assert_eq!(err, "Dog.Hidden$ is private");
+    let err = run_err(&format!("{}{}", ANIMALS, "LET d@ = NEW Dog()\n"));
+    assert_eq!(err, "Dog expects 1 constructor argument(s), got 0");
+    let err = run_err(&format!("{}{}", ANIMALS, "LET d@ = NEW Dog(\"Rex\")\nPRINTLN d@.Speak$(1)\n"));
+    assert_eq!(err, "Dog.Speak$ expects 0 argument(s), got 1");
+}
+
+#[test]
+fn raise_in_method_reaches_caller_try() {
+    let src = r#"
+CLASS Account
+  balance = 0
+  SUB Withdraw(n)
+    IF n > ME.balance THEN RAISE "insufficient funds"
+    ME.balance = ME.balance - n
+  END SUB
+END CLASS
+LET a@ = NEW Account()
+LET r$ = ""
+TRY
+  a@.Withdraw(10)
+CATCH e$
+  r$ = e$
+END TRY
+"#;
+    let (vm, _) = run(src);
+    assert_eq!(String::from_value(&vm.get_global("r$").unwrap()).unwrap(), "insufficient funds");
+}
+
+#[test]
+fn class_definition_errors() {
+    let compile = |src: &str| basil_compiler::compile(&basil_parser::parse(src).expect("parse")).err().map(|e| e.0);
+    assert_eq!(compile("CLASS A EXTENDS Nope\nEND CLASS\n").unwrap(), "CLASS A: unknown base class Nope");
+    assert_eq!(compile("CLASS A EXTENDS B\nEND CLASS\nCLASS B EXTENDS A\nEND CLASS\n").unwrap(), "CLASS A inherits from itself");
+    assert_eq!(compile("CLASS A\n  FUNC F()\n    RETURN SUPER.F()\n  END FUNC\nEND CLASS\n").unwrap(), "SUPER used in CLASS A which has no base class");
+}
@@ -0,0 +1,44 @@
+// Fixtures shared by the integration tests: compile a script into a VM and capture what
+// it prints. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use basil_common::Result;
+use basil_vm::VM;
+
+// An output sink collecting everything the VM flushes
+pub struct Captured(pub Rc<RefCell<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, b: &[u8]) -> io::Result<usize> { self.0.borrow_mut().extend_from_slice(b); Ok(b.len()) }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+pub fn vm_for(src: &str) -> VM {
+    let ast = basil_parser::parse(src).expect("parse");
+    let prog = basil_compiler::compile(&ast).expect("compile");
+    VM::new(prog)
+}
+
+// Send the VM's output to a buffer and return the buffer
+pub fn capture(vm: &mut VM) -> Rc<RefCell<Vec<u8>>> {
+    let out = Rc::new(RefCell::new(Vec::new()));
+    vm.set_output(Box::new(Captured(out.clone())), false);
+    out
+}
+
+pub fn text(out: &Rc<RefCell<Vec<u8>>>) -> String { String::from_utf8_lossy(&out.borrow()).into_owned() }
+
+// Run `src` after `setup` and return what it printed
+pub fn run_with(src: &str, setup: impl FnOnce(&mut VM)) -> Result<String> {
+    let mut vm = vm_for(src);
+    let out = capture(&mut vm);
+    setup(&mut vm);
+    vm.run()?;
+    Ok(text(&out))
+}
+
+pub fn run(src: &str) -> Result<String> { run_with(src, |_| {}) }
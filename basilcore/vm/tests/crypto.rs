@@ -0,0 +1,79 @@
+use basil_vm::{bytes, crypto};
+
+mod common;
+use common::run;
+
+#[test]
+fn digests_and_hmac() {
+    let src = r#"
+PRINTLN SHA256$("abc")
+PRINTLN SHA256$("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")
+PRINTLN SHA256$(BYTES(0)), SHA256$("abc", "base64")
+PRINTLN SHA1$("abc"), MD5$("abc"), MD5$("abc", "base64url")
+PRINTLN HMAC_SHA256$("Jefe", "what do ya want for nothing?")
+PRINTLN SECURE_EQ%("token", "token"), SECURE_EQ%("token", "tokeN"), SECURE_EQ%("a", "ab"), SECURE_EQ%(BYTES("hi"), "hi")
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n\
+         248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1\n\
+         e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\tungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0=\n\
+         a9993e364706816aba3e25717850c26c9cd0d89d\t900150983cd24fb0d6963f7d28e17f72\tkAFQmDzST7DWlj99KOF_cg\n\
+         5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843\n\
+         1\t0\t0\t1\n"
+    );
+    let err = run("PRINTLN SHA1$(\"x\", \"octal\")\n").unwrap_err();
+    assert_eq!(err.0, "SHA1$: unknown output format \"octal\" (use hex, base64 or base64url)");
+
+    // Inputs spanning many blocks, and an HMAC key longer than a block
+    let long = vec![b'a'; 1000];
+    assert_eq!(bytes::to_hex(&crypto::sha256(&long)), "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3");
+    assert_eq!(bytes::to_hex(&crypto::sha1(&long)), "291e9a6c66994949b57ba5e650361e98fc36b1ba");
+    assert_eq!(bytes::to_hex(&crypto::md5(&long)), "cabe45dcc9ae5b66ba86600cca6b8ba8");
+    assert_eq!(
+        bytes::to_hex(&crypto::hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First")),
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+}
+
+#[test]
+fn pbkdf2_vectors() {
+    assert_eq!(
+        bytes::to_hex(&crypto::pbkdf2_sha256(b"password", b"salt", 4096, 32)),
+        "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a"
+    );
+    assert_eq!(
+        bytes::to_hex(&crypto::pbkdf2_sha256(b"passwordPASSWORDpassword", b"saltSALTsaltSALTsaltSALTsaltSALTsalt", 4096, 40)),
+        "348c89dbcbd32b2f32d814b8116e84cf2b17347ebc1800181c4e2a1fb8dd53e1c635518c7dac47e9"
+    );
+}
+
+#[test]
+fn random_tokens_and_passwords() {
+    let src = r#"
+LET a$ = RANDOM_BYTES$(16)
+LET b$ = RANDOM_BYTES$(16)
+PRINTLN LEN(a$), a$ = b$, REGEX_MATCH%(a$, "^[0-9a-f]+$"), LEN(RANDOM_BYTES$(32, "base64url")), RANDOM_BYTES$(0)
+LET id$ = UUID$()
+PRINTLN LEN(id$), REGEX_MATCH%(id$, "^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$"), id$ = UUID$()
+LET h$ = PASSWORD_HASH$("s3cret", 1000)
+PRINTLN LEFT$(h$, 22), h$ = PASSWORD_HASH$("s3cret", 1000)
+PRINTLN PASSWORD_VERIFY%("s3cret", h$), PASSWORD_VERIFY%("S3cret", h$)
+"#;
+    assert_eq!(run(src).unwrap(), "32\tfalse\t1\t43\t\n36\t1\tfalse\n$pbkdf2-sha256$i=1000$\tfalse\n1\t0\n");
+
+    let err = run("PRINTLN PASSWORD_VERIFY%(\"x\", \"plain\")\n").unwrap_err();
+    assert_eq!(err.0, "PASSWORD_VERIFY%: not a hash from PASSWORD_HASH$");
+    let err = run("PRINTLN PASSWORD_HASH$(\"x\", 0)\n").unwrap_err();
+    assert_eq!(err.0, "PASSWORD_HASH$: iterations must be at least 1");
+
+    // Hashes made elsewhere in the same format verify too
+    let salt = b"0123456789abcdef";
+    let stored = format!(
+        "$pbkdf2-sha256$i=2${}${}",
+        bytes::to_base64(salt).trim_end_matches('='),
+        bytes::to_base64(&crypto::pbkdf2_sha256(b"pw", salt, 2, 32)).trim_end_matches('=')
+    );
+    assert!(crypto::password_verify("pw", &stored).unwrap());
+    assert!(!crypto::password_verify("pw2", &stored).unwrap());
+}
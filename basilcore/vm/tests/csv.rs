@@ -0,0 +1,155 @@
+use std::rc::Rc;
+
+use basil_common::Result;
+use basil_vm::providers::MemoryFileSystem;
+
+mod common;
+
+// Scripts run against an in-memory filesystem
+fn run_with(src: &str, fs: Rc<MemoryFileSystem>) -> Result<String> { common::run_with(src, |vm| vm.set_filesystem(fs)) }
+
+fn run(src: &str) -> Result<String> { run_with(src, Rc::new(MemoryFileSystem::new())) }
+
+#[test]
+fn streaming_reader_rows_and_handle_position() {
+    let fs = Rc::new(MemoryFileSystem::new());
+    fs.insert(
+        "people.csv",
+        "\u{feff}name,city,note\r\n\
+         Ada,London,\"says \"\"hi\"\"\"\r\n\
+         \r\n\
+         \"Lovelace, A.\",\"Two\nlines\",\r\n\
+         Bob,Paris\n\
+         # after\n",
+    );
+    let src = r##"
+LET fh% = FOPEN("people.csv", "r")
+LET r@ = CSV_READER(fh%)
+PRINTLN r@.Headers, r@.Row, TYPE$(r@)
+LET row = r@._NEXT()
+WHILE row["name"] <> "Bob" BEGIN
+  PRINTLN row["name"] + "|" + row["city"] + "|" + row["note"] + "|"
+  row = r@._NEXT()
+END
+PRINTLN r@.Row, FREADLINE$(fh%), FEOF(fh%), r@._NEXT(), r@.Done
+FCLOSE(fh%)
+LET fh% = FOPEN("people.csv", "r")
+LET r2@ = CSV_READER(fh%, {"header": FALSE})
+LET first = r2@._NEXT()
+PRINTLN first, LEN(first), FTELL&(fh%)
+FCLOSE(fh%)
+"##;
+    assert_eq!(
+        run_with(src, fs.clone()).unwrap(),
+        "[name, city, note]\t0\tCSV_READER\n\
+         Ada|London|says \"hi\"|\n\
+         Lovelace, A.|Two\nlines||\n\
+         3\t# after\ttrue\tnull\ttrue\n\
+         [\u{feff}name, city, note]\t3\t19\n"
+    );
+
+    // Rows longer than the read-ahead chunk, and a handle used between rows
+    let long = "x".repeat(200_000);
+    let mut text = String::from("id,text\n");
+    for i in 1..=3 { text.push_str(&format!("{},\"{}\"\n", i, long)); }
+    fs.insert("long.csv", text);
+    let src = r#"
+LET fh% = FOPEN("long.csv", "r")
+LET r@ = CSV_READER(fh%)
+LET a = r@._NEXT()
+LET at& = FTELL&(fh%)
+LET b = r@._NEXT()
+PRINTLN a["id"], LEN(a["text"]), at&, b["id"], LEN(b["text"]), FTELL&(fh%)
+FSEEK(fh%, at&, 0)
+PRINTLN r@._NEXT()["id"], r@._NEXT()["id"], r@._NEXT(), r@.Row
+FCLOSE(fh%)
+"#;
+    assert_eq!(run_with(src, fs).unwrap(), "1\t200000\t200013\t2\t200000\t400018\n2\t3\tnull\t4\n");
+}
+
+#[test]
+fn options_read_all_and_errors() {
+    let fs = Rc::new(MemoryFileSystem::new());
+    fs.insert("semi.csv", "id;label\n1;'a;b'\n2;it\\;s\n");
+    fs.insert("bad.csv", "a,b\n1,\"open\n");
+    fs.insert("wide.csv", "a,b\n1,2,3\n");
+    let src = r#"
+LET rows = CSV_READ_ALL("semi.csv", {"delimiter": ";", "quote": "'", "escape": "\\"})
+PRINTLN LEN(rows), rows[1]["label"], rows[2]["label"]
+LET fh% = FOPEN("semi.csv", "r")
+LET raw = CSV_READ_ALL(fh%, {"delimiter": ";", "header": ["x", "y", "z"], "quote": ""})
+PRINTLN raw, raw[1]["z"] = ""
+FCLOSE(fh%)
+PRINTLN CSV_READ_ALL("semi.csv", {"Delimiter": ";", "header": 0})[3]
+"#;
+    assert_eq!(
+        run_with(src, fs.clone()).unwrap(),
+        "2\ta;b\tit;s\n\
+         [{\"x\": id, \"y\": label, \"z\": }, {\"x\": 1, \"y\": 'a, \"z\": b'}, {\"x\": 2, \"y\": it\\, \"z\": s}]\ttrue\n\
+         [2, it\\, s]\n"
+    );
+    assert_eq!(
+        run_with("PRINTLN CSV_READ_ALL(\"bad.csv\")\n", fs.clone()).unwrap_err().0,
+        "CSV_READ_ALL: row 1: unterminated quoted field"
+    );
+    assert_eq!(
+        run_with("LET r@ = CSV_READER(FOPEN(\"wide.csv\", \"r\"))\nPRINTLN r@._NEXT()\n", fs.clone()).unwrap_err().0,
+        "CSV_READER: row 1 has 3 fields but the header has 2"
+    );
+    assert_eq!(run("PRINTLN CSV_FORMAT$([1], {\"sep\": \";\"})\n").unwrap_err().0, "CSV_FORMAT$: unknown option \"sep\"");
+    assert_eq!(
+        run("PRINTLN CSV_FORMAT$([1], {\"delimiter\": \"::\"})\n").unwrap_err().0,
+        "CSV_FORMAT$: delimiter must be a single ASCII character, got \"::\""
+    );
+}
+
+#[test]
+fn writing_rows_quoting_and_json_forms() {
+    let fs = Rc::new(MemoryFileSystem::new());
+    let src = r#"
+LET fh% = FOPEN("out.csv", "w")
+FOR i = 1 TO 3
+  CSV_WRITE_ROW(fh%, {"id": i, "name": "item " + i, "price": i * 1.25@})
+NEXT
+CSV_WRITE_ROW(fh%, {"id": 4, "name": "comma, \"quoted\"", "price": NULL})
+FCLOSE(fh%)
+LET fh% = FOPEN("out.csv", "a")
+CSV_WRITE_ROW(fh%, {"id": 5, "name": "Two" + CHR$(10) + "lines"})
+FCLOSE(fh%)
+LET fh% = FOPEN("out.csv", "r")
+FOR EACH row IN CSV_READER(fh%)
+  PRINT row["id"] + ":" + row["name"] + ":" + row["price"] + " "
+NEXT
+PRINTLN ""
+FCLOSE(fh%)
+LET fh% = FOPEN("cols.csv", "w")
+CSV_WRITE_ROW(fh%, [1, "x"], {"header": ["n", "s"]})
+CSV_WRITE_ROW(fh%, {"s": "y", "n": 2, "extra": TRUE}, {"header": ["n", "s"]})
+FCLOSE(fh%)
+PRINTLN CSV_FORMAT$([1, "a", 2.5, NULL, TRUE], {"quoting": "ALL"})
+PRINTLN CSV_FORMAT$([1, "a b", 2.50@], {"quoting": "NONNUMERIC", "delimiter": ";"})
+PRINTLN CSV_FORMAT$(["a;b", "c\"d"], {"quoting": "NONE", "escape": "\\", "delimiter": ";"})
+PRINTLN CSV_FORMAT$(["say \"hi\""], {"escape": "\\"}), CSV_FORMAT$([""]), CSV_FORMAT$({"a": 1, "b": "two"})
+PRINTLN CSV_PARSE$("name,age" + CHR$(10) + "Ann,31" + CHR$(10) + "\"Lee, J\",")
+PRINT CSV_WRITE$("[{\"b\": 1, \"a\": \"x,y\"}, {\"c\": [1, 2], \"a\": null}]")
+"#;
+    assert_eq!(
+        run_with(src, fs.clone()).unwrap(),
+        "1:item 1:1.25 2:item 2:2.50 3:item 3:3.75 4:comma, \"quoted\": 5:Two\nlines: \n\
+         \"1\",\"a\",\"2.5\",\"\",\"true\"\n\
+         1;\"a b\";2.50\n\
+         a\\;b;c\\\"d\n\
+         \"say \\\"hi\\\"\"\t\"\"\t1,two\n\
+         [{\"name\":\"Ann\",\"age\":\"31\"},{\"name\":\"Lee, J\",\"age\":\"\"}]\n\
+         b,a,c\n1,\"x,y\",\n,,\"[1,2]\"\n"
+    );
+    assert_eq!(
+        String::from_utf8(fs.contents("out.csv").unwrap()).unwrap(),
+        "id,name,price\n1,item 1,1.25\n2,item 2,2.50\n3,item 3,3.75\n4,\"comma, \"\"quoted\"\"\",\n5,\"Two\nlines\"\n"
+    );
+    assert_eq!(String::from_utf8(fs.contents("cols.csv").unwrap()).unwrap(), "n,s\n1,x\n2,y\n");
+    assert_eq!(
+        run("PRINTLN CSV_FORMAT$([\"a,b\"], {\"quoting\": \"NONE\"})\n").unwrap_err().0,
+        "CSV_FORMAT$: field \"a,b\" needs quoting, but quoting is off and no escape is set"
+    );
+}
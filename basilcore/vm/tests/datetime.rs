@@ -0,0 +1,100 @@
+use std::rc::Rc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use basil_common::Result;
+use basil_vm::datetime::DateTime;
+use basil_vm::providers::ManualClock;
+
+mod common;
+
+// 2024-03-09 14:05:06.250 UTC, a Saturday
+fn run(src: &str) -> Result<String> {
+    common::run_with(src, |vm| vm.set_clock(Rc::new(ManualClock::new(UNIX_EPOCH + Duration::from_millis(1_709_993_106_250)))))
+}
+
+#[test]
+fn clock_reads_use_the_clock_provider() {
+    let src = r#"
+PRINTLN DATE$, TIME$, TIMER
+LET t = NOW
+PRINTLN t, TYPE$(t), t.YEAR, t.MONTH, t.DAY, t.HOUR, t.MINUTE, t.SECOND, t.MILLISECOND
+PRINTLN t.WEEKDAY, t.YEARDAY, t.EPOCH, t.ZONE
+PRINTLN NOW("+05:30"), NOW(-480).ZONE, NOW("-08:00") = t
+SLEEP 1500
+PRINTLN NOW.DIFF(t)
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "03-09-2024\t14:05:06\t50706.25\n\
+         2024-03-09T14:05:06.250Z\tDateTime\t2024\t3\t9\t14\t5\t6\t250\n\
+         6\t69\t1709993106.25\tUTC\n\
+         2024-03-09T19:35:06.250+05:30\t-08:00\ttrue\n\
+         1.5\n"
+    );
+}
+
+#[test]
+fn build_format_and_arithmetic() {
+    let src = r#"
+LET d = DATETIME(2024, 1, 31, 9, 30, 0, "+01:00")
+PRINTLN d.FORMAT$("%A %d %B %Y, %I:%M %p (%Z, %z)"), d.FORMAT$("%F %T %j %y %e %a %b %u %w %s %%")
+PRINTLN d.ADD_MONTHS(1), d.ADD_MONTHS(13).ISO$(), d.ADD_DAYS(-31), d.ADD(90.5)
+PRINTLN d.TO_UTC(), d.RFC2822$(), d.HTTP$()
+LET e = DATETIME(1709993106)
+PRINTLN e, DATETIME(0, "Z"), e.DIFF(d) / 86400, d < e, e > d, d = d.TO_UTC()
+LET all = SORT([e, d, DATETIME(2000, 1, 1)])
+PRINTLN all
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "Wednesday 31 January 2024, 09:30 AM (+01:00, +0100)\t2024-01-31 09:30:00 031 24 31 Wed Jan 3 3 1706689800 %\n\
+         2024-02-29T09:30:00+01:00\t2025-02-28T09:30:00+01:00\t2023-12-31T09:30:00+01:00\t2024-01-31T09:31:30.500+01:00\n\
+         2024-01-31T08:30:00Z\tWed, 31 Jan 2024 09:30:00 +0100\tWed, 31 Jan 2024 08:30:00 GMT\n\
+         2024-03-09T14:05:06Z\t1970-01-01T00:00:00Z\t38.232708333333335\ttrue\ttrue\ttrue\n\
+         [2000-01-01T00:00:00Z, 2024-01-31T09:30:00+01:00, 2024-03-09T14:05:06Z]\n"
+    );
+}
+
+#[test]
+fn parsing() {
+    let src = r#"
+PRINTLN DATETIME_PARSE("2024-03-09"), DATETIME_PARSE("2024-03-09T14:05+02:00"), DATETIME_PARSE(" 2024-03-09 14:05:06.5 ")
+PRINTLN DATETIME_PARSE("Sat, 09 Mar 2024 14:05:06 GMT"), DATETIME_PARSE("9 Mar 2024 14:05:06 -0500"), DATETIME_PARSE("Sat, 09 Mar 2024 14:05 PST")
+PRINTLN DATETIME_PARSE("03/09/24 2:05 pm", "%m/%d/%y %I:%M %p"), DATETIME_PARSE("March 9, 2024", "%B %d, %Y"), DATETIME_PARSE("1709993106", "%s")
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "2024-03-09T00:00:00Z\t2024-03-09T14:05:00+02:00\t2024-03-09T14:05:06.500Z\n\
+         2024-03-09T14:05:06Z\t2024-03-09T14:05:06-05:00\t2024-03-09T14:05:00-08:00\n\
+         2024-03-09T14:05:00Z\t2024-03-09T00:00:00Z\t2024-03-09T14:05:06Z\n"
+    );
+
+    // The host can round-trip the same values
+    let d = DateTime::parse("2024-02-29T23:59:59.999999999-03:00").unwrap();
+    assert_eq!(d.iso(), "2024-02-29T23:59:59.999999999-03:00");
+    assert_eq!(DateTime::parse(&d.rfc2822()).unwrap().iso(), "2024-02-29T23:59:59-03:00");
+    assert_eq!(d.http(), "Fri, 01 Mar 2024 02:59:59 GMT");
+}
+
+#[test]
+fn date_time_errors() {
+    let err = |src: &str| run(src).unwrap_err().0;
+    assert_eq!(err("LET d = DATETIME(2023, 2, 29)\n"), "DATETIME: invalid date/time 2023-02-29 00:00:00");
+    assert_eq!(err("LET d = DATETIME_PARSE(\"yesterday\")\n"), "DATETIME_PARSE: \"yesterday\" is not an ISO-8601 or RFC 2822 date");
+    assert_eq!(err("LET d = DATETIME_PARSE(\"9/3\", \"%d-%m\")\n"), "DATETIME_PARSE: \"9/3\" does not match \"%d-%m\"");
+    assert_eq!(err("PRINTLN NOW.FORMAT$(\"%Q\")\n"), "FORMAT$: unknown directive %Q");
+    assert_eq!(err("PRINTLN NOW(\"Mars\")\n"), "invalid time zone Mars (use \"UTC\", \"+05:30\" or minutes east of UTC)");
+    assert_eq!(err("LET d = NOW\nd.YEAR = 1999\n"), "DateTime.YEAR is read-only; use ADD, ADD_DAYS, ADD_MONTHS or DATETIME()");
+    assert_eq!(err("PRINTLN NOW.DIFF(5)\n"), "DateTime.DIFF expects a DateTime");
+    assert_eq!(err("PRINTLN NOW.ADD()\n"), "DateTime.ADD expects 1 argument(s), got 0");
+    assert_eq!(err("PRINTLN DATETIME(9999, 12, 31).ADD_DAYS(1)\n"), "DateTime out of range (years 1 to 9999)");
+    // Far out-of-range inputs are refused before any conversion can overflow
+    assert_eq!(err("LET d = DATETIME(300000000000, 1, 1)\n"), "DATETIME: DateTime out of range (years 1 to 9999)");
+    assert_eq!(err("LET d = DATETIME(VAL(\"1e300\"))\n"), "DateTime out of range (years 1 to 9999)");
+    assert_eq!(err("PRINTLN DATETIME(2020, 1, 1).ADD(VAL(\"1e300\"))\n"), "DateTime out of range (years 1 to 9999)");
+    assert_eq!(err("PRINTLN DATETIME(2020, 1, 1).ADD_DAYS(-VAL(\"1e20\"))\n"), "DateTime out of range (years 1 to 9999)");
+    assert_eq!(err("PRINTLN DATETIME(2020, 1, 1).ADD_MONTHS(9223372036854775807)\n"), "DateTime out of range (years 1 to 9999)");
+    assert_eq!(run("PRINTLN DATETIME(1, 1, 1).ADD_MONTHS(9998 * 12 + 11)\n").unwrap(), "9999-12-01T00:00:00Z\n");
+    // A variable of the same name still wins
+    assert_eq!(run("LET timer = 5\nPRINTLN timer\n").unwrap(), "5\n");
+}
@@ -5,6 +5,7 @@ use std::thread;
 use basil_vm::{VM};
 use basil_vm::debug::{Debugger, DebugEvent};
 use basil_bytecode::{Chunk, Program as BCProgram, Value, Op};
+use basil_common::{SourceLoc, SourceMap};
 
 #[test]
 fn breakpoint_and_output_events() {
@@ -53,3 +54,41 @@ fn breakpoint_and_output_events() {
     assert!(evs.iter().any(|e| e.starts_with("Output:Hello")));
     assert!(evs.contains(&"Exited".to_string()));
 }
+
+#[test]
+fn source_map_drives_breakpoints_and_locations() {
+    // Generated line 1 comes from line 7, column 3 of an included partial
+    let mut chunk = Chunk::default();
+    chunk.push_op(Op::SetLine); chunk.push_u16(1);
+    chunk.push_op(Op::Halt);
+    let prog = BCProgram { chunk, globals: vec![] };
+
+    let dbg = Debugger::new();
+    let rx = dbg.subscribe();
+    let dbg_for_thread = dbg.clone();
+    let handle = thread::spawn(move || {
+        let mut stopped = None;
+        while let Ok(ev) = rx.recv() {
+            match ev {
+                DebugEvent::StoppedBreakpoint { file, line } => { stopped = Some((file, line)); dbg_for_thread.resume(); }
+                DebugEvent::Exited => break,
+                _ => {}
+            }
+        }
+        stopped
+    });
+
+    let mut vm = VM::new(prog);
+    vm.set_script_path("page.bas".to_string());
+    vm.set_source_map(SourceMap {
+        files: vec!["page.bas".into(), "partial.basil".into()],
+        lines: vec![SourceLoc { file: 1, line: 7, col: 3 }],
+    });
+    dbg.set_breakpoint("partial.basil".to_string(), 7);
+    vm.set_debugger(dbg.clone());
+    vm.run().expect("vm run");
+
+    let stopped = handle.join().unwrap();
+    assert_eq!(stopped, Some(("partial.basil".to_string(), 7)));
+    assert_eq!(vm.current_location(), Some(("partial.basil".to_string(), 7, 3)));
+}
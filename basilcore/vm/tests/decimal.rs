@@ -0,0 +1,125 @@
+use basil_bytecode::{deserialize_program, serialize_program, Decimal, Rounding};
+use basil_vm::VM;
+
+mod common;
+use common::{capture, run, text};
+
+#[test]
+fn literals_and_exact_arithmetic() {
+    let src = r#"
+LET a = 0.1@ + 0.2@
+PRINTLN a, TYPE$(a), a = 0.3, 0.1 + 0.2 = 0.3
+PRINTLN 1.10@ * 3, 10.00@ / 4, 1@ / 3, 19.99@ - 20, -a, 7.5@ MOD 2, -7.5@ MOD 2
+PRINTLN 1.50@ = 1.5@, 2@ > 1.99, 0.1@ < 0.1, 1@ <> 1, "Total: " + a, a + 1.5
+LET s = 0@
+FOR i = 1 TO 10
+  s = s + 0.1@
+NEXT
+PRINTLN s, s = 1, ABS(-2.50@), INT(-2.5@), INT(2.5@)
+PRINTLN 123456789012345678901234567890.123@ * 987654321098765432109876543210@
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "0.3\tDECIMAL\ttrue\tfalse\n\
+         3.30\t2.50\t0.3333333333\t-0.01\t-0.3\t1.5\t-1.5\n\
+         true\ttrue\tfalse\tfalse\tTotal: 0.3\t1.8\n\
+         1.0\ttrue\t2.50\t-3\t2\n\
+         121932631137021795226185032733744404813732611949260778341714.830\n"
+    );
+    assert_eq!(run("PRINTLN 1.5@ / 0\n").unwrap_err().0, "DECIMAL division by zero");
+}
+
+#[test]
+fn division_scale_and_rounding_modes() {
+    let src = r#"
+PRINTLN DEC("12.345"), DEC("12.345", 2), DEC("12.355", 2), DEC("-2.5", 0, "HALF_UP"), DEC(0.1), DEC(7), DEC(" 1.5E3 ")
+PRINTLN DEC_DIV(10, 3, 4), DEC_DIV(2, 3, 2, "DOWN"), DEC_ROUND(2.675@, 2, "HALF_UP"), DEC_ROUND(1.5@, 3)
+PRINTLN DEC_ROUND(-2.5@, 0, "HALF_DOWN"), DEC_ROUND(-2.1@, 0, "FLOOR"), DEC_ROUND(-2.9@, 0, "CEILING"), DEC_ROUND(2.1@, 0, "UP")
+PRINTLN DEC_SCALE%(4), 1@ / 3, DEC_ROUNDING$("CEILING"), 1@ / 3, DEC_ROUNDING$(), DEC_SCALE%()
+PRINTLN DEC_DIV(1, 7, 40)
+PRINTLN DEC_DIV(99999999999999999999.99@, 0.03@, 5)
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "12.345\t12.34\t12.36\t-3\t0.1\t7\t1500\n\
+         3.3333\t0.66\t2.68\t1.500\n\
+         -2\t-3\t-2\t3\n\
+         10\t0.3333\tHALF_EVEN\t0.3334\tCEILING\t4\n\
+         0.1428571428571428571428571428571428571429\n\
+         3333333333333333333333.00000\n"
+    );
+    assert_eq!(run("PRINTLN DEC(\"12,5\")\n").unwrap_err().0, "DEC: \"12,5\" is not a decimal number");
+    assert_eq!(
+        run("PRINTLN DEC_ROUND(1@, 2, \"NEAREST\")\n").unwrap_err().0,
+        "DEC_ROUND: unknown rounding mode \"NEAREST\" (use HALF_EVEN, HALF_UP, HALF_DOWN, UP, DOWN, CEILING or FLOOR)"
+    );
+    assert_eq!(run("PRINTLN DEC_SCALE%(-1)\n").unwrap_err().0, "DEC_SCALE%: scale must be from 0 to 1000");
+    let inf = "LET i = VAL(\"1e400\")\n";
+    assert_eq!(run(&format!("{}PRINTLN DEC(i)\n", inf)).unwrap_err().0, "DEC: inf is not a finite number");
+    assert_eq!(run(&format!("{}PRINTLN DEC(-i, 2)\n", inf)).unwrap_err().0, "DEC: -inf is not a finite number");
+    assert_eq!(run(&format!("{}PRINTLN DEC_ROUND(i - i, 2)\n", inf)).unwrap_err().0, "DEC_ROUND: NaN is not a finite number");
+
+    let third = Decimal::from_i64(1).quotient(&Decimal::from_i64(3), 3, Rounding::HalfEven).unwrap();
+    assert_eq!(third.to_string(), "0.333");
+    assert_eq!((&third * &Decimal::from_i64(3)).to_string(), "0.999");
+    assert_eq!(Decimal::parse("-0.00").unwrap().to_string(), "0.00");
+    assert!(Decimal::parse("1e-5000").is_none());
+}
+
+#[test]
+fn formatting_json_and_sql() {
+    let src = r##"
+PRINTLN USING "#.## "; 1.005, 1.005@, 0.285@
+PRINTLN USING "$$#,###,###.##"; 1234567.895@
+PRINTLN FORMAT$(1234.565@, "#,##0.00", "de"), FORMAT$(0.125@, "0.0%"), STR$(3.10@), STR$(-0.5@)
+LET d = JSON_PARSE("{\"price\": 19.990, \"qty\": 3, \"note\": \"1.5\", \"big\": 12345678901234567890.123456789}", "DECIMAL")
+PRINTLN d["price"], TYPE$(d["qty"]), d["note"], d["big"], d["price"] * d["qty"]
+PRINTLN JSON_STRINGIFY$({"total": 59.970@, "n": 1}), JSON_STRINGIFY$(JSON_PARSE("[1.10, -2E2]", "decimal"))
+PRINTLN SQL_BIND$("INSERT INTO t VALUES (?, ?, ?, ?, ?) -- ?", 19.99@, "O'Brien", NULL, -3, BYTES([1, 255]))
+PRINTLN SQL_BIND$("SELECT * FROM t WHERE a = ? AND b = '?'", [0.1@])
+PRINTLN SQL_BIND$("UPDATE t SET amt = :amt WHERE id = :id AND x = ':id' AND y::int = 1", {"amt": 1.50@, "id": 7})
+"##;
+    assert_eq!(
+        run(src).unwrap(),
+        "1.00 1.01 0.29 \n\
+         \x20$1,234,567.90\n\
+         1.234,57\t12.5%\t 3.10\t-0.5\n\
+         19.990\tDECIMAL\t1.5\t12345678901234567890.123456789\t59.970\n\
+         {\"total\":59.970,\"n\":1}\t[1.10,-200]\n\
+         INSERT INTO t VALUES (19.99, 'O''Brien', NULL, (-3), X'01ff') -- ?\n\
+         SELECT * FROM t WHERE a = 0.1 AND b = '?'\n\
+         UPDATE t SET amt = 1.50 WHERE id = 7 AND x = ':id' AND y::int = 1\n"
+    );
+    assert_eq!(
+        run("PRINTLN SQL_BIND$(\"SELECT ?, ?\", 1)\n").unwrap_err().0,
+        "SQL_BIND$: the statement has more ? placeholders than the 1 value(s) given"
+    );
+    assert_eq!(
+        run("PRINTLN SQL_BIND$(\"SELECT ?\", 1, 2)\n").unwrap_err().0,
+        "SQL_BIND$: 2 value(s) given but the statement has 1 ? placeholder(s)"
+    );
+    assert_eq!(run("PRINTLN SQL_BIND$(\"SELECT :x\", {\"y\": 1})\n").unwrap_err().0, "SQL_BIND$: no value for :x");
+
+    // A trailing backslash must not end a MySQL string early
+    let src = r#"
+LET name$ = "\\' OR 1=1 -- "
+PRINTLN SQL_BIND$("SELECT * FROM u WHERE name = ?", name$)
+PRINTLN SQL_BIND_MYSQL$("SELECT * FROM u WHERE name = ?", name$)
+PRINTLN SQL_BIND_MYSQL$("SELECT 'it\\'s ?', ? # ?", "a" + CHR$(10) + "b")
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "SELECT * FROM u WHERE name = '\\'' OR 1=1 -- '\n\
+         SELECT * FROM u WHERE name = '\\\\\\' OR 1=1 -- '\n\
+         SELECT 'it\\'s ?', 'a\\nb' # ?\n"
+    );
+    assert_eq!(run("PRINTLN SQL_BIND_MYSQL$(\"SELECT ?\")\n").unwrap_err().0, "SQL_BIND_MYSQL$: the statement has more ? placeholders than the 0 value(s) given");
+
+    // DECIMAL constants survive the compiled-program cache with their scale
+    let ast = basil_parser::parse("PRINTLN 2.50@ * 2\n").unwrap();
+    let prog = deserialize_program(&serialize_program(&basil_compiler::compile(&ast).unwrap())).unwrap();
+    let mut vm = VM::new(prog);
+    let out = capture(&mut vm);
+    vm.run().unwrap();
+    assert_eq!(text(&out), "5.00\n");
+}
@@ -0,0 +1,46 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use basil_bytecode::{Dict, Value};
+
+mod common;
+use common::run;
+
+#[test]
+fn dicts_keep_insertion_order() {
+    let src = r#"
+LET d = {"zeta": 1, "alpha": 2, "mid": 3}
+d["beta"] = 4
+d["zeta"] = 10
+PRINTLN d
+FOR EACH k IN d
+  PRINT k + " "
+NEXT
+PRINTLN ""
+PRINTLN JSON_STRINGIFY$(d)
+PRINTLN d = {"mid": 3, "beta": 4, "alpha": 2, "zeta": 10}, d = {"zeta": 10}
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "{\"zeta\": 10, \"alpha\": 2, \"mid\": 3, \"beta\": 4}\nzeta alpha mid beta \n{\"zeta\":10,\"alpha\":2,\"mid\":3,\"beta\":4}\ntrue\tfalse\n"
+    );
+}
+
+#[test]
+fn dict_keys_must_be_strings() {
+    let err = run("LET d = {\"a\": 1}\nPRINTLN d[1]\n").unwrap_err();
+    assert_eq!(err.0, "Dictionary key must be string, got FLOAT");
+    let err = run("LET d = {\"a\": 1}\nLET k% = 2\nd[k%] = 5\n").unwrap_err();
+    assert_eq!(err.0, "Dictionary key must be string, got INTEGER");
+}
+
+#[test]
+fn host_dicts_keep_order() {
+    let mut d: Dict = [("b", 1.0), ("a", 2.0), ("c", 3.0)].into_iter().map(|(k, v)| (k.to_string(), Value::Num(v))).collect();
+    d.insert("a".into(), Value::Num(20.0));
+    assert_eq!(d.remove("b"), Some(Value::Num(1.0)));
+    d.insert("b".into(), Value::Null);
+    assert_eq!(d.keys().cloned().collect::<Vec<_>>(), ["a", "c", "b"]);
+    assert_eq!(d.get("c"), Some(&Value::Num(3.0)));
+    assert_eq!(format!("{:?}", Value::Dict(Rc::new(RefCell::new(d)))), "Dict{a: Num(20), c: Num(3), b: Null}");
+}
@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use basil_vm::providers::{Clock, ManualClock};
+use basil_vm::{InputProvider, VM};
+
+mod common;
+use common::text;
+
+// Keys typed one at a time, then nothing
+struct Keys(Vec<char>);
+impl InputProvider for Keys {
+    fn read_line(&mut self) -> String { String::new() }
+    fn read_char(&mut self) -> Option<char> { if self.0.is_empty() { None } else { Some(self.0.remove(0)) } }
+}
+
+fn vm_for(src: &str) -> (VM, Rc<ManualClock>, Rc<RefCell<Vec<u8>>>) {
+    let mut vm = common::vm_for(src);
+    let clock = Rc::new(ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_000_000)));
+    vm.set_clock(clock.clone());
+    let out = common::capture(&mut vm);
+    (vm, clock, out)
+}
+
+#[test]
+fn timers_run_in_virtual_time() {
+    let src = r#"
+LET ticks = 0
+LET iv% = 0
+FUNC Tick()
+  ticks = ticks + 1
+  PRINTLN "tick " + ticks
+  IF ticks = 3 THEN CLEARINTERVAL(iv%)
+  RETURN 0
+END FUNC
+FUNC Once()
+  PRINTLN "once"
+  RETURN 0
+END FUNC
+iv% = SETINTERVAL(Tick, 100)
+LET t% = SETTIMEOUT(Once, 250)
+LET c% = SETTIMEOUT(Once, 50)
+PRINTLN CLEARTIMEOUT(c%), CLEARTIMER(c%)
+AWAIT 150
+PRINTLN "awaited"
+"#;
+    let (mut vm, clock, out) = vm_for(src);
+    let start = clock.now();
+    vm.run().expect("run");
+    // The top level ends after AWAIT; the remaining timers run before run() returns
+    assert_eq!(text(&out), "1\t0\ntick 1\nawaited\ntick 2\nonce\ntick 3\n");
+    assert_eq!(clock.now().duration_since(start).unwrap(), Duration::from_millis(300));
+    assert_eq!(vm.pending_timers(), 0);
+}
+
+#[test]
+fn event_loop_with_keys_until_stopped() {
+    let src = r#"
+FUNC Key(k%)
+  PRINTLN "key " + k%
+  IF k% = 113 THEN STOP_LOOP
+  RETURN 0
+END FUNC
+FUNC Never()
+  PRINTLN "never"
+  RETURN 0
+END FUNC
+ON KEY Key
+LET t% = SETTIMEOUT(Never, 60000)
+EVENT_LOOP
+ON KEY NULL
+PRINTLN "done", CLEARTIMER(t%)
+"#;
+    let (mut vm, _, out) = vm_for(src);
+    vm.set_input(Box::new(Keys(vec!['a', 'q', 'z'])));
+    vm.run().expect("run");
+    assert_eq!(text(&out), "key 97\nkey 113\ndone\t1\n");
+}
+
+#[test]
+fn signals_raised_by_the_host() {
+    let src = r#"
+FUNC Got(name$)
+  PRINTLN "got " + name$
+  RETURN 0
+END FUNC
+ON SIGNAL "SIGUSR2", Got
+AWAIT 10
+"#;
+    let (mut vm, clock, out) = vm_for(src);
+    vm.raise_signal("usr2").unwrap();
+    vm.run().expect("run");
+    vm.raise_signal("USR2").unwrap();
+    vm.raise_signal("HUP").unwrap();
+    vm.run_events(Some(clock.now())).unwrap();
+    vm.flush_output().unwrap();
+    // HUP has no handler and is dropped
+    assert_eq!(text(&out), "got USR2\ngot USR2\n");
+    assert_eq!(vm.raise_signal("BOGUS").unwrap_err().0, "unknown signal \"BOGUS\"");
+}
+
+#[test]
+fn event_errors() {
+    let run = |src: &str| {
+        let (mut vm, _, _) = vm_for(src);
+        vm.run().unwrap_err().0
+    };
+    assert_eq!(run("LET t% = SETTIMEOUT(5, 10)\n"), "SETTIMEOUT handler must be a FUNC or SUB name");
+    assert_eq!(run("FUNC F(x)\n  RETURN x\nEND FUNC\nLET t% = SETINTERVAL(F, 10)\n"), "SETINTERVAL handler must take no parameters");
+    assert_eq!(run("FUNC F()\n  RETURN 0\nEND FUNC\nON SIGNAL \"FOO\", F\n"), "ON SIGNAL: unknown signal \"FOO\" (use INT, TERM, HUP, USR1 or USR2)");
+    // Errors inside a handler stop the program
+    assert_eq!(run("FUNC F()\n  RAISE \"boom\"\nEND FUNC\nLET t% = SETTIMEOUT(F, 10)\n"), "boom");
+}
@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use basil_bytecode::{FromValue, Value};
+use basil_vm::VM;
+
+mod common;
+use common::text;
+
+fn vm_for(src: &str) -> (VM, Rc<RefCell<Vec<u8>>>) {
+    let mut vm = common::vm_for(src);
+    let out = common::capture(&mut vm);
+    (vm, out)
+}
+
+#[test]
+fn lazy_pipelines() {
+    let src = r#"
+FUNC Naturals()
+  LET n = 0
+  WHILE TRUE BEGIN
+    n = n + 1
+    YIELD n
+  END
+END FUNC
+FUNC Evens(src@)
+  FOR EACH v IN src@
+    IF v MOD 2 = 0 THEN YIELD v
+  NEXT
+END FUNC
+FUNC Take(src@, count)
+  FOR EACH v IN src@
+    IF count <= 0 THEN RETURN 0
+    count = count - 1
+    YIELD v
+  NEXT
+END FUNC
+FOR EACH x IN Take(Evens(Naturals()), 3)
+  PRINTLN x
+NEXT
+LET g@ = Take(Naturals(), 1)
+PRINTLN g@._NEXT(), g@._NEXT(), g@.Done, TYPE$(g@)
+PRINTLN SORT(Take(Evens(Naturals()), 4))
+FUNC Gaps()
+  YIELD 3
+  YIELD NULL
+  YIELD 1
+END FUNC
+FOR EACH x IN Gaps()
+  PRINTLN x
+NEXT
+"#;
+    let (mut vm, out) = vm_for(src);
+    vm.run().expect("run");
+    assert_eq!(text(&out), "2\n4\n6\n1\tnull\ttrue\tGENERATOR\n[2, 4, 6, 8]\n3\nnull\n1\n");
+    // SORT reads past the NULL too, and then cannot order it
+    let (mut vm, _) = vm_for(&format!("{}PRINTLN SORT(Gaps())\n", src));
+    assert_eq!(vm.run().unwrap_err().0, "SORT: cannot compare FLOAT with NULL");
+}
+
+#[test]
+fn host_steps_many_tasks() {
+    let src = r#"
+FUNC Task(name$, steps)
+  FOR i = 1 TO steps
+    PRINTLN name$ + " step " + i
+    YIELD i
+  NEXT i
+END FUNC
+"#;
+    let (mut vm, out) = vm_for(src);
+    vm.run().expect("run");
+    let mut tasks = vec![
+        vm.call_function("Task", &[Value::Str("a".into()), Value::Num(2.0)]).unwrap(),
+        vm.call_function("Task", &[Value::Str("b".into()), Value::Num(1.0)]).unwrap(),
+    ];
+    // Round-robin until every task has finished
+    let mut yielded = Vec::new();
+    while !tasks.is_empty() {
+        let mut live = Vec::new();
+        for t in tasks {
+            if let Some(v) = vm.generator_next(&t).unwrap() {
+                yielded.push(f64::from_value(&v).unwrap());
+                live.push(t);
+            }
+        }
+        tasks = live;
+    }
+    vm.flush_output().unwrap();
+    assert_eq!(yielded, [1.0, 1.0, 2.0]);
+    assert_eq!(text(&out), "a step 1\nb step 1\na step 2\n");
+}
+
+#[test]
+fn generator_errors() {
+    let compile = |src: &str| basil_compiler::compile(&basil_parser::parse(src).expect("parse")).err().map(|e| e.0);
+    assert_eq!(compile("YIELD 1\n").unwrap(), "YIELD is only allowed inside a FUNC or SUB");
+    // A variable may still be called yield
+    assert_eq!(compile("LET yield = 1\nyield = yield + 1\n"), None);
+
+    let (mut vm, _) = vm_for("FUNC G()\n  YIELD 1\nEND FUNC\n");
+    vm.run().expect("run");
+    assert_eq!(vm.generator_next(&Value::Num(1.0)).unwrap_err().0, "expected a GENERATOR, got FLOAT");
+    let g = vm.call_function("G", &[]).unwrap();
+    assert_eq!(vm.generator_next(&g).unwrap(), Some(Value::Num(1.0)));
+    assert_eq!(vm.generator_next(&g).unwrap(), None);
+    assert_eq!(vm.generator_next(&g).unwrap(), None);
+}
@@ -0,0 +1,76 @@
+use std::rc::Rc;
+
+use basil_bytecode::{HostFunctions, Value};
+use basil_vm::VM;
+
+fn host() -> Rc<HostFunctions> {
+    let mut h = HostFunctions::new();
+    h.register("MYAPP_LOOKUP$", 1..=1, |_, args| {
+        let id = match &args[0] { Value::Int(i) => *i, Value::Num(n) => *n as i64, _ => 0 };
+        let name = match id { 1 => "alice", 2 => "bob", _ => "" };
+        Ok(Value::Str(name.into()))
+    }).unwrap();
+    // Calls back into the script and reads a global of the caller
+    h.register("MYAPP_EACH%", 1..=1, |ctx, args| {
+        let base = match ctx.get_global("BASE%") { Some(Value::Int(i)) => i, _ => 0 };
+        let mut sum = 0;
+        for i in 1..=3 {
+            match ctx.call(&args[0], vec![Value::Int(base + i)])? {
+                Value::Int(n) => sum += n,
+                Value::Num(n) => sum += n as i64,
+                _ => {}
+            }
+        }
+        ctx.print("each done\n");
+        Ok(Value::Int(sum))
+    }).unwrap();
+    h.register("MYAPP_BROKEN%", 0..=0, |_, _| Ok(Value::Str("oops".into()))).unwrap();
+    Rc::new(h)
+}
+
+fn compile(src: &str, host: &HostFunctions) -> basil_common::Result<VM> {
+    let ast = basil_parser::parse(src)?;
+    let prog = basil_compiler::compile_with_host(&ast, host)?;
+    Ok(VM::new(prog))
+}
+
+fn global(vm: &VM, name: &str) -> String {
+    let (names, values) = vm.globals_snapshot();
+    let i = names.iter().position(|n| n.eq_ignore_ascii_case(name)).expect("global");
+    format!("{}", values[i])
+}
+
+#[test]
+fn host_functions_are_callable() {
+    let h = host();
+    let src = r#"
+FUNC Twice(n)
+  RETURN n * 2
+END FUNC
+LET base% = 10
+LET who$ = myapp_lookup$(2)
+LET total% = MYAPP_EACH%(Twice)
+LET e$ = EVAL("MYAPP_LOOKUP$(1)")
+"#;
+    let mut vm = compile(src, &h).expect("compile");
+    vm.set_host_functions(h.clone());
+    vm.run().expect("run");
+    assert_eq!(global(&vm, "who$"), "bob");
+    assert_eq!(global(&vm, "total%"), "72");
+    assert_eq!(global(&vm, "e$"), "alice");
+}
+
+#[test]
+fn host_function_checks() {
+    let h = host();
+    let err = compile("LET a$ = MYAPP_LOOKUP$(1, 2)", &h).err().expect("arity error");
+    assert_eq!(err.0, "MYAPP_LOOKUP$ expects 1 argument(s), got 2");
+
+    let mut vm = compile("LET n% = MYAPP_BROKEN%()", &h).expect("compile");
+    vm.set_host_functions(h.clone());
+    assert_eq!(vm.run().unwrap_err().0, "MYAPP_BROKEN% must return INTEGER, got STRING");
+
+    // Compiled against the table but run without it
+    let mut vm = compile("LET a$ = MYAPP_LOOKUP$(1)", &h).expect("compile");
+    assert_eq!(vm.run().unwrap_err().0, "Host function not available: MYAPP_LOOKUP$");
+}
@@ -0,0 +1,58 @@
+use basil_bytecode::Value;
+use basil_vm::json;
+
+mod common;
+use common::run;
+
+#[test]
+fn parse_to_lists_and_dicts() {
+    let src = r#"
+LET d = JSON_PARSE("{\"name\": \"Ann\", \"tags\": [\"a\", \"b\"], \"age\": 41, \"score\": 2.5, \"ok\": true, \"none\": null, \"big\": 9007199254740993}")
+PRINTLN d["name"], d["tags"][2], d["age"] + 1, d["score"] * 2, d["ok"], d["none"] = NULL
+PRINTLN TYPE$(d), TYPE$(d["tags"]), TYPE$(d["age"]), TYPE$(d["big"]), d["big"]
+FOR EACH t IN d["tags"]
+  PRINTLN t
+NEXT
+PRINTLN JSON_PARSE$(" [1, {\"b\": 2,  \"a\": 1}] ")
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "Ann\tb\t42\t5\ttrue\ttrue\nDICT\tLIST\tFLOAT\tINTEGER\t9007199254740993\na\nb\n[1,{\"b\":2,\"a\":1}]\n"
+    );
+}
+
+#[test]
+fn stringify_round_trips() {
+    let src = r#"
+LET v = {"b": [1, 2.5, "x\\y", TRUE, NULL], "a": {"n%": 7}, "e": [], "s": "say \"hi\""}
+LET s$ = JSON_STRINGIFY$(v)
+PRINTLN s$
+PRINTLN JSON_STRINGIFY$(JSON_PARSE(s$)) = s$
+PRINTLN JSON_STRINGIFY$([1, {"k": []}], 2)
+PRINTLN JSON_STRINGIFY$("42"), JSON_STRINGIFY$(1 / 4), JSON_STRINGIFY$(EXP(1000))
+"#;
+    let err = run(src).unwrap_err();
+    assert_eq!(err.0, "JSON_STRINGIFY$: cannot stringify inf (not a JSON number)");
+    let out = run(&src.replace(", JSON_STRINGIFY$(EXP(1000))", "")).unwrap();
+    assert_eq!(
+        out,
+        "{\"b\":[1,2.5,\"x\\\\y\",true,null],\"a\":{\"n%\":7},\"e\":[],\"s\":\"say \\\"hi\\\"\"}\ntrue\n[\n  1,\n  {\n    \"k\": []\n  }\n]\n\"42\"\t0.25\n"
+    );
+}
+
+#[test]
+fn json_errors() {
+    let err = run("LET l = [1]\nl[1] = l\nPRINTLN JSON_STRINGIFY$(l)\n").unwrap_err();
+    assert_eq!(err.0, "JSON_STRINGIFY$: cannot stringify a value that contains itself");
+    let err = run("FUNC F()\n  RETURN 1\nEND FUNC\nPRINTLN JSON_STRINGIFY$([F])\n").unwrap_err();
+    assert_eq!(err.0, "JSON_STRINGIFY$: cannot stringify a function");
+    let err = run("LET d = JSON_PARSE(\"{\\\"a\\\": }\")\n").unwrap_err();
+    assert_eq!(err.0, "JSON_PARSE: invalid JSON: expected value at line 1 column 7");
+    let err = run("LET d = JSON_PARSE(\"[1] 2\")\n").unwrap_err();
+    assert_eq!(err.0, "JSON_PARSE: invalid JSON: trailing characters at line 1 column 5");
+
+    // The same conversions are available to hosts
+    let v = json::parse("[1, 2]").unwrap();
+    assert_eq!(json::stringify(&v, 0).unwrap(), "[1,2]");
+    assert_eq!(json::stringify(&Value::Num(f64::NAN), 0).unwrap_err().0, "JSON_STRINGIFY$: cannot stringify NaN (not a JSON number)");
+}
@@ -0,0 +1,116 @@
+mod common;
+use common::run;
+
+#[test]
+fn randomize_rnd_ranges_and_rounding() {
+    let src = r#"
+RANDOMIZE 42
+LET first = [RND(6), RND(10, 20), RND(), RND(-3, -1)]
+RANDOMIZE(42)
+LET again = [RND(6), RND(10, 20), RND(), RND(-3, -1)]
+RANDOMIZE 7
+PRINTLN first = again, TYPE$(first[1]), TYPE$(first[3]), [RND(6), RND(10, 20), RND(), RND(-3, -1)] = again
+LET ok = TRUE
+FOR i = 1 TO 200
+  LET d = RND(6)
+  LET r = RND(5, 7)
+  IF d < 1 OR d > 6 OR r < 5 OR r > 7 THEN ok = FALSE
+NEXT
+PRINTLN ok, RND(1), RND(3, 3)
+PRINTLN ROUND(2.5), ROUND(3.5), ROUND(-2.5), ROUND(2.5, 0, "HALF_UP"), ROUND(-2.5, 0, "HALF_UP"), ROUND(2.675, 2, "HALF_UP"), ROUND(1234.5678, 2)
+PRINTLN ROUND(1250, -2), ROUND(1350, -2), ROUND(1.005@, 2), ROUND(1.005@, 2, "HALF_UP"), ROUND(1.5@, 4), ROUND(-1234.5@, -1, "FLOOR")
+PRINTLN FIX(-2.7), INT(-2.7), FIX(2.7@), SGN(-3), SGN(0), SGN(0.5@), SGN(-0.1)
+PRINTLN CINT(2.5), CINT(3.5), CINT(-2.5), CLNG("41.6"), CINT(2.5@), CDBL("1.5") + 1, TYPE$(CDBL(3)), TYPE$(CINT(3.9))
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "true\tINTEGER\tFLOAT\tfalse\n\
+         true\t1\t3\n\
+         2\t4\t-2\t3\t-3\t2.68\t1234.57\n\
+         1200\t1400\t1.00\t1.01\t1.5\t-1240\n\
+         -2\t-3\t2\t-1\t0\t1\t-1\n\
+         2\t4\t-2\t42\t2\t2.5\tFLOAT\tINTEGER\n"
+    );
+    assert_eq!(run("PRINTLN RND(0)\n").unwrap_err().0, "RND: n must be at least 1");
+    assert_eq!(run("PRINTLN RND(5, 1)\n").unwrap_err().0, "RND: empty range 5 to 1");
+    assert_eq!(run("PRINTLN CINT(\"abc\")\n").unwrap_err().0, "CINT: \"abc\" is not a number");
+}
+
+#[test]
+fn functions_and_integer_overflow() {
+    let src = r#"
+PRINTLN MIN(3, 1, 2), MAX([4, 9, 2]), MAX(1.5@, 2), MIN(2, 1.99@), MIN("pear", "apple"), TYPE$(MAX(1, 2.5@))
+PRINTLN HYPOT(3, 4), ROUND(ATAN2(1, -1) / PI, 4), LOG10(1000), POW(2, 10), POW(2, 0.5) = SQR(2), POW(1.1@, 3), PI * 2 = 2 * PI()
+PRINTLN GCD(12, 18), GCD(0, -8), GCD([84, 36, 120]), LCM(4, 6, 10), LCM(3, 0)
+LET a% = 3037000499
+LET b% = a% * a%
+PRINTLN b%, TYPE$(b%), TYPE$(a% + 1), TYPE$(a% + a%), -7 MOD 3, a% MOD 10
+LET c% = 2
+PRINTLN TYPE$(POW(c%, 62)), POW(c%, 62), TYPE$(c% / 2)
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "1\t9\t2\t1.99\tapple\tDECIMAL\n\
+         5\t0.75\t3\t1024\ttrue\t1.331\ttrue\n\
+         6\t8\t12\t60\t0\n\
+         9223372030926249001\tINTEGER\tFLOAT\tINTEGER\t-1\t9\n\
+         INTEGER\t4611686018427387904\tFLOAT\n"
+    );
+    // A program's own FUNC of the same name replaces the builtin
+    let own = "FUNC Max(x, y)\n  RETURN \"program's own\"\nEND FUNC\nPRINTLN Max(1, 2), MIN(1, 2)\n";
+    assert_eq!(run(own).unwrap(), "program's own\t1\n");
+    let overflow = "LET a% = 3037000500\nLET b% = a% * a%\n";
+    assert_eq!(run(overflow).unwrap_err().0, "INTEGER overflow: 3037000500 * 3037000500");
+    assert_eq!(
+        run("LET c% = 2\nPRINTLN POW(c%, 63)\n").unwrap_err().0,
+        "INTEGER overflow: POW(2, 63)"
+    );
+    assert_eq!(run("LET a% = 5\nLET z% = 0\nPRINTLN a% MOD z%\n").unwrap_err().0, "MOD by zero");
+    let min_mod = "LET lo% = -9223372036854775807 - 1\nLET m1% = -1\nPRINTLN lo% MOD m1%, TYPE$(lo% MOD m1%), lo% MOD 10\n";
+    assert_eq!(run(min_mod).unwrap(), "0\tINTEGER\t-8\n");
+    assert_eq!(run("LET x% = 10000000000000000000\n").unwrap_err().0, "10000000000000000000 is out of range for an INTEGER");
+    // Whole literals past 2^53 are exact INTEGERs, up to i64::MAX and down to i64::MIN
+    let limits = r#"
+LET hi% = 9223372036854775807
+LET lo% = -9223372036854775808
+LET odd% = 9007199254740993
+LET one% = 1
+PRINTLN hi%, lo%, odd%, TYPE$(9007199254740993), TYPE$(9007199254740992), hi% - one%, lo% + CLNG(1)
+"#;
+    assert_eq!(
+        run(limits).unwrap(),
+        "9223372036854775807\t-9223372036854775808\t9007199254740993\tINTEGER\tFLOAT\t9223372036854775806\t-9223372036854775807\n"
+    );
+    assert_eq!(run("LET hi% = 9223372036854775807\nLET b% = hi% + CLNG(1)\n").unwrap_err().0, "INTEGER overflow: 9223372036854775807 + 1");
+    assert_eq!(run("PRINTLN LOG10(0)\n").unwrap_err().0, "LOG10 domain error: x must be > 0");
+    assert_eq!(run("PRINTLN GCD(2.5, 5)\n").unwrap_err().0, "GCD: 2.5 is not a whole number");
+}
+
+#[test]
+fn statistics_over_lists_and_arrays() {
+    let src = r#"
+LET xs = [1, 2, 3, 4]
+PRINTLN SUM(xs), AVG(xs), MEDIAN(xs), MEDIAN(3, 1, 2), ROUND(STDDEV(2, 4, 4, 4, 5, 5, 7, 9), 6)
+LET tenths = [0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1]
+PRINTLN SUM(tenths), SUM(tenths) = 1, SUM(0.10@, 0.2), AVG(1@, 2@, 2@), MEDIAN(1.0@, 2.5@), MEDIAN([5@, 1@, 3@])
+DIM arr(3)
+arr(0) = 5
+arr(1) = 7
+arr(2) = 9
+arr(3) = 11
+PRINTLN SUM(arr), AVG(arr), MAX(arr), MIN(arr), STDDEV(arr) > 2.58
+LET n% = 4
+LET m% = 6
+PRINTLN SUM(n%, m%), TYPE$(SUM(n%, m%)), TYPE$(MEDIAN(n%, m%)), SUM([]), TYPE$(SUM([]))
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "10\t2.5\t2.5\t2\t2.13809\n\
+         1\ttrue\t0.30\t1.6666666667\t1.75\t3\n\
+         32\t8\t11\t5\ttrue\n\
+         10\tINTEGER\tFLOAT\t0\tINTEGER\n"
+    );
+    assert_eq!(run("PRINTLN AVG([])\n").unwrap_err().0, "AVG: no values");
+    assert_eq!(run("PRINTLN STDDEV(1)\n").unwrap_err().0, "STDDEV: needs at least 2 values");
+    assert_eq!(run("PRINTLN SUM(1, \"two\")\n").unwrap_err().0, "SUM: element 2 is not a number (STRING)");
+}
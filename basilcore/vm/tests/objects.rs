@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use basil_bytecode::{BasicObject, MethodDesc, ObjectDescriptor, ObjectRef, PropDesc, Value};
+use basil_common::{BasilError, Result};
+use basil_vm::VM;
+
+struct Point { x: i64, y: i64 }
+
+impl BasicObject for Point {
+    fn type_name(&self) -> &str { "Point" }
+    fn get_prop(&self, name: &str) -> Result<Value> {
+        match name.to_ascii_uppercase().as_str() {
+            "X" => Ok(Value::Int(self.x)),
+            "Y" => Ok(Value::Int(self.y)),
+            _ => Err(BasilError(format!("Point has no property {}", name))),
+        }
+    }
+    fn set_prop(&mut self, name: &str, v: Value) -> Result<()> {
+        let n = match v { Value::Int(i) => i, Value::Num(f) => f as i64, _ => return Err(BasilError("Point coordinates are numbers".into())) };
+        match name.to_ascii_uppercase().as_str() {
+            "X" => self.x = n,
+            "Y" => self.y = n,
+            _ => return Err(BasilError(format!("Point has no property {}", name))),
+        }
+        Ok(())
+    }
+    fn call(&mut self, method: &str, _args: &[Value]) -> Result<Value> {
+        match method.to_ascii_uppercase().as_str() {
+            "SUM" => Ok(Value::Int(self.x + self.y)),
+            _ => Err(BasilError(format!("Point has no method {}", method))),
+        }
+    }
+    fn descriptor(&self) -> ObjectDescriptor { point_desc() }
+}
+
+fn point_desc() -> ObjectDescriptor {
+    let prop = |n: &str| PropDesc { name: n.into(), type_name: "INTEGER".into(), readable: true, writable: true };
+    ObjectDescriptor {
+        type_name: "Point".into(),
+        version: "1.0".into(),
+        summary: "A point on the plane".into(),
+        properties: vec![prop("X"), prop("Y")],
+        methods: vec![
+            MethodDesc { name: "NEW".into(), arity: 2, arg_names: vec!["x%".into(), "y%".into()], return_type: "Point".into() },
+            MethodDesc { name: "Sum".into(), arity: 0, arg_names: Vec::new(), return_type: "INTEGER".into() },
+        ],
+        examples: Vec::new(),
+    }
+}
+
+fn vm_with_point(src: &str) -> VM {
+    let ast = basil_parser::parse(src).expect("parse");
+    let prog = basil_compiler::compile(&ast).expect("compile");
+    let mut vm = VM::new(prog);
+    vm.registry_mut().register("Point", point_desc(), Box::new(|args: &[Value]| {
+        let num = |v: &Value| match v { Value::Int(i) => *i, Value::Num(f) => *f as i64, _ => 0 };
+        let obj: ObjectRef = Rc::new(RefCell::new(Point { x: num(&args[0]), y: num(&args[1]) }));
+        Ok(obj)
+    }));
+    vm
+}
+
+fn global(vm: &VM, name: &str) -> String {
+    let (names, values) = vm.globals_snapshot();
+    let i = names.iter().position(|n| n.eq_ignore_ascii_case(name)).expect("global");
+    format!("{}", values[i])
+}
+
+#[test]
+fn host_types_construct_and_describe() {
+    let src = r#"
+DIM p@ AS POINT(1, 2)
+LET q@ = NEW point(3, 4)
+q@.X = 10
+LET total% = p@.Sum() + q@.Sum()
+LET d$ = DESCRIBE$("point")
+"#;
+    let mut vm = vm_with_point(src);
+    vm.run().expect("run");
+    assert_eq!(global(&vm, "total%"), "17");
+    let d = global(&vm, "d$");
+    assert!(d.starts_with("Point — v1.0\nA point on the plane\n"));
+    assert!(d.contains("NEW(x%, y%) -> Point"));
+}
+
+#[test]
+fn constructor_arity_and_unknown_types() {
+    let err = vm_with_point("LET p@ = NEW Point(1)").run().unwrap_err();
+    assert!(err.0.contains("Point expects 2 constructor argument(s), got 1"), "{}", err.0);
+    let err = vm_with_point("LET p@ = NEW Nothing()").run().unwrap_err();
+    assert!(err.0.contains("Unknown object type: Nothing"), "{}", err.0);
+}
@@ -0,0 +1,35 @@
+mod common;
+use common::{capture, run, text, vm_for};
+
+#[test]
+fn exec_and_eval_output_lands_in_ob_captures() {
+    let src = r#"
+PRINT "a"
+OB_START
+PRINT "b"
+EXEC("PRINT \"x\"")
+LET n = EVAL("1 + 2")
+EXEC("OB_START" + CHR$(10) + "PRINT \"open\"")
+LET s$ = OB_GET$()
+PRINTLN "[" + s$ + "]", n
+"#;
+    // The capture EXEC leaves open ends with EXEC; the caller's capture stays open
+    assert_eq!(run(src).unwrap(), "a[bxopen]\t3\n");
+}
+
+#[test]
+fn child_vm_output_goes_to_the_hosts_sink() {
+    let dir = std::env::temp_dir().join(format!("basil_output_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("Hello.bas"), "PRINTLN \"class top level\"\n").unwrap();
+    let mut vm = vm_for("PRINTLN 1\nEXEC(\"PRINTLN 2\")\nLET h@ = CLASS(\"Hello.bas\")\nPRINTLN 3\nEXEC(\"RAISE \\\"no\\\"\")\n");
+    vm.set_script_path(dir.join("main.bas").to_string_lossy().into_owned());
+    let out = capture(&mut vm);
+    assert_eq!(vm.run().unwrap_err().0, "no");
+    assert_eq!(text(&out), "1\n2\nclass top level\n3\n");
+    // The VM keeps its output after a failing child
+    vm.write_output(b"after");
+    vm.flush_output().unwrap();
+    assert_eq!(text(&out), "1\n2\nclass top level\n3\nafter");
+    let _ = std::fs::remove_dir_all(&dir);
+}
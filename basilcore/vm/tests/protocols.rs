@@ -0,0 +1,137 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use basil_bytecode::{BasicObject, MethodDesc, ObjectDescriptor, ObjectRef, Value};
+use basil_common::{BasilError, Result};
+
+mod common;
+
+// A host collection taking part in [], FOR EACH, SORT and PRINT
+struct Stack { items: Vec<Value> }
+
+impl BasicObject for Stack {
+    fn type_name(&self) -> &str { "Stack" }
+    fn get_prop(&self, name: &str) -> Result<Value> { Err(BasilError(format!("Stack has no property {}", name))) }
+    fn set_prop(&mut self, name: &str, _v: Value) -> Result<()> { Err(BasilError(format!("Stack has no property {}", name))) }
+    fn call(&mut self, method: &str, args: &[Value]) -> Result<Value> {
+        let slot = |items: &Vec<Value>, v: &Value| match v {
+            Value::Int(i) if *i >= 1 && (*i as usize) <= items.len() => Ok(*i as usize - 1),
+            Value::Num(n) if *n >= 1.0 && (*n as usize) <= items.len() => Ok(*n as usize - 1),
+            other => Err(BasilError(format!("Stack index out of range: {}", other))),
+        };
+        match method.to_ascii_uppercase().as_str() {
+            "PUSH" => { self.items.push(args[0].clone()); Ok(Value::Null) }
+            "_GETITEM" => Ok(self.items[slot(&self.items, &args[0])?].clone()),
+            "_SETITEM" => { let i = slot(&self.items, &args[0])?; self.items[i] = args[1].clone(); Ok(Value::Null) }
+            "_ITER" => Ok(Value::List(Rc::new(RefCell::new(self.items.clone())))),
+            "_STR$" => Ok(Value::Str(format!("Stack({})", self.items.len()))),
+            _ => Err(BasilError(format!("Stack has no method {}", method))),
+        }
+    }
+    fn descriptor(&self) -> ObjectDescriptor { stack_desc() }
+}
+
+fn stack_desc() -> ObjectDescriptor {
+    let m = |n: &str, arity: u8| MethodDesc { name: n.into(), arity, arg_names: Vec::new(), return_type: "ANY".into() };
+    ObjectDescriptor {
+        type_name: "Stack".into(),
+        version: "1.0".into(),
+        summary: "A growable stack".into(),
+        properties: Vec::new(),
+        methods: vec![m("Push", 1), m("_GETITEM", 1), m("_SETITEM", 2), m("_ITER", 0), m("_STR$", 0)],
+        examples: Vec::new(),
+    }
+}
+
+fn run(src: &str) -> Result<String> {
+    common::run_with(src, |vm| {
+        vm.registry_mut().register("Stack", stack_desc(), Box::new(|_args: &[Value]| {
+            let obj: ObjectRef = Rc::new(RefCell::new(Stack { items: Vec::new() }));
+            Ok(obj)
+        }));
+    })
+}
+
+#[test]
+fn class_protocols() {
+    let src = r#"
+CLASS Money
+  cents% = 0
+  SUB NEW(c%)
+    ME.cents% = c%
+  END SUB
+  FUNC _STR$()
+    RETURN "$" + (ME.cents% / 100)
+  END FUNC
+  FUNC _CMP(other@)
+    RETURN ME.cents% - other@.cents%
+  END FUNC
+END CLASS
+CLASS Bag
+  items = {}
+  FUNC _GETITEM(k$)
+    RETURN ME.items[k$]
+  END FUNC
+  SUB _SETITEM(k$, v)
+    ME.items[k$] = v
+  END SUB
+  FUNC _ITER()
+    RETURN ME.items
+  END FUNC
+END CLASS
+CLASS Countdown
+  n% = 0
+  SUB NEW(n%)
+    ME.n% = n%
+  END SUB
+  FUNC _NEXT()
+    IF ME.n% = 0 THEN RETURN NULL
+    ME.n% = ME.n% - 1
+    RETURN ME.n% + 1
+  END FUNC
+END CLASS
+LET a@ = NEW Money(250)
+LET b@ = NEW Money(100)
+PRINTLN a@, "total " + b@, [a@, b@]
+PRINTLN a@ > b@, a@ <= b@, a@ = NEW Money(250), a@ <> b@
+PRINTLN SORT([a@, b@, NEW Money(175)])
+LET g@ = NEW Bag()
+g@["x"] = 5
+PRINTLN g@["x"]
+FOR EACH k IN g@
+  PRINTLN k
+NEXT
+FOR EACH r IN NEW Countdown(3)
+  PRINTLN r
+NEXT
+PRINTLN SORT(NEW Countdown(4)), SORT(["b", "c", "a"])
+"#;
+    assert_eq!(run(src).unwrap(), "$2.5\ttotal $1\t[$2.5, $1]\ntrue\tfalse\ttrue\ttrue\n[$1, $1.75, $2.5]\n5\nx\n3\n2\n1\n[1, 2, 3, 4]\t[a, b, c]\n");
+}
+
+#[test]
+fn host_object_protocols() {
+    let src = r#"
+LET s@ = NEW Stack()
+s@.Push(3)
+s@.Push(1)
+s@.Push(2)
+s@[1] = 30
+LET total = 0
+FOR EACH v IN s@
+  total = total + v
+NEXT
+PRINTLN s@, s@[1], total, SORT(s@)
+"#;
+    assert_eq!(run(src).unwrap(), "Stack(3)\t30\t33\t[1, 2, 30]\n");
+}
+
+#[test]
+fn objects_without_protocols() {
+    let err = run("CLASS P\nEND CLASS\nLET p@ = NEW P()\nPRINTLN p@[1]\n").unwrap_err();
+    assert_eq!(err.0, "Attempted [] on P which has no _GETITEM");
+    let err = run("CLASS P\nEND CLASS\nFOR EACH x IN NEW P()\nNEXT\n").unwrap_err();
+    assert_eq!(err.0, "FOR EACH expects an array or iterable object after IN (got TYPE=P).");
+    let err = run("PRINTLN SORT([1, \"a\"])\n").unwrap_err();
+    assert_eq!(err.0, "SORT: cannot compare STRING with FLOAT");
+}
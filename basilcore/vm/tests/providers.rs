@@ -0,0 +1,103 @@
+use std::rc::Rc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use basil_vm::providers::{Clock, Environment, ManualClock, MapEnvironment, MemoryFileSystem};
+use basil_vm::InputProvider;
+
+mod common;
+use common::{capture, text, vm_for};
+
+struct Scripted(Vec<String>);
+impl InputProvider for Scripted {
+    fn read_line(&mut self) -> String { if self.0.is_empty() { String::new() } else { self.0.remove(0) } }
+    fn read_char(&mut self) -> Option<char> { None }
+}
+
+#[test]
+fn script_runs_against_virtual_services() {
+    let src = r#"
+LET name$ = INPUT$("name? ")
+WRITEFILE("out/greeting.txt", "Hello " + name$)
+APPENDFILE("out/greeting.txt", "!")
+LET fh% = FOPEN("data.txt", "r")
+LET first$ = FREADLINE$(fh%)
+FCLOSE(fh%)
+SETENV GREETED = name$
+SLEEP 1500
+PRINTLN first$ + "/" + ENV$("APP_MODE") + "/" + READFILE$("out/greeting.txt")
+"#;
+    let mut vm = vm_for(src);
+
+    let fs = Rc::new(MemoryFileSystem::new());
+    fs.insert("data.txt", "line one\nline two\n");
+    let env = Rc::new(MapEnvironment::new());
+    env.set_var("APP_MODE", "test");
+    let clock = Rc::new(ManualClock::new(UNIX_EPOCH));
+
+    let out = capture(&mut vm);
+    vm.set_input(Box::new(Scripted(vec!["Ada".into()])));
+    vm.set_filesystem(fs.clone());
+    vm.set_environment(env.clone());
+    vm.set_clock(clock.clone());
+    vm.run().expect("run");
+
+    assert_eq!(text(&out), "name? line one/test/Hello Ada!\n");
+    assert_eq!(fs.contents("out/greeting.txt").unwrap(), b"Hello Ada!");
+    assert_eq!(env.var("GREETED").as_deref(), Some("Ada"));
+    assert!(std::env::var("GREETED").is_err());
+    assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_millis(1500));
+}
+
+#[test]
+fn class_files_and_csrf_token_come_from_the_providers() {
+    let src = r#"
+LET g@ = CLASS("lib/Greeter.bas")
+PRINTLN g@.Hello("Ada"), CSRF_TOKEN$(), INSTR(CSRF_FIELD$(), CSRF_TOKEN$()) > 0
+PRINTLN CSRF_FORMS$("<form method=\"post\"></form>") = "<form method=\"post\">" + CSRF_FIELD$() + "</form>"
+"#;
+    let mut vm = vm_for(src);
+
+    let fs = Rc::new(MemoryFileSystem::new());
+    fs.insert("lib/Greeter.bas", "FUNC Hello(n$)\n  RETURN \"Hello \" + n$\nEND FUNC\n");
+    let env = Rc::new(MapEnvironment::new());
+    let token = "ab".repeat(32);
+    env.set_var("HTTP_COOKIE", &format!("basil_csrf={}", token));
+
+    let out = capture(&mut vm);
+    vm.set_filesystem(fs);
+    vm.set_environment(env);
+    vm.run().expect("run");
+
+    assert_eq!(text(&out), format!("Hello Ada\t{}\ttrue\ntrue\n", token));
+    assert!(!std::path::Path::new("lib/Greeter.bas").exists());
+}
+
+#[test]
+fn one_vm_serves_requests_with_their_own_variables_and_body() {
+    let src = r#"
+FUNC Show()
+  LET s$ = ""
+  FOR EACH x IN REQUEST$()
+    s$ = s$ + x + ";"
+  NEXT
+  RETURN s$
+END FUNC
+ROUTE "POST", "/f", Show
+"#;
+    let mut vm = vm_for(src);
+    vm.run().expect("run");
+    let (show, _) = vm.routes().find("POST", "/f").expect("match");
+
+    let mut serve = |query: &str, body: &str| {
+        let env = Rc::new(MapEnvironment::new());
+        env.set_var("QUERY_STRING", query);
+        env.set_var("CONTENT_TYPE", "application/x-www-form-urlencoded");
+        env.set_var("CONTENT_LENGTH", &body.len().to_string());
+        vm.set_environment(env);
+        vm.set_request_body(body.as_bytes().to_vec());
+        vm.call_value(&show, vec![]).expect("call").to_string()
+    };
+    assert_eq!(serve("a=1", "b=2&c=3"), "a=1;b=2;c=3;");
+    // Nothing is left over from the first request
+    assert_eq!(serve("", "d=4"), "d=4;");
+}
@@ -0,0 +1,62 @@
+use basil_vm::regex::Regex;
+
+mod common;
+use common::run;
+
+#[test]
+fn matching_and_finding() {
+    let src = r#"
+PRINTLN REGEX_MATCH%("ann@example.com", "^[\\w.+-]+@[\\w-]+(\\.[\\w-]+)+$"), REGEX_MATCH%("ann@", "^\\w+@\\w+$")
+PRINTLN REGEX_FIND$("order 66 shipped 2024", "\\d+"), "[" + REGEX_FIND$("none", "\\d") + "]"
+PRINTLN REGEX_FINDALL("a1 b22 c333", "[a-z](\\d+)")
+PRINTLN REGEX_FINDALL("Cat cAT dog", "(?i)cat"), REGEX_FINDALL("<a><b>", "<.+?>"), REGEX_FINDALL("<a><b>", "<.+>")
+PRINTLN REGEX_FINDALL("line1\nline2", "(?m)^\\w+$"), REGEX_FINDALL("ab", "x*")
+PRINTLN REGEX_MATCH%("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa!", "^(a|aa|a*)*$")
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "1\t0\n66\t[]\n[a1, b22, c333]\n[Cat, cAT]\t[<a>, <b>]\t[<a><b>]\n[line1, line2]\t[, , ]\n0\n"
+    );
+}
+
+#[test]
+fn captures_replace_and_split() {
+    let src = r#"
+LET m = REGEX_CAPTURES("2024-03-09", "(\\d{4})-(\\d\\d)-(\\d\\d)(T.*)?")
+PRINTLN m, m[2], m[5] = NULL
+LET d = REGEX_CAPTURES("GET /users/7 HTTP/1.1", "^(?P<method>[A-Z]+) (?<path>\\S+)")
+PRINTLN d["method"], d["path"], REGEX_CAPTURES("x", "\\d") = NULL
+PRINTLN REGEX_REPLACE$("2024-03-09", "(\\d+)-(\\d+)-(\\d+)", "$3/$2/$1")
+PRINTLN REGEX_REPLACE$("Ann Lee", "(?P<first>\\w+) (?P<last>\\w+)", "${last}, ${first} ($$5)")
+PRINTLN REGEX_REPLACE$("a.b.c", "\\.", "")
+PRINTLN REGEX_SPLIT("a, b,c ,, d", "\\s*,\\s*"), REGEX_SPLIT("one", ",")
+"#;
+    assert_eq!(
+        run(src).unwrap(),
+        "[2024-03-09, 2024, 03, 09, null]\t2024\ttrue\nGET\t/users/7\ttrue\n09/03/2024\nLee, Ann ($5)\nabc\n[a, b, c, , d]\t[one]\n"
+    );
+}
+
+#[test]
+fn regex_errors() {
+    let err = run("PRINTLN REGEX_MATCH%(\"x\", \"a(b\")\n").unwrap_err();
+    assert_eq!(err.0, "invalid regex \"a(b\": unclosed group");
+    let err = run("PRINTLN REGEX_FIND$(\"x\", \"*a\")\n").unwrap_err();
+    assert_eq!(err.0, "invalid regex \"*a\": nothing to repeat before '*'");
+    let err = run("PRINTLN REGEX_FIND$(\"x\", \"[z-a]\")\n").unwrap_err();
+    assert_eq!(err.0, "invalid regex \"[z-a]\": invalid class range z-a");
+    let err = run("PRINTLN REGEX_FIND$(\"x\", \"(?=a)\")\n").unwrap_err();
+    assert_eq!(err.0, "invalid regex \"(?=a)\": unsupported group (?=");
+    let err = run("PRINTLN REGEX_FIND$(\"x\", \"a{2,1}\")\n").unwrap_err();
+    assert_eq!(err.0, "invalid regex \"a{2,1}\": invalid repetition {2,1}");
+    // Braces that are not a repetition are literal
+    assert_eq!(run("PRINTLN REGEX_FIND$(\"a{,2}\", \"a{,2}\")\n").unwrap(), "a{,2}\n");
+    let err = run("PRINTLN REGEX_FIND$(\"x\")\n").unwrap_err();
+    assert_eq!(err.0, "REGEX_FIND$ expects 2 arguments");
+
+    // Hosts can use the same engine
+    let re = Regex::new(r"\b(\w)(\w*)").unwrap();
+    assert_eq!(re.replace_all("hello big world", "${1}_$2"), "h_ello b_ig w_orld");
+    assert_eq!(re.split("héllo wörld"), ["", " ", ""]);
+    assert_eq!(Regex::new("x{2,}").unwrap().find_all("x xx xxxx{"), ["xx", "xxxx"]);
+}
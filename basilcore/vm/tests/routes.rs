@@ -0,0 +1,63 @@
+use basil_vm::VM;
+use basil_bytecode::Value;
+
+mod common;
+
+fn vm_for(src: &str) -> VM {
+    let mut vm = common::vm_for(src);
+    vm.run().expect("run");
+    vm
+}
+
+#[test]
+fn route_statements_register_and_dispatch() {
+    let src = r#"
+FUNC Show(p)
+  RETURN "user " + p["id"] + "/" + ROUTE_PARAM$("ID") + " at " + ROUTE_PATH$()
+END FUNC
+FUNC Gate()
+  RETURN FALSE
+END FUNC
+ROUTE "GET", "/users/:id", Show
+ROUTE BEFORE Gate
+"#;
+    let mut vm = vm_for(src);
+    assert_eq!(vm.routes().routes.len(), 1);
+    assert_eq!(vm.routes().before.len(), 1);
+    assert!(vm.routes().find("POST", "/users/7").is_none());
+
+    let (handler, params) = vm.routes().find("GET", "/users/7").expect("match");
+    vm.set_route_params("/users/7", params.clone());
+    let mut map = basil_bytecode::Dict::new();
+    for (k, v) in params { map.insert(k, Value::Str(v)); }
+    let arg = Value::Dict(std::rc::Rc::new(std::cell::RefCell::new(map)));
+    let out = vm.call_value(&handler, vec![arg]).expect("call");
+    assert_eq!(format!("{}", out), "user 7/7 at /users/7");
+
+    let gate = vm.routes().before[0].clone();
+    assert!(matches!(vm.call_value(&gate, vec![]).expect("call"), Value::Bool(false)));
+}
+
+#[test]
+fn call_value_recovers_from_errors() {
+    let src = r#"
+FUNC Boom()
+  RAISE "kaboom"
+  RETURN 0
+END FUNC
+FUNC Twice(n)
+  RETURN n + n
+END FUNC
+ROUTE "*", "/x", Boom
+ROUTE "GET", "/twice", Twice
+"#;
+    let mut vm = vm_for(src);
+    let (boom, _) = vm.routes().find("DELETE", "/x").expect("match");
+    let err = vm.call_value(&boom, vec![]).unwrap_err();
+    assert!(err.to_string().contains("kaboom"));
+    assert!(vm.call_value(&boom, vec![Value::Int(1)]).is_err()); // arity mismatch
+    // The VM stays usable after a failed call
+    let (twice, _) = vm.routes().find("GET", "/twice").expect("match");
+    assert_eq!(vm.call_value(&twice, vec![Value::Int(1)]).unwrap(), Value::Int(2));
+    assert!(basil_parser::parse("ROUTE \"GET\", \"/\"\n").is_err());
+}
@@ -0,0 +1,74 @@
+mod common;
+use common::run;
+
+#[test]
+fn print_using_numeric_masks() {
+    let src = r#####"
+PRINTLN USING "###,###.##"; 12345.678
+PRINTLN USING "$$#####.##"; 456.78
+PRINTLN USING "**$##.##"; 2.34
+PRINTLN USING "**#.#"; 12.39, -0.9, 765.1
+PRINTLN USING "##.##  "; 0.78; -0.5; 0.125
+PRINTLN USING "+##  ##-  ##+"; 5, -12, -3
+PRINTLN USING "##.##^^^^"; 234.56
+PRINTLN USING "+.##^^^^  #.#^^^^^"; 123, 0.000123
+PRINTLN USING "#.##|"; 9.996, -0.5
+PRINT USING "Total: _##### #"; 42;
+PRINTLN ""
+"#####;
+    assert_eq!(
+        run(src).unwrap(),
+        " 12,345.68\n   $456.78\n***$2.34\n*12.4*-0.9765.1\n 0.78  -0.50   0.13  \n +5  12-   3-\n 2.35E+02\n+.12E+03   .1E-003\n%10.00|-.50|\nTotal: #  42 \n"
+    );
+}
+
+#[test]
+fn print_using_string_fields_and_reuse() {
+    let src = r#####"
+PRINTLN USING "[!] [\  \] [&]"; "Hello", "abcdefg", "whole"
+PRINT USING "\    \"; "ab"
+PRINTLN USING$("|\\    \\|", "abcdefgh")
+LET inf = VAL("1e400")
+PRINTLN USING$("##.## ", inf, -inf, inf - inf), USING$("#.##^^^^|$$#.#", inf, -inf)
+PRINTLN USING "& is ## years. "; "Ann", 41, "Bob", 7
+PRINTLN USING$("Page ##", 3), USING$("no fields")
+PRINTLN USING$("%5.2f|%3d|%s", 3.14159, 42, "ok")
+"#####;
+    assert_eq!(
+        run(src).unwrap(),
+        "[H] [abcd] [whole]\nab    |abcdef|\n%inf %-inf %NaN \t%inf|%-inf\nAnn is 41 years. Bob is  7 years. \nPage  3\tno fields\n 3.14| 42|ok\n"
+    );
+    let err = run("PRINTLN USING \"##\"; \"x\"\n").unwrap_err();
+    assert_eq!(err.0, "USING$: value 1 is not a number but its field is numeric");
+    let err = run("PRINTLN USING \"&\"; 5\n").unwrap_err();
+    assert_eq!(err.0, "USING$: value 1 is not a string but its field is");
+    let err = run("PRINTLN USING$(\"plain\", 5)\n").unwrap_err();
+    assert_eq!(err.0, "USING$: the format has no fields for its values");
+}
+
+#[test]
+fn format_patterns_and_locales() {
+    let src = r#####"
+PRINTLN FORMAT$(1234567.891, "#,##0.00"), FORMAT$(1234567.891, "#,##0.00", "de-DE"), FORMAT$(1234567.891, "#,##0.00", "de_CH")
+PRINTLN FORMAT$(0.256, "0.0%"), FORMAT$(-5, "$#,##0.00;($#,##0.00)"), FORMAT$(0, "#;-#;\"zero\""), FORMAT$(-3, "0.0")
+PRINTLN FORMAT$(5, "000"), FORMAT$(1.5, "#.##"), FORMAT$(0.5, "#.00"), FORMAT$(-0.001, "0.00"), FORMAT$(7, "0 \"items\"")
+PRINTLN FORMAT$(12345, "0.00E+00"), FORMAT$(0.00042, "0.0E0"), FORMAT$(99999, "0.0E+0"), FORMAT$(5551234, "###-####")
+"#####;
+    assert_eq!(
+        run(src).unwrap(),
+        "1,234,567.89\t1.234.567,89\t1'234'567.89\n25.6%\t($5.00)\tzero\t-3.0\n005\t1.5\t.50\t0.00\t7 items\n1.23E+04\t4.2E-4\t1.0E+5\t555-1234\n"
+    );
+    assert_eq!(run("PRINTLN FORMAT$(1, \"0\", \"xx\")\n").unwrap_err().0, "FORMAT$: unknown locale \"xx\"");
+    assert_eq!(run("PRINTLN FORMAT$(1, \"0\", \"fr\")\n").unwrap(), "1\n");
+    assert_eq!(run("PRINTLN FORMAT$(1234.5, \"#,##0.0\", \"fr-FR\")\n").unwrap(), "1\u{a0}234,5\n");
+}
+
+#[test]
+fn str_and_val_conversions() {
+    let src = r#####"
+PRINTLN "[" + STR$(5) + "]", "[" + STR$(-2.5) + "]", "[" + STR$(0) + "]"
+PRINTLN VAL(" 1 2.5xyz"), VAL("&HFF"), VAL("&O17"), VAL("&B101"), VAL("1.5D3"), VAL("-.5e-1"), VAL("abc"), VAL("3e"), VAL(STR$(42)) + 1
+"#####;
+    assert_eq!(run(src).unwrap(), "[ 5]\t[-2.5]\t[ 0]\n12.5\t255\t15\t5\t1500\t-0.05\t0\t3\t43\n");
+    assert_eq!(run("PRINTLN STR$(\"5\")\n").unwrap_err().0, "STR$ expects a number, got STRING");
+}
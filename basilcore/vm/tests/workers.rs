@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+
+use basil_bytecode::{FromValue, SendValue, SharedProgram, Value};
+use basil_common::BasilError;
+use basil_vm::providers::MemoryFileSystem;
+use basil_vm::workers::WorkerPool;
+use basil_vm::VM;
+
+mod common;
+use common::{capture, text, vm_for};
+
+const SUMMER: &str = r#"LET job = RECEIVE(0)
+LET total = 0
+FOR EACH v IN job["items"]
+  total = total + v
+NEXT
+LET ok = SEND(0, {"name": job["name"], "total": total})
+"#;
+
+// A scratch directory holding the worker scripts
+fn worker_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("basil_workers_{}_{}", test, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("summer.bas"), SUMMER).unwrap();
+    std::fs::write(dir.join("fails.bas"), "LET x = RECEIVE(0)\nRAISE \"bad input \" + x\n").unwrap();
+    dir
+}
+
+fn run_in(dir: &Path, src: &str) -> (Result<(), BasilError>, String) {
+    let mut vm = vm_for(src);
+    vm.set_script_path(dir.join("main.bas").to_string_lossy().into_owned());
+    let out = capture(&mut vm);
+    // Output is captured, so workers on the default providers are an explicit choice
+    vm.set_worker_setup(Arc::new(|_: &mut VM| Ok(())));
+    let res = vm.run();
+    (res, text(&out))
+}
+
+#[test]
+fn scripts_exchange_messages_with_workers() {
+    let dir = worker_dir("messages");
+    let src = r#"
+LET a% = SPAWN("summer.bas")
+LET b% = SPAWN("summer.bas")
+LET items = [1, 2, 3]
+LET s = SEND(a%, {"name": "a", "items": items})
+items[1] = 100
+s = SEND(b%, {"name": "b", "items": [10, 20]})
+LET rb = RECEIVE(b%)
+LET ra = RECEIVE(a%)
+JOIN(a%)
+JOIN(b%)
+PRINTLN ra["name"] + "=" + ra["total"], rb["name"] + "=" + rb["total"], items[1]
+PRINTLN SEND(SPAWN("summer.bas"), {"name": "c", "items": []}) + 10
+"#;
+    // The list was copied when sent, so changing it afterwards does not reach worker a
+    let (res, out) = run_in(&dir, src);
+    res.expect("run");
+    assert_eq!(out, "a=6\tb=30\t100\n11\n");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn worker_errors() {
+    let dir = worker_dir("errors");
+    let (res, _) = run_in(&dir, "LET w% = SPAWN(\"fails.bas\")\nLET s = SEND(w%, 7)\nJOIN(w%)\n");
+    assert_eq!(res.unwrap_err().0, "worker 1: bad input 7");
+    // Workers the script leaves running are waited for when it ends
+    let (res, _) = run_in(&dir, "LET w% = SPAWN(\"fails.bas\")\n");
+    assert_eq!(res.unwrap_err().0, "worker 1: bad input null");
+    let (res, _) = run_in(&dir, "CLASS P\nEND CLASS\nLET w% = SPAWN(\"summer.bas\")\nLET s = SEND(w%, NEW P())\n");
+    assert_eq!(res.unwrap_err().0, "cannot send P objects between VMs");
+    let (res, _) = run_in(&dir, "LET x = RECEIVE(0)\n");
+    assert_eq!(res.unwrap_err().0, "worker 0 (the parent) is only available inside a SPAWNed script");
+    let (res, _) = run_in(&dir, "LET w% = SPAWN(\"missing.bas\")\n");
+    assert_eq!(res.unwrap_err().0, "SPAWN: file not found: missing.bas");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn workers_follow_the_hosts_providers() {
+    let src = "LET w% = SPAWN(\"reader.bas\")\nPRINTLN RECEIVE(w%)\nJOIN(w%)\n";
+    let vm_in_memory = || {
+        let mut vm = vm_for(src);
+        let fs = Rc::new(MemoryFileSystem::new());
+        fs.insert("reader.bas", "LET ok = SEND(0, READFILE$(\"data.txt\"))\n");
+        vm.set_filesystem(fs);
+        vm
+    };
+    // A worker on the real disk would escape the in-memory filesystem
+    let mut vm = vm_in_memory();
+    assert_eq!(vm.run().unwrap_err().0, "SPAWN: this VM runs on host-supplied providers and the host has not set up workers for them");
+
+    let mut vm = vm_in_memory();
+    let out = capture(&mut vm);
+    vm.set_worker_setup(Arc::new(|w: &mut VM| {
+        let fs = Rc::new(MemoryFileSystem::new());
+        fs.insert("data.txt", "worker's own memory");
+        w.set_filesystem(fs);
+        Ok(())
+    }));
+    vm.run().expect("run");
+    assert_eq!(text(&out), "worker's own memory\n");
+    assert!(!Path::new("data.txt").exists());
+}
+
+const SQUARES: &str = r#"
+LET calls = 0
+LET seen = {}
+FUNC Square(n)
+  calls = calls + 1
+  seen["" + n] = TRUE
+  RETURN n * n
+END FUNC
+"#;
+
+fn shared(src: &str) -> SharedProgram {
+    let ast = basil_parser::parse(src).expect("parse");
+    SharedProgram::new(basil_compiler::compile(&ast).expect("compile")).expect("share")
+}
+
+#[test]
+fn programs_and_values_cross_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedProgram>();
+    assert_send_sync::<SendValue>();
+
+    let program = shared(SQUARES);
+    let handles: Vec<_> = (1..=4).map(|i| {
+        let p = program.clone();
+        thread::spawn(move || {
+            let mut vm = VM::new(p.program());
+            vm.run().unwrap();
+            let r = vm.call_function("Square", &[Value::Num(i as f64)]).unwrap();
+            SendValue::from_value(&r).unwrap()
+        })
+    }).collect();
+    let squares: Vec<f64> = handles.into_iter().map(|h| f64::from_value(&h.join().unwrap().to_value()).unwrap()).collect();
+    assert_eq!(squares, [1.0, 4.0, 9.0, 16.0]);
+}
+
+#[test]
+fn pool_runs_jobs_on_prepared_vms() {
+    let pool = WorkerPool::new(shared(SQUARES), 3, |vm: &mut VM| vm.run()).expect("pool");
+    assert_eq!(pool.size(), 3);
+    let pending: Vec<_> = (1..=10).map(|i| pool.execute(move |vm: &mut VM| {
+        let r = vm.call_function("Square", &[Value::Num(i as f64)]).unwrap();
+        f64::from_value(&r).unwrap()
+    })).collect();
+    let results: Vec<f64> = pending.into_iter().map(|rx| rx.recv().unwrap()).collect();
+    assert_eq!(results, (1..=10).map(|i| (i * i) as f64).collect::<Vec<_>>());
+
+    // Each job starts from the globals setup left, even on a VM that ran earlier jobs
+    let single = WorkerPool::new(shared(SQUARES), 1, |vm: &mut VM| vm.run()).expect("pool");
+    for i in 0..3 { single.execute(move |vm: &mut VM| vm.call_function("Square", &[Value::Num(i as f64)]).is_ok()); }
+    let state = single.execute(|vm: &mut VM| {
+        vm.call_function("Square", &[Value::Num(7.0)]).unwrap();
+        (vm.get_global("calls").unwrap().to_string(), vm.get_global("seen").unwrap().to_string())
+    });
+    assert_eq!(state.recv().unwrap(), ("1".to_string(), "{\"7\": true}".to_string()));
+
+    let err = WorkerPool::new(shared("RAISE \"no config\"\n"), 2, |vm: &mut VM| vm.run()).err().unwrap();
+    assert_eq!(err.0, "no config");
+}
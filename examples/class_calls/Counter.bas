@@ -0,0 +1,8 @@
+REM Class file used by bench.bas: one field and a method that changes it.
+
+count = 0
+
+FUNC Inc(n)
+  count = count + n
+  RETURN count
+END FUNC
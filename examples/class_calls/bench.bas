@@ -0,0 +1,20 @@
+REM Timing harness for method calls on an object loaded with CLASS("file").
+REM
+REM Each c@.Inc(1) below used to copy the instance's globals into a new child VM, run the
+REM method there and copy them back; it now runs as a frame on the calling VM. To compare
+REM two builds, time the same run with each, from the repository root:
+REM
+REM   cargo build --release
+REM   time target/release/basic run examples/class_calls/bench.bas
+REM
+REM The loop makes CALLS calls; the run's fixed cost (start-up, compiling both files) is a
+REM few milliseconds, so the total time divided by CALLS is the cost of one call.
+
+CONST CALLS = 200000
+
+LET c@ = CLASS("Counter.bas")
+LET t = 0
+FOR i = 1 TO CALLS
+  t = c@.Inc(1)
+NEXT i
+PRINTLN t
@@ -0,0 +1,42 @@
+REM Front controller for `basic serve examples/router` (or Apache with routes.bas as the
+REM fallback resource). The top level registers routes; the matching handler runs afterwards.
+
+FUNC Home()
+  PRINTLN "<h1>Basil router demo</h1>"
+  PRINTLN "<p><a href=\"/hello/World\">/hello/World</a></p>"
+  RETURN 0
+END FUNC
+
+REM Handlers may take a DICT of path params ...
+FUNC Hello(p)
+  PRINTLN "<p>Hello, " + HTML$(p["name"]) + "!</p>"
+  RETURN 0
+END FUNC
+
+REM ... or read them with ROUTE_PARAM$().
+FUNC ShowFile()
+  PRINTLN "<p>You asked for " + HTML$(ROUTE_PARAM$("path")) + "</p>"
+  RETURN 0
+END FUNC
+
+FUNC LogRequest()
+  PRINTLN "<!-- " + HTML$(ROUTE_PATH$()) + " -->"
+  RETURN TRUE
+END FUNC
+
+FUNC NotFound()
+  PRINTLN "<p>Nothing at " + HTML$(ROUTE_PATH$()) + "</p>"
+  RETURN 0
+END FUNC
+
+FUNC Failed(msg$)
+  PRINTLN "<p>Something went wrong: " + HTML$(msg$) + "</p>"
+  RETURN 0
+END FUNC
+
+ROUTE "GET", "/", Home
+ROUTE "GET", "/hello/:name", Hello
+ROUTE "GET", "/files/*path", ShowFile
+ROUTE BEFORE LogRequest
+ROUTE NOT_FOUND NotFound
+ROUTE ERROR Failed